@@ -0,0 +1,360 @@
+//! Derive macros of `rusty-openai`, re-exported by its `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Expr, ExprLit, Fields,
+    Lit, LitStr, Meta, Result,
+};
+
+/// Derive `rusty_openai::structured::StructuredOutput` for a struct with named fields, a newtype
+/// struct or an enum of unit variants.
+///
+/// The JSON schema is built from the types of the fields, which must implement `StructuredOutput`
+/// themselves, with the doc comments as descriptions. The `rename`, `rename_all` and `skip` serde
+/// attributes are honored.
+#[proc_macro_derive(StructuredOutput)]
+pub fn derive_structured_output(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(mut input: DeriveInput) -> Result<TokenStream2> {
+    let private = quote!(::rusty_openai::structured::__private);
+    let name = &input.ident;
+    let title = name.to_string();
+    let description = option(doc(&input.attrs));
+    let container = SerdeAttributes::parse(&input.attrs)?;
+
+    let schema = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let mut properties = Vec::new();
+
+                for field in &fields.named {
+                    let attributes = SerdeAttributes::parse(&field.attrs)?;
+                    if attributes.flatten {
+                        return Err(Error::new_spanned(
+                            field,
+                            "flattened fields aren't supported by StructuredOutput",
+                        ));
+                    }
+                    if attributes.skip {
+                        continue;
+                    }
+
+                    let ident = field.ident.as_ref().expect("named field");
+                    let key = attributes.rename.unwrap_or_else(|| {
+                        rename_field(&unraw(&ident.to_string()), container.rename_all.as_deref())
+                    });
+                    let description = option(doc(&field.attrs));
+                    let ty = &field.ty;
+
+                    properties.push(quote! {
+                        (
+                            #key,
+                            #description,
+                            <#ty as ::rusty_openai::structured::StructuredOutput>::json_schema(),
+                        )
+                    });
+                }
+
+                quote!(#private::object(#title, #description, ::std::vec![#(#properties),*]))
+            },
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+
+                quote!(<#ty as ::rusty_openai::structured::StructuredOutput>::json_schema())
+            },
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "StructuredOutput needs named fields or a single unnamed field",
+                ))
+            },
+        },
+        Data::Enum(data) => {
+            let mut variants = Vec::new();
+
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "StructuredOutput only supports enums of unit variants",
+                    ));
+                }
+
+                let attributes = SerdeAttributes::parse(&variant.attrs)?;
+                if attributes.skip {
+                    continue;
+                }
+                variants.push(attributes.rename.unwrap_or_else(|| {
+                    rename_variant(
+                        &unraw(&variant.ident.to_string()),
+                        container.rename_all.as_deref(),
+                    )
+                }));
+            }
+
+            quote!(#private::string_enum(#title, #description, &[#(#variants),*]))
+        },
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "StructuredOutput can't be derived for unions",
+            ))
+        },
+    };
+
+    let parameters: Vec<_> = input
+        .generics
+        .type_params()
+        .map(|parameter| parameter.ident.clone())
+        .collect();
+    let where_clause = input.generics.make_where_clause();
+    for parameter in parameters {
+        where_clause
+            .predicates
+            .push(parse_quote!(#parameter: ::rusty_openai::structured::StructuredOutput));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rusty_openai::structured::StructuredOutput for #name #ty_generics #where_clause {
+            fn json_schema() -> #private::Value {
+                #schema
+            }
+        }
+    })
+}
+
+/// The serde attributes affecting the schema.
+#[derive(Default)]
+struct SerdeAttributes {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    skip: bool,
+    flatten: bool,
+}
+
+impl SerdeAttributes {
+    fn parse(attributes: &[Attribute]) -> Result<Self> {
+        let mut parsed = Self::default();
+
+        for attribute in attributes
+            .iter()
+            .filter(|attribute| attribute.path().is_ident("serde"))
+        {
+            attribute.parse_nested_meta(|meta| {
+                let path = &meta.path;
+
+                if path.is_ident("rename") && meta.input.peek(syn::Token![=]) {
+                    parsed.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if path.is_ident("rename_all") && meta.input.peek(syn::Token![=]) {
+                    parsed.rename_all = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if path.is_ident("skip") || path.is_ident("skip_deserializing") {
+                    parsed.skip = true;
+                } else if path.is_ident("flatten") {
+                    parsed.flatten = true;
+                } else if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|nested| {
+                        if nested.input.peek(syn::Token![=]) {
+                            nested.value()?.parse::<Expr>()?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// The doc comment of an item, without the leading spaces of its lines.
+fn doc(attributes: &[Attribute]) -> String {
+    let lines: Vec<String> = attributes
+        .iter()
+        .filter_map(|attribute| match &attribute.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(line),
+                    ..
+                }) => Some(line.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    lines.join("\n").trim().to_string()
+}
+
+fn option(text: String) -> TokenStream2 {
+    if text.is_empty() {
+        quote!(::std::option::Option::None)
+    } else {
+        quote!(::std::option::Option::Some(#text))
+    }
+}
+
+fn unraw(ident: &str) -> String {
+    ident.trim_start_matches("r#").to_string()
+}
+
+/// Name of a snake_case field under a serde `rename_all` rule.
+fn rename_field(field: &str, rule: Option<&str>) -> String {
+    let pascal = || {
+        field
+            .split('_')
+            .map(|word| {
+                let mut characters = word.chars();
+                characters
+                    .next()
+                    .map(|first| first.to_ascii_uppercase().to_string() + characters.as_str())
+                    .unwrap_or_default()
+            })
+            .collect::<String>()
+    };
+
+    match rule {
+        Some("UPPERCASE" | "SCREAMING_SNAKE_CASE") => field.to_ascii_uppercase(),
+        Some("PascalCase") => pascal(),
+        Some("camelCase") => {
+            let pascal = pascal();
+            let mut characters = pascal.chars();
+            characters
+                .next()
+                .map(|first| first.to_ascii_lowercase().to_string() + characters.as_str())
+                .unwrap_or_default()
+        },
+        Some("kebab-case") => field.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => field.replace('_', "-").to_ascii_uppercase(),
+        _ => field.to_string(),
+    }
+}
+
+/// Name of a PascalCase variant under a serde `rename_all` rule.
+fn rename_variant(variant: &str, rule: Option<&str>) -> String {
+    let snake = || {
+        let mut snake = String::new();
+        for (index, character) in variant.char_indices() {
+            if character.is_uppercase() && index > 0 {
+                snake.push('_');
+            }
+            snake.push(character.to_ascii_lowercase());
+        }
+        snake
+    };
+
+    match rule {
+        Some("lowercase") => variant.to_ascii_lowercase(),
+        Some("UPPERCASE") => variant.to_ascii_uppercase(),
+        Some("camelCase") => {
+            let mut characters = variant.chars();
+            characters
+                .next()
+                .map(|first| first.to_ascii_lowercase().to_string() + characters.as_str())
+                .unwrap_or_default()
+        },
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_ascii_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => snake().replace('_', "-").to_ascii_uppercase(),
+        _ => variant.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: DeriveInput) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn fields_follow_rename_all_rules() {
+        let renamed = |rule| rename_field("unit_price_usd", Some(rule));
+
+        assert_eq!(renamed("camelCase"), "unitPriceUsd");
+        assert_eq!(renamed("PascalCase"), "UnitPriceUsd");
+        assert_eq!(renamed("SCREAMING_SNAKE_CASE"), "UNIT_PRICE_USD");
+        assert_eq!(renamed("kebab-case"), "unit-price-usd");
+        assert_eq!(renamed("SCREAMING-KEBAB-CASE"), "UNIT-PRICE-USD");
+        assert_eq!(rename_field("unit_price_usd", None), "unit_price_usd");
+    }
+
+    #[test]
+    fn variants_follow_rename_all_rules() {
+        let renamed = |rule| rename_variant("HomeGarden", Some(rule));
+
+        assert_eq!(renamed("lowercase"), "homegarden");
+        assert_eq!(renamed("UPPERCASE"), "HOMEGARDEN");
+        assert_eq!(renamed("camelCase"), "homeGarden");
+        assert_eq!(renamed("snake_case"), "home_garden");
+        assert_eq!(renamed("SCREAMING_SNAKE_CASE"), "HOME_GARDEN");
+        assert_eq!(renamed("kebab-case"), "home-garden");
+        assert_eq!(renamed("SCREAMING-KEBAB-CASE"), "HOME-GARDEN");
+        assert_eq!(rename_variant("HomeGarden", None), "HomeGarden");
+    }
+
+    #[test]
+    fn skipped_fields_are_left_out_of_the_schema() {
+        let expanded = expand(parse_quote! {
+            struct Product {
+                name: String,
+                #[serde(skip)]
+                internal_id: u64,
+                #[serde(skip_deserializing)]
+                cached: bool,
+            }
+        })
+        .unwrap()
+        .to_string();
+
+        assert!(expanded.contains("\"name\""));
+        assert!(!expanded.contains("internal_id"));
+        assert!(!expanded.contains("cached"));
+    }
+
+    #[test]
+    fn unsupported_shapes_are_rejected() {
+        assert_eq!(
+            error(parse_quote! {
+                struct Product {
+                    #[serde(flatten)]
+                    details: Details,
+                }
+            }),
+            "flattened fields aren't supported by StructuredOutput"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                enum Shape {
+                    Point,
+                    Circle { radius: f64 },
+                }
+            }),
+            "StructuredOutput only supports enums of unit variants"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                struct Pair(u8, u8);
+            )),
+            "StructuredOutput needs named fields or a single unnamed field"
+        );
+        assert_eq!(
+            error(parse_quote!(union Bits { int: u32, float: f32 })),
+            "StructuredOutput can't be derived for unions"
+        );
+    }
+}
@@ -0,0 +1,565 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::{completion::ChatCompletionRequest, types::Usage},
+    runtime::Instant,
+    transport::{BoxFuture, MaybeSend},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Handler of a tool, given the parsed arguments of the call and returning the tool output or an
+/// error message, both sent back to the model.
+pub type ToolHandler =
+    Arc<dyn Fn(Value) -> BoxFuture<'static, Result<String, String>> + Send + Sync>;
+
+struct Tool {
+    definition: Value,
+    handler: ToolHandler,
+}
+
+/// Step of an agent run captured by a [`TranscriptRecorder`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptEvent {
+    /// A completion of the model
+    ModelTurn {
+        /// Model that answered
+        model: Option<Model>,
+
+        /// The assistant message, tool calls included
+        message: Value,
+
+        /// Token usage of the completion
+        usage: Usage,
+
+        /// Time taken by the completion
+        latency: Duration,
+    },
+
+    /// A tool call requested by the model
+    ToolCall {
+        /// ID of the call
+        id: String,
+
+        /// Name of the tool
+        name: String,
+
+        /// Arguments of the call, as sent by the model
+        arguments: Value,
+
+        /// Output of the tool, if it succeeded
+        output: Option<String>,
+
+        /// Error of the call, if it failed
+        error: Option<String>,
+
+        /// Time taken by the tool
+        latency: Duration,
+    },
+}
+
+/// Structured transcript of one or more agent runs.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Transcript {
+    /// Every model turn and tool call, in order
+    pub events: Vec<TranscriptEvent>,
+}
+
+impl Transcript {
+    /// Write the transcript as pretty-printed JSON to the file at `path`.
+    ///
+    /// # Returns
+    ///
+    /// An empty Result on success, or an [`OpenAIError`] if the file couldn't be written.
+    #[cfg(feature = "fs")]
+    pub fn save(&self, path: impl AsRef<Path>) -> OpenAIResult<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Total token usage of the model turns.
+    pub fn usage(&self) -> Usage {
+        self.events
+            .iter()
+            .fold(Usage::default(), |mut total, event| {
+                if let TranscriptEvent::ModelTurn { usage, .. } = event {
+                    total += usage;
+                }
+                total
+            })
+    }
+}
+
+/// Recorder of the model turns and tool calls of an [`Agent`], shared between its clones so the
+/// transcript can be inspected while or after the agent runs, failed runs included.
+#[derive(Clone, Default)]
+pub struct TranscriptRecorder {
+    transcript: Arc<Mutex<Transcript>>,
+}
+
+impl TranscriptRecorder {
+    /// Create an empty recorder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of the transcript recorded so far.
+    pub fn transcript(&self) -> Transcript {
+        self.transcript.lock().unwrap().clone()
+    }
+
+    /// Forget the recorded events.
+    pub fn clear(&self) {
+        self.transcript.lock().unwrap().events.clear();
+    }
+
+    fn record(&self, event: TranscriptEvent) {
+        self.transcript.lock().unwrap().events.push(event);
+    }
+}
+
+/// Result of an [`Agent`] run.
+#[derive(Clone, Debug)]
+pub struct AgentOutput {
+    /// Text of the final answer
+    pub answer: String,
+
+    /// The whole exchange, tool calls and results included
+    pub messages: Vec<Value>,
+
+    /// Total token usage of the run
+    pub usage: Usage,
+}
+
+/// Tool-calling loop: the model is sent the prompt with the registered tools, the tools it calls
+/// are run and their outputs sent back, until it answers without calling any tool.
+#[derive(Clone)]
+pub struct Agent {
+    openai: OpenAI,
+    model: Option<Model>,
+    system: Option<String>,
+    tools: Vec<Arc<Tool>>,
+    max_turns: usize,
+    recorder: Option<TranscriptRecorder>,
+}
+
+impl Agent {
+    /// Create an agent without tools using the client's default chat model, stopping after 8
+    /// model turns.
+    pub fn new(openai: OpenAI) -> Self {
+        Self {
+            openai,
+            model: None,
+            system: None,
+            tools: Vec::new(),
+            max_turns: 8,
+            recorder: None,
+        }
+    }
+
+    /// Use `model` instead of the client's default chat model.
+    pub fn model(mut self, model: impl Into<Model>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Send `system` as the system prompt of every run.
+    pub fn system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// Register a function tool.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the function, as called by the model.
+    /// * `description` - What the function does and when to call it.
+    /// * `parameters` - JSON schema of the arguments.
+    /// * `handler` - Function running the tool with the parsed arguments.
+    pub fn tool<F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: Value,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, String>> + MaybeSend + 'static,
+    {
+        let definition = json!({
+            "type": "function",
+            "function": {
+                "name": name.into(),
+                "description": description.into(),
+                "parameters": parameters,
+            },
+        });
+        let handler: ToolHandler = Arc::new(move |arguments| Box::pin(handler(arguments)));
+
+        self.tools.push(Arc::new(Tool {
+            definition,
+            handler,
+        }));
+        self
+    }
+
+    /// Stop a run failing after `max_turns` model turns without a final answer.
+    pub fn max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = max_turns.max(1);
+        self
+    }
+
+    /// Capture every model turn and tool call of the runs with `recorder`.
+    pub fn recorder(mut self, recorder: TranscriptRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Run the loop on a user prompt.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`AgentOutput`] on success, or an [`OpenAIError`] on failure, e.g.
+    /// when the model is still calling tools after the maximum number of turns. Tool failures are
+    /// sent back to the model rather than failing the run.
+    pub async fn run(&self, prompt: &str) -> OpenAIResult<AgentOutput> {
+        let mut messages: Vec<Value> = self
+            .system
+            .iter()
+            .map(|system| json!({ "role": "system", "content": system }))
+            .collect();
+        messages.push(json!({ "role": "user", "content": prompt }));
+
+        let mut usage = Usage::default();
+
+        for _ in 0..self.max_turns {
+            let message = self.complete(&messages, &mut usage).await?;
+            let calls = message["tool_calls"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            messages.push(message);
+
+            if calls.is_empty() {
+                let answer = messages[messages.len() - 1]["content"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+
+                return Ok(AgentOutput {
+                    answer,
+                    messages,
+                    usage,
+                });
+            }
+
+            for call in calls {
+                let content = self.call(&call).await;
+
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call["id"],
+                    "content": content,
+                }));
+            }
+        }
+
+        Err(OpenAIError::UnexpectedResponse(format!(
+            "agent still calling tools after {} turns",
+            self.max_turns
+        )))
+    }
+
+    /// Send the exchange so far, returning the assistant message and adding its usage to `usage`.
+    async fn complete(&self, messages: &[Value], usage: &mut Usage) -> OpenAIResult<Value> {
+        let mut request = ChatCompletionRequest::from_messages(messages.to_vec());
+        if let Some(model) = &self.model {
+            request = request.model(model.clone());
+        }
+        if !self.tools.is_empty() {
+            request = request.tools(
+                self.tools
+                    .iter()
+                    .map(|tool| tool.definition.clone())
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        let started = Instant::now();
+        let mut response = self.openai.completions().create(request).await?;
+        let latency = started.elapsed();
+
+        let message = response["choices"][0]["message"].take();
+        if message.is_null() {
+            return Err(OpenAIError::UnexpectedResponse(
+                "missing message in chat completion".to_string(),
+            ));
+        }
+
+        let turn_usage =
+            serde_json::from_value::<Usage>(response["usage"].take()).unwrap_or_default();
+        *usage += &turn_usage;
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(TranscriptEvent::ModelTurn {
+                model: response["model"].as_str().map(Model::from),
+                message: message.clone(),
+                usage: turn_usage,
+                latency,
+            });
+        }
+
+        Ok(message)
+    }
+
+    /// Run the tool called by `call`, returning the content of the tool message.
+    async fn call(&self, call: &Value) -> String {
+        let name = call["function"]["name"].as_str().unwrap_or_default();
+        let raw_arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+
+        let started = Instant::now();
+        let (arguments, result) = match serde_json::from_str::<Value>(raw_arguments) {
+            Ok(arguments) => {
+                let tool = self
+                    .tools
+                    .iter()
+                    .find(|tool| tool.definition["function"]["name"] == name);
+
+                let result = match tool {
+                    Some(tool) => (tool.handler)(arguments.clone()).await,
+                    None => Err(format!("unknown tool `{name}`")),
+                };
+                (arguments, result)
+            },
+            Err(error) => (
+                Value::String(raw_arguments.to_string()),
+                Err(format!("invalid arguments: {error}")),
+            ),
+        };
+        let latency = started.elapsed();
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(TranscriptEvent::ToolCall {
+                id: call["id"].as_str().unwrap_or_default().to_string(),
+                name: name.to_string(),
+                arguments,
+                output: result.as_ref().ok().cloned(),
+                error: result.as_ref().err().cloned(),
+                latency,
+            });
+        }
+
+        match result {
+            Ok(output) => output,
+            Err(error) => format!("Error: {error}"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        fixtures,
+        mock_transport::{MockResponse, MockTransport},
+    };
+    use reqwest::Method;
+
+    /// A completion calling `name` with `arguments` once per `(id, name, arguments)`.
+    fn tool_calls(calls: &[(&str, &str, &str)]) -> MockResponse {
+        let tool_calls: Vec<Value> = calls
+            .iter()
+            .map(|(id, name, arguments)| {
+                json!({
+                    "id": id,
+                    "type": "function",
+                    "function": { "name": name, "arguments": arguments },
+                })
+            })
+            .collect();
+
+        MockResponse::json(&json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": null, "tool_calls": tool_calls },
+                "finish_reason": "tool_calls",
+            }],
+            "usage": fixtures::usage(20, 10),
+        }))
+    }
+
+    fn add(openai: OpenAI) -> Agent {
+        Agent::new(openai).model("gpt-4o-mini").tool(
+            "add",
+            "Add two numbers",
+            json!({ "type": "object", "properties": { "a": {}, "b": {} } }),
+            |arguments: Value| async move {
+                match (arguments["a"].as_i64(), arguments["b"].as_i64()) {
+                    (Some(a), Some(b)) => Ok((a + b).to_string()),
+                    _ => Err("a and b must be integers".to_string()),
+                }
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn parallel_tool_calls_are_answered_in_one_turn() {
+        let mock = Arc::new(MockTransport::new());
+        mock.once(
+            Method::POST,
+            "/chat/completions",
+            tool_calls(&[
+                ("call_1", "add", r#"{"a":1,"b":2}"#),
+                ("call_2", "add", r#"{"a":3,"b":4}"#),
+            ]),
+        )
+        .on(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::json(&fixtures::chat_completion("gpt-4o-mini", "3 and 7")),
+        );
+        let recorder = TranscriptRecorder::new();
+
+        let output = add(mock.client())
+            .system("Be brief")
+            .recorder(recorder.clone())
+            .run("1 + 2 and 3 + 4?")
+            .await
+            .unwrap();
+
+        assert_eq!(output.answer, "3 and 7");
+        assert_eq!(output.usage.prompt_tokens, 32);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        let first = requests[0].json().unwrap();
+        assert_eq!(first["tools"][0]["function"]["name"], "add");
+        assert_eq!(first["messages"].as_array().unwrap().len(), 2);
+
+        let messages = requests[1].json().unwrap()["messages"].clone();
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[2]["tool_calls"][1]["id"], "call_2");
+        assert_eq!(
+            messages[3],
+            json!({ "role": "tool", "tool_call_id": "call_1", "content": "3" })
+        );
+        assert_eq!(
+            messages[4],
+            json!({ "role": "tool", "tool_call_id": "call_2", "content": "7" })
+        );
+        assert_eq!(output.messages.len(), 6);
+
+        let transcript = recorder.transcript();
+        let kinds: Vec<_> = transcript
+            .events
+            .iter()
+            .map(|event| match event {
+                TranscriptEvent::ModelTurn { .. } => "model",
+                TranscriptEvent::ToolCall { .. } => "tool",
+            })
+            .collect();
+        assert_eq!(kinds, ["model", "tool", "tool", "model"]);
+        assert!(matches!(
+            &transcript.events[2],
+            TranscriptEvent::ToolCall { id, name, arguments, output: Some(output), error: None, .. }
+                if id == "call_2" && name == "add" && arguments["b"] == 4 && output == "7"
+        ));
+        assert_eq!(transcript.usage().prompt_tokens, 32);
+
+        recorder.clear();
+        assert!(recorder.transcript().events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tool_errors_are_sent_back_to_the_model() {
+        let mock = Arc::new(MockTransport::new());
+        mock.once(
+            Method::POST,
+            "/chat/completions",
+            tool_calls(&[
+                ("call_1", "add", r#"{"a":"one","b":2}"#),
+                ("call_2", "sub", r#"{"a":1,"b":2}"#),
+                ("call_3", "add", "{"),
+            ]),
+        )
+        .on(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::json(&fixtures::chat_completion("gpt-4o-mini", "Sorry")),
+        );
+        let recorder = TranscriptRecorder::new();
+
+        let output = add(mock.client())
+            .recorder(recorder.clone())
+            .run("one + 2?")
+            .await
+            .unwrap();
+        assert_eq!(output.answer, "Sorry");
+
+        let messages = mock.last_request().unwrap().json().unwrap()["messages"].clone();
+        assert_eq!(messages[2]["content"], "Error: a and b must be integers");
+        assert_eq!(messages[3]["content"], "Error: unknown tool `sub`");
+        assert!(messages[4]["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("Error: invalid arguments: "));
+
+        let errors: Vec<_> = recorder
+            .transcript()
+            .events
+            .into_iter()
+            .filter_map(|event| match event {
+                TranscriptEvent::ToolCall {
+                    arguments,
+                    output: None,
+                    error: Some(error),
+                    ..
+                } => Some((arguments, error)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[2].0, Value::String("{".to_string()));
+    }
+
+    #[tokio::test]
+    async fn runs_stop_after_max_turns() {
+        let mock = Arc::new(MockTransport::new());
+        mock.on(
+            Method::POST,
+            "/chat/completions",
+            tool_calls(&[("call_1", "add", r#"{"a":1,"b":2}"#)]),
+        );
+        let recorder = TranscriptRecorder::new();
+
+        let result = add(mock.client())
+            .max_turns(2)
+            .recorder(recorder.clone())
+            .run("1 + 2?")
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(OpenAIError::UnexpectedResponse(message))
+                if message == "agent still calling tools after 2 turns"
+        ));
+        assert_eq!(mock.requests().len(), 2);
+        assert_eq!(recorder.transcript().events.len(), 4);
+        assert_eq!(recorder.transcript().usage().total_tokens, 60);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    metrics::request_model,
+};
+use reqwest::{Request, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Endpoints scoped to a deployment on Azure OpenAI, called on
+/// `/openai/deployments/{deployment}` instead of `/openai`.
+const DEPLOYMENT_ENDPOINTS: &[&str] = &[
+    "/chat/completions",
+    "/completions",
+    "/embeddings",
+    "/audio/transcriptions",
+    "/audio/translations",
+    "/audio/speech",
+    "/images/generations",
+    "/images/edits",
+    "/images/variations",
+];
+
+/// Settings of a client talking to Azure OpenAI, see [`OpenAI::azure`][crate::openai::OpenAI::azure].
+///
+/// Requests keep the paths of the OpenAI API and are rewritten for Azure: the endpoints served by
+/// a deployment (chat completions, embeddings, audio and images) go to
+/// `/openai/deployments/{deployment}/...`, the deployment being the one mapped to the model of the
+/// request or, if none is, the model name itself, and every request gets the `api-version`
+/// query parameter.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AzureConfig {
+    /// Version of the Azure OpenAI API, e.g. `2024-10-21`
+    pub api_version: String,
+
+    /// Deployment serving each model name
+    pub deployments: BTreeMap<String, String>,
+}
+
+impl AzureConfig {
+    /// Create a new [`AzureConfig`] for `api_version`, deployments being named after their models.
+    pub fn new(api_version: impl Into<String>) -> Self {
+        Self {
+            api_version: api_version.into(),
+            deployments: BTreeMap::new(),
+        }
+    }
+
+    /// Send the requests for `model` to `deployment`.
+    pub fn deployment(mut self, model: impl Into<String>, deployment: impl Into<String>) -> Self {
+        self.deployments.insert(model.into(), deployment.into());
+        self
+    }
+
+    /// The deployment serving `model`.
+    pub fn deployment_for<'a>(&'a self, model: &'a str) -> &'a str {
+        self.deployments.get(model).map_or(model, String::as_str)
+    }
+
+    /// Point `request`, sent to `base_url` with an OpenAI API path, at the Azure endpoint, the
+    /// deployment being the one of `model` or, without one, of the model of its JSON body.
+    ///
+    /// The body is never buffered: the model of multipart forms must be given.
+    pub(crate) fn rewrite(
+        &self,
+        base_url: &str,
+        mut request: Request,
+        model: Option<&str>,
+    ) -> OpenAIResult<Request> {
+        let base_url = base_url.trim_end_matches('/');
+        let url = request.url().to_string();
+        let Some(relative) = url.strip_prefix(base_url) else {
+            return Ok(request);
+        };
+        let (path, query) = match relative.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (relative, None),
+        };
+
+        let prefix = if DEPLOYMENT_ENDPOINTS.contains(&path) {
+            let model = model
+                .map(str::to_string)
+                .or_else(|| {
+                    request
+                        .body()
+                        .and_then(|body| body.as_bytes())
+                        .and_then(request_model)
+                })
+                .ok_or_else(|| {
+                    OpenAIError::ConfigError(format!(
+                        "{path} needs a model to find its Azure deployment"
+                    ))
+                })?;
+
+            format!("/openai/deployments/{}", self.deployment_for(&model))
+        } else {
+            "/openai".to_string()
+        };
+
+        let mut rewritten = Url::parse(&format!("{base_url}{prefix}{path}"))
+            .map_err(|error| OpenAIError::ConfigError(format!("invalid Azure URL: {error}")))?;
+        rewritten.set_query(query);
+        rewritten
+            .query_pairs_mut()
+            .append_pair("api-version", &self.api_version);
+        *request.url_mut() = rewritten;
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+
+    #[test]
+    fn other_endpoints_keep_their_query() {
+        let azure = AzureConfig::new("2024-10-21");
+        let request = Client::new()
+            .get("https://res.openai.azure.com/files?purpose=batch")
+            .build()
+            .unwrap();
+
+        let request = azure
+            .rewrite("https://res.openai.azure.com/", request, None)
+            .unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://res.openai.azure.com/openai/files?purpose=batch&api-version=2024-10-21"
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    mod client {
+        use super::*;
+        use crate::{
+            openai::OpenAI,
+            openai_api::completion::ChatCompletionRequest,
+            test_util::{
+                fixtures,
+                mock_transport::{MockRequest, MockResponse, MockTransport},
+            },
+            transport::HttpTransport,
+        };
+        use reqwest::Method;
+        use serde_json::json;
+        use std::sync::Arc;
+
+        const ENDPOINT: &str = "http://res.openai.invalid";
+
+        fn client(mock: &Arc<MockTransport>) -> OpenAI {
+            let azure = AzureConfig::new("2024-10-21")
+                .deployment("gpt-4o-mini", "chat-prod")
+                .deployment("dall-e-2", "images-prod");
+            let mut openai = OpenAI::azure(ENDPOINT, "azure-key", azure);
+            openai.set_transport(Arc::clone(mock) as Arc<dyn HttpTransport>);
+
+            openai
+        }
+
+        fn assert_sent_to_azure(request: &MockRequest, path: &str) {
+            assert_eq!(
+                request.url.as_str(),
+                format!("{ENDPOINT}{path}?api-version=2024-10-21")
+            );
+            assert_eq!(request.headers["api-key"], "azure-key");
+            assert!(request.headers.get("authorization").is_none());
+        }
+
+        #[tokio::test]
+        async fn chat_completions_go_to_the_mapped_deployment() {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::POST,
+                "/openai/deployments/chat-prod/chat/completions",
+                MockResponse::json(&fixtures::chat_completion("gpt-4o-mini", "Hello")),
+            );
+            let openai = client(&mock);
+
+            let request = ChatCompletionRequest::new(
+                "gpt-4o-mini",
+                vec![json!({"role": "user", "content": "Hi"})],
+            );
+            openai.completions().create(request).await.unwrap();
+
+            assert_sent_to_azure(
+                &mock.last_request().unwrap(),
+                "/openai/deployments/chat-prod/chat/completions",
+            );
+        }
+
+        #[tokio::test]
+        async fn unmapped_models_are_their_own_deployment() {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::POST,
+                "/openai/deployments/text-embedding-3-small/embeddings",
+                MockResponse::json(&fixtures::embeddings("text-embedding-3-small", &["a"], 3)),
+            );
+            let openai = client(&mock);
+
+            openai
+                .embeddings()
+                .create("a", Some("text-embedding-3-small".into()), None, None, None)
+                .await
+                .unwrap();
+
+            assert_sent_to_azure(
+                &mock.last_request().unwrap(),
+                "/openai/deployments/text-embedding-3-small/embeddings",
+            );
+        }
+
+        #[tokio::test]
+        async fn multipart_forms_go_to_the_deployment_of_their_model() {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::POST,
+                "/openai/deployments/images-prod/images/edits",
+                MockResponse::json(&json!({ "created": 0, "data": [] })),
+            );
+            let openai = client(&mock);
+
+            openai
+                .images()
+                .edit_bytes(
+                    Some("dall-e-2".into()),
+                    b"png".to_vec(),
+                    None,
+                    "Add a hat",
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+
+            let request = mock.last_request().unwrap();
+            assert_sent_to_azure(&request, "/openai/deployments/images-prod/images/edits");
+            let content_type = request.headers["content-type"].to_str().unwrap();
+            assert!(content_type.starts_with("multipart/form-data"));
+        }
+
+        #[tokio::test]
+        async fn other_endpoints_go_to_the_resource() {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::GET,
+                "/openai/models",
+                MockResponse::json(&fixtures::list(Vec::new())),
+            );
+            let openai = client(&mock);
+
+            openai.client().get_models().await.unwrap();
+
+            assert_sent_to_azure(&mock.last_request().unwrap(), "/openai/models");
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::{
+        completion::{ChatCompletionChunk, ChatCompletionRequest, Classification},
+        embeddings::EmbeddingsResponse,
+        images::ImageGenerationRequest,
+    },
+    structured::StructuredOutput,
+};
+use futures::{stream::BoxStream, StreamExt};
+#[cfg(feature = "schemars")]
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{future::Future, sync::Arc};
+use tokio::runtime::Runtime;
+
+/// Synchronous client, for CLI tools, build scripts and other programs without an async runtime,
+/// e.g. `OpenAIBlocking::new(api_key, base_url)?.completions().create(request)?`.
+///
+/// It doesn't use `reqwest::blocking`: it drives an async [`OpenAI`] client on a current-thread
+/// tokio runtime of its own, on the calling thread. A client on `reqwest::blocking` would need a
+/// second implementation of every API, and would skip the async layers of [`OpenAI`] (transports,
+/// retries, rate limits, providers, streaming...), which all apply here. It wouldn't work within
+/// an async runtime either, `reqwest::blocking` panicking there. Background work of the client
+/// (endpoint health checks, scheduler refills) only runs during calls.
+///
+/// # Errors
+///
+/// Tokio doesn't allow blocking on a runtime within another, so every call fails with an
+/// [`OpenAIError::ConfigError`] when made from within an async runtime (e.g. in a
+/// `#[tokio::main]` function or a `#[tokio::test]`), without sending any request. Async programs
+/// should use [`OpenAI`] directly, or call the blocking client from
+/// `tokio::task::spawn_blocking`. Dropping the client within a runtime is fine.
+#[derive(Clone)]
+pub struct OpenAIBlocking {
+    openai: OpenAI,
+    runtime: Arc<BlockingRuntime>,
+}
+
+impl OpenAIBlocking {
+    /// Create a new blocking client with `api_key` and `base_url`, see [`OpenAI::new`].
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the client on success, or an
+    /// [`OpenAIError::IoError`][crate::error_handling::OpenAIError::IoError] if its runtime can't
+    /// be started.
+    pub fn new(api_key: impl Into<String>, base_url: impl Into<String>) -> OpenAIResult<Self> {
+        Self::from_client(OpenAI::new(api_key, base_url))
+    }
+
+    /// Make the calls of `openai` (e.g. built with [`OpenAI::builder`]) synchronously.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the client on success, or an
+    /// [`OpenAIError::IoError`][crate::error_handling::OpenAIError::IoError] if its runtime can't
+    /// be started.
+    pub fn from_client(openai: OpenAI) -> OpenAIResult<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        Ok(Self {
+            openai,
+            runtime: Arc::new(BlockingRuntime(Some(runtime))),
+        })
+    }
+
+    /// The underlying async client.
+    pub const fn client(&self) -> &OpenAI {
+        &self.openai
+    }
+
+    /// The underlying async client, to change its settings.
+    pub fn client_mut(&mut self) -> &mut OpenAI {
+        &mut self.openai
+    }
+
+    /// Wait for `future`, e.g. a call of an API without a blocking counterpart:
+    /// `blocking.block_on(blocking.client().threads().create(...))`.
+    ///
+    /// # Returns
+    ///
+    /// The output of `future`, or an [`OpenAIError::ConfigError`] when called from within an
+    /// async runtime, see [`OpenAIBlocking`].
+    pub fn block_on<T>(&self, future: impl Future<Output = OpenAIResult<T>>) -> OpenAIResult<T> {
+        self.runtime.block_on(future)
+    }
+
+    pub fn completions(&self) -> CompletionsBlocking {
+        CompletionsBlocking(self.clone())
+    }
+
+    pub fn embeddings(&self) -> EmbeddingsBlocking {
+        EmbeddingsBlocking(self.clone())
+    }
+
+    pub fn images(&self) -> ImagesBlocking {
+        ImagesBlocking(self.clone())
+    }
+
+    pub fn audio(&self) -> AudioBlocking {
+        AudioBlocking(self.clone())
+    }
+}
+
+/// Iterator over a stream of the client, e.g. the chunks of
+/// [`CompletionsBlocking::create_stream`], each item being waited for in turn.
+///
+/// It ends after an [`OpenAIError::ConfigError`] if it's iterated from within an async runtime.
+pub struct BlockingStream<T> {
+    runtime: Arc<BlockingRuntime>,
+    stream: Option<BoxStream<'static, OpenAIResult<T>>>,
+}
+
+impl<T> Iterator for BlockingStream<T> {
+    type Item = OpenAIResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let stream = self.stream.as_mut()?;
+
+        match self.runtime.block_on(async { Ok(stream.next().await) }) {
+            Ok(item) => item,
+            Err(error) => {
+                self.stream = None;
+                Some(Err(error))
+            },
+        }
+    }
+}
+
+/// Runtime of a blocking client, failing instead of panicking within another runtime.
+struct BlockingRuntime(Option<Runtime>);
+
+impl BlockingRuntime {
+    /// Wait for `future`, unless called from within an async runtime.
+    fn block_on<T>(&self, future: impl Future<Output = OpenAIResult<T>>) -> OpenAIResult<T> {
+        match &self.0 {
+            Some(runtime) if tokio::runtime::Handle::try_current().is_err() => {
+                runtime.block_on(future)
+            },
+            _ => Err(OpenAIError::ConfigError(
+                "the blocking client can't be used from within an async runtime, use OpenAI or \
+                 tokio::task::spawn_blocking instead"
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+impl Drop for BlockingRuntime {
+    fn drop(&mut self) {
+        // Shutting a runtime down blocks, which isn't allowed within another runtime.
+        if let Some(runtime) = self.0.take() {
+            if tokio::runtime::Handle::try_current().is_ok() {
+                runtime.shutdown_background();
+            }
+        }
+    }
+}
+
+/// Generate a blocking API wrapping each listed method of an async API.
+macro_rules! blocking_api {
+    (
+        $(#[$attribute:meta])*
+        $name:ident => $accessor:ident($module:ident::$api:ident) {
+            $(
+                $(#[$method_attribute:meta])*
+                fn $method:ident $([$($generics:tt)*])?
+                    ($($argument:ident: $type:ty),* $(,)?) -> $output:ty;
+            )*
+        }
+    ) => {
+        $(#[$attribute])*
+        #[derive(Clone)]
+        pub struct $name(OpenAIBlocking);
+
+        impl $name {
+            $(
+                #[doc = concat!(
+                    "Blocking version of [`", stringify!($api), "::", stringify!($method),
+                    "`][crate::openai_api::", stringify!($module), "::", stringify!($api), "::",
+                    stringify!($method), "].",
+                )]
+                $(#[$method_attribute])*
+                pub fn $method $(<$($generics)*>)? (
+                    &self,
+                    $($argument: $type),*
+                ) -> OpenAIResult<$output> {
+                    self.0.block_on(self.0.openai.$accessor().$method($($argument),*))
+                }
+            )*
+        }
+    };
+}
+
+blocking_api! {
+    /// Blocking counterpart of [`CompletionsApi`][crate::openai_api::completion::CompletionsApi].
+    CompletionsBlocking => completions(completion::CompletionsApi) {
+        fn create(request: ChatCompletionRequest) -> Value;
+        #[cfg(feature = "schemars")]
+        fn extract[T: JsonSchema + DeserializeOwned](request: ChatCompletionRequest) -> T;
+        #[cfg(feature = "schemars")]
+        fn extract_with_repairs[T: JsonSchema + DeserializeOwned](
+            request: ChatCompletionRequest,
+            max_repairs: u32,
+        ) -> T;
+        fn create_structured[T: StructuredOutput](
+            request: ChatCompletionRequest,
+            max_repairs: u32,
+        ) -> T;
+        fn create_parsed[T: DeserializeOwned](
+            request: ChatCompletionRequest,
+            max_repairs: u32,
+        ) -> T;
+        #[cfg(feature = "schemars")]
+        fn extract_text[T: JsonSchema + DeserializeOwned](text: &str, model: Option<Model>) -> T;
+        fn classify(text: &str, labels: &[&str], model: Option<Model>) -> Classification;
+    }
+}
+
+impl CompletionsBlocking {
+    /// Blocking version of
+    /// [`CompletionsApi::create_stream`][crate::openai_api::completion::CompletionsApi::create_stream],
+    /// iterating over the chunks as they arrive.
+    pub fn create_stream(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> OpenAIResult<BlockingStream<ChatCompletionChunk>> {
+        let client = &self.0;
+        let stream = client.block_on(client.openai.completions().create_stream(request))?;
+
+        Ok(BlockingStream {
+            runtime: client.runtime.clone(),
+            stream: Some(stream.boxed()),
+        })
+    }
+}
+
+blocking_api! {
+    /// Blocking counterpart of [`EmbeddingsApi`][crate::openai_api::embeddings::EmbeddingsApi].
+    EmbeddingsBlocking => embeddings(embeddings::EmbeddingsApi) {
+        fn create(
+            input: &str,
+            model: Option<Model>,
+            encoding_format: Option<&str>,
+            dimensions: Option<u64>,
+            user: Option<&str>,
+        ) -> Value;
+        fn create_typed(
+            input: &str,
+            model: Option<Model>,
+            dimensions: Option<u64>,
+            user: Option<&str>,
+        ) -> EmbeddingsResponse;
+        fn create_base64(
+            input: &str,
+            model: Option<Model>,
+            dimensions: Option<u64>,
+            user: Option<&str>,
+        ) -> EmbeddingsResponse;
+        fn embed_many(
+            inputs: &[&str],
+            model: Option<Model>,
+            dimensions: Option<u64>,
+        ) -> Vec<Vec<f32>>;
+    }
+}
+
+blocking_api! {
+    /// Blocking counterpart of [`ImagesApi`][crate::openai_api::images::ImagesApi].
+    ImagesBlocking => images(images::ImagesApi) {
+        fn generate(
+            prompt: &str,
+            model: Option<Model>,
+            size: Option<&str>,
+            response_format: Option<&str>,
+            n: Option<u64>,
+            user: Option<&str>,
+        ) -> Value;
+        fn create(request: ImageGenerationRequest) -> Value;
+        #[cfg(feature = "fs")]
+        fn edit(
+            model: Option<Model>,
+            image_path: &str,
+            mask_path: &str,
+            prompt: &str,
+            size: Option<&str>,
+            response_format: Option<&str>,
+            n: Option<u64>,
+            user: Option<&str>,
+        ) -> Value;
+        #[cfg(feature = "fs")]
+        fn variation(
+            model: Option<Model>,
+            image_path: &str,
+            size: Option<&str>,
+            response_format: Option<&str>,
+            n: Option<u64>,
+            user: Option<&str>,
+        ) -> Value;
+        fn edit_bytes(
+            model: Option<Model>,
+            image: Vec<u8>,
+            mask: Option<Vec<u8>>,
+            prompt: &str,
+            size: Option<&str>,
+            response_format: Option<&str>,
+            n: Option<u64>,
+            user: Option<&str>,
+        ) -> Value;
+        fn variation_bytes(
+            model: Option<Model>,
+            image: Vec<u8>,
+            size: Option<&str>,
+            response_format: Option<&str>,
+            n: Option<u64>,
+            user: Option<&str>,
+        ) -> Value;
+    }
+}
+
+blocking_api! {
+    /// Blocking counterpart of [`AudioApi`][crate::openai_api::audio::AudioApi].
+    AudioBlocking => audio(audio::AudioApi) {
+        #[cfg(feature = "fs")]
+        fn transcribe(
+            model: Option<Model>,
+            file_path: &str,
+            prompt: Option<&str>,
+            response_format: Option<&str>,
+            temperature: Option<f64>,
+            language: Option<&str>,
+        ) -> Value;
+        #[cfg(feature = "fs")]
+        fn translate(
+            model: Option<Model>,
+            file_path: &str,
+            prompt: Option<&str>,
+            response_format: Option<&str>,
+            temperature: Option<f64>,
+        ) -> Value;
+        fn transcribe_bytes(
+            model: Option<Model>,
+            audio: Vec<u8>,
+            file_name: &str,
+            prompt: Option<&str>,
+            response_format: Option<&str>,
+            temperature: Option<f64>,
+            language: Option<&str>,
+        ) -> Value;
+        fn translate_bytes(
+            model: Option<Model>,
+            audio: Vec<u8>,
+            file_name: &str,
+            prompt: Option<&str>,
+            response_format: Option<&str>,
+            temperature: Option<f64>,
+        ) -> Value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai_api::completion::ChatCompletionRequest;
+    use serde_json::json;
+
+    fn chat_request() -> ChatCompletionRequest {
+        ChatCompletionRequest::new(
+            "gpt-4o-mini",
+            vec![json!({"role": "user", "content": "Hi"})],
+        )
+    }
+
+    #[tokio::test]
+    async fn calls_fail_within_an_async_runtime() {
+        let blocking = OpenAIBlocking::new("sk-test", "http://localhost:1/v1").unwrap();
+
+        let result = blocking.completions().create(chat_request());
+        assert!(matches!(
+            result,
+            Err(OpenAIError::ConfigError(message)) if message.contains("spawn_blocking")
+        ));
+    }
+
+    #[cfg(feature = "test-util")]
+    mod server {
+        use super::*;
+        use crate::test_util::mock_server::MockOpenAI;
+
+        /// A mock server running on a runtime of its own, the test thread staying outside of it.
+        fn start(mount: impl Future<Output = MockOpenAI>) -> (Runtime, MockOpenAI) {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .enable_all()
+                .build()
+                .unwrap();
+            let server = runtime.block_on(mount);
+
+            (runtime, server)
+        }
+
+        #[test]
+        fn requests_are_sent_from_the_calling_thread() {
+            let (_runtime, server) = start(async {
+                let server = MockOpenAI::start().await;
+                server.mock_chat_completion("Hello").await;
+                server.mock_embeddings(3).await;
+                server
+            });
+            let blocking = OpenAIBlocking::new("sk-test", server.base_url()).unwrap();
+
+            let response = blocking.completions().create(chat_request()).unwrap();
+            assert_eq!(response["choices"][0]["message"]["content"], "Hello");
+
+            let embeddings = blocking
+                .embeddings()
+                .embed_many(&["a", "b"], Some("text-embedding-3-small".into()), None)
+                .unwrap();
+            assert_eq!(embeddings.len(), 2);
+            assert_eq!(embeddings[0].len(), 3);
+        }
+
+        #[test]
+        fn streams_are_iterated_chunk_by_chunk() {
+            let (_runtime, server) = start(async {
+                let server = MockOpenAI::start().await;
+                server.mock_chat_completion_stream(&["Hel", "lo"]).await;
+                server
+            });
+            let blocking = OpenAIBlocking::new("sk-test", server.base_url()).unwrap();
+
+            let chunks = blocking
+                .completions()
+                .create_stream(chat_request())
+                .unwrap()
+                .collect::<OpenAIResult<Vec<_>>>()
+                .unwrap();
+            let text: String = chunks
+                .iter()
+                .filter_map(|chunk| chunk.choices.first()?.delta.content.clone())
+                .collect();
+            assert_eq!(text, "Hello");
+        }
+
+        #[test]
+        fn api_errors_are_returned() {
+            let (_runtime, server) = start(async {
+                let server = MockOpenAI::start().await;
+                server
+                    .mock_error("POST", "/chat/completions", 400, "Bad model")
+                    .await;
+                server
+            });
+            let blocking = OpenAIBlocking::new("sk-test", server.base_url()).unwrap();
+
+            let result = blocking.completions().create(chat_request());
+            assert!(matches!(
+                result,
+                Err(OpenAIError::ApiError { status: 400, message, .. }) if message == "Bad model"
+            ));
+        }
+
+        #[test]
+        fn streams_end_when_iterated_within_an_async_runtime() {
+            let (runtime, server) = start(async {
+                let server = MockOpenAI::start().await;
+                server.mock_chat_completion_stream(&["Hel", "lo"]).await;
+                server
+            });
+            let blocking = OpenAIBlocking::new("sk-test", server.base_url()).unwrap();
+            let mut stream = blocking
+                .completions()
+                .create_stream(chat_request())
+                .unwrap();
+
+            runtime.block_on(async {
+                assert!(matches!(
+                    stream.next(),
+                    Some(Err(OpenAIError::ConfigError(_)))
+                ));
+                assert!(stream.next().is_none());
+            });
+        }
+    }
+}
@@ -0,0 +1,481 @@
+#[cfg(target_arch = "wasm32")]
+use crate::config::UNSUPPORTED_ON_WASM;
+use crate::{
+    azure::AzureConfig,
+    circuit_breaker::CircuitBreakerPolicy,
+    config::{apply_response_compression, DefaultModels, Http2Config},
+    credentials::{ApiKeyPool, ApiKeyProvider},
+    error_handling::{OpenAIError, OpenAIResult},
+    openai::OpenAI,
+    provider::{AuthScheme, Provider},
+    retry::RetryPolicy,
+    scheduler::SchedulingPolicy,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::Proxy;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Client,
+};
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Builder of an [`OpenAI`] client, gathering its settings in one place, e.g.
+/// `OpenAI::builder().api_key(key).http_client(client).organization("org-123").build()?` around a
+/// pre-configured [`reqwest::Client`] (corporate proxy, custom TLS, pooling settings).
+///
+/// The settings that aren't given keep the defaults of [`OpenAI::new`]. Its `Debug` output leaves
+/// out the API key, the values of the default headers (e.g. gateway tokens) and the proxies and
+/// HTTP client, which may hold credentials.
+#[derive(Clone, Default)]
+pub struct OpenAIBuilder {
+    api_key: Option<String>,
+    api_key_provider: Option<SharedApiKeyProvider>,
+    base_url: Option<String>,
+    provider: Option<Provider>,
+    azure: Option<AzureConfig>,
+    organization: Option<String>,
+    project: Option<String>,
+    default_headers: Vec<(String, String)>,
+    http_client: Option<Client>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxies: Vec<Proxy>,
+    no_proxy: bool,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    http2: Option<Http2Config>,
+    response_compression: Option<bool>,
+    json_timeout: Option<Duration>,
+    upload_timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    circuit_breaker: Option<CircuitBreakerPolicy>,
+    max_concurrent_requests: Option<usize>,
+    default_models: DefaultModels,
+    adaptive_rate_limit: bool,
+    app_info: Option<String>,
+}
+
+impl OpenAIBuilder {
+    /// Set the API key, which only providers of local servers don't require.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Fetch the API key from `api_key_provider` before every request instead, see
+    /// [`OpenAI::set_api_key_provider`].
+    pub fn api_key_provider(mut self, api_key_provider: Arc<dyn ApiKeyProvider>) -> Self {
+        self.api_key_provider = Some(SharedApiKeyProvider(api_key_provider));
+        self
+    }
+
+    /// Spread the requests over several API keys, e.g. of projects with their own quotas, picked
+    /// round-robin and set aside while they keep failing, see [`ApiKeyPool`] for the other
+    /// selection strategies and weights.
+    pub fn api_keys<I>(self, api_keys: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.api_key_provider(Arc::new(ApiKeyPool::new(api_keys)))
+    }
+
+    /// Set the base URL of the API, defaulting to the provider's.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Set the OpenAI-compatible provider, with its base URL, authentication scheme and extra
+    /// headers.
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Talk to Azure OpenAI with `azure`, the base URL being the endpoint of the resource (e.g.
+    /// `https://my-resource.openai.azure.com`), see [`OpenAI::azure`].
+    pub fn azure(mut self, azure: AzureConfig) -> Self {
+        self.azure = Some(azure);
+        self
+    }
+
+    /// Set the organization ID sent in the `OpenAI-Organization` header.
+    pub fn organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+
+    /// Set the project ID sent in the `OpenAI-Project` header.
+    pub fn project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    /// Send the header `name` with `value` with every request, e.g. the token of an internal
+    /// gateway, `OpenAI-Beta` flags (merged with the beta features of the client) or tracking IDs.
+    pub fn default_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Send the requests with `http_client` instead of a client built by the builder, keeping its
+    /// proxy, TLS, pooling and timeout settings.
+    pub fn http_client(mut self, http_client: Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Send the requests through `proxy`, of the HTTP client built by the builder, e.g.
+    /// `Proxy::https("http://proxy.corp:3128")?.basic_auth(user, password)`. SOCKS proxies
+    /// (`socks5://` URLs) need the `socks` feature.
+    ///
+    /// Several proxies can be given, the first one matching the URL of a request being used.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Ignore the system proxies (from the `HTTP_PROXY`, `HTTPS_PROXY` and `ALL_PROXY` environment
+    /// variables), of the HTTP client built by the builder.
+    pub fn no_proxy(mut self) -> Self {
+        self.no_proxy = true;
+        self
+    }
+
+    /// Set the total timeout of every request, of the HTTP client built by the builder.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for establishing a connection, of the HTTP client built by the builder.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Set the timeout of each read of a response (reset whenever data arrives), of the HTTP
+    /// client built by the builder.
+    pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// Keep at most `max_idle` idle connections per host in the pool, of the HTTP client built by
+    /// the builder.
+    pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max_idle);
+        self
+    }
+
+    /// Close the pooled connections idle for longer than `idle_timeout` (90 seconds by default),
+    /// of the HTTP client built by the builder, e.g. a longer one to keep connections warm
+    /// between the batches of an embedding pipeline.
+    pub fn pool_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Send TCP keepalive probes every `interval` on the connections of the HTTP client built by
+    /// the builder, so idle connections aren't dropped by NATs and load balancers.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Set the HTTP/2 settings of the HTTP client built by the builder, e.g.
+    /// `Http2Config { adaptive_window: true, ..Default::default() }` to adapt the flow-control
+    /// windows to the bandwidth of high-latency links.
+    pub fn http2(mut self, http2: Http2Config) -> Self {
+        self.http2 = Some(http2);
+        self
+    }
+
+    /// Set whether the HTTP client built by the builder asks for compressed responses (with
+    /// `Accept-Encoding`) and decompresses them, cutting the bandwidth of large embedding and list
+    /// responses. Needs the `gzip` or `brotli` feature, which turns it on by default.
+    pub fn response_compression(mut self, response_compression: bool) -> Self {
+        self.response_compression = Some(response_compression);
+        self
+    }
+
+    /// Set the total timeout of the JSON requests, see [`OpenAI::set_json_timeout`].
+    pub fn json_timeout(mut self, json_timeout: Duration) -> Self {
+        self.json_timeout = Some(json_timeout);
+        self
+    }
+
+    /// Set the total timeout of the multipart uploads, see [`OpenAI::set_upload_timeout`].
+    pub fn upload_timeout(mut self, upload_timeout: Duration) -> Self {
+        self.upload_timeout = Some(upload_timeout);
+        self
+    }
+
+    /// Set the retry policy of failed requests.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Fail requests fast while the API keeps failing, see [`OpenAI::set_circuit_breaker`].
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreakerPolicy) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Cap the number of requests in flight across the client and its clones, queueing the others
+    /// by priority, e.g. so a large embedding job doesn't exhaust the file descriptors, see
+    /// [`OpenAI::set_scheduling_policy`]. A request is in flight until its response body is read,
+    /// streams and downloads included.
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = Some(max_concurrent_requests);
+        self
+    }
+
+    /// Delay requests according to the rate limits reported by the API, see
+    /// [`OpenAI::set_adaptive_rate_limit`].
+    pub fn adaptive_rate_limit(mut self, adaptive_rate_limit: bool) -> Self {
+        self.adaptive_rate_limit = adaptive_rate_limit;
+        self
+    }
+
+    /// Set the model of the chat completions that don't specify one, see
+    /// [`OpenAI::set_default_models`].
+    pub fn default_chat_model(mut self, model: impl Into<String>) -> Self {
+        self.default_models.chat = Some(model.into());
+        self
+    }
+
+    /// Set the model of the embeddings that don't specify one.
+    pub fn default_embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.default_models.embeddings = Some(model.into());
+        self
+    }
+
+    /// Set the model of the image generations, edits and variations that don't specify one.
+    pub fn default_image_model(mut self, model: impl Into<String>) -> Self {
+        self.default_models.images = Some(model.into());
+        self
+    }
+
+    /// Set the model of the transcriptions and translations that don't specify one.
+    pub fn default_audio_model(mut self, model: impl Into<String>) -> Self {
+        self.default_models.audio = Some(model.into());
+        self
+    }
+
+    /// Set the model of the moderations that don't specify one.
+    pub fn default_moderation_model(mut self, model: impl Into<String>) -> Self {
+        self.default_models.moderation = Some(model.into());
+        self
+    }
+
+    /// Append an application identifier (e.g. `myapp/1.2`) to the `User-Agent` header.
+    pub fn app_info(mut self, app_info: impl Into<String>) -> Self {
+        self.app_info = Some(app_info.into());
+        self
+    }
+
+    /// Build the client.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the client on success, or an [`OpenAIError::ConfigError`] if the API
+    /// key is missing, a default header is invalid or settings of the HTTP client (timeouts,
+    /// proxies, connection pooling) are set along with a given HTTP client, or on `wasm32`, where
+    /// the `fetch` API manages connections itself.
+    pub fn build(self) -> OpenAIResult<OpenAI> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let proxies = !self.proxies.is_empty() || self.no_proxy;
+        #[cfg(target_arch = "wasm32")]
+        let proxies = self.no_proxy;
+
+        let client = match self.http_client {
+            Some(client) => {
+                if self.timeout.is_some()
+                    || self.connect_timeout.is_some()
+                    || self.read_timeout.is_some()
+                {
+                    return Err(OpenAIError::ConfigError(
+                        "the timeouts of a given HTTP client must be set on it".to_string(),
+                    ));
+                }
+                if proxies {
+                    return Err(OpenAIError::ConfigError(
+                        "the proxies of a given HTTP client must be set on it".to_string(),
+                    ));
+                }
+                if self.pool_max_idle_per_host.is_some()
+                    || self.pool_idle_timeout.is_some()
+                    || self.tcp_keepalive.is_some()
+                    || self.http2.is_some()
+                    || self.response_compression.is_some()
+                {
+                    return Err(OpenAIError::ConfigError(
+                        "the connection settings of a given HTTP client must be set on it"
+                            .to_string(),
+                    ));
+                }
+
+                client
+            },
+            None => {
+                let mut client = Client::builder();
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    if let Some(timeout) = self.timeout {
+                        client = client.timeout(timeout);
+                    }
+                    if let Some(connect_timeout) = self.connect_timeout {
+                        client = client.connect_timeout(connect_timeout);
+                    }
+                    if let Some(read_timeout) = self.read_timeout {
+                        client = client.read_timeout(read_timeout);
+                    }
+                    if let Some(max_idle) = self.pool_max_idle_per_host {
+                        client = client.pool_max_idle_per_host(max_idle);
+                    }
+                    if let Some(idle_timeout) = self.pool_idle_timeout {
+                        client = client.pool_idle_timeout(idle_timeout);
+                    }
+                    if let Some(interval) = self.tcp_keepalive {
+                        client = client.tcp_keepalive(interval);
+                    }
+                    if let Some(http2) = &self.http2 {
+                        client = http2.apply(client);
+                    }
+                    if self.no_proxy {
+                        client = client.no_proxy();
+                    }
+                    for proxy in self.proxies {
+                        client = client.proxy(proxy);
+                    }
+                }
+                #[cfg(target_arch = "wasm32")]
+                if self.timeout.is_some()
+                    || self.connect_timeout.is_some()
+                    || self.read_timeout.is_some()
+                    || self.pool_max_idle_per_host.is_some()
+                    || self.pool_idle_timeout.is_some()
+                    || self.tcp_keepalive.is_some()
+                    || self.http2.is_some()
+                    || proxies
+                {
+                    return Err(OpenAIError::ConfigError(UNSUPPORTED_ON_WASM.to_string()));
+                }
+                if let Some(response_compression) = self.response_compression {
+                    client = apply_response_compression(client, response_compression);
+                }
+
+                client.build()?
+            },
+        };
+
+        for (name, value) in &self.default_headers {
+            HeaderName::try_from(name.as_str())
+                .map_err(|error| OpenAIError::ConfigError(format!("header {name:?}: {error}")))?;
+            HeaderValue::try_from(value.as_str())
+                .map_err(|error| OpenAIError::ConfigError(format!("header {name:?}: {error}")))?;
+        }
+
+        let provider = self.provider.unwrap_or_default();
+        let api_key = match self.api_key {
+            Some(api_key) => api_key,
+            None if self.api_key_provider.is_some() => String::new(),
+            None if provider.auth_scheme() == AuthScheme::None => String::new(),
+            None => return Err(OpenAIError::ConfigError("missing api_key".to_string())),
+        };
+
+        let mut openai = OpenAI::from_parts(client, api_key, String::new());
+        if let Some(SharedApiKeyProvider(api_key_provider)) = self.api_key_provider {
+            openai.set_api_key_provider(api_key_provider);
+        }
+        openai.set_provider(&provider);
+        if let Some(base_url) = self.base_url {
+            openai.set_base_url(base_url);
+        }
+        if let Some(azure) = self.azure {
+            openai.set_azure(Some(azure));
+        }
+
+        openai.set_organization(self.organization);
+        openai.set_project(self.project);
+        openai.set_default_headers(self.default_headers);
+        openai.set_json_timeout(self.json_timeout);
+        openai.set_upload_timeout(self.upload_timeout);
+        if let Some(retry_policy) = self.retry_policy {
+            openai.set_retry_policy(retry_policy);
+        }
+        openai.set_circuit_breaker(self.circuit_breaker);
+        if let Some(max_concurrency) = self.max_concurrent_requests {
+            openai.set_scheduling_policy(Some(SchedulingPolicy {
+                max_concurrency,
+                ..Default::default()
+            }));
+        }
+        openai.set_default_models(self.default_models);
+        openai.set_adaptive_rate_limit(self.adaptive_rate_limit);
+        openai.set_app_info(self.app_info.as_deref());
+
+        Ok(openai)
+    }
+}
+
+impl fmt::Debug for OpenAIBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let default_headers: Vec<(&str, &str)> = self
+            .default_headers
+            .iter()
+            .map(|(name, _)| (name.as_str(), "[REDACTED]"))
+            .collect();
+        #[cfg(not(target_arch = "wasm32"))]
+        let proxies = self.proxies.len();
+        #[cfg(target_arch = "wasm32")]
+        let proxies = 0;
+
+        f.debug_struct("OpenAIBuilder")
+            .field("api_key", &self.api_key.as_ref().map(|_| "[REDACTED]"))
+            .field("api_key_provider", &self.api_key_provider)
+            .field("base_url", &self.base_url)
+            .field("provider", &self.provider)
+            .field("azure", &self.azure)
+            .field("organization", &self.organization)
+            .field("project", &self.project)
+            .field("default_headers", &default_headers)
+            .field("http_client", &self.http_client.is_some())
+            .field("proxies", &proxies)
+            .field("no_proxy", &self.no_proxy)
+            .field("timeout", &self.timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("pool_max_idle_per_host", &self.pool_max_idle_per_host)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("tcp_keepalive", &self.tcp_keepalive)
+            .field("http2", &self.http2)
+            .field("response_compression", &self.response_compression)
+            .field("json_timeout", &self.json_timeout)
+            .field("upload_timeout", &self.upload_timeout)
+            .field("retry_policy", &self.retry_policy)
+            .field("circuit_breaker", &self.circuit_breaker)
+            .field("max_concurrent_requests", &self.max_concurrent_requests)
+            .field("default_models", &self.default_models)
+            .field("adaptive_rate_limit", &self.adaptive_rate_limit)
+            .field("app_info", &self.app_info)
+            .finish()
+    }
+}
+
+/// An [`ApiKeyProvider`] of a builder, formatted without its keys.
+#[derive(Clone)]
+struct SharedApiKeyProvider(Arc<dyn ApiKeyProvider>);
+
+impl fmt::Debug for SharedApiKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiKeyProvider")
+    }
+}
@@ -0,0 +1,376 @@
+use crate::{
+    capabilities::model_info,
+    chunking::{content_tokens, ApproximateTokenCounter, TokenCounter},
+    error_handling::OpenAIResult,
+    model::Model,
+    openai::OpenAI,
+    openai_api::{completion::ChatCompletionRequest, embeddings::EmbeddingsResponse},
+    retry::RetryPolicy,
+    runtime::{self, Instant},
+    scheduler::Priority,
+};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::VecDeque, time::Duration};
+use tokio::sync::Mutex;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits a [`BulkEngine`] schedules requests within.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BulkLimits {
+    /// Maximum number of requests sent per minute
+    pub requests_per_minute: Option<u32>,
+
+    /// Maximum number of (estimated) tokens sent per minute, prompt and completion included
+    pub tokens_per_minute: Option<u32>,
+
+    /// Maximum number of requests in flight
+    pub concurrency: usize,
+
+    /// Number of times an item failing with a retryable error (see
+    /// [`RetryPolicy::should_retry`][crate::retry::RetryPolicy::should_retry]) is sent again
+    /// before its error is returned, in place of the retries of the client
+    pub max_retries: u32,
+}
+
+impl Default for BulkLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            concurrency: 8,
+            max_retries: 3,
+        }
+    }
+}
+
+/// A request of a bulk batch.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+pub enum BulkRequest {
+    /// A chat completion
+    Chat(ChatCompletionRequest),
+
+    /// Embeddings of a text, with the client's default embeddings model if `model` is `None`
+    Embeddings {
+        input: String,
+        model: Option<Model>,
+        dimensions: Option<u64>,
+    },
+}
+
+impl BulkRequest {
+    /// Embeddings of `input` with `model`.
+    pub fn embeddings(input: impl Into<String>, model: Option<Model>) -> Self {
+        Self::Embeddings {
+            input: input.into(),
+            model,
+            dimensions: None,
+        }
+    }
+
+    /// Estimated number of tokens the request consumes, prompt and maximum completion included,
+    /// the completion being capped by the [`model_info`] of the model if it's known.
+    pub fn estimated_tokens(&self) -> u64 {
+        match self {
+            Self::Chat(request) => {
+                let prompt: usize = request
+                    .messages()
+                    .iter()
+                    .map(|message| content_tokens(&message["content"]))
+                    .sum();
+
+                let completion = match (
+                    request.token_limit(),
+                    request.model_name().and_then(model_info),
+                ) {
+                    (Some(limit), Some(info)) => limit.min(info.max_output_tokens),
+                    (limit, _) => limit.unwrap_or_default(),
+                };
+
+                prompt as u64 + completion
+            },
+            Self::Embeddings { input, .. } => ApproximateTokenCounter.count_tokens(input) as u64,
+        }
+    }
+}
+
+/// Response to a [`BulkRequest`].
+#[derive(Clone, Debug)]
+pub enum BulkResponse {
+    /// Response to a [`BulkRequest::Chat`]
+    Chat(Value),
+
+    /// Response to a [`BulkRequest::Embeddings`]
+    Embeddings(EmbeddingsResponse),
+}
+
+/// Engine executing large batches of heterogeneous requests within rate limits.
+///
+/// Requests are admitted so that neither the configured [`BulkLimits`] nor the limits reported by
+/// the API in its `x-ratelimit-*` headers are exceeded, failed items are retried, and results come
+/// back in request order. The budget is kept across [`BulkEngine::run`] calls.
+pub struct BulkEngine {
+    openai: OpenAI,
+    limits: BulkLimits,
+    sent: Mutex<VecDeque<(Instant, u64)>>,
+}
+
+impl BulkEngine {
+    /// Create a new [`BulkEngine`] sending requests through `openai`, with the
+    /// [`Priority::Batch`] priority.
+    ///
+    /// Failed items are retried by the engine, after being admitted again, up to
+    /// [`BulkLimits::max_retries`] times with the delays of the client's [`RetryPolicy`]: the
+    /// client doesn't retry them itself, so its retries don't multiply with the engine's.
+    pub fn new(openai: OpenAI, limits: BulkLimits) -> Self {
+        let mut openai = openai.with_priority(Priority::Batch);
+        openai.set_retry_policy(RetryPolicy {
+            max_retries: 0,
+            ..openai.retry_policy().clone()
+        });
+
+        Self {
+            openai,
+            limits,
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub const fn limits(&self) -> &BulkLimits {
+        &self.limits
+    }
+
+    /// Execute every request, returning one result per request in the same order.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - The requests to execute.
+    ///
+    /// # Returns
+    ///
+    /// The response or the [`OpenAIError`][crate::error_handling::OpenAIError] of the last attempt of each request.
+    pub async fn run(&self, requests: Vec<BulkRequest>) -> Vec<OpenAIResult<BulkResponse>> {
+        stream::iter(requests)
+            .map(|request| self.execute(request))
+            .buffered(self.limits.concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    async fn execute(&self, request: BulkRequest) -> OpenAIResult<BulkResponse> {
+        let tokens = request.estimated_tokens();
+        let mut attempt = 0;
+
+        loop {
+            self.admit(tokens).await;
+
+            match self.send(request.clone()).await {
+                Err(error)
+                    if attempt < self.limits.max_retries
+                        && self.openai.retry_policy().should_retry_failure(&error) =>
+                {
+                    runtime::sleep(self.openai.retry_policy().delay(attempt)).await;
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+
+    async fn send(&self, request: BulkRequest) -> OpenAIResult<BulkResponse> {
+        match request {
+            BulkRequest::Chat(request) => {
+                let response = self.openai.completions().create(request).await?;
+
+                Ok(BulkResponse::Chat(response))
+            },
+            BulkRequest::Embeddings {
+                input,
+                model,
+                dimensions,
+            } => {
+                let response = self
+                    .openai
+                    .embeddings()
+                    .create_typed(&input, model, dimensions, None)
+                    .await?;
+
+                Ok(BulkResponse::Embeddings(response))
+            },
+        }
+    }
+
+    /// Wait until a request of `tokens` tokens fits in the limits, then count it as sent.
+    async fn admit(&self, tokens: u64) {
+        // Holding the lock while waiting admits requests one at a time, in order.
+        let mut sent = self.sent.lock().await;
+
+        loop {
+            let now = Instant::now();
+            while sent
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) >= WINDOW)
+            {
+                sent.pop_front();
+            }
+
+            let wait = self.local_wait(&sent, tokens, now).max(
+                self.openai
+                    .rate_limits()
+                    .map_or(Duration::ZERO, |limits| limits.wait_for(tokens)),
+            );
+            if wait.is_zero() {
+                break;
+            }
+
+            runtime::sleep(wait).await;
+        }
+
+        sent.push_back((Instant::now(), tokens));
+    }
+
+    /// Time until the oldest requests leave the window enough for the configured limits to allow
+    /// one more request of `tokens` tokens.
+    fn local_wait(&self, sent: &VecDeque<(Instant, u64)>, tokens: u64, now: Instant) -> Duration {
+        let until_expired = |index: usize| {
+            sent.get(index)
+                .map_or(Duration::ZERO, |(at, _)| WINDOW - now.duration_since(*at))
+        };
+
+        let requests = match self.limits.requests_per_minute {
+            Some(limit) if sent.len() >= limit.max(1) as usize => {
+                until_expired(sent.len() - limit.max(1) as usize)
+            },
+            _ => Duration::ZERO,
+        };
+
+        let tokens = match self.limits.tokens_per_minute {
+            Some(limit) => {
+                // Expire the oldest requests until the new one fits, a request larger than the
+                // whole limit being sent alone.
+                let mut used: u64 = sent.iter().map(|(_, tokens)| tokens).sum();
+                let mut index = 0;
+                while used + tokens > u64::from(limit) && index < sent.len() {
+                    used -= sent[index].1;
+                    index += 1;
+                }
+
+                if index == 0 {
+                    Duration::ZERO
+                } else {
+                    until_expired(index - 1)
+                }
+            },
+            None => Duration::ZERO,
+        };
+
+        requests.max(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(limits: BulkLimits) -> BulkEngine {
+        BulkEngine::new(OpenAI::new("sk-test", "https://api.openai.com/v1"), limits)
+    }
+
+    /// Requests sent `ago` with their tokens, oldest first.
+    fn sent(now: Instant, requests: &[(u64, u64)]) -> VecDeque<(Instant, u64)> {
+        requests
+            .iter()
+            .map(|&(ago, tokens)| (now - Duration::from_secs(ago), tokens))
+            .collect()
+    }
+
+    #[test]
+    fn requests_wait_for_enough_tokens_to_leave_the_window() {
+        let engine = engine(BulkLimits {
+            tokens_per_minute: Some(100),
+            ..BulkLimits::default()
+        });
+        let now = Instant::now();
+        let sent = sent(now, &[(50, 60), (20, 30)]);
+
+        assert_eq!(engine.local_wait(&sent, 10, now), Duration::ZERO);
+        // The first request leaves the window in 10 seconds.
+        assert_eq!(engine.local_wait(&sent, 20, now), Duration::from_secs(10));
+        assert_eq!(engine.local_wait(&sent, 70, now), Duration::from_secs(10));
+        // Both have to leave it.
+        assert_eq!(engine.local_wait(&sent, 71, now), Duration::from_secs(40));
+        // A request larger than the limit is sent alone.
+        assert_eq!(engine.local_wait(&sent, 500, now), Duration::from_secs(40));
+        assert_eq!(
+            engine.local_wait(&VecDeque::new(), 500, now),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn requests_wait_for_the_request_limit() {
+        let engine = engine(BulkLimits {
+            requests_per_minute: Some(2),
+            ..BulkLimits::default()
+        });
+        let now = Instant::now();
+
+        assert_eq!(
+            engine.local_wait(&sent(now, &[(30, 0)]), 0, now),
+            Duration::ZERO
+        );
+        assert_eq!(
+            engine.local_wait(&sent(now, &[(45, 0), (30, 0)]), 0, now),
+            Duration::from_secs(15)
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn items_are_retried_by_the_engine_only() {
+        use crate::{
+            error_handling::OpenAIError,
+            test_util::mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+        use std::sync::Arc;
+
+        let mock = Arc::new(MockTransport::new());
+        mock.on(
+            Method::POST,
+            "/embeddings",
+            MockResponse::error(503, "overloaded"),
+        );
+        let mut openai = mock.client();
+        openai.set_retry_policy(RetryPolicy {
+            max_retries: 3,
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            ..RetryPolicy::default()
+        });
+        let engine = BulkEngine::new(
+            openai,
+            BulkLimits {
+                max_retries: 2,
+                ..BulkLimits::default()
+            },
+        );
+
+        let results = engine
+            .run(vec![BulkRequest::embeddings(
+                "a",
+                Some("text-embedding-3-small".into()),
+            )])
+            .await;
+
+        assert!(matches!(
+            results[..],
+            [Err(OpenAIError::ApiError { status: 503, .. })]
+        ));
+        assert_eq!(mock.requests().len(), 3);
+    }
+}
@@ -0,0 +1,231 @@
+#[cfg(feature = "fs")]
+use crate::runtime;
+use crate::{runtime::Instant, util::request_key};
+use reqwest::Request;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+#[cfg(feature = "fs")]
+use std::{fs, path::PathBuf};
+
+/// Storage backend for a [`ResponseCache`], mapping cache keys to raw response bodies.
+pub trait CacheStore: Send + Sync {
+    /// Return the body stored under `key`, if any and not expired.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store `body` under `key`, expiring after `ttl` if set.
+    fn set(&self, key: &str, body: Vec<u8>, ttl: Option<Duration>);
+}
+
+struct CachedBody {
+    body: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+/// [`CacheStore`] keeping every response in memory.
+#[derive(Default)]
+pub struct InMemoryCacheStore {
+    entries: Mutex<HashMap<String, CachedBody>>,
+}
+
+impl InMemoryCacheStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheStore for InMemoryCacheStore {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get(key)? {
+            CachedBody {
+                expires_at: Some(expires_at),
+                ..
+            } if *expires_at <= Instant::now() => {
+                entries.remove(key);
+                None
+            },
+            CachedBody { body, .. } => Some(body.clone()),
+        }
+    }
+
+    fn set(&self, key: &str, body: Vec<u8>, ttl: Option<Duration>) {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), CachedBody { body, expires_at });
+    }
+}
+
+/// [`CacheStore`] writing each response to a file in a directory, so the cache survives restarts
+/// (e.g. between CI runs).
+///
+/// Each file holds the expiry as a Unix timestamp (0 if the entry never expires) on its first line,
+/// followed by the response body.
+#[cfg(feature = "fs")]
+pub struct FileCacheStore {
+    directory: PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl FileCacheStore {
+    /// Create a store writing to `directory`, which is created if it doesn't exist.
+    pub fn new(directory: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self { directory })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{key}.cache"))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl CacheStore for FileCacheStore {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let contents = fs::read(&path).ok()?;
+        let newline = contents.iter().position(|byte| *byte == b'\n')?;
+        let expires_at = std::str::from_utf8(&contents[..newline])
+            .ok()?
+            .parse::<u64>()
+            .ok()?;
+
+        if expires_at != 0 && expires_at <= runtime::unix_time().as_secs() {
+            let _ = fs::remove_file(path);
+            return None;
+        }
+
+        Some(contents[newline + 1..].to_vec())
+    }
+
+    fn set(&self, key: &str, body: Vec<u8>, ttl: Option<Duration>) {
+        let expires_at = ttl.map_or(0, |ttl| (runtime::unix_time() + ttl).as_secs());
+        let mut contents = format!("{expires_at}\n").into_bytes();
+        contents.extend(body);
+
+        // Caching is best-effort: a failed write only means a future cache miss.
+        let _ = fs::write(self.path(key), contents);
+    }
+}
+
+/// Opt-in cache of JSON POST responses keyed on a hash of the built request: its full URL (base
+/// URL and Azure deployment included), its headers (credentials, organization and project
+/// included, but not the `Idempotency-Key`) and its serialized body (model, parameters and
+/// messages), so clients with other credentials, settings or base URLs never share entries.
+///
+/// Only successful responses from the configured endpoints are cached. This is meant for
+/// deterministic calls (temperature 0, fixed seeds), e.g. to stop re-running the same prompts in CI.
+#[derive(Clone)]
+pub struct ResponseCache {
+    store: Arc<dyn CacheStore>,
+    ttl: Option<Duration>,
+    endpoints: Vec<String>,
+}
+
+impl ResponseCache {
+    /// Create a cache backed by `store`, caching `/chat/completions`, `/embeddings` and
+    /// `/moderations` responses without expiry.
+    pub fn new(store: impl CacheStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            ttl: None,
+            endpoints: ["/chat/completions", "/embeddings", "/moderations"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+
+    /// Expire cached responses after `ttl`.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Replace the list of endpoints whose responses are cached.
+    pub fn endpoints<I>(mut self, endpoints: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.endpoints = endpoints.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Whether responses from `url` are cached.
+    pub fn caches(&self, url: &str) -> bool {
+        self.endpoints.iter().any(|endpoint| endpoint == url)
+    }
+
+    /// Cache key of a built request, from its URL, headers and body, so clients with other API
+    /// keys, organizations, projects or providers sharing the cache don't get each other's
+    /// responses.
+    pub fn key(request: &Request) -> String {
+        request_key(request)
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.store.get(key)
+    }
+
+    pub(crate) fn set(&self, key: &str, body: Vec<u8>) {
+        self.store.set(key, body, self.ttl);
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::*;
+    use crate::{
+        openai::OpenAI,
+        test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        },
+    };
+    use reqwest::Method;
+    use serde_json::Value;
+
+    async fn embed(openai: &OpenAI) -> Value {
+        openai
+            .embeddings()
+            .create("a", Some("text-embedding-3-small".into()), None, None, None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn clients_with_other_credentials_dont_share_entries() {
+        let mock = Arc::new(MockTransport::new());
+        mock.on(
+            Method::POST,
+            "/embeddings",
+            MockResponse::json(&fixtures::embeddings("text-embedding-3-small", &["a"], 3)),
+        );
+        let mut openai = mock.client();
+        openai.set_response_cache(Some(ResponseCache::new(InMemoryCacheStore::new())));
+        let mut other_key = openai.clone();
+        other_key.set_api_key("sk-other");
+        let mut other_project = openai.clone();
+        other_project.set_project(Some("proj-other".to_string()));
+
+        embed(&openai).await;
+        embed(&openai).await;
+        assert_eq!(mock.requests().len(), 1);
+
+        embed(&other_key).await;
+        embed(&other_project).await;
+        assert_eq!(mock.requests().len(), 3);
+
+        embed(&other_key).await;
+        assert_eq!(mock.requests().len(), 3);
+    }
+}
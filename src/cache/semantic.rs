@@ -0,0 +1,292 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::completion::ChatCompletionRequest,
+};
+use serde_json::Value;
+use std::sync::RwLock;
+
+/// A cached completion along with the embedding of the prompt that produced it.
+#[derive(Clone, Debug)]
+pub struct SemanticCacheEntry {
+    /// Embedding of the prompt
+    pub embedding: Vec<f32>,
+
+    /// Text of the prompt
+    pub prompt: String,
+
+    /// Completion returned for the prompt
+    pub response: Value,
+}
+
+/// Storage backend for a [`SemanticCache`].
+pub trait SemanticCacheStore: Send + Sync {
+    /// Find the stored entry most similar to `embedding`, returning its cosine similarity and response.
+    ///
+    /// Entries with embeddings of other dimensions, e.g. from another embedding model, never match.
+    fn nearest(&self, embedding: &[f32]) -> Option<(f32, Value)>;
+
+    /// Store a new entry.
+    fn insert(&self, entry: SemanticCacheEntry);
+}
+
+/// [`SemanticCacheStore`] keeping every entry in memory and searching them linearly.
+#[derive(Default)]
+pub struct InMemorySemanticStore {
+    entries: RwLock<Vec<SemanticCacheEntry>>,
+}
+
+impl InMemorySemanticStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored entries.
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    /// Whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl SemanticCacheStore for InMemorySemanticStore {
+    fn nearest(&self, embedding: &[f32]) -> Option<(f32, Value)> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.embedding.len() == embedding.len())
+            .map(|entry| (cosine_similarity(embedding, &entry.embedding), entry))
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(similarity, entry)| (similarity, entry.response.clone()))
+    }
+
+    fn insert(&self, entry: SemanticCacheEntry) {
+        self.entries.write().unwrap().push(entry);
+    }
+}
+
+/// Cache returning a previous chat completion when a new prompt is semantically close enough to
+/// an earlier one.
+///
+/// Prompts are embedded with the configured embedding model and compared using cosine similarity.
+/// The cache doesn't look at the request parameters, so use one cache per model/settings combination.
+pub struct SemanticCache<S = InMemorySemanticStore> {
+    store: S,
+    embedding_model: Model,
+    threshold: f32,
+}
+
+impl SemanticCache<InMemorySemanticStore> {
+    /// Create a new in-memory semantic cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `embedding_model` - Model used to embed the prompts.
+    /// * `threshold` - Minimum cosine similarity (between -1 and 1) for a cached completion to be returned.
+    pub fn new(embedding_model: impl Into<Model>, threshold: f32) -> Self {
+        Self::with_store(InMemorySemanticStore::new(), embedding_model, threshold)
+    }
+}
+
+impl<S: SemanticCacheStore> SemanticCache<S> {
+    /// Create a new semantic cache backed by a custom [`SemanticCacheStore`].
+    pub fn with_store(store: S, embedding_model: impl Into<Model>, threshold: f32) -> Self {
+        Self {
+            store,
+            embedding_model: embedding_model.into(),
+            threshold,
+        }
+    }
+
+    /// The storage backend of the cache.
+    pub const fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Create a chat completion, or return a cached one for a similar enough prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `openai` - Client used for the embedding and completion requests.
+    /// * `request` - A [`ChatCompletionRequest`] containing the parameters for the completion.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn create(
+        &self,
+        openai: &OpenAI,
+        request: ChatCompletionRequest,
+    ) -> OpenAIResult<Value> {
+        let prompt = prompt_text(request.messages());
+        let embedding = openai
+            .embeddings()
+            .create_typed(&prompt, Some(self.embedding_model.clone()), None, None)
+            .await?
+            .into_vectors()
+            .pop()
+            .ok_or_else(|| OpenAIError::UnexpectedResponse("missing embedding".to_string()))?;
+
+        if let Some((similarity, cached)) = self.store.nearest(&embedding) {
+            if similarity >= self.threshold {
+                return Ok(cached);
+            }
+        }
+
+        let response = openai.completions().create(request).await?;
+
+        self.store.insert(SemanticCacheEntry {
+            embedding,
+            prompt,
+            response: response.clone(),
+        });
+
+        Ok(response)
+    }
+}
+
+/// Cosine similarity between two vectors (0 if either is a zero vector or if their dimensions
+/// differ).
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let (dot, norm_a, norm_b) = a
+        .iter()
+        .zip(b)
+        .fold((0.0, 0.0, 0.0), |(dot, norm_a, norm_b), (x, y)| {
+            (dot + x * y, norm_a + x * x, norm_b + y * y)
+        });
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// Flatten the text content of chat messages into a single prompt.
+fn prompt_text(messages: &[Value]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let role = message["role"].as_str().unwrap_or_default();
+            let content = match &message["content"] {
+                Value::String(text) => text.clone(),
+                Value::Array(parts) => parts
+                    .iter()
+                    .filter_map(|part| part["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                _ => String::new(),
+            };
+
+            format!("{role}: {content}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        fixtures,
+        mock_transport::{MockResponse, MockTransport},
+    };
+    use reqwest::Method;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    /// A client whose `n`-th embedding is `embeddings[n]`, answering every completion with
+    /// `"Hello"`.
+    fn client(embeddings: &[&[f32]]) -> (Arc<MockTransport>, OpenAI) {
+        let mock = Arc::new(MockTransport::new());
+        for embedding in embeddings {
+            mock.once(
+                Method::POST,
+                "/embeddings",
+                MockResponse::json(&json!({
+                    "object": "list",
+                    "data": [{ "object": "embedding", "index": 0, "embedding": embedding }],
+                    "model": "text-embedding-3-small",
+                    "usage": { "prompt_tokens": 1, "total_tokens": 1 },
+                })),
+            );
+        }
+        mock.on(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::json(&fixtures::chat_completion("gpt-4o-mini", "Hello")),
+        );
+        let openai = mock.client();
+
+        (mock, openai)
+    }
+
+    fn request(prompt: &str) -> ChatCompletionRequest {
+        ChatCompletionRequest::from_messages(vec![json!({ "role": "user", "content": prompt })])
+            .model("gpt-4o-mini")
+    }
+
+    fn completions(mock: &MockTransport) -> usize {
+        mock.requests()
+            .iter()
+            .filter(|request| request.url.path().ends_with("/chat/completions"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn similar_prompts_hit_and_others_miss() {
+        let (mock, openai) = client(&[&[1.0, 0.0], &[0.99, 0.1], &[0.0, 1.0]]);
+        let cache = SemanticCache::new("text-embedding-3-small", 0.9);
+
+        let first = cache.create(&openai, request("Hi")).await.unwrap();
+        let similar = cache.create(&openai, request("Hi!")).await.unwrap();
+        assert_eq!(similar, first);
+        assert_eq!(completions(&mock), 1);
+
+        cache.create(&openai, request("Bye")).await.unwrap();
+        assert_eq!(completions(&mock), 2);
+        assert_eq!(cache.store().len(), 2);
+        assert_eq!(
+            mock.requests()[0].json().unwrap()["model"],
+            "text-embedding-3-small"
+        );
+    }
+
+    #[tokio::test]
+    async fn prompts_exactly_at_the_threshold_hit() {
+        let (mock, openai) = client(&[&[1.0, 0.0], &[3.0, 4.0], &[3.0, 4.0]]);
+        let cache = SemanticCache::new("text-embedding-3-small", 0.6);
+
+        cache.create(&openai, request("Hi")).await.unwrap();
+        cache.create(&openai, request("Hey")).await.unwrap();
+        assert_eq!(completions(&mock), 1);
+
+        let (mock, openai) = client(&[&[1.0, 0.0], &[3.0, 4.0]]);
+        let cache = SemanticCache::new("text-embedding-3-small", 0.61);
+
+        cache.create(&openai, request("Hi")).await.unwrap();
+        cache.create(&openai, request("Hey")).await.unwrap();
+        assert_eq!(completions(&mock), 2);
+    }
+
+    #[tokio::test]
+    async fn embeddings_of_other_dimensions_never_hit() {
+        let (mock, openai) = client(&[&[1.0, 0.0, 0.0], &[1.0, 0.0]]);
+        let cache = SemanticCache::new("text-embedding-3-small", -1.0);
+
+        cache.create(&openai, request("Hi")).await.unwrap();
+        cache.create(&openai, request("Hi")).await.unwrap();
+        assert_eq!(completions(&mock), 2);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), 0.0);
+    }
+}
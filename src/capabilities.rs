@@ -0,0 +1,453 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+/// Sampling parameters rejected by reasoning models.
+const SAMPLING_PARAMETERS: &[&str] = &[
+    "temperature",
+    "top_p",
+    "presence_penalty",
+    "frequency_penalty",
+    "logit_bias",
+    "logprobs",
+    "top_logprobs",
+];
+
+/// Callback receiving the model of a request and a parameter removed or renamed because the model
+/// doesn't support it.
+pub type ParameterReport = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+/// Request parameters a model supports.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ModelCapabilities {
+    /// Request fields the model rejects
+    pub unsupported_parameters: Vec<String>,
+
+    /// Whether the model takes `max_completion_tokens` instead of `max_tokens`
+    pub max_completion_tokens: bool,
+}
+
+impl ModelCapabilities {
+    /// Capabilities of `model` according to the built-in table: reasoning models (`o1`, `o3`,
+    /// `o4-mini`, `gpt-5`...) take no sampling parameters and `max_completion_tokens`, other models
+    /// support everything.
+    pub fn lookup(model: &str) -> Self {
+        let reasoning = ["o1", "o3", "o4", "gpt-5"]
+            .iter()
+            .any(|family| model == *family || model.starts_with(&format!("{family}-")))
+            && !model.starts_with("gpt-5-chat");
+
+        if !reasoning {
+            return Self::default();
+        }
+
+        Self {
+            unsupported_parameters: SAMPLING_PARAMETERS
+                .iter()
+                .map(|parameter| parameter.to_string())
+                .collect(),
+            max_completion_tokens: true,
+        }
+    }
+}
+
+/// Gating of chat completion parameters by model: parameters the target model doesn't support are
+/// omitted from the requests (and `max_tokens` renamed for models expecting
+/// `max_completion_tokens`) instead of failing with a 400, reporting every adjustment to a hook.
+#[derive(Clone, Default)]
+pub struct ParameterGating {
+    models: HashMap<String, ModelCapabilities>,
+    report: Option<ParameterReport>,
+}
+
+impl fmt::Debug for ParameterGating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParameterGating")
+            .field("models", &self.models)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ParameterGating {
+    /// Create a gating using the built-in capability table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `capabilities` for `model` instead of the built-in table, e.g. for a provider's models.
+    pub fn model(mut self, model: impl Into<String>, capabilities: ModelCapabilities) -> Self {
+        self.models.insert(model.into(), capabilities);
+        self
+    }
+
+    /// Call `report` with the model and the parameter every time a parameter is removed or renamed.
+    pub fn on_adjust(mut self, report: impl Fn(&str, &str) + Send + Sync + 'static) -> Self {
+        self.report = Some(Arc::new(report));
+        self
+    }
+
+    /// Capabilities of `model`.
+    pub fn capabilities(&self, model: &str) -> ModelCapabilities {
+        self.models
+            .get(model)
+            .cloned()
+            .unwrap_or_else(|| ModelCapabilities::lookup(model))
+    }
+
+    /// Adjust a chat completion request body to the capabilities of its model.
+    pub(crate) fn apply(&self, body: &mut Value) {
+        let Some(model) = body["model"].as_str().map(str::to_string) else {
+            return;
+        };
+        let Some(object) = body.as_object_mut() else {
+            return;
+        };
+        let capabilities = self.capabilities(&model);
+        let report = |parameter: &str| {
+            if let Some(report) = &self.report {
+                report(&model, parameter);
+            }
+        };
+
+        for parameter in &capabilities.unsupported_parameters {
+            if object.remove(parameter).is_some() {
+                report(parameter);
+            }
+        }
+        if capabilities.max_completion_tokens {
+            if let Some(max_tokens) = object.remove("max_tokens") {
+                object.entry("max_completion_tokens").or_insert(max_tokens);
+                report("max_tokens");
+            }
+        }
+    }
+}
+
+/// Kind of content a model takes or produces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Modality {
+    /// Text, code and JSON included
+    Text,
+
+    /// Images
+    Image,
+
+    /// Speech and other audio
+    Audio,
+}
+
+/// Limits and features of a model, from the built-in table or registered with
+/// [`register_model_info`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ModelInfo {
+    /// Maximum number of tokens of the prompt and the completion together
+    pub context_window: u64,
+
+    /// Maximum number of tokens the model generates in a completion
+    pub max_output_tokens: u64,
+
+    /// Kinds of content the model takes
+    pub input_modalities: Vec<Modality>,
+
+    /// Kinds of content the model produces
+    pub output_modalities: Vec<Modality>,
+
+    /// Whether the model supports tool (function) calling
+    pub tools: bool,
+}
+
+impl ModelInfo {
+    /// Number of prompt tokens left once the maximum completion is reserved.
+    pub fn max_prompt_tokens(&self) -> u64 {
+        self.context_window.saturating_sub(self.max_output_tokens)
+    }
+}
+
+/// Built-in table: model family, context window, max output tokens, image input, tools.
+const MODELS: &[(&str, u64, u64, bool, bool)] = &[
+    ("gpt-5", 400_000, 128_000, true, true),
+    ("gpt-5-mini", 400_000, 128_000, true, true),
+    ("gpt-5-nano", 400_000, 128_000, true, true),
+    ("gpt-4.1", 1_047_576, 32_768, true, true),
+    ("gpt-4.1-mini", 1_047_576, 32_768, true, true),
+    ("gpt-4.1-nano", 1_047_576, 32_768, true, true),
+    ("gpt-4o", 128_000, 16_384, true, true),
+    ("gpt-4o-mini", 128_000, 16_384, true, true),
+    ("gpt-4-turbo", 128_000, 4_096, true, true),
+    ("gpt-4", 8_192, 8_192, false, true),
+    ("gpt-3.5-turbo", 16_385, 4_096, false, true),
+    ("o1", 200_000, 100_000, true, true),
+    ("o1-mini", 128_000, 65_536, false, false),
+    ("o3", 200_000, 100_000, true, true),
+    ("o3-mini", 200_000, 100_000, false, true),
+    ("o4-mini", 200_000, 100_000, true, true),
+    ("text-embedding-3-small", 8_191, 0, false, false),
+    ("text-embedding-3-large", 8_191, 0, false, false),
+    ("text-embedding-ada-002", 8_191, 0, false, false),
+];
+
+/// Model information registered at runtime, taking precedence over the built-in table, shared by
+/// the whole process.
+static REGISTERED: RwLock<BTreeMap<String, ModelInfo>> = RwLock::new(BTreeMap::new());
+
+/// Limits and features of `model`, or `None` for unknown models.
+///
+/// Dated snapshots (`gpt-4o-2024-08-06`) and fine-tuned models (`ft:gpt-4o-mini:org::id`) resolve
+/// to their base model. Entries registered with [`register_model_info`] take precedence over the
+/// built-in table of OpenAI models.
+pub fn model_info(model: &str) -> Option<ModelInfo> {
+    let model = base_model(model);
+
+    {
+        let registered = REGISTERED.read().unwrap();
+        if let Some(name) = family(registered.keys().map(String::as_str), model) {
+            return registered.get(name).cloned();
+        }
+    }
+
+    let name = family(MODELS.iter().map(|(name, ..)| *name), model)?;
+    let &(_, context_window, max_output_tokens, images, tools) =
+        MODELS.iter().find(|(family, ..)| *family == name)?;
+    let embeddings = max_output_tokens == 0;
+
+    Some(ModelInfo {
+        context_window,
+        max_output_tokens,
+        input_modalities: if images {
+            vec![Modality::Text, Modality::Image]
+        } else {
+            vec![Modality::Text]
+        },
+        output_modalities: if embeddings {
+            Vec::new()
+        } else {
+            vec![Modality::Text]
+        },
+        tools,
+    })
+}
+
+/// Use `info` for `model` (and its snapshots) instead of the built-in table, e.g. for a
+/// provider's models or a new release, or remove the registered entry with `None`.
+///
+/// The registry is global: an entry applies to every client of the process, the validation of
+/// requests and the token estimates of [`BulkRequest`][crate::bulk::BulkRequest]s included, until
+/// it is removed.
+pub fn register_model_info(model: impl Into<String>, info: Option<ModelInfo>) {
+    let model = model.into();
+    let mut registered = REGISTERED.write().unwrap();
+
+    match info {
+        Some(info) => registered.insert(model, info),
+        None => registered.remove(&model),
+    };
+}
+
+/// The base model of a fine-tuned model name.
+fn base_model(model: &str) -> &str {
+    model
+        .strip_prefix("ft:")
+        .and_then(|model| model.split(':').next())
+        .unwrap_or(model)
+}
+
+/// The longest of `names` that is `model` or a prefix of it followed by a dash.
+fn family<'a>(names: impl Iterator<Item = &'a str>, model: &str) -> Option<&'a str> {
+    names
+        .filter(|name| {
+            model == *name
+                || model
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.starts_with('-'))
+        })
+        .max_by_key(|name| name.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    #[test]
+    fn reasoning_models_take_no_sampling_parameters() {
+        let reasoning = [
+            "o1",
+            "o1-2024-12-17",
+            "o3-mini",
+            "o4-mini",
+            "gpt-5",
+            "gpt-5-mini",
+        ];
+        let legacy = [
+            "gpt-4o",
+            "gpt-4.1-mini",
+            "gpt-5-chat-latest",
+            "o1x",
+            "llama3",
+        ];
+
+        for model in reasoning {
+            let capabilities = ModelCapabilities::lookup(model);
+            assert!(capabilities.max_completion_tokens, "{model}");
+            assert_eq!(
+                capabilities.unsupported_parameters, SAMPLING_PARAMETERS,
+                "{model}"
+            );
+        }
+        for model in legacy {
+            assert_eq!(
+                ModelCapabilities::lookup(model),
+                ModelCapabilities::default(),
+                "{model}"
+            );
+        }
+    }
+
+    /// A chat completion request body for `model` with sampling parameters and `max_tokens`.
+    fn body(model: &str) -> Value {
+        json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": "Hi" }],
+            "temperature": 0.2,
+            "top_p": 0.9,
+            "max_tokens": 100,
+            "seed": 7,
+        })
+    }
+
+    #[test]
+    fn requests_are_adjusted_to_their_model() {
+        let adjusted = Arc::new(Mutex::new(Vec::new()));
+        let gating = ParameterGating::new().on_adjust({
+            let adjusted = Arc::clone(&adjusted);
+            move |model, parameter| {
+                adjusted
+                    .lock()
+                    .unwrap()
+                    .push(format!("{model}: {parameter}"))
+            }
+        });
+
+        let mut reasoning = body("o3-mini");
+        gating.apply(&mut reasoning);
+        assert_eq!(
+            reasoning,
+            json!({
+                "model": "o3-mini",
+                "messages": [{ "role": "user", "content": "Hi" }],
+                "max_completion_tokens": 100,
+                "seed": 7,
+            })
+        );
+        assert_eq!(
+            *adjusted.lock().unwrap(),
+            [
+                "o3-mini: temperature",
+                "o3-mini: top_p",
+                "o3-mini: max_tokens"
+            ]
+        );
+
+        adjusted.lock().unwrap().clear();
+        for model in ["gpt-4o", "llama3"] {
+            let mut legacy = body(model);
+            gating.apply(&mut legacy);
+            assert_eq!(legacy, body(model));
+        }
+        let mut no_model = json!({ "messages": [], "temperature": 0.2 });
+        gating.apply(&mut no_model);
+        assert_eq!(no_model["temperature"], 0.2);
+        assert!(adjusted.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn explicit_max_completion_tokens_win() {
+        let mut body = body("gpt-5");
+        body["max_completion_tokens"] = json!(50);
+        ParameterGating::new().apply(&mut body);
+
+        assert_eq!(body["max_completion_tokens"], 50);
+        assert!(body.get("max_tokens").is_none());
+    }
+
+    #[test]
+    fn registered_capabilities_replace_the_table() {
+        let gating = ParameterGating::new().model(
+            "llama3",
+            ModelCapabilities {
+                unsupported_parameters: vec!["seed".to_string()],
+                max_completion_tokens: false,
+            },
+        );
+
+        let mut body = body("llama3");
+        gating.apply(&mut body);
+        assert!(body.get("seed").is_none());
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(body["max_tokens"], 100);
+    }
+
+    #[test]
+    fn snapshots_and_fine_tuned_models_resolve_to_their_base_model() {
+        let gpt_4o = model_info("gpt-4o").unwrap();
+        assert_eq!(gpt_4o.context_window, 128_000);
+        assert_eq!(gpt_4o.input_modalities, [Modality::Text, Modality::Image]);
+
+        assert_eq!(model_info("gpt-4o-2024-08-06"), Some(gpt_4o.clone()));
+        assert_eq!(model_info("ft:gpt-4o:acme::abc123"), Some(gpt_4o));
+        assert_eq!(
+            model_info("gpt-4o-mini-2024-07-18")
+                .unwrap()
+                .max_output_tokens,
+            16_384
+        );
+        assert_eq!(
+            model_info("ft:gpt-4.1-nano-2025-04-14:acme:support:xyz")
+                .unwrap()
+                .context_window,
+            1_047_576
+        );
+        assert_eq!(model_info("gpt-4ox"), None);
+        assert_eq!(model_info("llama3"), None);
+
+        let embeddings = model_info("text-embedding-3-small").unwrap();
+        assert!(embeddings.output_modalities.is_empty());
+        assert_eq!(embeddings.max_prompt_tokens(), 8_191);
+    }
+
+    #[test]
+    fn registered_models_take_precedence_over_the_table() {
+        // A name of its own, the registry being shared by the whole test process.
+        let model = "gpt-4.1-nano-registry-test";
+        let info = ModelInfo {
+            context_window: 32_000,
+            max_output_tokens: 4_000,
+            input_modalities: vec![Modality::Text],
+            output_modalities: vec![Modality::Text],
+            tools: false,
+        };
+        assert_eq!(
+            model_info(&format!("{model}-2025")).unwrap().context_window,
+            1_047_576
+        );
+
+        register_model_info(model, Some(info.clone()));
+        assert_eq!(model_info(model), Some(info.clone()));
+        assert_eq!(model_info(&format!("{model}-2025")), Some(info.clone()));
+        assert_eq!(model_info(&format!("ft:{model}:acme::id")), Some(info));
+        assert_eq!(
+            model_info("gpt-4.1-nano").unwrap().context_window,
+            1_047_576
+        );
+
+        register_model_info(model, None);
+        assert_eq!(model_info(model).unwrap().context_window, 1_047_576);
+    }
+}
@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Counts the tokens of a piece of text.
+///
+/// Implement this with a real tokenizer (e.g. tiktoken) for exact chunk sizes;
+/// [`ApproximateTokenCounter`] is used by default.
+pub trait TokenCounter {
+    /// Number of tokens in `text`.
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// Token counter using the usual "one token is about four characters" approximation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApproximateTokenCounter;
+
+impl TokenCounter for ApproximateTokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+}
+
+impl<F: Fn(&str) -> usize> TokenCounter for F {
+    fn count_tokens(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// Approximate number of tokens in a chat message content, either a string or a list of parts.
+pub(crate) fn content_tokens(content: &Value) -> usize {
+    match content {
+        Value::String(text) => ApproximateTokenCounter.count_tokens(text),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .map(|text| ApproximateTokenCounter.count_tokens(text))
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// A piece of a larger text produced by a chunker.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Chunk {
+    /// Position of the chunk in the source text
+    pub index: usize,
+
+    /// Text of the chunk
+    pub text: String,
+
+    /// Byte offset where the chunk starts in the source text
+    pub start: usize,
+
+    /// Byte offset where the chunk ends in the source text
+    pub end: usize,
+
+    /// Number of tokens in the chunk, as reported by the token counter
+    pub tokens: usize,
+}
+
+impl From<Chunk> for String {
+    fn from(chunk: Chunk) -> Self {
+        chunk.text
+    }
+}
+
+/// Splits text on word boundaries into chunks of at most `chunk_size` tokens.
+///
+/// Words longer than `chunk_size` are kept whole in their own chunk.
+pub struct TokenChunker<C = ApproximateTokenCounter> {
+    chunk_size: usize,
+    overlap: usize,
+    counter: C,
+}
+
+/// Splits text on sentence boundaries into chunks of at most `chunk_size` tokens.
+///
+/// Sentences longer than `chunk_size` are kept whole in their own chunk.
+pub struct SentenceChunker<C = ApproximateTokenCounter> {
+    chunk_size: usize,
+    overlap: usize,
+    counter: C,
+}
+
+macro_rules! chunker_impl {
+    ($chunker:ident, $segments:ident) => {
+        impl $chunker<ApproximateTokenCounter> {
+            /// Create a new chunker producing chunks of at most `chunk_size` tokens, without overlap.
+            pub fn new(chunk_size: usize) -> Self {
+                Self {
+                    chunk_size: chunk_size.max(1),
+                    overlap: 0,
+                    counter: ApproximateTokenCounter,
+                }
+            }
+        }
+
+        impl<C: TokenCounter> $chunker<C> {
+            /// Set how many tokens consecutive chunks share (must be smaller than the chunk size).
+            pub fn overlap(mut self, overlap: usize) -> Self {
+                self.overlap = overlap.min(self.chunk_size - 1);
+                self
+            }
+
+            /// Use a custom [`TokenCounter`] to measure chunk sizes.
+            pub fn token_counter<T: TokenCounter>(self, counter: T) -> $chunker<T> {
+                $chunker {
+                    chunk_size: self.chunk_size,
+                    overlap: self.overlap,
+                    counter,
+                }
+            }
+
+            /// Split `text` into chunks.
+            pub fn split(&self, text: &str) -> Vec<Chunk> {
+                pack(
+                    text,
+                    &$segments(text),
+                    self.chunk_size,
+                    self.overlap,
+                    &self.counter,
+                )
+            }
+
+            /// Split `text` into chunk texts, ready to be embedded or uploaded.
+            pub fn split_texts(&self, text: &str) -> Vec<String> {
+                self.split(text).into_iter().map(String::from).collect()
+            }
+        }
+    };
+}
+
+chunker_impl!(TokenChunker, word_segments);
+chunker_impl!(SentenceChunker, sentence_segments);
+
+/// Byte ranges of the whitespace-separated words of `text`.
+fn word_segments(text: &str) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    let mut start = None;
+
+    for (index, character) in text.char_indices() {
+        match (character.is_whitespace(), start) {
+            (true, Some(word_start)) => {
+                segments.push((word_start, index));
+                start = None;
+            },
+            (false, None) => start = Some(index),
+            _ => {},
+        }
+    }
+
+    if let Some(word_start) = start {
+        segments.push((word_start, text.len()));
+    }
+
+    segments
+}
+
+/// Byte ranges of the sentences of `text`, ending on `.`, `!`, `?` or a blank line.
+fn sentence_segments(text: &str) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    let mut start = None;
+    let mut characters = text.char_indices().peekable();
+
+    while let Some((index, character)) = characters.next() {
+        if start.is_none() {
+            if character.is_whitespace() {
+                continue;
+            }
+            start = Some(index);
+        }
+
+        let next = characters.peek().map(|&(_, next)| next);
+        let ends_sentence = match character {
+            '.' | '!' | '?' => next.is_none_or(char::is_whitespace),
+            '\n' => next == Some('\n'),
+            _ => false,
+        };
+
+        if let (true, Some(sentence_start)) = (ends_sentence, start) {
+            let end = index + character.len_utf8();
+            segments.push((sentence_start, end));
+            start = None;
+        }
+    }
+
+    if let Some(sentence_start) = start {
+        segments.push((sentence_start, text.trim_end().len().max(sentence_start)));
+    }
+
+    segments
+}
+
+/// Greedily pack segments into chunks of at most `chunk_size` tokens, repeating up to
+/// `overlap` tokens of trailing segments at the start of the next chunk.
+///
+/// Chunks are measured as a whole, the text between their segments included.
+fn pack(
+    text: &str,
+    segments: &[(usize, usize)],
+    chunk_size: usize,
+    overlap: usize,
+    counter: &impl TokenCounter,
+) -> Vec<Chunk> {
+    let span =
+        |from: usize, to: usize| counter.count_tokens(&text[segments[from].0..segments[to].1]);
+    let mut chunks = Vec::new();
+    let mut first = 0;
+
+    while first < segments.len() {
+        let mut last = first;
+        while last + 1 < segments.len() && span(first, last + 1) <= chunk_size {
+            last += 1;
+        }
+
+        let (start, end) = (segments[first].0, segments[last].1);
+        let chunk_text = &text[start..end];
+
+        chunks.push(Chunk {
+            index: chunks.len(),
+            text: chunk_text.to_string(),
+            start,
+            end,
+            tokens: counter.count_tokens(chunk_text),
+        });
+
+        if last + 1 >= segments.len() {
+            break;
+        }
+
+        // Step back over trailing segments that fit in the overlap (leaving room for the
+        // following segment), always moving forward.
+        let mut next = last + 1;
+        while next - 1 > first
+            && span(next - 1, last) <= overlap
+            && span(next - 1, last + 1) <= chunk_size
+        {
+            next -= 1;
+        }
+
+        first = next;
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    fn texts(chunks: &[Chunk]) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+    }
+
+    fn assert_offsets(text: &str, chunks: &[Chunk]) {
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.index, index);
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn token_chunks_count_the_whitespace_between_words() {
+        let text = ["abcd"; 100].join(" ");
+        let chunks = TokenChunker::new(50).split(&text);
+
+        assert!(chunks.iter().all(|chunk| chunk.tokens <= 50));
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| words(&chunk.text))
+                .collect::<Vec<_>>(),
+            [40, 40, 20]
+        );
+        assert_eq!((chunks[1].start, chunks[2].end), (200, text.len()));
+        assert_offsets(&text, &chunks);
+    }
+
+    #[test]
+    fn token_chunks_overlap_on_whole_words() {
+        let text = (0..20)
+            .map(|word| format!("w{word}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let chunks = TokenChunker::new(10)
+            .overlap(4)
+            .token_counter(words)
+            .split(&text);
+
+        assert_eq!(
+            texts(&chunks),
+            [
+                "w0 w1 w2 w3 w4 w5 w6 w7 w8 w9",
+                "w6 w7 w8 w9 w10 w11 w12 w13 w14 w15",
+                "w12 w13 w14 w15 w16 w17 w18 w19",
+            ]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.tokens <= 10));
+        assert_offsets(&text, &chunks);
+    }
+
+    #[test]
+    fn long_words_are_kept_whole() {
+        let text = "a bbbbbbbbbbbbbbbbbbbb c";
+        let chunks = TokenChunker::new(2).split(text);
+
+        assert_eq!(texts(&chunks), ["a", "bbbbbbbbbbbbbbbbbbbb", "c"]);
+        assert_eq!(chunks[1].tokens, 5);
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn sentence_chunks_end_on_sentence_boundaries() {
+        let text = "One two. Three four five!  Six?\n\nSeven eight nine ten eleven twelve. End";
+        let chunks = SentenceChunker::new(5).token_counter(words).split(text);
+
+        assert_eq!(
+            texts(&chunks),
+            [
+                "One two. Three four five!",
+                "Six?",
+                "Seven eight nine ten eleven twelve.",
+                "End",
+            ]
+        );
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (chunk.start, chunk.end, chunk.tokens))
+                .collect::<Vec<_>>(),
+            [(0, 25, 5), (27, 31, 1), (33, 68, 6), (69, 72, 1)]
+        );
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn sentence_chunks_overlap_on_whole_sentences() {
+        let text = "A b. C d. E f. G h.";
+        let chunks = SentenceChunker::new(4)
+            .overlap(2)
+            .token_counter(words)
+            .split(text);
+
+        assert_eq!(texts(&chunks), ["A b. C d.", "C d. E f.", "E f. G h."]);
+        assert_offsets(text, &chunks);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{
+    events::{ClientEvent, EventBus},
+    runtime::Instant,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+/// Circuit breaker failing requests fast while the API keeps failing, instead of piling up
+/// requests and retries on a struggling provider.
+///
+/// The circuit opens when at least `failure_rate` of the last `window` requests failed (connection
+/// errors, timeouts or `5xx` responses), or after `consecutive_failures` failed requests in a row if
+/// set, e.g. to stop hammering a degraded region before the window fills up. Requests then fail
+/// immediately with
+/// [`OpenAIError::CircuitOpen`][crate::error_handling::OpenAIError::CircuitOpen] for `open_secs`
+/// seconds, after which the circuit is half-open: a single probe request goes through, closing the
+/// circuit if it succeeds and opening it again otherwise.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CircuitBreakerPolicy {
+    /// Share of failed requests, between 0 and 1, opening the circuit
+    pub failure_rate: f64,
+
+    /// Number of recent requests the failure rate is computed over, and the minimum number of
+    /// requests needed to open the circuit
+    pub window: usize,
+
+    /// Time the circuit stays open before a probe request is let through, in seconds
+    pub open_secs: u64,
+
+    /// Number of consecutive failed requests opening the circuit, whatever the failure rate
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.5,
+            window: 20,
+            open_secs: 30,
+            consecutive_failures: None,
+        }
+    }
+}
+
+/// State of a circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through
+    Closed,
+
+    /// Requests fail immediately
+    Open,
+
+    /// A probe request decides whether the circuit closes again
+    HalfOpen,
+}
+
+enum State {
+    Closed {
+        outcomes: VecDeque<bool>,
+        consecutive_failures: u32,
+    },
+    Open(Instant),
+    HalfOpen(Instant),
+}
+
+impl State {
+    fn closed() -> Self {
+        Self::Closed {
+            outcomes: VecDeque::new(),
+            consecutive_failures: 0,
+        }
+    }
+}
+
+/// Shared circuit breaker of a client and its clones.
+pub(crate) struct CircuitBreaker {
+    policy: CircuitBreakerPolicy,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(policy: CircuitBreakerPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(State::closed()),
+        }
+    }
+
+    pub(crate) const fn policy(&self) -> &CircuitBreakerPolicy {
+        &self.policy
+    }
+
+    pub(crate) fn state(&self) -> CircuitState {
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open(_) => CircuitState::Open,
+            State::HalfOpen(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether a request may be sent, returning the time left before the next probe otherwise.
+    pub(crate) fn acquire(&self, events: &EventBus) -> Result<(), Duration> {
+        let open = Duration::from_secs(self.policy.open_secs);
+        let mut state = self.state.lock().unwrap();
+
+        let changed = match *state {
+            State::Closed { .. } => return Ok(()),
+            State::Open(since) | State::HalfOpen(since) if since.elapsed() >= open => {
+                // Let a probe through, or another one if the previous probe never finished.
+                let changed = matches!(*state, State::Open(_));
+                *state = State::HalfOpen(Instant::now());
+                changed
+            },
+            State::Open(since) | State::HalfOpen(since) => return Err(open - since.elapsed()),
+        };
+        drop(state);
+
+        if changed {
+            events.publish(&ClientEvent::CircuitChanged {
+                state: CircuitState::HalfOpen,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Record the outcome of a request.
+    pub(crate) fn record(&self, success: bool, events: &EventBus) {
+        let changed = self.transition(success);
+
+        if let Some(state) = changed {
+            events.publish(&ClientEvent::CircuitChanged { state });
+        }
+    }
+
+    fn transition(&self, success: bool) -> Option<CircuitState> {
+        let mut state = self.state.lock().unwrap();
+
+        match &mut *state {
+            State::Closed {
+                outcomes,
+                consecutive_failures,
+            } => {
+                outcomes.push_back(success);
+                if outcomes.len() > self.policy.window {
+                    outcomes.pop_front();
+                }
+                *consecutive_failures = if success {
+                    0
+                } else {
+                    *consecutive_failures + 1
+                };
+
+                let failures = outcomes.iter().filter(|success| !**success).count();
+                let rate_exceeded = outcomes.len() >= self.policy.window.max(1)
+                    && (failures as f64) >= self.policy.failure_rate * outcomes.len() as f64;
+                let streak_exceeded = self
+                    .policy
+                    .consecutive_failures
+                    .is_some_and(|threshold| *consecutive_failures >= threshold.max(1));
+                if !rate_exceeded && !streak_exceeded {
+                    return None;
+                }
+
+                *state = State::Open(Instant::now());
+                Some(CircuitState::Open)
+            },
+            State::HalfOpen(_) if success => {
+                *state = State::closed();
+                Some(CircuitState::Closed)
+            },
+            State::HalfOpen(_) => {
+                *state = State::Open(Instant::now());
+                Some(CircuitState::Open)
+            },
+            // Requests sent before the circuit opened.
+            State::Open(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn breaker(
+        policy: CircuitBreakerPolicy,
+    ) -> (CircuitBreaker, EventBus, Arc<Mutex<Vec<CircuitState>>>) {
+        let events = EventBus::default();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&changes);
+        events.subscribe(Arc::new(move |event| {
+            if let ClientEvent::CircuitChanged { state } = event {
+                recorded.lock().unwrap().push(*state);
+            }
+        }));
+
+        (CircuitBreaker::new(policy), events, changes)
+    }
+
+    #[test]
+    fn circuit_opens_at_the_failure_rate_of_a_full_window() {
+        let (breaker, events, _) = breaker(CircuitBreakerPolicy {
+            failure_rate: 0.5,
+            window: 4,
+            open_secs: 60,
+            consecutive_failures: None,
+        });
+
+        for success in [false, true, false] {
+            breaker.record(success, &events);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.acquire(&events).is_ok());
+
+        breaker.record(true, &events);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        let wait = breaker.acquire(&events).unwrap_err();
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn circuit_stays_closed_below_the_failure_rate() {
+        let (breaker, events, changes) = breaker(CircuitBreakerPolicy {
+            failure_rate: 0.5,
+            window: 4,
+            ..CircuitBreakerPolicy::default()
+        });
+
+        for success in [false, true, true, true, false, true, true] {
+            breaker.record(success, &events);
+        }
+
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(changes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn half_open_probe_closes_the_circuit_on_success() {
+        let (breaker, events, changes) = breaker(CircuitBreakerPolicy {
+            failure_rate: 1.0,
+            window: 2,
+            open_secs: 0,
+            consecutive_failures: None,
+        });
+
+        breaker.record(false, &events);
+        breaker.record(false, &events);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        assert!(breaker.acquire(&events).is_ok());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        breaker.record(true, &events);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // The window starts over once closed.
+        breaker.record(false, &events);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(
+            *changes.lock().unwrap(),
+            [
+                CircuitState::Open,
+                CircuitState::HalfOpen,
+                CircuitState::Closed
+            ]
+        );
+    }
+
+    #[test]
+    fn half_open_probe_reopens_the_circuit_on_failure() {
+        let (breaker, events, _) = breaker(CircuitBreakerPolicy {
+            failure_rate: 1.0,
+            window: 1,
+            open_secs: 0,
+            consecutive_failures: None,
+        });
+
+        breaker.record(false, &events);
+        assert!(breaker.acquire(&events).is_ok());
+        breaker.record(false, &events);
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn half_open_circuit_lets_a_single_probe_through() {
+        let (breaker, events, _) = breaker(CircuitBreakerPolicy {
+            failure_rate: 1.0,
+            window: 1,
+            open_secs: 60,
+            consecutive_failures: None,
+        });
+        breaker.record(false, &events);
+        *breaker.state.lock().unwrap() = State::Open(Instant::now() - Duration::from_secs(61));
+
+        assert!(breaker.acquire(&events).is_ok());
+        assert!(breaker.acquire(&events).is_err());
+    }
+
+    #[test]
+    fn open_circuit_ignores_requests_sent_before_it_opened() {
+        let (breaker, events, _) = breaker(CircuitBreakerPolicy {
+            failure_rate: 1.0,
+            window: 1,
+            ..CircuitBreakerPolicy::default()
+        });
+
+        breaker.record(false, &events);
+        breaker.record(true, &events);
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let (breaker, events, _) = breaker(CircuitBreakerPolicy {
+            consecutive_failures: Some(3),
+            ..CircuitBreakerPolicy::default()
+        });
+
+        for success in [false, false, true, false, false] {
+            breaker.record(success, &events);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.record(false, &events);
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn client_fails_fast_while_open_and_recovers_with_a_probe() {
+        use crate::{
+            error_handling::{OpenAIError, OpenAIResult},
+            openai::OpenAI,
+            test_util::{
+                fixtures,
+                mock_transport::{MockResponse, MockTransport},
+            },
+        };
+        use reqwest::Method;
+
+        async fn embed(openai: &OpenAI) -> OpenAIResult<serde_json::Value> {
+            openai
+                .embeddings()
+                .create("a", Some("text-embedding-3-small".into()), None, None, None)
+                .await
+        }
+
+        let mock = Arc::new(MockTransport::new());
+        mock.once(
+            Method::POST,
+            "/embeddings",
+            MockResponse::error(500, "down"),
+        )
+        .once(
+            Method::POST,
+            "/embeddings",
+            MockResponse::error(503, "down"),
+        )
+        .on(
+            Method::POST,
+            "/embeddings",
+            MockResponse::json(&fixtures::embeddings("text-embedding-3-small", &["a"], 3)),
+        );
+        let mut openai = mock.client();
+        openai.set_circuit_breaker(Some(CircuitBreakerPolicy {
+            open_secs: 1,
+            consecutive_failures: Some(2),
+            ..CircuitBreakerPolicy::default()
+        }));
+
+        assert!(embed(&openai).await.is_err());
+        assert!(embed(&openai).await.is_err());
+        assert_eq!(openai.circuit_state(), Some(CircuitState::Open));
+        assert!(matches!(
+            embed(&openai).await,
+            Err(OpenAIError::CircuitOpen(_))
+        ));
+        assert_eq!(mock.requests().len(), 2);
+
+        crate::runtime::sleep(Duration::from_millis(1_100)).await;
+        assert!(embed(&openai).await.is_ok());
+        assert_eq!(openai.circuit_state(), Some(CircuitState::Closed));
+        assert_eq!(mock.requests().len(), 3);
+    }
+}
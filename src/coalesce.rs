@@ -0,0 +1,251 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    openai::ApiResponse,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+
+type Shared = Arc<OnceCell<Result<Arc<ApiResponse>, Arc<OpenAIError>>>>;
+
+/// Requests in flight, so identical concurrent requests share a single HTTP call.
+#[derive(Default)]
+pub(crate) struct Coalescer {
+    in_flight: Mutex<HashMap<String, Shared>>,
+}
+
+impl Coalescer {
+    /// Run `send`, unless a request with the same `key` is already in flight, in which case its
+    /// response is awaited and shared instead.
+    ///
+    /// The caller actually sending the request gets its error as is, the others a copy of it (see
+    /// [`copy_error`]). If that caller is cancelled, a waiting one sends the request instead.
+    pub(crate) async fn run<F, Fut>(&self, key: &str, send: F) -> OpenAIResult<Arc<ApiResponse>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = OpenAIResult<ApiResponse>>,
+    {
+        let shared = Arc::clone(
+            self.in_flight
+                .lock()
+                .unwrap()
+                .entry(key.to_string())
+                .or_default(),
+        );
+
+        let mut error = None;
+        let result = shared
+            .get_or_init(|| async {
+                send().await.map(Arc::new).map_err(|sent| {
+                    let shared = Arc::new(copy_error(&sent));
+                    error = Some(sent);
+                    shared
+                })
+            })
+            .await
+            .clone();
+
+        // The first caller to finish ends the sharing, later requests are sent again.
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, &shared))
+        {
+            in_flight.remove(key);
+        }
+        drop(in_flight);
+
+        match error {
+            Some(error) => Err(error),
+            None => result.map_err(|shared| copy_error(&shared)),
+        }
+    }
+}
+
+/// Copy of `error` for the callers sharing its request, keeping the fields of the variants the
+/// callers match on (API errors, deadlines...): reqwest, JSON and I/O errors can't be copied and
+/// are given as [`OpenAIError::UnexpectedResponse`].
+fn copy_error(error: &OpenAIError) -> OpenAIError {
+    match error {
+        OpenAIError::ConfigError(message) => OpenAIError::ConfigError(message.clone()),
+        OpenAIError::UnexpectedResponse(message) => {
+            OpenAIError::UnexpectedResponse(message.clone())
+        },
+        OpenAIError::CircuitOpen(message) => OpenAIError::CircuitOpen(message.clone()),
+        OpenAIError::DeadlineExceeded {
+            attempts,
+            elapsed,
+            last_status,
+        } => OpenAIError::DeadlineExceeded {
+            attempts: *attempts,
+            elapsed: *elapsed,
+            last_status: *last_status,
+        },
+        OpenAIError::Refusal(message) => OpenAIError::Refusal(message.clone()),
+        OpenAIError::ParseError { message, raw } => OpenAIError::ParseError {
+            message: message.clone(),
+            raw: raw.clone(),
+        },
+        OpenAIError::ApiError {
+            status,
+            code,
+            message,
+            param,
+            request_id,
+        } => OpenAIError::ApiError {
+            status: *status,
+            code: code.clone(),
+            message: message.clone(),
+            param: param.clone(),
+            request_id: request_id.clone(),
+        },
+        OpenAIError::DryRun(prepared) => OpenAIError::DryRun(prepared.clone()),
+        OpenAIError::ReqwestError(_) | OpenAIError::SerdeJsonError(_) | OpenAIError::IoError(_) => {
+            OpenAIError::UnexpectedResponse(error.to_string())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderMap, StatusCode};
+    use std::time::Duration;
+
+    fn response(body: &str) -> ApiResponse {
+        ApiResponse {
+            status: StatusCode::OK,
+            request_id: None,
+            headers: HeaderMap::new(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_runs_share_the_response() {
+        let coalescer = Coalescer::default();
+        let sent = Mutex::new(0);
+        let send = || async {
+            *sent.lock().unwrap() += 1;
+            crate::runtime::sleep(Duration::from_millis(20)).await;
+            Ok(response("shared"))
+        };
+
+        let (first, second) = tokio::join!(coalescer.run("key", send), coalescer.run("key", send));
+
+        assert_eq!(first.unwrap().body, b"shared");
+        assert_eq!(second.unwrap().body, b"shared");
+        assert_eq!(*sent.lock().unwrap(), 1);
+
+        // Finished requests aren't shared anymore.
+        coalescer.run("key", send).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn waiters_get_the_fields_of_api_errors() {
+        let coalescer = Coalescer::default();
+        let send = || async {
+            crate::runtime::sleep(Duration::from_millis(20)).await;
+            Err(OpenAIError::ApiError {
+                status: 503,
+                code: Some("server_error".to_string()),
+                message: "overloaded".to_string(),
+                param: None,
+                request_id: Some("req_1".to_string()),
+            })
+        };
+
+        let (first, second) = tokio::join!(coalescer.run("key", send), coalescer.run("key", send));
+
+        for result in [first, second] {
+            assert!(matches!(
+                result,
+                Err(OpenAIError::ApiError { status: 503, code: Some(code), request_id: Some(id), .. })
+                    if code == "server_error" && id == "req_1"
+            ));
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    mod client {
+        use crate::{
+            error_handling::OpenAIResult,
+            openai::OpenAI,
+            options::RequestOptions,
+            test_util::mock_transport::{MockResponse, MockTransport},
+            transport::{BoxFuture, HttpTransport, Request, Response},
+        };
+        use futures::future::join_all;
+        use reqwest::Method;
+        use serde_json::{json, Value};
+        use std::{sync::Arc, time::Duration};
+
+        /// Transport answering after a delay, so concurrent requests overlap.
+        struct Slow(Arc<MockTransport>);
+
+        impl HttpTransport for Slow {
+            fn send(&self, request: Request) -> BoxFuture<'_, OpenAIResult<Response>> {
+                Box::pin(async move {
+                    crate::runtime::sleep(Duration::from_millis(20)).await;
+                    self.0.send(request).await
+                })
+            }
+        }
+
+        fn client() -> (Arc<MockTransport>, OpenAI) {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::GET,
+                "/models",
+                MockResponse::json(&json!({ "data": [] })),
+            );
+            let mut openai = mock.client();
+            openai.set_transport(Arc::new(Slow(Arc::clone(&mock))));
+            openai.set_request_coalescing(true);
+
+            (mock, openai)
+        }
+
+        #[tokio::test]
+        async fn identical_concurrent_gets_make_one_call() {
+            let (mock, openai) = client();
+
+            let responses = join_all((0..5).map(|_| openai.get::<Value>("/models"))).await;
+
+            assert!(responses.iter().all(Result::is_ok));
+            assert_eq!(mock.requests().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn clones_with_other_credentials_or_headers_are_not_coalesced() {
+            let (mock, openai) = client();
+            let mut other_key = openai.clone();
+            other_key.set_api_key("sk-other");
+            let mut other_organization = openai.clone();
+            other_organization.set_organization(Some("org-other".to_string()));
+            let other_headers = openai.with_options(RequestOptions::new().header("X-Tenant", "b"));
+
+            let clients = [&openai, &other_key, &other_organization, &other_headers];
+            let responses = join_all(clients.map(|client| client.get::<Value>("/models"))).await;
+
+            assert!(responses.iter().all(Result::is_ok));
+            let requests = mock.requests();
+            assert_eq!(requests.len(), 4);
+            let authorizations: Vec<_> = requests
+                .iter()
+                .map(|request| request.headers["authorization"].clone())
+                .collect();
+            assert_eq!(
+                authorizations
+                    .iter()
+                    .filter(|value| *value == "Bearer sk-other")
+                    .count(),
+                1
+            );
+        }
+    }
+}
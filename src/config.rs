@@ -0,0 +1,617 @@
+use crate::{
+    azure::AzureConfig,
+    circuit_breaker::CircuitBreakerPolicy,
+    error_handling::{OpenAIError, OpenAIResult},
+    failover::FailoverPolicy,
+    model::Model,
+    provider::Provider,
+    retry::{HedgePolicy, RetryBudget, RetryPolicy},
+    scheduler::SchedulingPolicy,
+};
+use reqwest::ClientBuilder;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+use std::{env, str::FromStr};
+
+/// Error of the HTTP client settings (timeouts, proxies, connection pooling, HTTP/2) on `wasm32`,
+/// where the `fetch` API manages connections itself.
+#[cfg(target_arch = "wasm32")]
+pub(crate) const UNSUPPORTED_ON_WASM: &str =
+    "timeouts, proxies and connection settings aren't supported on wasm32";
+
+/// Default models used for each capability when a request doesn't specify one.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DefaultModels {
+    /// Default model for chat completions
+    pub chat: Option<String>,
+
+    /// Default model for embeddings
+    pub embeddings: Option<String>,
+
+    /// Default model for image generation, edits and variations
+    pub images: Option<String>,
+
+    /// Default model for audio transcription and translation
+    pub audio: Option<String>,
+
+    /// Default model for moderation
+    pub moderation: Option<String>,
+}
+
+impl DefaultModels {
+    /// Overlay the models set in `other` on top of these ones.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            chat: other.chat.or(self.chat),
+            embeddings: other.embeddings.or(self.embeddings),
+            images: other.images.or(self.images),
+            audio: other.audio.or(self.audio),
+            moderation: other.moderation.or(self.moderation),
+        }
+    }
+}
+
+/// HTTP/2 tuning for high-concurrency and streaming workloads.
+///
+/// The maximum number of concurrent streams per connection is advertised by the server and
+/// respected automatically; it can't be raised from the client side.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Http2Config {
+    /// Use HTTP/2 without negotiation, for internal gateways speaking cleartext HTTP/2 (h2c)
+    pub prior_knowledge: bool,
+
+    /// Adjust the flow-control windows to the measured bandwidth-delay product
+    pub adaptive_window: bool,
+
+    /// Initial flow-control window of each stream, in bytes
+    pub initial_stream_window_size: Option<u32>,
+
+    /// Initial flow-control window of each connection, in bytes
+    pub initial_connection_window_size: Option<u32>,
+
+    /// Maximum frame size, in bytes
+    pub max_frame_size: Option<u32>,
+
+    /// Interval between keep-alive pings, in seconds
+    pub keep_alive_interval_secs: Option<u64>,
+
+    /// Timeout for keep-alive ping acknowledgements, in seconds
+    pub keep_alive_timeout_secs: Option<u64>,
+
+    /// Send keep-alive pings on idle connections too
+    pub keep_alive_while_idle: bool,
+}
+
+impl Http2Config {
+    /// Apply the settings to an HTTP client under construction.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn apply(&self, mut client: ClientBuilder) -> ClientBuilder {
+        if self.prior_knowledge {
+            client = client.http2_prior_knowledge();
+        }
+        if let Some(interval) = self.keep_alive_interval_secs {
+            client = client.http2_keep_alive_interval(Duration::from_secs(interval));
+        }
+        if let Some(timeout) = self.keep_alive_timeout_secs {
+            client = client.http2_keep_alive_timeout(Duration::from_secs(timeout));
+        }
+
+        client
+            .http2_adaptive_window(self.adaptive_window)
+            .http2_initial_stream_window_size(self.initial_stream_window_size)
+            .http2_initial_connection_window_size(self.initial_connection_window_size)
+            .http2_max_frame_size(self.max_frame_size)
+            .http2_keep_alive_while_idle(self.keep_alive_while_idle)
+    }
+}
+
+/// Turn the compression of the responses on or off on an HTTP client under construction, with the
+/// encodings enabled by the `gzip` and `brotli` features.
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "brotli")),
+    allow(unused_variables)
+)]
+pub(crate) fn apply_response_compression(client: ClientBuilder, enabled: bool) -> ClientBuilder {
+    #[cfg(feature = "gzip")]
+    let client = client.gzip(enabled);
+    #[cfg(feature = "brotli")]
+    let client = client.brotli(enabled);
+
+    client
+}
+
+/// Declarative configuration for an [`OpenAI`][crate::openai::OpenAI] client.
+///
+/// Every field is optional so configurations can be layered: defaults, then a
+/// configuration file, then environment variables (see [`OpenAIConfig::load`]).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OpenAIConfig {
+    /// API key used to authenticate requests
+    pub api_key: Option<String>,
+
+    /// Base URL of the API (defaults to the provider's, or `https://api.openai.com/v1`)
+    pub base_url: Option<String>,
+
+    /// OpenAI-compatible provider, setting the base URL, authentication scheme and extra headers
+    pub provider: Option<Provider>,
+
+    /// Azure OpenAI deployments and API version, the base URL being the endpoint of the resource
+    pub azure: Option<AzureConfig>,
+
+    /// Organization ID sent in the `OpenAI-Organization` header
+    pub organization: Option<String>,
+
+    /// Project ID sent in the `OpenAI-Project` header
+    pub project: Option<String>,
+
+    /// Total timeout for a request, in seconds
+    pub timeout_secs: Option<u64>,
+
+    /// Timeout for establishing a connection, in seconds
+    pub connect_timeout_secs: Option<u64>,
+
+    /// Timeout for each read of a response (reset whenever data arrives), in seconds
+    pub read_timeout_secs: Option<u64>,
+
+    /// Total timeout for a JSON request, overriding `timeout_secs`, in seconds
+    pub json_timeout_secs: Option<u64>,
+
+    /// Total timeout for a multipart upload, overriding `timeout_secs`, in seconds
+    pub upload_timeout_secs: Option<u64>,
+
+    /// Retry policy applied to failed requests
+    pub retry: Option<RetryPolicy>,
+
+    /// Hedging policy applied to slow idempotent requests
+    pub hedging: Option<HedgePolicy>,
+
+    /// Failover to fallback base URLs
+    pub failover: Option<FailoverPolicy>,
+
+    /// Circuit breaker failing requests fast during incidents
+    pub circuit_breaker: Option<CircuitBreakerPolicy>,
+
+    /// Budget capping retries across requests
+    pub retry_budget: Option<RetryBudget>,
+
+    /// Priority scheduling of the requests within a concurrency and rate budget
+    pub scheduling: Option<SchedulingPolicy>,
+
+    /// Default models per capability
+    pub default_models: DefaultModels,
+
+    /// Application identifier appended to the `User-Agent` header (e.g. `myapp/1.2`)
+    pub app_info: Option<String>,
+
+    /// Maximum number of idle connections kept in the pool per host
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// Time after which idle pooled connections are closed, in seconds
+    pub pool_idle_timeout_secs: Option<u64>,
+
+    /// Interval between TCP keepalive probes on the connections, in seconds
+    pub tcp_keepalive_secs: Option<u64>,
+
+    /// HTTP/2 settings of the HTTP client
+    pub http2: Option<Http2Config>,
+
+    /// Ask for compressed responses and decompress them (needs the `gzip` or `brotli` feature,
+    /// which turns it on by default)
+    pub response_compression: Option<bool>,
+
+    /// Size of the buffer used to stream file uploads and downloads, in bytes
+    pub stream_chunk_size: Option<usize>,
+
+    /// Minimum size of the JSON request bodies to gzip, in bytes (no compression by default)
+    pub compression_threshold: Option<usize>,
+
+    /// Delay requests according to the `x-ratelimit-*` headers of the responses to avoid `429`s
+    pub adaptive_rate_limit: Option<bool>,
+}
+
+impl OpenAIConfig {
+    /// Parse a configuration from a JSON string.
+    pub fn from_json_str(json: &str) -> OpenAIResult<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Parse a configuration from a TOML string.
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(toml: &str) -> OpenAIResult<Self> {
+        toml::from_str(toml).map_err(|error| OpenAIError::ConfigError(error.to_string()))
+    }
+
+    /// Read a configuration file, choosing the format from its extension (`.json` or `.toml`).
+    #[cfg(feature = "fs")]
+    pub fn from_file(path: impl AsRef<Path>) -> OpenAIResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json_str(&contents),
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml_str(&contents),
+            _ => Err(OpenAIError::ConfigError(format!(
+                "unsupported configuration file format: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Build a configuration from the `OPENAI_*` environment variables, see
+    /// [`OpenAIConfig::with_env`].
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the configuration on success, or an [`OpenAIError::ConfigError`] if a numeric or boolean variable can't be parsed.
+    pub fn from_env() -> OpenAIResult<Self> {
+        Self::default().with_env()
+    }
+
+    /// Overlay the `OPENAI_*` environment variables on this configuration, each one overriding
+    /// only the setting it names, e.g. `OPENAI_MAX_RETRIES` keeps the delays of the `retry` table.
+    ///
+    /// Only the variables that are set are applied:
+    ///
+    /// * `OPENAI_API_KEY`, `OPENAI_BASE_URL`, `OPENAI_ORG_ID`, `OPENAI_PROJECT_ID`
+    /// * `OPENAI_TIMEOUT_SECS`, `OPENAI_CONNECT_TIMEOUT_SECS`, `OPENAI_READ_TIMEOUT_SECS`,
+    ///   `OPENAI_JSON_TIMEOUT_SECS`, `OPENAI_UPLOAD_TIMEOUT_SECS`, `OPENAI_MAX_RETRIES`,
+    ///   `OPENAI_HEDGE_DELAY_MS`, `OPENAI_STREAM_CHUNK_SIZE`, `OPENAI_COMPRESSION_THRESHOLD`,
+    ///   `OPENAI_ADAPTIVE_RATE_LIMIT` and `OPENAI_RESPONSE_COMPRESSION` (`true` or `false`),
+    ///   `OPENAI_FALLBACK_BASE_URLS` (comma-separated), `OPENAI_APP_INFO`
+    /// * `OPENAI_DEFAULT_CHAT_MODEL`, `OPENAI_DEFAULT_EMBEDDINGS_MODEL`, `OPENAI_DEFAULT_IMAGES_MODEL`,
+    ///   `OPENAI_DEFAULT_AUDIO_MODEL`, `OPENAI_DEFAULT_MODERATION_MODEL`
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the configuration on success, or an [`OpenAIError::ConfigError`] if a numeric or boolean variable can't be parsed.
+    pub fn with_env(self) -> OpenAIResult<Self> {
+        let retry = match parse_env_var("OPENAI_MAX_RETRIES")? {
+            Some(max_retries) => Some(RetryPolicy {
+                max_retries,
+                ..self.retry.unwrap_or_default()
+            }),
+            None => self.retry,
+        };
+        let hedging = match parse_env_var("OPENAI_HEDGE_DELAY_MS")? {
+            Some(delay_ms) => Some(HedgePolicy {
+                delay_ms,
+                ..self.hedging.unwrap_or_default()
+            }),
+            None => self.hedging,
+        };
+        let failover = match env_var("OPENAI_FALLBACK_BASE_URLS") {
+            Some(urls) => Some(FailoverPolicy {
+                fallback_urls: urls.split(',').map(|url| url.trim().to_string()).collect(),
+                ..self.failover.unwrap_or_default()
+            }),
+            None => self.failover,
+        };
+        let default_models = DefaultModels {
+            chat: env_var("OPENAI_DEFAULT_CHAT_MODEL"),
+            embeddings: env_var("OPENAI_DEFAULT_EMBEDDINGS_MODEL"),
+            images: env_var("OPENAI_DEFAULT_IMAGES_MODEL"),
+            audio: env_var("OPENAI_DEFAULT_AUDIO_MODEL"),
+            moderation: env_var("OPENAI_DEFAULT_MODERATION_MODEL"),
+        };
+
+        Ok(Self {
+            api_key: env_var("OPENAI_API_KEY").or(self.api_key),
+            base_url: env_var("OPENAI_BASE_URL").or(self.base_url),
+            organization: env_var("OPENAI_ORG_ID").or(self.organization),
+            project: env_var("OPENAI_PROJECT_ID").or(self.project),
+            timeout_secs: parse_env_var("OPENAI_TIMEOUT_SECS")?.or(self.timeout_secs),
+            connect_timeout_secs: parse_env_var("OPENAI_CONNECT_TIMEOUT_SECS")?
+                .or(self.connect_timeout_secs),
+            read_timeout_secs: parse_env_var("OPENAI_READ_TIMEOUT_SECS")?
+                .or(self.read_timeout_secs),
+            json_timeout_secs: parse_env_var("OPENAI_JSON_TIMEOUT_SECS")?
+                .or(self.json_timeout_secs),
+            upload_timeout_secs: parse_env_var("OPENAI_UPLOAD_TIMEOUT_SECS")?
+                .or(self.upload_timeout_secs),
+            retry,
+            hedging,
+            failover,
+            default_models: self.default_models.merge(default_models),
+            app_info: env_var("OPENAI_APP_INFO").or(self.app_info),
+            response_compression: parse_env_var("OPENAI_RESPONSE_COMPRESSION")?
+                .or(self.response_compression),
+            stream_chunk_size: parse_env_var("OPENAI_STREAM_CHUNK_SIZE")?
+                .or(self.stream_chunk_size),
+            compression_threshold: parse_env_var("OPENAI_COMPRESSION_THRESHOLD")?
+                .or(self.compression_threshold),
+            adaptive_rate_limit: parse_env_var("OPENAI_ADAPTIVE_RATE_LIMIT")?
+                .or(self.adaptive_rate_limit),
+            ..self
+        })
+    }
+
+    /// Load a layered configuration: the optional file first, then environment variables on top,
+    /// see [`OpenAIConfig::with_env`].
+    #[cfg(feature = "fs")]
+    pub fn load(path: Option<&Path>) -> OpenAIResult<Self> {
+        let file = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        file.with_env()
+    }
+
+    /// Overlay the values set in `other` on top of this configuration.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            api_key: other.api_key.or(self.api_key),
+            base_url: other.base_url.or(self.base_url),
+            provider: other.provider.or(self.provider),
+            azure: other.azure.or(self.azure),
+            organization: other.organization.or(self.organization),
+            project: other.project.or(self.project),
+            timeout_secs: other.timeout_secs.or(self.timeout_secs),
+            connect_timeout_secs: other.connect_timeout_secs.or(self.connect_timeout_secs),
+            read_timeout_secs: other.read_timeout_secs.or(self.read_timeout_secs),
+            json_timeout_secs: other.json_timeout_secs.or(self.json_timeout_secs),
+            upload_timeout_secs: other.upload_timeout_secs.or(self.upload_timeout_secs),
+            retry: other.retry.or(self.retry),
+            hedging: other.hedging.or(self.hedging),
+            failover: other.failover.or(self.failover),
+            circuit_breaker: other.circuit_breaker.or(self.circuit_breaker),
+            retry_budget: other.retry_budget.or(self.retry_budget),
+            scheduling: other.scheduling.or(self.scheduling),
+            default_models: self.default_models.merge(other.default_models),
+            app_info: other.app_info.or(self.app_info),
+            pool_max_idle_per_host: other.pool_max_idle_per_host.or(self.pool_max_idle_per_host),
+            pool_idle_timeout_secs: other.pool_idle_timeout_secs.or(self.pool_idle_timeout_secs),
+            tcp_keepalive_secs: other.tcp_keepalive_secs.or(self.tcp_keepalive_secs),
+            http2: other.http2.or(self.http2),
+            response_compression: other.response_compression.or(self.response_compression),
+            stream_chunk_size: other.stream_chunk_size.or(self.stream_chunk_size),
+            compression_threshold: other.compression_threshold.or(self.compression_threshold),
+            adaptive_rate_limit: other.adaptive_rate_limit.or(self.adaptive_rate_limit),
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn parse_env_var<T: FromStr>(name: &str) -> OpenAIResult<Option<T>> {
+    env_var(name)
+        .map(|value| {
+            value.parse().map_err(|_| {
+                OpenAIError::ConfigError(format!("invalid value for {name}: {value:?}"))
+            })
+        })
+        .transpose()
+}
+
+/// Resolve the model of a request, falling back to the client's default for the capability.
+pub(crate) fn required_model<'m>(
+    model: Option<&'m Model>,
+    default: &'m Option<String>,
+    capability: &str,
+) -> OpenAIResult<&'m str> {
+    optional_model(model, default).ok_or_else(|| {
+        OpenAIError::ConfigError(format!(
+            "no model given and no default {capability} model configured"
+        ))
+    })
+}
+
+/// Resolve the model of a request, falling back to the client's default if there is one.
+pub(crate) fn optional_model<'m>(
+    model: Option<&'m Model>,
+    default: &'m Option<String>,
+) -> Option<&'m str> {
+    model.map(Model::as_str).or(default.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Serializes the tests reading or writing the process environment.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const JSON: &str = r#"{
+        "api_key": "sk-file",
+        "base_url": "https://gateway.example.com/v1",
+        "timeout_secs": 30,
+        "retry": { "max_retries": 2, "base_delay_ms": 50, "deadline_ms": 10000 },
+        "hedging": { "delay_ms": 200, "endpoints": ["/embeddings"] },
+        "failover": { "fallback_urls": ["https://a.example.com/v1"], "failure_threshold": 5 },
+        "default_models": { "chat": "gpt-4o", "embeddings": "text-embedding-3-small" }
+    }"#;
+
+    /// Run `test` with the environment variables `vars` set, removing them afterwards.
+    fn with_vars<T>(vars: &[(&str, &str)], test: impl FnOnce() -> T) -> T {
+        let _lock = ENV.lock().unwrap_or_else(|error| error.into_inner());
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+        let result = test();
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+
+        result
+    }
+
+    #[test]
+    fn json_configurations_fill_in_the_defaults_of_partial_tables() {
+        let config = OpenAIConfig::from_json_str(JSON).unwrap();
+
+        assert_eq!(config.api_key.as_deref(), Some("sk-file"));
+        assert_eq!(config.timeout_secs, Some(30));
+        let retry = config.retry.unwrap();
+        assert_eq!(
+            (retry.max_retries, retry.base_delay_ms, retry.max_delay_ms),
+            (2, 50, 8_000)
+        );
+        assert_eq!(retry.deadline_ms, Some(10_000));
+        assert_eq!(config.failover.unwrap().health_check_path, "/models");
+        assert_eq!(config.default_models.chat.as_deref(), Some("gpt-4o"));
+        assert!(config.default_models.images.is_none());
+        assert!(config.scheduling.is_none());
+
+        assert!(matches!(
+            OpenAIConfig::from_json_str(r#"{ "timeout_secs": "soon" }"#),
+            Err(OpenAIError::SerdeJsonError(_))
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_configurations_match_json_ones() {
+        let config = OpenAIConfig::from_toml_str(
+            r#"
+            api_key = "sk-file"
+            timeout_secs = 30
+
+            [retry]
+            max_retries = 2
+            base_delay_ms = 50
+
+            [default_models]
+            chat = "gpt-4o"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.api_key.as_deref(), Some("sk-file"));
+        assert_eq!(config.timeout_secs, Some(30));
+        assert_eq!(config.retry.unwrap().base_delay_ms, 50);
+        assert_eq!(config.default_models.chat.as_deref(), Some("gpt-4o"));
+
+        assert!(matches!(
+            OpenAIConfig::from_toml_str("timeout_secs = "),
+            Err(OpenAIError::ConfigError(_))
+        ));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn files_are_read_according_to_their_extension() {
+        let directory = env::temp_dir();
+        let json = directory.join(format!("rusty-openai-config-{}.json", std::process::id()));
+        let yaml = json.with_extension("yaml");
+        std::fs::write(&json, JSON).unwrap();
+        std::fs::write(&yaml, "api_key: sk-file").unwrap();
+
+        let config = OpenAIConfig::from_file(&json);
+        let unsupported = OpenAIConfig::from_file(&yaml);
+        std::fs::remove_file(&json).unwrap();
+        std::fs::remove_file(&yaml).unwrap();
+
+        assert_eq!(config.unwrap().api_key.as_deref(), Some("sk-file"));
+        assert!(matches!(unsupported, Err(OpenAIError::ConfigError(_))));
+    }
+
+    #[test]
+    fn merged_values_override_only_the_values_they_set() {
+        let base = OpenAIConfig::from_json_str(JSON).unwrap();
+        let overlay = OpenAIConfig {
+            api_key: Some("sk-overlay".to_string()),
+            retry: Some(RetryPolicy::new(9)),
+            default_models: DefaultModels {
+                chat: Some("gpt-4o-mini".to_string()),
+                ..DefaultModels::default()
+            },
+            ..OpenAIConfig::default()
+        };
+
+        let config = base.merge(overlay);
+
+        assert_eq!(config.api_key.as_deref(), Some("sk-overlay"));
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("https://gateway.example.com/v1")
+        );
+        assert_eq!(config.timeout_secs, Some(30));
+        // Tables are replaced as a whole.
+        assert_eq!(config.retry.unwrap().base_delay_ms, 500);
+        assert_eq!(config.hedging.unwrap().delay_ms, 200);
+        assert_eq!(config.default_models.chat.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(
+            config.default_models.embeddings.as_deref(),
+            Some("text-embedding-3-small")
+        );
+    }
+
+    #[test]
+    fn environment_variables_override_only_the_settings_they_name() {
+        let config = with_vars(
+            &[
+                ("OPENAI_API_KEY", "sk-env"),
+                ("OPENAI_MAX_RETRIES", "7"),
+                ("OPENAI_HEDGE_DELAY_MS", "300"),
+                (
+                    "OPENAI_FALLBACK_BASE_URLS",
+                    "https://b.example.com/v1, https://c.example.com/v1",
+                ),
+                ("OPENAI_DEFAULT_EMBEDDINGS_MODEL", "text-embedding-3-large"),
+                ("OPENAI_ADAPTIVE_RATE_LIMIT", "true"),
+            ],
+            || OpenAIConfig::from_json_str(JSON).unwrap().with_env(),
+        )
+        .unwrap();
+
+        assert_eq!(config.api_key.as_deref(), Some("sk-env"));
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("https://gateway.example.com/v1")
+        );
+        assert_eq!(config.timeout_secs, Some(30));
+        assert_eq!(config.adaptive_rate_limit, Some(true));
+
+        let retry = config.retry.unwrap();
+        assert_eq!(
+            (retry.max_retries, retry.base_delay_ms, retry.deadline_ms),
+            (7, 50, Some(10_000))
+        );
+        let hedging = config.hedging.unwrap();
+        assert_eq!(
+            (hedging.delay_ms, hedging.endpoints),
+            (300, vec!["/embeddings".to_string()])
+        );
+        let failover = config.failover.unwrap();
+        assert_eq!(
+            failover.fallback_urls,
+            ["https://b.example.com/v1", "https://c.example.com/v1"]
+        );
+        assert_eq!(failover.failure_threshold, 5);
+
+        assert_eq!(config.default_models.chat.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            config.default_models.embeddings.as_deref(),
+            Some("text-embedding-3-large")
+        );
+    }
+
+    #[test]
+    fn from_env_fills_in_only_the_variables_that_are_set() {
+        let config = with_vars(
+            &[("OPENAI_MAX_RETRIES", "3"), ("OPENAI_ORG_ID", "")],
+            OpenAIConfig::from_env,
+        )
+        .unwrap();
+
+        assert_eq!(config.retry.unwrap().base_delay_ms, 500);
+        assert!(config.organization.is_none());
+        assert!(config.hedging.is_none());
+        assert!(config.failover.is_none());
+
+        let invalid = with_vars(&[("OPENAI_TIMEOUT_SECS", "soon")], OpenAIConfig::from_env);
+        assert!(
+            matches!(invalid, Err(OpenAIError::ConfigError(message)) if message.contains("OPENAI_TIMEOUT_SECS"))
+        );
+    }
+}
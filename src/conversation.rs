@@ -0,0 +1,940 @@
+use crate::{
+    capabilities::model_info,
+    chunking::{content_tokens, ApproximateTokenCounter, TokenCounter},
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::{completion::ChatCompletionRequest, types::Usage},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+#[cfg(feature = "fs")]
+use std::{fs, path::PathBuf};
+
+/// How a [`Conversation`] keeps its history within the model's context window.
+///
+/// The system prompt is always kept; the oldest other messages are dropped first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Send the whole history
+    #[default]
+    None,
+
+    /// Keep at most this many messages
+    MaxMessages(usize),
+
+    /// Keep the most recent messages totalling at most this many (approximate) tokens
+    MaxTokens(usize),
+
+    /// Keep the most recent messages fitting in the context window of the model with room for its
+    /// maximum output, according to its [`model_info`] (no truncation for unknown models)
+    ContextWindow,
+}
+
+/// Opt-in compaction of a [`Conversation`] history: when it nears the context budget, the older
+/// turns are summarized by a cheap model and replaced with the summary, the most recent turns being
+/// kept verbatim.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SummarizationPolicy {
+    /// Model writing the summaries
+    pub model: Model,
+
+    /// Number of (approximate) tokens of the history, system prompt included, above which older
+    /// turns are summarized
+    pub trigger_tokens: usize,
+
+    /// Number of most recent messages kept verbatim
+    pub keep_recent: usize,
+
+    /// Instructions given to the summarizing model
+    pub instructions: String,
+}
+
+impl Default for SummarizationPolicy {
+    fn default() -> Self {
+        Self {
+            model: Model::from("gpt-4o-mini"),
+            trigger_tokens: 6_000,
+            keep_recent: 6,
+            instructions: "Summarize the conversation below so that it can be continued from the \
+                           summary alone. Keep every fact, decision, name, number and open \
+                           question; drop pleasantries."
+                .to_string(),
+        }
+    }
+}
+
+impl SummarizationPolicy {
+    /// Create a new [`SummarizationPolicy`] summarizing once the history exceeds `trigger_tokens`.
+    pub fn new(trigger_tokens: usize) -> Self {
+        Self {
+            trigger_tokens,
+            ..Default::default()
+        }
+    }
+}
+
+/// Persisted state of a [`Conversation`].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConversationState {
+    /// System prompt
+    pub system: Option<String>,
+
+    /// History, system prompt excluded
+    pub messages: Vec<Value>,
+
+    /// Token usage accumulated over every turn
+    pub usage: Usage,
+}
+
+/// Storage backend persisting [`Conversation`] histories by ID, e.g. to survive restarts or to
+/// share conversations between replicas.
+pub trait ConversationStore: Send + Sync {
+    /// Return the state stored under `id`, if any.
+    fn load(&self, id: &str) -> OpenAIResult<Option<ConversationState>>;
+
+    /// Store `state` under `id`, replacing any previous state.
+    fn save(&self, id: &str, state: &ConversationState) -> OpenAIResult<()>;
+
+    /// Remove the state stored under `id`, if any.
+    fn delete(&self, id: &str) -> OpenAIResult<()>;
+}
+
+/// [`ConversationStore`] keeping every conversation in memory.
+#[derive(Default)]
+pub struct InMemoryConversationStore {
+    conversations: Mutex<HashMap<String, ConversationState>>,
+}
+
+impl InMemoryConversationStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ConversationStore for InMemoryConversationStore {
+    fn load(&self, id: &str) -> OpenAIResult<Option<ConversationState>> {
+        Ok(self.conversations.lock().unwrap().get(id).cloned())
+    }
+
+    fn save(&self, id: &str, state: &ConversationState) -> OpenAIResult<()> {
+        self.conversations
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), state.clone());
+
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> OpenAIResult<()> {
+        self.conversations.lock().unwrap().remove(id);
+
+        Ok(())
+    }
+}
+
+/// [`ConversationStore`] writing each conversation to a JSON file named after its ID in a
+/// directory.
+///
+/// IDs may only contain ASCII letters, digits, `-` and `_`.
+#[cfg(feature = "fs")]
+pub struct FileConversationStore {
+    directory: PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl FileConversationStore {
+    /// Create a store writing to `directory`, which is created if it doesn't exist.
+    pub fn new(directory: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self { directory })
+    }
+
+    fn path(&self, id: &str) -> OpenAIResult<PathBuf> {
+        let valid = !id.is_empty()
+            && id.chars().all(|character| {
+                character.is_ascii_alphanumeric() || matches!(character, '-' | '_')
+            });
+
+        if valid {
+            Ok(self.directory.join(format!("{id}.json")))
+        } else {
+            Err(OpenAIError::ConfigError(format!(
+                "invalid conversation ID: {id:?}"
+            )))
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl ConversationStore for FileConversationStore {
+    fn load(&self, id: &str) -> OpenAIResult<Option<ConversationState>> {
+        match fs::read(self.path(id)?) {
+            Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn save(&self, id: &str, state: &ConversationState) -> OpenAIResult<()> {
+        // Write to a temporary file first so a crash can't leave a truncated history.
+        let path = self.path(id)?;
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_vec_pretty(state)?)?;
+        fs::rename(temporary, path)?;
+
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> OpenAIResult<()> {
+        match fs::remove_file(self.path(id)?) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Chat conversation keeping its message history, appending the model replies and tracking the
+/// token usage of every turn.
+#[derive(Clone)]
+pub struct Conversation {
+    openai: OpenAI,
+    model: Option<Model>,
+    system: Option<String>,
+    messages: Vec<Value>,
+    truncation: TruncationPolicy,
+    summarization: Option<SummarizationPolicy>,
+    usage: Usage,
+    store: Option<(Arc<dyn ConversationStore>, String)>,
+}
+
+impl Conversation {
+    /// Start an empty conversation using the client's default chat model.
+    pub fn new(openai: OpenAI) -> Self {
+        Self {
+            openai,
+            model: None,
+            system: None,
+            messages: Vec::new(),
+            truncation: TruncationPolicy::None,
+            summarization: None,
+            usage: Usage::default(),
+            store: None,
+        }
+    }
+
+    /// Resume the conversation stored under `id` in `store`, or start an empty one if there is
+    /// none, saving it there after every turn.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the conversation on success, or an [`OpenAIError`] if it couldn't be loaded.
+    pub fn resume(
+        openai: OpenAI,
+        store: Arc<dyn ConversationStore>,
+        id: impl Into<String>,
+    ) -> OpenAIResult<Self> {
+        let id = id.into();
+        let state = store.load(&id)?;
+        let mut conversation = Self::new(openai);
+
+        if let Some(state) = state {
+            conversation.restore(state);
+        }
+        conversation.store = Some((store, id));
+
+        Ok(conversation)
+    }
+
+    /// Save the conversation under `id` in `store` after every turn.
+    pub fn persist(mut self, store: Arc<dyn ConversationStore>, id: impl Into<String>) -> Self {
+        self.store = Some((store, id.into()));
+        self
+    }
+
+    /// Use `model` instead of the client's default chat model.
+    pub fn model(mut self, model: impl Into<Model>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Set the system prompt sent at the start of every request.
+    pub fn system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// Set how the history is truncated before every request.
+    pub fn truncation(mut self, truncation: TruncationPolicy) -> Self {
+        self.truncation = truncation;
+        self
+    }
+
+    /// Summarize older turns according to `summarization` when the history nears the context
+    /// budget. Summarization happens before truncation.
+    pub fn summarization(mut self, summarization: SummarizationPolicy) -> Self {
+        self.summarization = Some(summarization);
+        self
+    }
+
+    /// The system prompt, if any.
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system.as_deref()
+    }
+
+    /// The history of the conversation, system prompt excluded.
+    pub fn messages(&self) -> &[Value] {
+        &self.messages
+    }
+
+    /// Token usage accumulated over every turn.
+    pub fn usage(&self) -> Usage {
+        self.usage.clone()
+    }
+
+    /// The state of the conversation, to persist it.
+    pub fn state(&self) -> ConversationState {
+        ConversationState {
+            system: self.system.clone(),
+            messages: self.messages.clone(),
+            usage: self.usage.clone(),
+        }
+    }
+
+    /// Replace the system prompt, history and usage with `state`.
+    pub fn restore(&mut self, state: ConversationState) {
+        self.system = state.system;
+        self.messages = state.messages;
+        self.usage = state.usage;
+    }
+
+    /// Save the conversation to its store, if it has one.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing nothing on success, or an [`OpenAIError`] if the store failed.
+    pub fn save(&self) -> OpenAIResult<()> {
+        match &self.store {
+            Some((store, id)) => store.save(id, &self.state()),
+            None => Ok(()),
+        }
+    }
+
+    /// Forget the history and the accumulated usage, keeping the system prompt.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.usage = Usage::default();
+    }
+
+    /// Append a message to the history without sending anything, e.g. to restore a conversation.
+    pub fn push(&mut self, message: Value) {
+        self.messages.push(message);
+    }
+
+    /// Send a user message and return the text of the reply, both being appended to the history.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text of the user message.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the text of the reply on success, or an [`OpenAIError`] on failure, in
+    /// which case the user message is removed from the history. With a store, the conversation is
+    /// saved after a successful turn.
+    pub async fn send(&mut self, text: &str) -> OpenAIResult<String> {
+        self.messages
+            .push(json!({ "role": "user", "content": text }));
+
+        let result = match self.summarize().await {
+            Ok(()) => {
+                self.truncate();
+                self.complete().await
+            },
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(reply) => {
+                self.save()?;
+                Ok(reply)
+            },
+            Err(error) => {
+                self.messages.pop();
+                Err(error)
+            },
+        }
+    }
+
+    async fn complete(&mut self) -> OpenAIResult<String> {
+        let messages = self
+            .system
+            .iter()
+            .map(|system| json!({ "role": "system", "content": system }))
+            .chain(self.messages.iter().cloned())
+            .collect();
+
+        let mut request = ChatCompletionRequest::from_messages(messages);
+        if let Some(model) = &self.model {
+            request = request.model(model.clone());
+        }
+
+        let mut response = self.openai.completions().create(request).await?;
+
+        let message = response["choices"][0]["message"].take();
+        if message.is_null() {
+            return Err(OpenAIError::UnexpectedResponse(
+                "missing message in chat completion".to_string(),
+            ));
+        }
+
+        if let Ok(usage) = serde_json::from_value::<Usage>(response["usage"].take()) {
+            self.usage += usage;
+        }
+
+        let reply = message["content"].as_str().unwrap_or_default().to_string();
+        self.messages.push(message);
+
+        Ok(reply)
+    }
+
+    /// Replace the older turns with a summary if the summarization policy asks for it.
+    async fn summarize(&mut self) -> OpenAIResult<()> {
+        let Some(policy) = &self.summarization else {
+            return Ok(());
+        };
+
+        let tokens: usize = self.system_tokens()
+            + self
+                .messages
+                .iter()
+                .map(|message| content_tokens(&message["content"]))
+                .sum::<usize>();
+        if tokens <= policy.trigger_tokens {
+            return Ok(());
+        }
+
+        // Don't separate tool results from the assistant message calling the tools.
+        let mut split = self
+            .messages
+            .len()
+            .saturating_sub(policy.keep_recent.max(1));
+        while split > 0 && self.messages[split]["role"] == "tool" {
+            split -= 1;
+        }
+        if split == 0 {
+            return Ok(());
+        }
+
+        let transcript = self.messages[..split]
+            .iter()
+            .map(|message| {
+                let content = match &message["content"] {
+                    Value::String(content) => content.clone(),
+                    content => content.to_string(),
+                };
+                format!("{}: {content}", message["role"].as_str().unwrap_or("user"))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let request = ChatCompletionRequest::from_messages(vec![
+            json!({ "role": "system", "content": policy.instructions }),
+            json!({ "role": "user", "content": transcript }),
+        ])
+        .model(policy.model.clone());
+
+        let mut response = self.openai.completions().create(request).await?;
+
+        let Some(summary) = response["choices"][0]["message"]["content"].as_str() else {
+            return Err(OpenAIError::UnexpectedResponse(
+                "missing summary in chat completion".to_string(),
+            ));
+        };
+        let summary = json!({
+            "role": "system",
+            "content": format!("Summary of the earlier conversation:\n{summary}"),
+        });
+
+        if let Ok(usage) = serde_json::from_value::<Usage>(response["usage"].take()) {
+            self.usage += usage;
+        }
+        self.messages.splice(..split, [summary]);
+
+        Ok(())
+    }
+
+    fn system_tokens(&self) -> usize {
+        self.system
+            .as_deref()
+            .map_or(0, |system| ApproximateTokenCounter.count_tokens(system))
+    }
+
+    /// Drop the oldest messages according to the truncation policy, always keeping the last one and
+    /// the tool results along with the assistant message calling the tools.
+    fn truncate(&mut self) {
+        let keep = match self.truncation {
+            TruncationPolicy::None => return,
+            TruncationPolicy::MaxMessages(max) => max.max(1),
+            TruncationPolicy::MaxTokens(max) => self.recent_within(max),
+            TruncationPolicy::ContextWindow => {
+                let model = self.model.as_ref().map(Model::as_str).or(self
+                    .openai
+                    .default_models()
+                    .chat
+                    .as_deref());
+                let Some(info) = model.and_then(model_info) else {
+                    return;
+                };
+
+                self.recent_within(usize::try_from(info.max_prompt_tokens()).unwrap_or(usize::MAX))
+            },
+        };
+
+        let excess = tool_boundary(&self.messages, self.messages.len().saturating_sub(keep));
+        self.messages.drain(..excess);
+    }
+
+    /// Number of most recent messages totalling at most `max` tokens with the system prompt, at
+    /// least one.
+    fn recent_within(&self, max: usize) -> usize {
+        let mut total = self.system_tokens();
+
+        self.messages
+            .iter()
+            .rev()
+            .take_while(|message| {
+                total += content_tokens(&message["content"]);
+                total <= max
+            })
+            .count()
+            .max(1)
+    }
+}
+
+/// Move a cut before `messages[cut]` so it doesn't keep tool results without the assistant message
+/// calling the tools: past them, or before that message if they're the last messages.
+fn tool_boundary(messages: &[Value], mut cut: usize) -> usize {
+    let is_tool = |message: &Value| message["role"] == "tool";
+    if cut == 0 || !messages.get(cut).is_some_and(is_tool) {
+        return cut;
+    }
+
+    match messages[cut..].iter().position(|message| !is_tool(message)) {
+        Some(offset) => cut + offset,
+        None => {
+            while cut > 0 && is_tool(&messages[cut]) {
+                cut -= 1;
+            }
+            cut
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(text: &str) -> Value {
+        json!({ "role": "user", "content": text })
+    }
+
+    fn assistant(text: &str) -> Value {
+        json!({ "role": "assistant", "content": text })
+    }
+
+    fn tool_call(ids: &[&str]) -> Value {
+        let calls: Vec<Value> = ids
+            .iter()
+            .map(|id| json!({ "id": id, "type": "function", "function": { "name": "lookup", "arguments": "{}" } }))
+            .collect();
+
+        json!({ "role": "assistant", "content": null, "tool_calls": calls })
+    }
+
+    fn tool(id: &str) -> Value {
+        json!({ "role": "tool", "tool_call_id": id, "content": "result" })
+    }
+
+    fn conversation(messages: &[Value]) -> Conversation {
+        let mut conversation =
+            Conversation::new(OpenAI::new("sk-test", "https://api.openai.com/v1"))
+                .system("Answer in one sentence, be very concise.");
+        for message in messages {
+            conversation.push(message.clone());
+        }
+
+        conversation
+    }
+
+    /// The history left of `messages` after truncating it with `truncation`.
+    fn truncated(truncation: TruncationPolicy, messages: &[Value]) -> Vec<Value> {
+        let mut conversation = conversation(messages).truncation(truncation);
+        conversation.truncate();
+
+        conversation.messages().to_vec()
+    }
+
+    #[test]
+    fn truncation_keeps_the_system_prompt_and_the_latest_messages() {
+        let messages = [
+            user("one"),
+            assistant("two"),
+            user("three"),
+            assistant("four"),
+            user("five"),
+        ];
+
+        let mut conversation = conversation(&messages).truncation(TruncationPolicy::MaxMessages(2));
+        conversation.truncate();
+        assert_eq!(conversation.messages(), &messages[3..]);
+        assert_eq!(
+            conversation.system_prompt(),
+            Some("Answer in one sentence, be very concise.")
+        );
+
+        // The system prompt (10 tokens) counts against the budget, each message is 1 or 2 tokens.
+        assert_eq!(
+            truncated(TruncationPolicy::MaxTokens(14), &messages),
+            &messages[2..]
+        );
+        // The last message is kept even if it doesn't fit.
+        assert_eq!(
+            truncated(TruncationPolicy::MaxTokens(1), &messages),
+            &messages[4..]
+        );
+        assert_eq!(truncated(TruncationPolicy::None, &messages), messages);
+    }
+
+    #[test]
+    fn truncation_keeps_the_history_of_unknown_models_whole() {
+        let messages = [user("one"), assistant("two"), user("three")];
+        let mut conversation = conversation(&messages)
+            .model("my-fine-tune")
+            .truncation(TruncationPolicy::ContextWindow);
+
+        conversation.truncate();
+
+        assert_eq!(conversation.messages(), messages);
+    }
+
+    #[test]
+    fn truncation_drops_tool_results_with_their_call() {
+        let messages = [
+            user("look it up"),
+            tool_call(&["call_1", "call_2"]),
+            tool("call_1"),
+            tool("call_2"),
+            assistant("found it"),
+            user("thanks"),
+        ];
+
+        // Cutting between the call and its results drops the results too.
+        assert_eq!(
+            truncated(TruncationPolicy::MaxMessages(4), &messages),
+            &messages[4..]
+        );
+        // Cutting right before the call keeps it with its results.
+        assert_eq!(
+            truncated(TruncationPolicy::MaxMessages(5), &messages),
+            &messages[1..]
+        );
+    }
+
+    #[test]
+    fn truncation_keeps_the_call_of_trailing_tool_results() {
+        let messages = [
+            user("look it up"),
+            tool_call(&["call_1", "call_2"]),
+            tool("call_1"),
+            tool("call_2"),
+        ];
+
+        assert_eq!(
+            truncated(TruncationPolicy::MaxMessages(1), &messages),
+            &messages[1..]
+        );
+    }
+
+    #[test]
+    fn tool_boundaries_never_start_on_a_tool_result() {
+        let messages = [user("a"), tool_call(&["call_1"]), tool("call_1"), user("b")];
+
+        assert_eq!(tool_boundary(&messages, 0), 0);
+        assert_eq!(tool_boundary(&messages, 1), 1);
+        assert_eq!(tool_boundary(&messages, 2), 3);
+        assert_eq!(tool_boundary(&messages, 3), 3);
+        assert_eq!(tool_boundary(&messages[..3], 2), 1);
+    }
+
+    fn state() -> ConversationState {
+        ConversationState {
+            system: Some("Be brief.".to_string()),
+            messages: vec![user("Hello"), assistant("Hi")],
+            usage: Usage {
+                prompt_tokens: 12,
+                completion_tokens: 1,
+                total_tokens: 13,
+                ..Usage::default()
+            },
+        }
+    }
+
+    /// Save, load, overwrite and delete a conversation in `store`.
+    fn round_trip(store: &dyn ConversationStore) {
+        assert_eq!(store.load("chat-1").unwrap(), None);
+
+        store.save("chat-1", &state()).unwrap();
+        assert_eq!(store.load("chat-1").unwrap(), Some(state()));
+
+        let mut longer = state();
+        longer.messages.push(user("Bye"));
+        store.save("chat-1", &longer).unwrap();
+        store.save("chat_2", &state()).unwrap();
+        assert_eq!(store.load("chat-1").unwrap(), Some(longer));
+
+        store.delete("chat-1").unwrap();
+        store.delete("chat-1").unwrap();
+        assert_eq!(store.load("chat-1").unwrap(), None);
+        assert_eq!(store.load("chat_2").unwrap(), Some(state()));
+    }
+
+    #[test]
+    fn in_memory_stores_keep_conversations_by_id() {
+        round_trip(&InMemoryConversationStore::new());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn file_stores_keep_conversations_by_id() {
+        let directory =
+            std::env::temp_dir().join(format!("rusty-openai-conversations-{}", std::process::id()));
+        let store = FileConversationStore::new(&directory).unwrap();
+
+        round_trip(&store);
+        for id in ["", "../secrets", "a/b", "chat 1"] {
+            assert!(matches!(
+                store.save(id, &state()),
+                Err(OpenAIError::ConfigError(_))
+            ));
+        }
+        let files: Vec<_> = fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(files, ["chat_2.json"]);
+    }
+
+    #[test]
+    fn resumed_conversations_are_saved_after_every_turn() {
+        let store = Arc::new(InMemoryConversationStore::new());
+        store.save("chat-1", &state()).unwrap();
+        let openai = OpenAI::new("sk-test", "https://api.openai.com/v1");
+
+        let mut conversation =
+            Conversation::resume(openai.clone(), Arc::clone(&store) as _, "chat-1").unwrap();
+        assert_eq!(conversation.state(), state());
+
+        conversation.push(user("Bye"));
+        conversation.save().unwrap();
+        assert_eq!(store.load("chat-1").unwrap().unwrap().messages.len(), 3);
+
+        let empty = Conversation::resume(openai, store as _, "chat-2").unwrap();
+        assert_eq!(empty.state(), ConversationState::default());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn usage_accumulates_over_successful_turns() {
+        use crate::test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+
+        let mock = Arc::new(MockTransport::new());
+        mock.once(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::error(400, "invalid request"),
+        );
+        mock.on(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::json(&fixtures::chat_completion("gpt-4o", "Hi there")),
+        );
+        let mut conversation = Conversation::new(mock.client())
+            .model("gpt-4o")
+            .system("Be brief.");
+
+        assert!(conversation.send("Hello").await.is_err());
+        assert!(conversation.messages().is_empty());
+        assert_eq!(conversation.usage().total_tokens, 0);
+
+        assert_eq!(conversation.send("Hello").await.unwrap(), "Hi there");
+        assert_eq!(conversation.send("Again").await.unwrap(), "Hi there");
+
+        let usage = conversation.usage();
+        assert_eq!(
+            (
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.total_tokens
+            ),
+            (24, 4, 28)
+        );
+        assert_eq!(conversation.messages().len(), 4);
+        assert_eq!(conversation.messages()[3]["role"], "assistant");
+
+        // The system prompt is sent first, not kept in the history.
+        let request = mock.last_request().unwrap().json().unwrap();
+        assert_eq!(
+            request["messages"][0],
+            json!({ "role": "system", "content": "Be brief." })
+        );
+        assert_eq!(request["messages"].as_array().unwrap().len(), 4);
+
+        conversation.clear();
+        assert!(conversation.messages().is_empty());
+        assert_eq!(conversation.usage().total_tokens, 0);
+        assert_eq!(conversation.system_prompt(), Some("Be brief."));
+    }
+
+    #[cfg(feature = "test-util")]
+    mod summarization {
+        use super::*;
+        use crate::test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+
+        /// A conversation whose history is `messages`, summarized above 10 tokens, the summary
+        /// being answered first and then the reply.
+        fn summarized(mock: &Arc<MockTransport>, messages: &[Value]) -> Conversation {
+            mock.once(
+                Method::POST,
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_completion(
+                    "gpt-4o-mini",
+                    "They said hello.",
+                )),
+            );
+            mock.on(
+                Method::POST,
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_completion("gpt-4o", "Sure.")),
+            );
+
+            let mut conversation = Conversation::new(mock.client())
+                .model("gpt-4o")
+                .summarization(SummarizationPolicy {
+                    keep_recent: 2,
+                    ..SummarizationPolicy::new(10)
+                });
+            for message in messages {
+                conversation.push(message.clone());
+            }
+
+            conversation
+        }
+
+        #[tokio::test]
+        async fn older_turns_are_replaced_with_a_summary() {
+            let mock = Arc::new(MockTransport::new());
+            let mut conversation = summarized(
+                &mock,
+                &[
+                    user("Hello, my name is Ada."),
+                    assistant("Hello Ada!"),
+                    user("I live in London."),
+                    assistant("Noted."),
+                ],
+            );
+
+            assert_eq!(conversation.send("Remind me?").await.unwrap(), "Sure.");
+
+            let requests = mock.requests();
+            assert_eq!(requests.len(), 2);
+            let summary_request = requests[0].json().unwrap();
+            assert_eq!(summary_request["model"], "gpt-4o-mini");
+            assert_eq!(
+                summary_request["messages"][1]["content"],
+                "user: Hello, my name is Ada.\n\nassistant: Hello Ada!\n\nuser: I live in London."
+            );
+
+            let messages = conversation.messages();
+            assert_eq!(
+                messages[0],
+                json!({
+                    "role": "system",
+                    "content": "Summary of the earlier conversation:\nThey said hello.",
+                })
+            );
+            assert_eq!(messages[1..3], [assistant("Noted."), user("Remind me?")]);
+            assert_eq!(messages.len(), 4);
+            assert_eq!(
+                requests[1].json().unwrap()["messages"]
+                    .as_array()
+                    .unwrap()
+                    .len(),
+                3
+            );
+
+            // Both calls count.
+            assert_eq!(conversation.usage().prompt_tokens, 24);
+        }
+
+        #[tokio::test]
+        async fn summaries_keep_tool_results_with_their_call() {
+            let mock = Arc::new(MockTransport::new());
+            let mut conversation = summarized(
+                &mock,
+                &[
+                    user("What's the weather in London today?"),
+                    tool_call(&["call_1"]),
+                    tool("call_1"),
+                ],
+            );
+
+            conversation.send("Thanks").await.unwrap();
+
+            let summary_request = mock.requests()[0].json().unwrap();
+            assert_eq!(
+                summary_request["messages"][1]["content"],
+                "user: What's the weather in London today?"
+            );
+            assert_eq!(conversation.messages()[1], tool_call(&["call_1"]));
+            assert_eq!(conversation.messages()[2], tool("call_1"));
+        }
+
+        #[tokio::test]
+        async fn short_histories_are_not_summarized() {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::POST,
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_completion("gpt-4o", "Sure.")),
+            );
+            let mut conversation = Conversation::new(mock.client())
+                .model("gpt-4o")
+                .summarization(SummarizationPolicy::new(10));
+
+            assert_eq!(conversation.send("Hi").await.unwrap(), "Sure.");
+
+            assert_eq!(mock.requests().len(), 1);
+            assert_eq!(conversation.messages()[0], user("Hi"));
+        }
+    }
+}
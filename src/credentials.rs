@@ -0,0 +1,428 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    rate_limit::RateLimits,
+    runtime::Instant,
+    transport::BoxFuture,
+};
+use reqwest::StatusCode;
+use std::{fmt, sync::Mutex, time::Duration};
+
+/// Source of the API key of a client, consulted before every request, e.g. to rotate keys kept in
+/// a secret manager without rebuilding the client.
+///
+/// Every request waits for [`ApiKeyProvider::api_key`], so implementations fetching keys remotely
+/// should cache them and only refresh them when they're about to expire. An error fails the
+/// request before it's sent.
+pub trait ApiKeyProvider: Send + Sync {
+    /// The API key to authenticate the next request with.
+    fn api_key(&self) -> BoxFuture<'_, OpenAIResult<String>>;
+
+    /// Report the outcome of an attempt authenticated with `api_key`: the status of its response
+    /// (`None` if no response was received) and the rate limits reported in its headers, e.g. to
+    /// stop handing out a key that keeps failing. Ignored by default.
+    fn report(&self, api_key: &str, status: Option<StatusCode>, rate_limits: Option<&RateLimits>) {
+        let _ = (api_key, status, rate_limits);
+    }
+}
+
+/// [`ApiKeyProvider`] always returning the same key, used by the clients created with an API key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct StaticApiKey(String);
+
+impl StaticApiKey {
+    /// Create a new provider of `api_key`.
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self(api_key.into())
+    }
+}
+
+impl fmt::Debug for StaticApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StaticApiKey").field(&"[REDACTED]").finish()
+    }
+}
+
+impl ApiKeyProvider for StaticApiKey {
+    fn api_key(&self) -> BoxFuture<'_, OpenAIResult<String>> {
+        Box::pin(futures::future::ready(Ok(self.0.clone())))
+    }
+}
+
+/// How an [`ApiKeyPool`] picks the key of the next request among its available keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum KeySelection {
+    /// In turn, each key being picked in proportion to its weight
+    #[default]
+    RoundRobin,
+
+    /// The key with the largest share of its rate limits left, as last reported by the API, the
+    /// keys with as much left being picked in turn in proportion to their weight
+    RateLimitBudget,
+}
+
+/// Health of a key of an [`ApiKeyPool`], the key itself being left out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyHealth {
+    /// Position of the key in the pool
+    pub index: usize,
+
+    /// Weight of the key
+    pub weight: u32,
+
+    /// Consecutive failures of the requests authenticated with the key
+    pub consecutive_failures: u32,
+
+    /// Whether the key is handed out, rather than set aside after failing
+    pub available: bool,
+
+    /// Rate limits last reported for the key
+    pub rate_limits: Option<RateLimits>,
+}
+
+/// [`ApiKeyProvider`] spreading the requests over several API keys, e.g. of projects with their
+/// own quotas, see [`OpenAIBuilder::api_keys`][crate::builder::OpenAIBuilder::api_keys].
+///
+/// Keys are picked according to the [`KeySelection`]. After `failure_threshold` consecutive
+/// failures (authentication errors, `429`s, `5xx`s or no response at all) a key is set aside for
+/// `cooldown`, unless every key is, in which case the one coming back first is used. The retries
+/// of a request keep its key.
+pub struct ApiKeyPool {
+    keys: Vec<(String, u32)>,
+    selection: KeySelection,
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<Vec<KeyState>>,
+}
+
+#[derive(Default)]
+struct KeyState {
+    /// Current weight in the smooth weighted round robin
+    current_weight: i64,
+    failures: u32,
+    set_aside_until: Option<Instant>,
+    rate_limits: Option<RateLimits>,
+}
+
+impl ApiKeyPool {
+    /// Create a new pool of `keys` with the same weight, picked round-robin.
+    pub fn new<I>(keys: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self::weighted(keys.into_iter().map(|key| (key, 1)))
+    }
+
+    /// Create a new pool of keys with their weight, e.g. `[(key_a, 3), (key_b, 1)]` to send three
+    /// requests with `key_a` for every one with `key_b`, picked round-robin.
+    pub fn weighted<I, K>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = (K, u32)>,
+        K: Into<String>,
+    {
+        let keys: Vec<(String, u32)> = keys
+            .into_iter()
+            .map(|(key, weight)| (key.into(), weight.max(1)))
+            .collect();
+
+        Self {
+            state: Mutex::new(keys.iter().map(|_| KeyState::default()).collect()),
+            keys,
+            selection: KeySelection::default(),
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+
+    /// Pick the keys according to `selection`.
+    pub fn selection(mut self, selection: KeySelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Set the number of consecutive failures after which a key is set aside, 3 by default.
+    pub fn failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    /// Set how long a failing key is set aside, a minute by default.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Number of keys of the pool.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the pool has no key.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Health of every key, in the order they were given.
+    pub fn health(&self) -> Vec<KeyHealth> {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        self.keys
+            .iter()
+            .zip(state.iter())
+            .enumerate()
+            .map(|(index, ((_, weight), key))| KeyHealth {
+                index,
+                weight: *weight,
+                consecutive_failures: key.failures,
+                available: key.set_aside_until.is_none_or(|until| until <= now),
+                rate_limits: key.rate_limits,
+            })
+            .collect()
+    }
+
+    /// Index of the key of the next request.
+    fn pick(&self, state: &mut [KeyState]) -> usize {
+        let now = Instant::now();
+        let mut candidates: Vec<usize> = (0..self.keys.len())
+            .filter(|&index| {
+                state[index]
+                    .set_aside_until
+                    .is_none_or(|until| until <= now)
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            return (0..self.keys.len())
+                .min_by_key(|&index| state[index].set_aside_until)
+                .unwrap_or_default();
+        }
+
+        if self.selection == KeySelection::RateLimitBudget {
+            let budget = |index: usize| budget_left(state[index].rate_limits.as_ref());
+            let best = candidates
+                .iter()
+                .map(|&index| budget(index))
+                .fold(0.0, f64::max);
+            candidates.retain(|&index| budget(index) >= best);
+        }
+
+        // Smooth weighted round robin: every candidate gains its weight, and the one with the
+        // largest current weight is picked and loses the total.
+        let mut total = 0;
+        for &index in &candidates {
+            let weight = i64::from(self.keys[index].1);
+            state[index].current_weight += weight;
+            total += weight;
+        }
+        let picked = candidates
+            .iter()
+            .copied()
+            .max_by_key(|&index| (state[index].current_weight, std::cmp::Reverse(index)))
+            .unwrap_or_default();
+        state[picked].current_weight -= total;
+
+        // Counted against the key until the API reports its limits again.
+        if let Some(remaining) = state[picked]
+            .rate_limits
+            .as_mut()
+            .and_then(|limits| limits.remaining_requests.as_mut())
+        {
+            *remaining = remaining.saturating_sub(1);
+        }
+
+        picked
+    }
+}
+
+/// Share of the rate limits left, the smallest of the requests' and tokens' ones, `1` for limits
+/// that weren't reported or have reset since.
+fn budget_left(rate_limits: Option<&RateLimits>) -> f64 {
+    let Some(limits) = rate_limits else {
+        return 1.0;
+    };
+    let elapsed = limits.received_at.elapsed();
+
+    let requests = share_left(limits.remaining_requests, limits.limit_requests);
+    let tokens = share_left(limits.remaining_tokens, limits.limit_tokens);
+    let reset = |reset: Option<Duration>| reset.is_some_and(|reset| elapsed >= reset);
+
+    match (reset(limits.reset_requests), reset(limits.reset_tokens)) {
+        (true, true) => 1.0,
+        (true, false) => tokens,
+        (false, true) => requests,
+        (false, false) => requests.min(tokens),
+    }
+}
+
+/// Share of a limit left, `1` if it wasn't reported.
+fn share_left(remaining: Option<u64>, limit: Option<u64>) -> f64 {
+    match (remaining, limit) {
+        (Some(remaining), Some(limit)) if limit > 0 => remaining as f64 / limit as f64,
+        _ => 1.0,
+    }
+}
+
+impl fmt::Debug for ApiKeyPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeyPool")
+            .field("keys", &self.keys.len())
+            .field("selection", &self.selection)
+            .field("failure_threshold", &self.failure_threshold)
+            .field("cooldown", &self.cooldown)
+            .finish()
+    }
+}
+
+impl ApiKeyProvider for ApiKeyPool {
+    fn api_key(&self) -> BoxFuture<'_, OpenAIResult<String>> {
+        let key = if self.keys.is_empty() {
+            Err(OpenAIError::ConfigError(
+                "an API key pool needs at least one key".to_string(),
+            ))
+        } else {
+            let index = self.pick(&mut self.state.lock().unwrap());
+            Ok(self.keys[index].0.clone())
+        };
+
+        Box::pin(futures::future::ready(key))
+    }
+
+    fn report(&self, api_key: &str, status: Option<StatusCode>, rate_limits: Option<&RateLimits>) {
+        let Some(index) = self.keys.iter().position(|(key, _)| key == api_key) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let key = &mut state[index];
+
+        if let Some(rate_limits) = rate_limits {
+            key.rate_limits = Some(*rate_limits);
+        }
+
+        let failed = status.is_none_or(|status| {
+            matches!(
+                status,
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+            ) || status.is_server_error()
+        });
+        if !failed {
+            key.failures = 0;
+            key.set_aside_until = None;
+            return;
+        }
+
+        key.failures += 1;
+        if key.failures >= self.failure_threshold {
+            key.set_aside_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(remaining_requests: u64, limit_requests: u64) -> RateLimits {
+        RateLimits {
+            limit_requests: Some(limit_requests),
+            limit_tokens: None,
+            remaining_requests: Some(remaining_requests),
+            remaining_tokens: None,
+            reset_requests: Some(Duration::from_secs(60)),
+            reset_tokens: None,
+            received_at: Instant::now(),
+        }
+    }
+
+    fn picks(pool: &ApiKeyPool, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| {
+                let index = pool.pick(&mut pool.state.lock().unwrap());
+                pool.keys[index].0.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keys_are_picked_smoothly_in_proportion_to_their_weight() {
+        let pool = ApiKeyPool::weighted([("a", 3), ("b", 1), ("c", 2)]);
+
+        let picks = picks(&pool, 60);
+
+        for (key, expected) in [("a", 30), ("b", 10), ("c", 20)] {
+            assert_eq!(picks.iter().filter(|pick| *pick == key).count(), expected);
+        }
+        // Interleaved rather than in runs of each key.
+        assert_eq!(picks[..6], ["a", "c", "a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn failing_keys_are_set_aside() {
+        let pool = ApiKeyPool::new(["a", "b"]).failure_threshold(2);
+
+        pool.report("a", Some(StatusCode::TOO_MANY_REQUESTS), None);
+        assert_eq!(picks(&pool, 2), ["a", "b"]);
+        pool.report("a", None, None);
+        assert_eq!(picks(&pool, 3), ["b", "b", "b"]);
+        assert!(!pool.health()[0].available);
+    }
+
+    #[test]
+    fn keys_with_an_exhausted_budget_are_skipped() {
+        let pool = ApiKeyPool::new(["a", "b", "c"]).selection(KeySelection::RateLimitBudget);
+
+        pool.report("a", Some(StatusCode::OK), Some(&limits(0, 100)));
+        pool.report("b", Some(StatusCode::OK), Some(&limits(2, 100)));
+        pool.report("c", Some(StatusCode::OK), Some(&limits(2, 100)));
+
+        // Each pick is counted against its key until the API reports its limits again.
+        assert_eq!(picks(&pool, 4), ["b", "c", "c", "b"]);
+        assert_eq!(
+            pool.health()[1].rate_limits.unwrap().remaining_requests,
+            Some(0)
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn each_key_is_paced_on_its_own_rate_limits() {
+        use crate::test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+        use std::sync::Arc;
+
+        let response = fixtures::embeddings("text-embedding-3-small", &["a"], 3);
+        let mock = Arc::new(MockTransport::new());
+        mock.once(
+            Method::POST,
+            "/embeddings",
+            MockResponse::json(&response)
+                .header("x-ratelimit-limit-requests", "100")
+                .header("x-ratelimit-remaining-requests", "0")
+                .header("x-ratelimit-reset-requests", "60s"),
+        )
+        .on(Method::POST, "/embeddings", MockResponse::json(&response));
+        let mut openai = mock.client();
+        openai.set_api_key_provider(Arc::new(ApiKeyPool::new(["sk-a", "sk-b"])));
+        openai.set_adaptive_rate_limit(true);
+
+        let embeddings = openai.embeddings();
+        for _ in 0..2 {
+            let call =
+                embeddings.create("a", Some("text-embedding-3-small".into()), None, None, None);
+            tokio::time::timeout(Duration::from_secs(5), call)
+                .await
+                .expect("the request of sk-b waited for the limits of sk-a")
+                .unwrap();
+        }
+
+        let authorizations: Vec<_> = mock
+            .requests()
+            .iter()
+            .map(|request| request.headers["authorization"].clone())
+            .collect();
+        assert_eq!(authorizations, ["Bearer sk-a", "Bearer sk-b"]);
+    }
+}
@@ -1,6 +1,7 @@
-use reqwest::Error as ReqwestError;
-use serde_json::Error as SerdeJsonError;
-use std::io::Error as IoError;
+use crate::prepared::PreparedRequest;
+use reqwest::{Error as ReqwestError, StatusCode};
+use serde_json::{Error as SerdeJsonError, Value};
+use std::{io::Error as IoError, time::Duration};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -13,6 +14,75 @@ pub enum OpenAIError {
 
     #[error("IO Error: {0}")]
     IoError(#[from] IoError),
+
+    #[error("Config Error: {0}")]
+    ConfigError(String),
+
+    #[error("Unexpected Response: {0}")]
+    UnexpectedResponse(String),
+
+    #[error("Circuit Open: {0}")]
+    CircuitOpen(String),
+
+    #[error("Deadline Exceeded: {attempts} attempts in {elapsed:?}")]
+    DeadlineExceeded {
+        attempts: u32,
+        elapsed: Duration,
+        last_status: Option<u16>,
+    },
+
+    #[error("Refusal: {0}")]
+    Refusal(String),
+
+    #[error("Parse Error: {message}")]
+    ParseError { message: String, raw: String },
+
+    #[error("API Error ({status}): {message}")]
+    ApiError {
+        status: u16,
+        code: Option<String>,
+        message: String,
+        param: Option<String>,
+        request_id: Option<String>,
+    },
+
+    #[error("Dry Run: {} {}", .0.method, .0.url)]
+    DryRun(Box<PreparedRequest>),
+}
+
+impl OpenAIError {
+    /// The [`OpenAIError::ApiError`] of an error response, read from its
+    /// `{"error": {"message": ..., "code": ..., "param": ...}}` envelope or from its text.
+    pub(crate) fn api_error(status: StatusCode, request_id: Option<String>, body: &[u8]) -> Self {
+        let envelope = serde_json::from_slice::<Value>(body).ok();
+        let error = envelope.as_ref().and_then(|envelope| envelope.get("error"));
+        let field = |name: &str| match error.and_then(|error| error.get(name)) {
+            Some(Value::String(value)) => Some(value.clone()),
+            Some(Value::Number(value)) => Some(value.to_string()),
+            _ => None,
+        };
+
+        let message = match error {
+            Some(Value::String(message)) => Some(message.clone()),
+            _ => field("message").filter(|message| !message.is_empty()),
+        };
+        let message = message.unwrap_or_else(|| {
+            let text = String::from_utf8_lossy(body).trim().to_string();
+            if text.is_empty() {
+                status.to_string()
+            } else {
+                text
+            }
+        });
+
+        Self::ApiError {
+            status: status.as_u16(),
+            code: field("code").or_else(|| field("type")),
+            message,
+            param: field("param"),
+            request_id,
+        }
+    }
 }
 
 pub type OpenAIResult<T> = std::result::Result<T, OpenAIError>;
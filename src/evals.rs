@@ -0,0 +1,365 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::{completion::ChatCompletionRequest, types::Usage},
+    runtime::Instant,
+    structured,
+};
+use futures::stream::{self, StreamExt};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// How the answer of an [`EvalCase`] is judged.
+#[derive(Clone, Debug)]
+pub enum Checker {
+    /// The answer, trimmed, equals this text
+    ExactMatch(String),
+
+    /// The answer contains this text
+    Contains(String),
+
+    /// The answer matches this regular expression
+    Regex(Regex),
+
+    /// A grader model judges whether the answer meets these criteria
+    ModelGraded(String),
+}
+
+impl Checker {
+    /// A [`Checker::Regex`] matching `pattern`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the checker on success, or an [`OpenAIError::ConfigError`] if the pattern is invalid.
+    pub fn regex(pattern: &str) -> OpenAIResult<Self> {
+        Regex::new(pattern)
+            .map(Self::Regex)
+            .map_err(|error| OpenAIError::ConfigError(format!("invalid regex: {error}")))
+    }
+}
+
+/// A prompt of an [`EvalSuite`] and how to judge its answer.
+#[derive(Clone, Debug)]
+pub struct EvalCase {
+    name: String,
+    messages: Vec<Value>,
+    checker: Checker,
+}
+
+impl EvalCase {
+    /// Create a case sending `prompt` as a user message.
+    pub fn new(name: impl Into<String>, prompt: impl Into<String>, checker: Checker) -> Self {
+        let prompt: String = prompt.into();
+
+        Self::with_messages(
+            name,
+            vec![json!({ "role": "user", "content": prompt })],
+            checker,
+        )
+    }
+
+    /// Create a case sending a whole conversation.
+    pub fn with_messages(name: impl Into<String>, messages: Vec<Value>, checker: Checker) -> Self {
+        Self {
+            name: name.into(),
+            messages,
+            checker,
+        }
+    }
+
+    /// The name of the case.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Outcome of an [`EvalCase`] against a model.
+#[derive(Clone, Debug, Serialize)]
+pub struct CaseResult {
+    /// Name of the case
+    pub case: String,
+
+    /// Model the case ran against
+    pub model: Model,
+
+    /// Answer of the model, `None` if the request failed
+    pub output: Option<String>,
+
+    /// Whether the answer passed the checker
+    pub passed: bool,
+
+    /// Explanation of the grader model, for model-graded cases
+    pub reason: Option<String>,
+
+    /// Error of the completion or of the grading, if any
+    pub error: Option<String>,
+
+    /// Time taken by the completion
+    pub latency: Duration,
+
+    /// Token usage of the completion and of the grading
+    pub usage: Usage,
+}
+
+/// Aggregate results of an [`EvalSuite`] for a model.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ModelSummary {
+    /// Number of cases passed
+    pub passed: usize,
+
+    /// Number of cases whose answer failed the checker
+    pub failed: usize,
+
+    /// Number of cases whose completion or grading failed
+    pub errored: usize,
+
+    /// Total token usage
+    pub usage: Usage,
+}
+
+impl ModelSummary {
+    /// Share of passed cases, between 0 and 1.
+    pub fn pass_rate(&self) -> f64 {
+        let total = self.passed + self.failed + self.errored;
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.passed as f64 / total as f64
+    }
+}
+
+/// Report of an [`EvalSuite`] run.
+#[derive(Clone, Debug, Serialize)]
+pub struct EvalReport {
+    /// Result of every case against every model, grouped by model in the order they were given
+    pub results: Vec<CaseResult>,
+}
+
+impl EvalReport {
+    /// Aggregate results of `model`.
+    pub fn summary(&self, model: &Model) -> ModelSummary {
+        self.results
+            .iter()
+            .filter(|result| &result.model == model)
+            .fold(ModelSummary::default(), |mut summary, result| {
+                match (&result.error, result.passed) {
+                    (Some(_), _) => summary.errored += 1,
+                    (None, true) => summary.passed += 1,
+                    (None, false) => summary.failed += 1,
+                }
+                summary.usage += &result.usage;
+                summary
+            })
+    }
+
+    /// Results that didn't pass, errors included.
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+
+    /// Whether every case passed against every model.
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+}
+
+/// Suite of prompts with checkers on their answers, run against one or more models to catch prompt
+/// regressions before deploys.
+pub struct EvalSuite {
+    cases: Vec<EvalCase>,
+    concurrency: usize,
+    temperature: Option<f64>,
+    grader_model: Option<Model>,
+}
+
+impl Default for EvalSuite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EvalSuite {
+    /// Create an empty suite running 4 cases at a time, model-graded cases being graded by the
+    /// client's default chat model.
+    pub fn new() -> Self {
+        Self {
+            cases: Vec::new(),
+            concurrency: 4,
+            temperature: None,
+            grader_model: None,
+        }
+    }
+
+    /// Add a case.
+    pub fn case(mut self, case: EvalCase) -> Self {
+        self.cases.push(case);
+        self
+    }
+
+    /// Run at most `concurrency` cases at a time.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sample the answers with `temperature`, e.g. 0 for reproducible runs.
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Grade model-graded cases with `model`.
+    pub fn grader_model(mut self, model: impl Into<Model>) -> Self {
+        self.grader_model = Some(model.into());
+        self
+    }
+
+    /// The cases of the suite.
+    pub fn cases(&self) -> &[EvalCase] {
+        &self.cases
+    }
+
+    /// Run every case against every model.
+    ///
+    /// # Arguments
+    ///
+    /// * `openai` - Client used for the completion and grading requests.
+    /// * `models` - The models to evaluate.
+    ///
+    /// # Returns
+    ///
+    /// The [`EvalReport`], failed requests being reported as errored cases.
+    pub async fn run(&self, openai: &OpenAI, models: &[Model]) -> EvalReport {
+        let runs = models
+            .iter()
+            .flat_map(|model| self.cases.iter().map(move |case| (model, case)));
+
+        let results = stream::iter(runs)
+            .map(|(model, case)| self.run_case(openai, model, case))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        EvalReport { results }
+    }
+
+    async fn run_case(&self, openai: &OpenAI, model: &Model, case: &EvalCase) -> CaseResult {
+        let mut result = CaseResult {
+            case: case.name.clone(),
+            model: model.clone(),
+            output: None,
+            passed: false,
+            reason: None,
+            error: None,
+            latency: Duration::ZERO,
+            usage: Usage::default(),
+        };
+
+        let mut request = ChatCompletionRequest::new(model.clone(), case.messages.clone());
+        if let Some(temperature) = self.temperature {
+            request = request.temperature(temperature);
+        }
+
+        let started = Instant::now();
+        let response = complete(openai, request, &mut result.usage).await;
+        result.latency = started.elapsed();
+
+        let output = response.and_then(|response| {
+            response["choices"][0]["message"]["content"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    OpenAIError::UnexpectedResponse(
+                        "missing message in chat completion".to_string(),
+                    )
+                })
+        });
+        let output = match output {
+            Ok(output) => output,
+            Err(error) => {
+                result.error = Some(error.to_string());
+                return result;
+            },
+        };
+
+        match &case.checker {
+            Checker::ExactMatch(expected) => result.passed = output.trim() == expected.trim(),
+            Checker::Contains(expected) => result.passed = output.contains(expected.as_str()),
+            Checker::Regex(regex) => result.passed = regex.is_match(&output),
+            Checker::ModelGraded(criteria) => {
+                match self
+                    .grade(openai, case, &output, criteria, &mut result.usage)
+                    .await
+                {
+                    Ok((passed, reason)) => {
+                        result.passed = passed;
+                        result.reason = Some(reason);
+                    },
+                    Err(error) => result.error = Some(error.to_string()),
+                }
+            },
+        }
+        result.output = Some(output);
+
+        result
+    }
+
+    /// Ask the grader model whether `output` meets `criteria`, returning its verdict and reason.
+    async fn grade(
+        &self,
+        openai: &OpenAI,
+        case: &EvalCase,
+        output: &str,
+        criteria: &str,
+        usage: &mut Usage,
+    ) -> OpenAIResult<(bool, String)> {
+        let schema = json!({
+            "title": "grade",
+            "type": "object",
+            "properties": {
+                "reason": { "type": "string" },
+                "pass": { "type": "boolean" },
+            },
+        });
+        let prompt = format!(
+            "Conversation:\n{}\n\nAnswer:\n{output}\n\nCriteria:\n{criteria}",
+            Value::from(case.messages.clone())
+        );
+
+        let mut request = ChatCompletionRequest::from_messages(vec![
+            json!({ "role": "system", "content": "You grade answers. Explain whether the answer to the conversation meets the criteria, then give your verdict." }),
+            json!({ "role": "user", "content": prompt }),
+        ])
+        .response_format(structured::response_format(schema));
+        if let Some(model) = &self.grader_model {
+            request = request.model(model.clone());
+        }
+
+        let response = complete(openai, request, usage).await?;
+        let grade: Value = serde_json::from_str(&structured::content(response)?)?;
+
+        Ok((
+            grade["pass"].as_bool().unwrap_or_default(),
+            grade["reason"].as_str().unwrap_or_default().to_string(),
+        ))
+    }
+}
+
+/// Send `request`, adding its usage to `usage`.
+async fn complete(
+    openai: &OpenAI,
+    request: ChatCompletionRequest,
+    usage: &mut Usage,
+) -> OpenAIResult<Value> {
+    let mut response = openai.completions().create(request).await?;
+
+    if let Ok(response_usage) = serde_json::from_value::<Usage>(response["usage"].take()) {
+        *usage += response_usage;
+    }
+
+    Ok(response)
+}
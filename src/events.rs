@@ -0,0 +1,115 @@
+use crate::{circuit_breaker::CircuitState, retry::Attempt, stream::ChunkMetrics};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+/// Structured event describing client activity, delivered to the handlers registered with
+/// [`OpenAI::subscribe`][crate::openai::OpenAI::subscribe].
+///
+/// Events of the same request share its `id`.
+#[derive(Clone, Debug)]
+pub enum ClientEvent {
+    /// A request is about to be sent
+    RequestStarted {
+        id: u64,
+        method: String,
+        path: String,
+    },
+
+    /// A failed attempt is about to be retried after `delay`, `status` being `None` if no response
+    /// was received and `retry_after` the wait requested by the API, if any (see
+    /// [`retry_after`][crate::retry::retry_after])
+    Retry {
+        id: u64,
+        attempt: u32,
+        status: Option<u16>,
+        delay: Duration,
+        retry_after: Option<Duration>,
+    },
+
+    /// An attempt was slow to answer, so a second one was sent concurrently
+    Hedged { id: u64 },
+
+    /// Requests switched to another base URL, after failures or once the primary one recovered
+    EndpointChanged { base_url: String },
+
+    /// The circuit breaker changed state
+    CircuitChanged { state: CircuitState },
+
+    /// The API answered with `429 Too Many Requests`
+    RateLimited { id: u64, retrying: bool },
+
+    /// A request finished, `status` being `None` if no response was received, with the outcome of
+    /// each of its attempts
+    RequestFinished {
+        id: u64,
+        status: Option<u16>,
+        duration: Duration,
+        retries: u32,
+        base_url: Option<String>,
+        error: Option<String>,
+        attempts: Vec<Attempt>,
+    },
+
+    /// A chunk of a streamed response arrived
+    StreamChunk { metrics: ChunkMetrics },
+}
+
+/// Identifies a subscription, to cancel it with [`OpenAI::unsubscribe`][crate::openai::OpenAI::unsubscribe].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Handler = Arc<dyn Fn(&ClientEvent) + Send + Sync>;
+
+/// Registered event handlers of a client.
+#[derive(Default)]
+pub(crate) struct EventBus {
+    handlers: RwLock<Vec<(SubscriptionId, Handler)>>,
+    next_subscription: AtomicU64,
+    next_request: AtomicU64,
+}
+
+impl EventBus {
+    pub(crate) fn subscribe(&self, handler: Handler) -> SubscriptionId {
+        let id = SubscriptionId(self.next_subscription.fetch_add(1, Ordering::Relaxed));
+        self.handlers.write().unwrap().push((id, handler));
+
+        id
+    }
+
+    pub(crate) fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut handlers = self.handlers.write().unwrap();
+        let count = handlers.len();
+        handlers.retain(|(subscription, _)| *subscription != id);
+
+        handlers.len() != count
+    }
+
+    pub(crate) fn has_subscribers(&self) -> bool {
+        !self.handlers.read().unwrap().is_empty()
+    }
+
+    /// A new request ID.
+    pub(crate) fn next_request_id(&self) -> u64 {
+        self.next_request.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) fn publish(&self, event: &ClientEvent) {
+        // Clone the handlers so they can subscribe or unsubscribe without deadlocking.
+        let handlers = self
+            .handlers
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, handler)| Arc::clone(handler))
+            .collect::<Vec<_>>();
+
+        for handler in handlers {
+            handler(event);
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use crate::{
+    events::{ClientEvent, EventBus},
+    runtime::{self, Instant},
+    transport::HttpTransport,
+};
+use reqwest::{Client, Request, Url};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Failover of requests from the client's base URL to fallback base URLs (other gateways or
+/// regions) when it keeps failing.
+///
+/// After `failure_threshold` consecutive failures (connection errors or `5xx` responses) on the
+/// active base URL, requests switch to the next one in order. While a fallback is active, the
+/// primary base URL is probed every `health_check_interval_secs` seconds and requests fail back to
+/// it as soon as it answers successfully.
+///
+/// With `periodic_health_checks`, every base URL (e.g. one per region) is instead probed in the
+/// background every `health_check_interval_secs` seconds: requests go to the most preferred
+/// healthy one, and failovers skip the endpoints known to be unhealthy.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FailoverPolicy {
+    /// Base URLs to fail over to, in order of preference
+    pub fallback_urls: Vec<String>,
+
+    /// Consecutive failures after which the next base URL is used
+    pub failure_threshold: u32,
+
+    /// Path (relative to the base URL) requested to check whether the primary base URL recovered
+    pub health_check_path: String,
+
+    /// Minimum time between two health checks of the primary base URL, in seconds
+    pub health_check_interval_secs: u64,
+
+    /// Probe every base URL periodically instead of only the primary one while failed over
+    pub periodic_health_checks: bool,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            fallback_urls: Vec::new(),
+            failure_threshold: 3,
+            health_check_path: "/models".to_string(),
+            health_check_interval_secs: 30,
+            periodic_health_checks: false,
+        }
+    }
+}
+
+impl FailoverPolicy {
+    /// Create a new [`FailoverPolicy`] failing over to `fallback_urls` with the default settings.
+    pub fn new<I>(fallback_urls: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            fallback_urls: fallback_urls.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Health of a base URL of a [`FailoverPolicy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EndpointHealth {
+    /// The base URL
+    pub base_url: String,
+
+    /// Whether the last health check (or the failures of the requests) left it healthy
+    pub healthy: bool,
+
+    /// Whether requests are currently sent to it
+    pub active: bool,
+}
+
+/// Shared failover state of a client and its clones.
+pub(crate) struct Failover {
+    policy: FailoverPolicy,
+    endpoints: Vec<String>,
+    healthy: Vec<AtomicBool>,
+    active: AtomicUsize,
+    failures: AtomicU32,
+    checking: AtomicBool,
+    monitoring: AtomicBool,
+    last_check: Mutex<Option<Instant>>,
+}
+
+impl Failover {
+    /// Fail over from `primary` according to `policy`.
+    pub(crate) fn new(primary: &str, policy: FailoverPolicy) -> Self {
+        let endpoints: Vec<String> = std::iter::once(primary)
+            .chain(policy.fallback_urls.iter().map(String::as_str))
+            .map(|url| url.trim_end_matches('/').to_string())
+            .collect();
+
+        Self {
+            policy,
+            healthy: endpoints.iter().map(|_| AtomicBool::new(true)).collect(),
+            endpoints,
+            active: AtomicUsize::new(0),
+            failures: AtomicU32::new(0),
+            checking: AtomicBool::new(false),
+            monitoring: AtomicBool::new(false),
+            last_check: Mutex::new(None),
+        }
+    }
+
+    pub(crate) const fn policy(&self) -> &FailoverPolicy {
+        &self.policy
+    }
+
+    /// Number of base URLs, the primary one included.
+    pub(crate) fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// The base URL requests are currently sent to.
+    pub(crate) fn active_url(&self) -> &str {
+        &self.endpoints[self.active.load(Ordering::Relaxed)]
+    }
+
+    /// The base URL at `index`.
+    pub(crate) fn url(&self, index: usize) -> &str {
+        &self.endpoints[index]
+    }
+
+    /// Health of every base URL, in order of preference.
+    pub(crate) fn health(&self) -> Vec<EndpointHealth> {
+        let active = self.active.load(Ordering::Relaxed);
+
+        self.endpoints
+            .iter()
+            .zip(&self.healthy)
+            .enumerate()
+            .map(|(index, (base_url, healthy))| EndpointHealth {
+                base_url: base_url.clone(),
+                healthy: healthy.load(Ordering::Relaxed),
+                active: index == active,
+            })
+            .collect()
+    }
+
+    /// Point `request` at the active base URL, returning its index.
+    pub(crate) fn rebase(&self, request: &mut Request) -> usize {
+        let active = self.active.load(Ordering::Relaxed);
+        let url = request.url().as_str();
+
+        let rebased = self
+            .endpoints
+            .iter()
+            .find_map(|endpoint| url.strip_prefix(endpoint.as_str()))
+            .and_then(|path| Url::parse(&format!("{}{path}", self.endpoints[active])).ok());
+        if let Some(rebased) = rebased {
+            *request.url_mut() = rebased;
+        }
+
+        active
+    }
+
+    /// Record the outcome of a request sent to the base URL at `index`, returning whether it made
+    /// requests switch to another base URL.
+    pub(crate) fn record(&self, index: usize, success: bool, events: &EventBus) -> bool {
+        if self.endpoints.len() < 2 || self.active.load(Ordering::Relaxed) != index {
+            return false;
+        }
+        if success {
+            self.failures.store(0, Ordering::Relaxed);
+            return false;
+        }
+
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures < self.policy.failure_threshold.max(1) {
+            return false;
+        }
+
+        let next = if self.policy.periodic_health_checks {
+            self.healthy[index].store(false, Ordering::Relaxed);
+            self.next_healthy(index)
+        } else {
+            (index + 1) % self.endpoints.len()
+        };
+        let switched = self
+            .active
+            .compare_exchange(index, next, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok();
+
+        if switched {
+            self.failures.store(0, Ordering::Relaxed);
+            events.publish(&ClientEvent::EndpointChanged {
+                base_url: self.endpoints[next].clone(),
+            });
+        }
+
+        switched
+    }
+
+    /// The first healthy base URL after the one at `index`, or simply the next one if none is.
+    fn next_healthy(&self, index: usize) -> usize {
+        let count = self.endpoints.len();
+
+        (1..count)
+            .map(|offset| (index + offset) % count)
+            .find(|&next| self.healthy[next].load(Ordering::Relaxed))
+            .unwrap_or((index + 1) % count)
+    }
+
+    /// Check the health of the base URLs according to the policy: start the periodic checks of
+    /// every base URL, or probe the primary one while failed over.
+    pub(crate) fn check_health(
+        self: &Arc<Self>,
+        client: &Client,
+        transport: &Arc<dyn HttpTransport>,
+        headers: Vec<(String, String)>,
+        events: &Arc<EventBus>,
+    ) {
+        if self.policy.periodic_health_checks {
+            self.start_monitor(client, transport, headers, events);
+        } else {
+            self.check_primary(client, transport, headers, events);
+        }
+    }
+
+    /// Probe every base URL in the background every `health_check_interval_secs` seconds, until
+    /// the client and its clones are dropped, sending requests to the most preferred healthy one.
+    fn start_monitor(
+        self: &Arc<Self>,
+        client: &Client,
+        transport: &Arc<dyn HttpTransport>,
+        headers: Vec<(String, String)>,
+        events: &Arc<EventBus>,
+    ) {
+        if self.endpoints.len() < 2 || self.monitoring.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let failover = Arc::downgrade(self);
+        let client = client.clone();
+        let transport = Arc::clone(transport);
+        let events = Arc::clone(events);
+        let interval = Duration::from_secs(self.policy.health_check_interval_secs.max(1));
+
+        runtime::spawn(async move {
+            loop {
+                let Some(failover) = failover.upgrade() else {
+                    return;
+                };
+
+                let probes = failover.endpoints.iter().map(|endpoint| {
+                    probe(&client, &transport, &headers, endpoint, &failover.policy)
+                });
+                let results = futures::future::join_all(probes).await;
+
+                for (healthy, result) in failover.healthy.iter().zip(results) {
+                    healthy.store(result, Ordering::Relaxed);
+                }
+
+                let active = failover.active.load(Ordering::Relaxed);
+                let preferred = failover
+                    .healthy
+                    .iter()
+                    .position(|healthy| healthy.load(Ordering::Relaxed));
+
+                if let Some(preferred) = preferred.filter(|&preferred| preferred != active) {
+                    if failover
+                        .active
+                        .compare_exchange(active, preferred, Ordering::Relaxed, Ordering::Relaxed)
+                        .is_ok()
+                    {
+                        failover.failures.store(0, Ordering::Relaxed);
+                        events.publish(&ClientEvent::EndpointChanged {
+                            base_url: failover.endpoints[preferred].clone(),
+                        });
+                    }
+                }
+
+                drop(failover);
+                runtime::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Probe the primary base URL in the background if a fallback is active and the last check is
+    /// old enough, failing back to it if it answers successfully.
+    fn check_primary(
+        self: &Arc<Self>,
+        client: &Client,
+        transport: &Arc<dyn HttpTransport>,
+        headers: Vec<(String, String)>,
+        events: &Arc<EventBus>,
+    ) {
+        if self.active.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let interval = Duration::from_secs(self.policy.health_check_interval_secs);
+        {
+            let mut last_check = self.last_check.lock().unwrap();
+            if last_check.is_some_and(|last_check| last_check.elapsed() < interval)
+                || self.checking.swap(true, Ordering::Relaxed)
+            {
+                return;
+            }
+            *last_check = Some(Instant::now());
+        }
+
+        let failover = Arc::clone(self);
+        let events = Arc::clone(events);
+        let client = client.clone();
+        let transport = Arc::clone(transport);
+
+        runtime::spawn(async move {
+            let healthy = probe(
+                &client,
+                &transport,
+                &headers,
+                &failover.endpoints[0],
+                &failover.policy,
+            )
+            .await;
+
+            if healthy && failover.active.swap(0, Ordering::Relaxed) != 0 {
+                failover.failures.store(0, Ordering::Relaxed);
+                events.publish(&ClientEvent::EndpointChanged {
+                    base_url: failover.endpoints[0].clone(),
+                });
+            }
+            failover.checking.store(false, Ordering::Relaxed);
+        });
+    }
+}
+
+/// Whether the health check path of `endpoint` answers successfully.
+async fn probe(
+    client: &Client,
+    transport: &Arc<dyn HttpTransport>,
+    headers: &[(String, String)],
+    endpoint: &str,
+    policy: &FailoverPolicy,
+) -> bool {
+    let request = headers.iter().fold(
+        client.get(format!("{endpoint}{}", policy.health_check_path)),
+        |request, (name, value)| request.header(name, value),
+    );
+
+    match request.build() {
+        Ok(request) => transport
+            .send(request)
+            .await
+            .is_ok_and(|response| response.status().is_success()),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_rebased_on_the_active_base_url() {
+        let failover = Failover::new(
+            "https://primary.test/v1",
+            FailoverPolicy {
+                failure_threshold: 1,
+                ..FailoverPolicy::new(["https://fallback.test/v1/"])
+            },
+        );
+        let events = EventBus::default();
+        let client = Client::new();
+        let mut request = client
+            .get("https://primary.test/v1/files?purpose=batch")
+            .build()
+            .unwrap();
+
+        assert_eq!(failover.rebase(&mut request), 0);
+        assert_eq!(
+            request.url().as_str(),
+            "https://primary.test/v1/files?purpose=batch"
+        );
+
+        assert!(failover.record(0, false, &events));
+        assert_eq!(failover.rebase(&mut request), 1);
+        assert_eq!(
+            request.url().as_str(),
+            "https://fallback.test/v1/files?purpose=batch"
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    mod client {
+        use super::*;
+        use crate::{
+            error_handling::OpenAIResult,
+            openai::OpenAI,
+            retry::RetryPolicy,
+            test_util::{
+                fixtures,
+                mock_transport::{MockResponse, MockTransport},
+            },
+        };
+        use reqwest::Method;
+        use serde_json::Value;
+
+        const PRIMARY: &str = "http://mock.openai.invalid/primary";
+        const FALLBACK: &str = "http://mock.openai.invalid/fallback";
+
+        fn client(mock: &Arc<MockTransport>, policy: FailoverPolicy) -> OpenAI {
+            let mut openai = mock.client();
+            openai.set_base_url(PRIMARY);
+            openai.set_retry_policy(RetryPolicy::new(0));
+            openai.set_failover_policy(Some(policy));
+            openai
+        }
+
+        fn embeddings() -> MockResponse {
+            MockResponse::json(&fixtures::embeddings("text-embedding-3-small", &["a"], 3))
+        }
+
+        async fn embed(openai: &OpenAI) -> OpenAIResult<Value> {
+            openai
+                .embeddings()
+                .create("a", Some("text-embedding-3-small".into()), None, None, None)
+                .await
+        }
+
+        /// URLs of the API requests received by `mock`, without the health checks.
+        fn posted(mock: &MockTransport) -> Vec<String> {
+            mock.requests()
+                .into_iter()
+                .filter(|request| request.method == Method::POST)
+                .map(|request| request.url.to_string())
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn fails_over_after_consecutive_failures() {
+            let mock = Arc::new(MockTransport::new());
+            mock.once(
+                Method::POST,
+                "primary/embeddings",
+                MockResponse::error(500, "down"),
+            )
+            .once(
+                Method::POST,
+                "primary/embeddings",
+                MockResponse::error(502, "down"),
+            )
+            .on(Method::POST, "fallback/embeddings", embeddings());
+            let openai = client(
+                &mock,
+                FailoverPolicy {
+                    failure_threshold: 2,
+                    health_check_interval_secs: 3_600,
+                    ..FailoverPolicy::new([FALLBACK])
+                },
+            );
+
+            assert!(embed(&openai).await.is_err());
+            assert_eq!(openai.active_base_url(), PRIMARY);
+            assert!(embed(&openai).await.is_err());
+            assert_eq!(openai.active_base_url(), FALLBACK);
+
+            let response = openai.with_meta(embed(&openai)).await.unwrap();
+            assert_eq!(response.meta.base_url.as_deref(), Some(FALLBACK));
+            assert_eq!(
+                posted(&mock),
+                [
+                    format!("{PRIMARY}/embeddings"),
+                    format!("{PRIMARY}/embeddings"),
+                    format!("{FALLBACK}/embeddings"),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn fails_back_once_the_health_check_succeeds() {
+            let mock = Arc::new(MockTransport::new());
+            mock.once(
+                Method::POST,
+                "primary/embeddings",
+                MockResponse::error(500, "down"),
+            )
+            .on(Method::POST, "primary/embeddings", embeddings())
+            .on(Method::POST, "fallback/embeddings", embeddings())
+            .once(
+                Method::GET,
+                "primary/models",
+                MockResponse::error(503, "down"),
+            )
+            .on(
+                Method::GET,
+                "primary/models",
+                MockResponse::json(&fixtures::list(Vec::new())),
+            );
+            let openai = client(
+                &mock,
+                FailoverPolicy {
+                    failure_threshold: 1,
+                    health_check_interval_secs: 0,
+                    ..FailoverPolicy::new([FALLBACK])
+                },
+            );
+
+            // The health check run after the failover still fails.
+            assert!(embed(&openai).await.is_err());
+            crate::runtime::sleep(Duration::from_millis(50)).await;
+            assert_eq!(openai.active_base_url(), FALLBACK);
+
+            // The one run after the next request succeeds.
+            assert!(embed(&openai).await.is_ok());
+            crate::runtime::sleep(Duration::from_millis(50)).await;
+            assert_eq!(openai.active_base_url(), PRIMARY);
+
+            assert!(embed(&openai).await.is_ok());
+            assert_eq!(
+                posted(&mock),
+                [
+                    format!("{PRIMARY}/embeddings"),
+                    format!("{FALLBACK}/embeddings"),
+                    format!("{PRIMARY}/embeddings"),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn multipart_requests_are_rebased() {
+            let mock = Arc::new(MockTransport::new());
+            mock.once(
+                Method::POST,
+                "primary/embeddings",
+                MockResponse::error(500, "down"),
+            )
+            .on(
+                Method::POST,
+                "fallback/images/edits",
+                MockResponse::json(&serde_json::json!({ "created": 0, "data": [] })),
+            );
+            let openai = client(
+                &mock,
+                FailoverPolicy {
+                    failure_threshold: 1,
+                    health_check_interval_secs: 3_600,
+                    ..FailoverPolicy::new([FALLBACK])
+                },
+            );
+
+            assert!(embed(&openai).await.is_err());
+            openai
+                .images()
+                .edit_bytes(
+                    None,
+                    b"png".to_vec(),
+                    None,
+                    "Add a hat",
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+
+            let request = mock.last_request().unwrap();
+            assert_eq!(request.url.as_str(), format!("{FALLBACK}/images/edits"));
+            let content_type = request.headers["content-type"].to_str().unwrap();
+            assert!(content_type.starts_with("multipart/form-data; boundary="));
+            let body = String::from_utf8_lossy(&request.body);
+            assert!(body.contains("name=\"prompt\"\r\n\r\nAdd a hat\r\n"));
+            assert!(body.contains("\r\n\r\npng\r\n"));
+        }
+    }
+}
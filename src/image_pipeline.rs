@@ -0,0 +1,427 @@
+use crate::{
+    config::optional_model,
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    util::decode_base64,
+};
+use serde_json::Value;
+use std::{path::PathBuf, sync::Arc};
+
+/// Hook rewriting a prompt rejected by the content policy, given the prompt and the rejection
+/// message, or giving up with `None`.
+pub type PromptAdjuster = Arc<dyn Fn(&str, &str) -> Option<String> + Send + Sync>;
+
+enum Step {
+    Generate(String),
+    Edit {
+        prompt: String,
+        mask: Option<Vec<u8>>,
+    },
+    Variation,
+    #[cfg(feature = "fs")]
+    Save(PathBuf),
+}
+
+/// Result of an [`ImagePipeline`] run.
+#[derive(Clone, Debug)]
+pub struct ImageOutput {
+    /// PNG bytes of the last image
+    pub image: Vec<u8>,
+
+    /// Files written by the save steps, in order
+    pub saved: Vec<PathBuf>,
+}
+
+/// Fluent pipeline over [`ImagesApi`][crate::openai_api::images::ImagesApi] chaining a generation
+/// with edits, variations and saves, each step working on the image produced by the previous one.
+///
+/// Images are passed between steps as bytes, without temporary files. Steps whose prompt is
+/// rejected by the content policy are retried with the prompt rewritten by the
+/// [`ImagePipeline::on_rejection`] hook, if any.
+pub struct ImagePipeline {
+    openai: OpenAI,
+    model: Option<Model>,
+    size: Option<String>,
+    image: Option<Vec<u8>>,
+    steps: Vec<Step>,
+    max_retries: u32,
+    adjuster: Option<PromptAdjuster>,
+}
+
+impl ImagePipeline {
+    /// Create an empty pipeline using the client's default images model.
+    pub fn new(openai: OpenAI) -> Self {
+        Self {
+            openai,
+            model: None,
+            size: None,
+            image: None,
+            steps: Vec::new(),
+            max_retries: 2,
+            adjuster: None,
+        }
+    }
+
+    /// Use `model` instead of the client's default images model.
+    pub fn model(mut self, model: impl Into<Model>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Produce images of `size`, e.g. `1024x1024`.
+    pub fn size(mut self, size: impl Into<String>) -> Self {
+        self.size = Some(size.into());
+        self
+    }
+
+    /// Start from the PNG bytes of an existing image instead of a generation.
+    pub fn start_from(mut self, image: Vec<u8>) -> Self {
+        self.image = Some(image);
+        self
+    }
+
+    /// Generate an image from `prompt`, replacing the current image.
+    pub fn generate(mut self, prompt: impl Into<String>) -> Self {
+        self.steps.push(Step::Generate(prompt.into()));
+        self
+    }
+
+    /// Edit the current image according to `prompt`.
+    pub fn edit(mut self, prompt: impl Into<String>) -> Self {
+        self.steps.push(Step::Edit {
+            prompt: prompt.into(),
+            mask: None,
+        });
+        self
+    }
+
+    /// Edit the areas of the current image left transparent by the PNG `mask`.
+    pub fn edit_with_mask(mut self, prompt: impl Into<String>, mask: Vec<u8>) -> Self {
+        self.steps.push(Step::Edit {
+            prompt: prompt.into(),
+            mask: Some(mask),
+        });
+        self
+    }
+
+    /// Replace the current image with a variation of it.
+    pub fn variation(mut self) -> Self {
+        self.steps.push(Step::Variation);
+        self
+    }
+
+    /// Write the current image to `path`.
+    #[cfg(feature = "fs")]
+    pub fn save(mut self, path: impl Into<PathBuf>) -> Self {
+        self.steps.push(Step::Save(path.into()));
+        self
+    }
+
+    /// Retry a step rejected by the content policy up to `max_retries` times.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Rewrite rejected prompts with `adjuster` before retrying, e.g. to soften or rephrase them.
+    ///
+    /// Without a hook, rejected steps fail immediately.
+    pub fn on_rejection(
+        mut self,
+        adjuster: impl Fn(&str, &str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.adjuster = Some(Arc::new(adjuster));
+        self
+    }
+
+    /// Run every step in order.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`ImageOutput`] on success, or an [`OpenAIError`] on failure,
+    /// [`OpenAIError::Refusal`] if a prompt was rejected by the content policy and not rewritten,
+    /// or [`OpenAIError::ConfigError`] if the pipeline has no steps.
+    pub async fn run(self) -> OpenAIResult<ImageOutput> {
+        if self.steps.is_empty() {
+            return Err(OpenAIError::ConfigError(
+                "image pipeline has no steps".to_string(),
+            ));
+        }
+
+        let mut image = self.image.clone();
+        #[cfg_attr(not(feature = "fs"), allow(unused_mut))]
+        let mut saved = Vec::new();
+
+        for step in &self.steps {
+            match step {
+                Step::Generate(prompt) => {
+                    image = Some(self.prompted(prompt, None, None).await?);
+                },
+                Step::Edit { prompt, mask } => {
+                    let current = self.current(&image)?;
+                    image = Some(self.prompted(prompt, Some(current), mask.clone()).await?);
+                },
+                Step::Variation => {
+                    let current = self.current(&image)?;
+                    let response = self
+                        .openai
+                        .images()
+                        .variation_bytes(
+                            self.model.clone(),
+                            current,
+                            self.size.as_deref(),
+                            self.response_format(),
+                            None,
+                            None,
+                        )
+                        .await
+                        .map_err(refusal)?;
+                    image = Some(decode_image(response)?);
+                },
+                #[cfg(feature = "fs")]
+                Step::Save(path) => {
+                    let current = self.current(&image)?;
+                    if let Some(parent) = path.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    tokio::fs::write(path, current).await?;
+                    saved.push(path.clone());
+                },
+            }
+        }
+
+        Ok(ImageOutput {
+            image: image.unwrap_or_default(),
+            saved,
+        })
+    }
+
+    fn current(&self, image: &Option<Vec<u8>>) -> OpenAIResult<Vec<u8>> {
+        image.clone().ok_or_else(|| {
+            OpenAIError::ConfigError("image step without a previous image".to_string())
+        })
+    }
+
+    /// `b64_json`, except for GPT image models, which always return base64 and reject the
+    /// parameter.
+    fn response_format(&self) -> Option<&'static str> {
+        let model = optional_model(self.model.as_ref(), &self.openai.default_models().images);
+
+        match model {
+            Some(model) if model.starts_with("gpt-image") => None,
+            _ => Some("b64_json"),
+        }
+    }
+
+    /// Generate (without `image`) or edit an image, retrying content policy rejections with the
+    /// adjusted prompt.
+    async fn prompted(
+        &self,
+        prompt: &str,
+        image: Option<Vec<u8>>,
+        mask: Option<Vec<u8>>,
+    ) -> OpenAIResult<Vec<u8>> {
+        let images = self.openai.images();
+        let mut prompt = prompt.to_string();
+        let mut attempt = 0;
+
+        loop {
+            let response = match &image {
+                None => {
+                    images
+                        .generate(
+                            &prompt,
+                            self.model.clone(),
+                            self.size.as_deref(),
+                            self.response_format(),
+                            None,
+                            None,
+                        )
+                        .await
+                },
+                Some(image) => {
+                    images
+                        .edit_bytes(
+                            self.model.clone(),
+                            image.clone(),
+                            mask.clone(),
+                            &prompt,
+                            self.size.as_deref(),
+                            self.response_format(),
+                            None,
+                            None,
+                        )
+                        .await
+                },
+            };
+
+            match response.map_err(refusal).and_then(decode_image) {
+                Err(OpenAIError::Refusal(message)) if attempt < self.max_retries => {
+                    let adjusted = self
+                        .adjuster
+                        .as_ref()
+                        .and_then(|adjuster| adjuster(&prompt, &message));
+
+                    match adjusted {
+                        Some(adjusted) => prompt = adjusted,
+                        None => return Err(OpenAIError::Refusal(message)),
+                    }
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Content policy rejections reported as API errors are [`OpenAIError::Refusal`]s.
+fn refusal(error: OpenAIError) -> OpenAIError {
+    match error {
+        OpenAIError::ApiError {
+            code: Some(code),
+            message,
+            ..
+        } if matches!(
+            code.as_str(),
+            "content_policy_violation" | "moderation_blocked"
+        ) =>
+        {
+            OpenAIError::Refusal(message)
+        },
+        error => error,
+    }
+}
+
+/// The bytes of the first image of a response.
+fn decode_image(response: Value) -> OpenAIResult<Vec<u8>> {
+    let encoded = response["data"][0]["b64_json"].as_str().ok_or_else(|| {
+        OpenAIError::UnexpectedResponse("missing b64_json image in response".to_string())
+    })?;
+
+    decode_base64(encoded).map_err(|error| OpenAIError::UnexpectedResponse(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pipelines_without_steps_fail() {
+        let openai = OpenAI::new("sk-test", "https://api.openai.com/v1");
+
+        let result = ImagePipeline::new(openai)
+            .start_from(b"png".to_vec())
+            .run()
+            .await;
+        assert!(
+            matches!(result, Err(OpenAIError::ConfigError(message)) if message == "image pipeline has no steps")
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    mod rejections {
+        use super::*;
+        use crate::test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+        use serde_json::json;
+        use std::sync::Mutex;
+
+        fn rejected() -> MockResponse {
+            MockResponse::json(&fixtures::error(
+                "Your request was rejected by the safety system.",
+                "invalid_request_error",
+                Some("content_policy_violation"),
+            ))
+            .status(400)
+        }
+
+        fn image() -> MockResponse {
+            // "png" in base64
+            MockResponse::json(&json!({ "created": 0, "data": [{ "b64_json": "cG5n" }] }))
+        }
+
+        fn prompts(mock: &MockTransport) -> Vec<String> {
+            mock.requests()
+                .iter()
+                .map(|request| {
+                    request.json().unwrap()["prompt"]
+                        .as_str()
+                        .unwrap()
+                        .to_string()
+                })
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn rejected_prompts_are_refusals_without_an_adjuster() {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(Method::POST, "/images/generations", rejected());
+
+            let result = ImagePipeline::new(mock.client())
+                .model("dall-e-3")
+                .generate("A fight")
+                .run()
+                .await;
+
+            assert!(matches!(
+                result,
+                Err(OpenAIError::Refusal(message)) if message.contains("safety system")
+            ));
+            assert_eq!(prompts(&mock), ["A fight"]);
+        }
+
+        #[tokio::test]
+        async fn rejected_prompts_are_retried_adjusted() {
+            let mock = Arc::new(MockTransport::new());
+            mock.once(Method::POST, "/images/generations", rejected())
+                .on(Method::POST, "/images/generations", image());
+            let rejections = Arc::new(Mutex::new(Vec::new()));
+
+            let output = ImagePipeline::new(mock.client())
+                .model("dall-e-3")
+                .generate("A fight")
+                .on_rejection({
+                    let rejections = Arc::clone(&rejections);
+                    move |prompt, message| {
+                        rejections.lock().unwrap().push(message.to_string());
+                        Some(format!("{prompt}, friendly"))
+                    }
+                })
+                .run()
+                .await
+                .unwrap();
+
+            assert_eq!(output.image, b"png");
+            assert_eq!(prompts(&mock), ["A fight", "A fight, friendly"]);
+            assert_eq!(
+                *rejections.lock().unwrap(),
+                ["Your request was rejected by the safety system."]
+            );
+            assert_eq!(
+                mock.last_request().unwrap().json().unwrap()["response_format"],
+                "b64_json"
+            );
+        }
+
+        #[tokio::test]
+        async fn adjusted_prompts_are_retried_at_most_max_retries_times() {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(Method::POST, "/images/generations", rejected());
+
+            let result = ImagePipeline::new(mock.client())
+                .model("dall-e-3")
+                .generate("A fight")
+                .max_retries(2)
+                .on_rejection(|prompt, _| Some(format!("{prompt}!")))
+                .run()
+                .await;
+
+            assert!(matches!(result, Err(OpenAIError::Refusal(_))));
+            assert_eq!(prompts(&mock), ["A fight", "A fight!", "A fight!!"]);
+        }
+    }
+}
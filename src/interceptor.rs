@@ -0,0 +1,21 @@
+use reqwest::{Request, Response};
+
+/// Hook on the requests and responses of a client, e.g. to add custom headers, write an audit log
+/// or count the calls of each tenant, installed with
+/// [`OpenAI::add_interceptor`][crate::openai::OpenAI::add_interceptor].
+///
+/// Interceptors run once per call, in the order they were added: [`Interceptor::on_request`] on
+/// the request before its first attempt (so its changes are kept by the retries and signed by the
+/// [`RequestSigner`][crate::signing::RequestSigner]), and [`Interceptor::on_response`] on the
+/// final response, after the retries, before its body is read.
+pub trait Interceptor: Send + Sync {
+    /// Inspect or change `request` before it's sent.
+    fn on_request(&self, request: &mut Request) {
+        let _ = request;
+    }
+
+    /// Inspect `response`, whatever its status.
+    fn on_response(&self, response: &Response) {
+        let _ = response;
+    }
+}
@@ -1,6 +1,54 @@
 #![allow(clippy::too_many_arguments)]
 
+// Lets the code generated by the derive macros, which names the crate, compile in its own tests.
+#[cfg(all(test, feature = "macros"))]
+extern crate self as rusty_openai;
+
+pub mod agent;
+pub mod azure;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+pub mod builder;
+pub mod bulk;
+pub mod cache;
+pub mod capabilities;
+pub mod chunking;
+pub mod circuit_breaker;
+pub(crate) mod coalesce;
+pub mod config;
+pub mod conversation;
+pub mod credentials;
 pub mod error_handling;
+#[cfg(feature = "evals")]
+pub mod evals;
+pub mod events;
+pub mod failover;
+pub mod image_pipeline;
+pub mod interceptor;
+pub mod meta;
+pub mod metrics;
+pub mod model;
 pub mod openai;
 pub mod openai_api;
+pub mod options;
+pub mod prepared;
+pub mod provider;
+pub mod rag;
+pub mod rate_limit;
+pub mod reconcile;
+pub mod retry;
+pub(crate) mod runtime;
+pub mod scheduler;
+pub mod signing;
+pub mod stream;
+pub mod structured;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "tracing")]
+pub(crate) mod trace;
+#[cfg(feature = "fs")]
+pub mod transcription;
+pub mod transport;
 pub(crate) mod util;
+pub mod validation;
+pub mod vector_index;
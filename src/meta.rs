@@ -0,0 +1,92 @@
+use crate::rate_limit::RateLimits;
+use reqwest::{header::HeaderMap, Response, StatusCode};
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Metadata of a response of the API, e.g. to quote its request ID in a support ticket or to
+/// chart the remaining quota.
+#[derive(Clone, Debug, Default)]
+pub struct ResponseMeta {
+    /// Status of the response, `None` if the call was answered without a request (e.g. from the
+    /// response cache)
+    pub status: Option<StatusCode>,
+
+    /// ID of the request (`x-request-id`)
+    pub request_id: Option<String>,
+
+    /// Time the API spent processing the request (`openai-processing-ms`)
+    pub processing_time: Option<Duration>,
+
+    /// Rate limits reported by the `x-ratelimit-*` headers
+    pub rate_limits: Option<RateLimits>,
+
+    /// Every header of the response
+    pub headers: HeaderMap,
+
+    /// Base URL the request was sent to, e.g. to tell which endpoint of a
+    /// [`FailoverPolicy`][crate::failover::FailoverPolicy] answered
+    pub base_url: Option<String>,
+}
+
+impl ResponseMeta {
+    /// Read the metadata of `response` to a request sent to `base_url`.
+    pub(crate) fn from_response(response: &Response, base_url: Option<&str>) -> Self {
+        let headers = response.headers();
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        Self {
+            status: Some(response.status()),
+            request_id: header("x-request-id").map(str::to_string),
+            processing_time: header("openai-processing-ms")
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|millis| millis.is_finite() && *millis >= 0.0)
+                .map(|millis| Duration::from_secs_f64(millis / 1000.0)),
+            rate_limits: RateLimits::from_headers(headers),
+            headers: headers.clone(),
+            base_url: base_url.map(str::to_string),
+        }
+    }
+}
+
+/// Result of a call along with the metadata of its response, see
+/// [`OpenAI::with_meta`][crate::openai::OpenAI::with_meta].
+#[derive(Clone, Debug)]
+pub struct WithMeta<T> {
+    /// Result of the call, e.g. the parsed body of the response
+    pub data: T,
+
+    /// Metadata of the last response received by the call
+    pub meta: ResponseMeta,
+}
+
+impl<T> WithMeta<T> {
+    /// The result of the call, without the metadata.
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+}
+
+impl<T> Deref for WithMeta<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+tokio::task_local! {
+    /// Set while the metadata of the responses of a call are recorded (see
+    /// [`OpenAI::with_meta`][crate::openai::OpenAI::with_meta]).
+    pub(crate) static META: Arc<Mutex<Option<ResponseMeta>>>;
+}
+
+/// Keep the metadata of `response` to a request sent to `base_url` if the current call records
+/// them.
+pub(crate) fn record(response: &Response, base_url: Option<&str>) {
+    let _ = META.try_with(|meta| {
+        *meta.lock().unwrap() = Some(ResponseMeta::from_response(response, base_url));
+    });
+}
@@ -0,0 +1,187 @@
+use crate::{openai_api::types::Usage, retry::Attempt};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+/// Measurements of a single API call, passed to a [`MetricsSink`].
+#[derive(Clone, Debug)]
+pub struct RequestMetrics {
+    /// HTTP method of the request
+    pub method: String,
+
+    /// Endpoint template of the request, with IDs replaced by `{id}` (e.g. `/threads/{id}/runs`)
+    pub endpoint: String,
+
+    /// Model named in the request body, if any
+    pub model: Option<String>,
+
+    /// HTTP status of the final response, or `None` if no response was received
+    pub status: Option<u16>,
+
+    /// Total duration of the call, retries and backoff included
+    pub duration: Duration,
+
+    /// Number of retries made after the initial attempt
+    pub retries: u32,
+
+    /// Base URL that served the request, which differs from the client's while failed over
+    pub base_url: Option<String>,
+
+    /// Outcome of each attempt, the initial one first
+    pub attempts: Vec<Attempt>,
+
+    /// Token usage reported in the response, if any
+    pub usage: Option<Usage>,
+}
+
+/// Receives the [`RequestMetrics`] of every API call made by a client.
+pub trait MetricsSink: Send + Sync {
+    /// Record the metrics of a finished call.
+    fn record(&self, metrics: &RequestMetrics);
+}
+
+impl<F: Fn(&RequestMetrics) + Send + Sync> MetricsSink for F {
+    fn record(&self, metrics: &RequestMetrics) {
+        self(metrics)
+    }
+}
+
+#[derive(Default)]
+struct Series {
+    requests: u64,
+    duration_seconds: f64,
+    retries: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+/// [`MetricsSink`] aggregating metrics in memory and rendering them in the Prometheus text
+/// exposition format, e.g. from a `/metrics` handler.
+#[derive(Default)]
+pub struct PrometheusMetrics {
+    series: Mutex<BTreeMap<(String, String, String, String), Series>>,
+}
+
+impl PrometheusMetrics {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render the aggregated metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let series = self.series.lock().unwrap();
+        let mut output = String::new();
+
+        let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&Series) -> String| {
+            let _ = writeln!(output, "# HELP {name} {help}");
+            let _ = writeln!(output, "# TYPE {name} {kind}");
+
+            for ((method, endpoint, model, status), series) in series.iter() {
+                let _ = writeln!(
+                    output,
+                    "{name}{{method=\"{method}\",endpoint=\"{endpoint}\",model=\"{model}\",status=\"{status}\"}} {}",
+                    value(series)
+                );
+            }
+        };
+
+        family(
+            "openai_requests_total",
+            "counter",
+            "Number of OpenAI API calls.",
+            &|series| series.requests.to_string(),
+        );
+        family(
+            "openai_request_duration_seconds_total",
+            "counter",
+            "Total duration of OpenAI API calls in seconds.",
+            &|series| series.duration_seconds.to_string(),
+        );
+        family(
+            "openai_retries_total",
+            "counter",
+            "Number of retried OpenAI API attempts.",
+            &|series| series.retries.to_string(),
+        );
+        family(
+            "openai_prompt_tokens_total",
+            "counter",
+            "Number of prompt tokens used.",
+            &|series| series.prompt_tokens.to_string(),
+        );
+        family(
+            "openai_completion_tokens_total",
+            "counter",
+            "Number of completion tokens used.",
+            &|series| series.completion_tokens.to_string(),
+        );
+
+        output
+    }
+}
+
+impl MetricsSink for PrometheusMetrics {
+    fn record(&self, metrics: &RequestMetrics) {
+        let key = (
+            metrics.method.clone(),
+            metrics.endpoint.clone(),
+            metrics.model.clone().unwrap_or_default(),
+            metrics
+                .status
+                .map_or_else(|| "error".to_string(), |status| status.to_string()),
+        );
+
+        let mut series = self.series.lock().unwrap();
+        let series = series.entry(key).or_default();
+        let usage = metrics.usage.clone().unwrap_or_default();
+
+        series.requests += 1;
+        series.duration_seconds += metrics.duration.as_secs_f64();
+        series.retries += metrics.retries as u64;
+        series.prompt_tokens += usage.prompt_tokens;
+        series.completion_tokens += usage.completion_tokens;
+    }
+}
+
+/// Replace the IDs in a request path with `{id}` and drop the query string, so the endpoint can
+/// be used as a low-cardinality label.
+pub fn endpoint_template(path: &str) -> String {
+    const PLAIN_SEGMENTS: [&str; 2] = ["vector_stores", "submit_tool_outputs"];
+
+    path.split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .map(|segment| {
+            let is_id = segment.chars().any(|character| character.is_ascii_digit())
+                || (segment.contains('_') && !PLAIN_SEGMENTS.contains(&segment));
+
+            if is_id {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Deserialize)]
+struct ModelField {
+    model: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UsageField {
+    usage: Option<Usage>,
+}
+
+/// The `model` field of a JSON request body.
+pub(crate) fn request_model(body: &[u8]) -> Option<String> {
+    serde_json::from_slice::<ModelField>(body).ok()?.model
+}
+
+/// The `usage` field of a JSON response body.
+pub(crate) fn response_usage(body: &[u8]) -> Option<Usage> {
+    serde_json::from_slice::<UsageField>(body).ok()?.usage
+}
@@ -0,0 +1,176 @@
+use crate::capabilities::{model_info, ModelInfo};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+macro_rules! models {
+    ($(
+        $(#[$variant_attributes:meta])*
+        $variant:ident => $name:literal,
+    )*) => {
+        /// Identifier of a model, with variants for well-known models and
+        /// [`Model::Custom`] for everything else (fine-tuned models, new releases, ...).
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum Model {
+            $(
+                $(#[$variant_attributes])*
+                $variant,
+            )*
+
+            /// Any other model name
+            Custom(String),
+        }
+
+        impl Model {
+            /// The model name as sent to the API.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $name,)*
+                    Self::Custom(name) => name,
+                }
+            }
+        }
+
+        impl From<&str> for Model {
+            fn from(name: &str) -> Self {
+                match name {
+                    $($name => Self::$variant,)*
+                    _ => Self::Custom(name.to_string()),
+                }
+            }
+        }
+    };
+}
+
+models! {
+    /// `gpt-4o`
+    Gpt4o => "gpt-4o",
+
+    /// `gpt-4o-mini`
+    Gpt4oMini => "gpt-4o-mini",
+
+    /// `gpt-4.1`
+    Gpt41 => "gpt-4.1",
+
+    /// `gpt-4.1-mini`
+    Gpt41Mini => "gpt-4.1-mini",
+
+    /// `gpt-4.1-nano`
+    Gpt41Nano => "gpt-4.1-nano",
+
+    /// `gpt-4-turbo`
+    Gpt4Turbo => "gpt-4-turbo",
+
+    /// `gpt-4`
+    Gpt4 => "gpt-4",
+
+    /// `gpt-3.5-turbo`
+    Gpt35Turbo => "gpt-3.5-turbo",
+
+    /// `o1`
+    O1 => "o1",
+
+    /// `o1-mini`
+    O1Mini => "o1-mini",
+
+    /// `o3`
+    O3 => "o3",
+
+    /// `o3-mini`
+    O3Mini => "o3-mini",
+
+    /// `o4-mini`
+    O4Mini => "o4-mini",
+
+    /// `text-embedding-3-small`
+    TextEmbedding3Small => "text-embedding-3-small",
+
+    /// `text-embedding-3-large`
+    TextEmbedding3Large => "text-embedding-3-large",
+
+    /// `text-embedding-ada-002`
+    TextEmbeddingAda002 => "text-embedding-ada-002",
+
+    /// `whisper-1`
+    Whisper1 => "whisper-1",
+
+    /// `gpt-4o-transcribe`
+    Gpt4oTranscribe => "gpt-4o-transcribe",
+
+    /// `gpt-4o-mini-transcribe`
+    Gpt4oMiniTranscribe => "gpt-4o-mini-transcribe",
+
+    /// `tts-1`
+    Tts1 => "tts-1",
+
+    /// `tts-1-hd`
+    Tts1Hd => "tts-1-hd",
+
+    /// `dall-e-2`
+    DallE2 => "dall-e-2",
+
+    /// `dall-e-3`
+    DallE3 => "dall-e-3",
+
+    /// `gpt-image-1`
+    GptImage1 => "gpt-image-1",
+
+    /// `omni-moderation-latest`
+    OmniModerationLatest => "omni-moderation-latest",
+
+    /// `text-moderation-latest`
+    TextModerationLatest => "text-moderation-latest",
+}
+
+impl Model {
+    /// Limits and features of the model, see [`model_info`].
+    pub fn info(&self) -> Option<ModelInfo> {
+        model_info(self.as_str())
+    }
+}
+
+impl From<String> for Model {
+    fn from(name: String) -> Self {
+        match Self::from(name.as_str()) {
+            Self::Custom(_) => Self::Custom(name),
+            model => model,
+        }
+    }
+}
+
+impl From<&String> for Model {
+    fn from(name: &String) -> Self {
+        Self::from(name.as_str())
+    }
+}
+
+impl FromStr for Model {
+    type Err = std::convert::Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(name))
+    }
+}
+
+impl AsRef<str> for Model {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Model {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Model {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from(String::deserialize(deserializer)?))
+    }
+}
@@ -1,138 +1,2385 @@
 use crate::{
-    error_handling::OpenAIResult,
+    azure::AzureConfig,
+    builder::OpenAIBuilder,
+    cache::exact::ResponseCache,
+    capabilities::ParameterGating,
+    chunking::{ApproximateTokenCounter, TokenCounter},
+    circuit_breaker::{CircuitBreaker, CircuitBreakerPolicy, CircuitState},
+    coalesce::Coalescer,
+    config::{DefaultModels, OpenAIConfig},
+    credentials::{ApiKeyProvider, StaticApiKey},
+    error_handling::{OpenAIError, OpenAIResult},
+    events::{ClientEvent, EventBus, SubscriptionId},
+    failover::{EndpointHealth, Failover, FailoverPolicy},
+    interceptor::Interceptor,
+    meta::{self, WithMeta, META},
+    metrics::{endpoint_template, request_model, response_usage, MetricsSink, RequestMetrics},
     openai_api::{
-        assistants::AssistantsApi, audio::AudioApi, client::ClientApi, completion::CompletionsApi,
-        embeddings::EmbeddingsApi, fine_tuning::FineTuningApi, images::ImagesApi,
-        moderations::ModerationApi, threads::ThreadsApi, vectors::VectorsApi,
+        assistants::AssistantsApi,
+        client::ClientApi,
+        completion::CompletionsApi,
+        embeddings::EmbeddingsApi,
+        evals::EvalsApi,
+        fine_tuning::FineTuningApi,
+        images::ImagesApi,
+        moderations::ModerationApi,
         projects::ProjectsApi,
+        raw::{self, RawApi, RawResponse},
+        threads::ThreadsApi,
+        vectors::VectorsApi,
     },
+    options::RequestOptions,
+    prepared::{PreparedRequest, CAPTURE},
+    provider::{normalize_local_completion, normalize_local_error, AuthScheme, Provider},
+    rate_limit::{RateLimits, ReportedLimits},
+    retry::{self, Attempt, Deadline, HedgePolicy, RetryBalance, RetryBudget, RetryPolicy},
+    runtime::{self, Instant},
+    scheduler::{Priority, RequestBudget, Scheduler, SchedulingPolicy},
+    signing::{buffer_body, sign, RequestSigner},
+    stream::{
+        ArrayFieldScanner, ChunkMetrics, ChunkTokens, InstrumentedStream, ServerSentEvent,
+        SseParser,
+    },
+    transport::HttpTransport,
+    util::{relative_path, request_key, DEFAULT_STREAM_CHUNK_SIZE},
+    validation::UnknownFields,
+};
+use flate2::{write::GzEncoder, Compression};
+use futures::{Stream, StreamExt};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE},
+    multipart::Form,
+    Client, Method, Request, RequestBuilder, Response, StatusCode,
 };
-use reqwest::{multipart::Form, Client};
 use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const USER_AGENT: &str = concat!("rusty-openai/", env!("CARGO_PKG_VERSION"));
+
+/// Beta feature required by the assistants, threads and vector stores endpoints.
+const ASSISTANTS_BETA: &str = "assistants=v2";
+
+/// Header letting the API recognize the resubmissions of a request, so they don't create duplicates.
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// State of a request across its attempts.
+pub(crate) struct RequestContext {
+    /// ID of the request in [`ClientEvent`]s
+    pub(crate) id: u64,
+
+    /// Number of retries made so far
+    pub(crate) retries: u32,
 
-pub struct OpenAI<'a> {
-    pub(crate) client: Client,
-    authorization: String,
-    base_url: &'a str,
+    /// Base URL the last attempt was sent to
+    pub(crate) base_url: Option<String>,
+
+    /// Outcome of the attempts made so far
+    pub(crate) attempts: Vec<Attempt>,
+
+    /// API key the request is authenticated with
+    pub(crate) api_key: String,
+}
+
+impl RequestContext {
+    /// Keep the outcome of an attempt started at `started`.
+    fn record_attempt(&mut self, started: Instant, result: &OpenAIResult<Response>) {
+        self.attempts.push(Attempt {
+            status: result
+                .as_ref()
+                .ok()
+                .map(|response| response.status().as_u16()),
+            duration: started.elapsed(),
+            base_url: self.base_url.clone(),
+            error: result.as_ref().err().map(ToString::to_string),
+            retry_delay: None,
+        });
+    }
 }
 
-impl<'a> OpenAI<'a> {
-    pub fn new(api_key: &str, base_url: &'a str) -> Self {
-        let default_base_url = "https://api.openai.com/v1";
+/// Details of a request kept while it's observed by events or metrics.
+struct Observation {
+    method: String,
+    path: String,
+    model: Option<String>,
+    started: Instant,
+}
 
-        Self {
-            client: Client::new(),
-            authorization: format!("Bearer {api_key}"),
-            base_url: {
-                if base_url.is_empty() {
-                    default_base_url
-                } else {
-                    base_url
-                }
+/// A response whose body has been read.
+pub(crate) struct ApiResponse {
+    pub(crate) status: StatusCode,
+    pub(crate) request_id: Option<String>,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Vec<u8>,
+}
+
+impl ApiResponse {
+    async fn read(response: Response) -> OpenAIResult<Self> {
+        Ok(Self {
+            status: response.status(),
+            request_id: response
+                .headers()
+                .get("x-request-id")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            headers: response.headers().clone(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+}
+
+/// Client of the OpenAI API.
+///
+/// Cloning is cheap: clones share the same connection pool, event subscriptions, response cache
+/// and in-flight requests, so a client can be cloned into spawned tasks or stored in web framework
+/// state. Changing a setting on a clone only affects that clone.
+///
+/// The client is `Send + Sync + 'static`, and so are the futures of its calls. API handles such as
+/// [`OpenAI::completions`] hold a clone of it, so they can be kept in structs or moved into tasks
+/// as well, e.g. `let completions = state.openai.completions();` in a request handler.
+#[derive(Clone)]
+pub struct OpenAI {
+    inner: Arc<Inner>,
+}
+
+// Keeps the client and its API handles shareable across tasks and threads.
+const _: () = {
+    const fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+    assert_shareable::<OpenAI>();
+    assert_shareable::<CompletionsApi>();
+    assert_shareable::<ProjectsApi>();
+};
+
+#[derive(Clone)]
+struct Inner {
+    client: Client,
+    transport: Arc<dyn HttpTransport>,
+    api_key: Arc<dyn ApiKeyProvider>,
+    auth_scheme: AuthScheme,
+    headers: Vec<(String, String)>,
+    default_headers: Vec<(String, String)>,
+    user_agent: String,
+    base_url: String,
+    azure: Option<Arc<AzureConfig>>,
+    organization: Option<String>,
+    project: Option<String>,
+    beta_features: Vec<String>,
+    call_headers: Vec<(String, String)>,
+    call_timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    hedge_policy: Option<HedgePolicy>,
+    coalescer: Option<Arc<Coalescer>>,
+    stream_chunk_size: usize,
+    compression_threshold: Option<usize>,
+    json_timeout: Option<Duration>,
+    upload_timeout: Option<Duration>,
+    failover: Option<Arc<Failover>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    retry_balance: Option<Arc<RetryBalance>>,
+    scheduler: Option<Arc<Scheduler>>,
+    priority: Option<Priority>,
+    rate_limits: Arc<Mutex<ReportedLimits>>,
+    adaptive_rate_limit: bool,
+    default_models: DefaultModels,
+    response_cache: Option<ResponseCache>,
+    metrics_sink: Option<Arc<dyn MetricsSink>>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    events: Arc<EventBus>,
+    dry_run: bool,
+    local_compat: bool,
+    unsupported_fields: Vec<String>,
+    parameter_gating: Option<Arc<ParameterGating>>,
+    request_signer: Option<RequestSigner>,
+    unknown_fields: UnknownFields,
+    #[cfg(feature = "test-util")]
+    cassette: Option<Arc<crate::test_util::cassette::Cassette>>,
+    #[cfg(feature = "tracing")]
+    body_logging: Option<usize>,
+}
+
+impl OpenAI {
+    pub fn new(api_key: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self::from_parts(Client::new(), api_key.into(), base_url.into())
+    }
+
+    /// Start building a client, e.g. around a pre-configured [`reqwest::Client`].
+    pub fn builder() -> OpenAIBuilder {
+        OpenAIBuilder::default()
+    }
+
+    /// Create a client for an OpenAI-compatible provider, with its base URL, authentication
+    /// scheme and extra headers.
+    pub fn for_provider(provider: &Provider, api_key: impl Into<String>) -> Self {
+        let mut openai = Self::new(api_key, provider.base_url());
+        openai.set_provider(provider);
+
+        openai
+    }
+
+    /// Create a client for Azure OpenAI at `endpoint` (e.g. `https://my-resource.openai.azure.com`),
+    /// sending the API key in the `api-key` header and the requests to the deployments of `azure`.
+    pub fn azure(
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+        azure: AzureConfig,
+    ) -> Self {
+        let mut openai = Self::new(api_key, endpoint);
+        openai.set_azure(Some(azure));
+
+        openai
+    }
+
+    /// Create a client for a local OpenAI-compatible server (Ollama, LM Studio, vLLM...), sending
+    /// no API key and with the local compatibility mode on.
+    pub fn local(base_url: impl Into<String>) -> Self {
+        let mut openai = Self::new("", base_url);
+        openai.set_auth_scheme(AuthScheme::None);
+        openai.set_local_compat(true);
+
+        openai
+    }
+
+    /// Create a client from a declarative [`OpenAIConfig`].
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the client on success, or an [`OpenAIError`] if the API key is missing or the HTTP client can't be built.
+    pub fn from_config(config: OpenAIConfig) -> OpenAIResult<Self> {
+        let api_key = config
+            .api_key
+            .ok_or_else(|| OpenAIError::ConfigError("missing api_key".to_string()))?;
+
+        let mut client = Client::builder();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(timeout_secs) = config.timeout_secs {
+                client = client.timeout(Duration::from_secs(timeout_secs));
+            }
+            if let Some(connect_timeout_secs) = config.connect_timeout_secs {
+                client = client.connect_timeout(Duration::from_secs(connect_timeout_secs));
+            }
+            if let Some(read_timeout_secs) = config.read_timeout_secs {
+                client = client.read_timeout(Duration::from_secs(read_timeout_secs));
+            }
+            if let Some(max_idle) = config.pool_max_idle_per_host {
+                client = client.pool_max_idle_per_host(max_idle);
+            }
+            if let Some(idle_timeout_secs) = config.pool_idle_timeout_secs {
+                client = client.pool_idle_timeout(Duration::from_secs(idle_timeout_secs));
+            }
+            if let Some(keepalive_secs) = config.tcp_keepalive_secs {
+                client = client.tcp_keepalive(Duration::from_secs(keepalive_secs));
+            }
+            if let Some(http2) = &config.http2 {
+                client = http2.apply(client);
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        if config.timeout_secs.is_some()
+            || config.connect_timeout_secs.is_some()
+            || config.read_timeout_secs.is_some()
+            || config.pool_max_idle_per_host.is_some()
+            || config.pool_idle_timeout_secs.is_some()
+            || config.tcp_keepalive_secs.is_some()
+            || config.http2.is_some()
+        {
+            return Err(OpenAIError::ConfigError(
+                crate::config::UNSUPPORTED_ON_WASM.to_string(),
+            ));
+        }
+        if let Some(response_compression) = config.response_compression {
+            client = crate::config::apply_response_compression(client, response_compression);
+        }
+
+        let base_url = config
+            .base_url
+            .or_else(|| {
+                config
+                    .provider
+                    .as_ref()
+                    .map(|provider| provider.base_url().to_string())
+            })
+            .unwrap_or_default();
+        let mut openai = Self::from_parts(client.build()?, api_key, base_url);
+
+        if let Some(provider) = &config.provider {
+            openai.apply_provider(provider);
+        }
+        if config.azure.is_some() {
+            openai.set_azure(config.azure);
+        }
+
+        openai.inner_mut().organization = config.organization;
+        openai.inner_mut().project = config.project;
+        openai.inner_mut().retry_policy = config.retry.unwrap_or_default();
+        openai.inner_mut().hedge_policy = config.hedging;
+        openai.inner_mut().default_models = config.default_models;
+        openai.set_app_info(config.app_info.as_deref());
+        openai.inner_mut().compression_threshold = config.compression_threshold;
+        openai.inner_mut().adaptive_rate_limit = config.adaptive_rate_limit.unwrap_or_default();
+        openai.set_json_timeout(config.json_timeout_secs.map(Duration::from_secs));
+        openai.set_upload_timeout(config.upload_timeout_secs.map(Duration::from_secs));
+        openai.set_failover_policy(config.failover);
+        openai.set_circuit_breaker(config.circuit_breaker);
+        openai.set_retry_budget(config.retry_budget);
+        openai.set_scheduling_policy(config.scheduling);
+        if let Some(stream_chunk_size) = config.stream_chunk_size {
+            openai.set_stream_chunk_size(stream_chunk_size);
+        }
+
+        Ok(openai)
+    }
+
+    /// Create a client from the `OPENAI_API_KEY`, `OPENAI_BASE_URL`, `OPENAI_ORG_ID` and
+    /// `OPENAI_PROJECT_ID` environment variables, only the API key being required, and the other
+    /// `OPENAI_*` variables of [`OpenAIConfig::from_env`].
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the client on success, or an [`OpenAIError::ConfigError`] naming the variable that is missing or invalid.
+    pub fn from_env() -> OpenAIResult<Self> {
+        let invalid = |name: &str, reason: &str| {
+            OpenAIError::ConfigError(format!("invalid value for {name}: {reason}"))
+        };
+
+        for name in [
+            "OPENAI_API_KEY",
+            "OPENAI_BASE_URL",
+            "OPENAI_ORG_ID",
+            "OPENAI_PROJECT_ID",
+        ] {
+            if let Err(std::env::VarError::NotUnicode(_)) = std::env::var(name) {
+                return Err(invalid(name, "not valid unicode"));
+            }
+        }
+
+        let config = OpenAIConfig::from_env()?;
+        if config.api_key.is_none() {
+            return Err(OpenAIError::ConfigError(
+                "missing OPENAI_API_KEY environment variable".to_string(),
+            ));
+        }
+        if let Some(base_url) = &config.base_url {
+            reqwest::Url::parse(base_url)
+                .map_err(|error| invalid("OPENAI_BASE_URL", &format!("{base_url:?} ({error})")))?;
+        }
+
+        let headers = [
+            ("OPENAI_API_KEY", &config.api_key),
+            ("OPENAI_ORG_ID", &config.organization),
+            ("OPENAI_PROJECT_ID", &config.project),
+        ];
+        for (name, value) in headers {
+            if value
+                .as_deref()
+                .is_some_and(|value| HeaderValue::try_from(value).is_err())
+            {
+                return Err(invalid(name, "not a valid header value"));
+            }
+        }
+
+        Self::from_config(config)
+    }
+
+    /// Create a client with default settings around an HTTP client.
+    pub(crate) fn from_parts(client: Client, api_key: String, base_url: String) -> Self {
+        let inner = Inner {
+            transport: Arc::new(client.clone()),
+            client,
+            api_key: Arc::new(StaticApiKey::new(api_key)),
+            auth_scheme: AuthScheme::Bearer,
+            headers: Vec::new(),
+            default_headers: Vec::new(),
+            user_agent: USER_AGENT.to_string(),
+            base_url: if base_url.is_empty() {
+                DEFAULT_BASE_URL.to_string()
+            } else {
+                base_url
             },
+            azure: None,
+            organization: None,
+            project: None,
+            beta_features: Vec::new(),
+            call_headers: Vec::new(),
+            call_timeout: None,
+            retry_policy: RetryPolicy::default(),
+            hedge_policy: None,
+            coalescer: None,
+            stream_chunk_size: DEFAULT_STREAM_CHUNK_SIZE,
+            compression_threshold: None,
+            json_timeout: None,
+            upload_timeout: None,
+            failover: None,
+            circuit_breaker: None,
+            retry_balance: None,
+            scheduler: None,
+            priority: None,
+            rate_limits: Arc::default(),
+            adaptive_rate_limit: false,
+            default_models: DefaultModels::default(),
+            response_cache: None,
+            metrics_sink: None,
+            interceptors: Vec::new(),
+            events: Arc::default(),
+            dry_run: false,
+            local_compat: false,
+            unsupported_fields: Vec::new(),
+            parameter_gating: None,
+            request_signer: None,
+            unknown_fields: UnknownFields::Ignore,
+            #[cfg(feature = "test-util")]
+            cassette: None,
+            #[cfg(feature = "tracing")]
+            body_logging: None,
+        };
+
+        Self {
+            inner: Arc::new(inner),
         }
     }
 
-    pub async fn get<T: DeserializeOwned>(&self, url: &str) -> OpenAIResult<T> {
-        Ok(self
+    /// The settings of this client, copied first if they're shared with clones.
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    /// The authentication header with `api_key`, if any, followed by the extra headers of the
+    /// provider and the default headers of the client.
+    fn provider_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        self.inner
+            .auth_scheme
+            .header(api_key)
+            .into_iter()
+            .chain(self.inner.headers.iter().cloned())
+            .chain(self.inner.default_headers.iter().cloned())
+            .collect()
+    }
+
+    /// Start a request to `url` (relative to the base URL) with the headers of the client set, the
+    /// authentication header being added when the request is built.
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let mut builder = self
+            .inner
             .client
-            .get(format!("{}{url}", self.base_url))
-            .header("Authorization", &self.authorization)
-            .send()
-            .await?
-            .json()
-            .await?)
+            .request(method, format!("{}{url}", self.inner.base_url))
+            .header("User-Agent", &self.inner.user_agent);
+
+        // Default `OpenAI-Beta` headers are merged into the client's beta features below.
+        let headers = self.inner.headers.iter().chain(
+            self.inner
+                .default_headers
+                .iter()
+                .filter(|(name, _)| !is_beta_header(name)),
+        );
+        for (name, value) in headers {
+            builder = builder.header(name, value);
+        }
+
+        if let Some(organization) = &self.inner.organization {
+            builder = builder.header("OpenAI-Organization", organization);
+        }
+        if let Some(project) = &self.inner.project {
+            builder = builder.header("OpenAI-Project", project);
+        }
+        if let Some(beta) = self.beta_header(url) {
+            builder = builder.header("OpenAI-Beta", beta);
+        }
+
+        // Headers of the call replace the client's, invalid ones fail the request when it's built.
+        let mut call_headers = HeaderMap::new();
+        for (name, value) in &self.inner.call_headers {
+            match (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::try_from(value.as_str()),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    call_headers.append(name, value);
+                },
+                _ => builder = builder.header(name, value),
+            }
+        }
+
+        let builder = builder.headers(call_headers);
+
+        match self.inner.call_timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        }
     }
 
-    pub async fn post_json<B: Serialize + ?Sized, T: DeserializeOwned>(
+    /// Start a JSON request to `url`, bounded by the timeout of the call or the client's JSON
+    /// timeout.
+    fn json_request(&self, method: Method, url: &str) -> RequestBuilder {
+        let builder = self.request(method, url);
+
+        match self.inner.call_timeout.or(self.inner.json_timeout) {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        }
+    }
+
+    /// Set the `Idempotency-Key` header of `builder` to `idempotency_key` if there is one, the key
+    /// of a single call: the API answers every request reusing it with the response of the first
+    /// one.
+    fn idempotency_key(builder: RequestBuilder, idempotency_key: Option<&str>) -> RequestBuilder {
+        let Some(key) = idempotency_key else {
+            return builder;
+        };
+
+        match HeaderValue::try_from(key) {
+            Ok(value) => builder.headers(HeaderMap::from_iter([(IDEMPOTENCY_KEY, value)])),
+            // Invalid keys fail the request when it's built.
+            Err(_) => builder.header(IDEMPOTENCY_KEY, key),
+        }
+    }
+
+    /// The `OpenAI-Beta` header of a request to `url`: the client's beta features, plus the
+    /// assistants one for the endpoints requiring it.
+    fn beta_header(&self, url: &str) -> Option<String> {
+        let client_features: Vec<&str> = self
+            .inner
+            .beta_features
+            .iter()
+            .map(String::as_str)
+            .chain(
+                self.inner
+                    .default_headers
+                    .iter()
+                    .filter(|(name, _)| is_beta_header(name))
+                    .flat_map(|(_, value)| value.split(','))
+                    .map(str::trim)
+                    .filter(|feature| !feature.is_empty()),
+            )
+            .collect();
+
+        let assistants = ["/assistants", "/threads", "/vector_stores"]
+            .iter()
+            .any(|prefix| url.starts_with(prefix))
+            && !client_features
+                .iter()
+                .any(|feature| feature.starts_with("assistants="));
+
+        let features: Vec<&str> = assistants
+            .then_some(ASSISTANTS_BETA)
+            .into_iter()
+            .chain(client_features)
+            .collect();
+
+        (!features.is_empty()).then(|| features.join(","))
+    }
+
+    /// Build and send a request, reading its response and reporting it to the metrics sink.
+    async fn send(&self, builder: RequestBuilder) -> OpenAIResult<ApiResponse> {
+        self.send_for_model(builder, None).await
+    }
+
+    /// Build and send a request for `model`, if its body doesn't tell it (e.g. a multipart form),
+    /// reading its response and reporting it to the metrics sink.
+    async fn send_for_model(
         &self,
-        url: &str,
-        body: &B,
-    ) -> OpenAIResult<T> {
-        Ok(self
-            .client
-            .post(format!("{}{url}", self.base_url))
-            .header("Authorization", &self.authorization)
-            .header("Content-Type", "application/json")
-            .json(body)
-            .send()
-            .await?
-            .json()
-            .await?)
+        builder: RequestBuilder,
+        model: Option<&str>,
+    ) -> OpenAIResult<ApiResponse> {
+        let (request, api_key) = self.build_request(builder, model).await?;
+        self.send_request(request, api_key).await
     }
 
-    pub async fn post_form<T: DeserializeOwned>(&self, url: &str, form: Form) -> OpenAIResult<T> {
-        Ok(self
-            .client
-            .post(format!("{}{url}", self.base_url))
-            .header("Authorization", &self.authorization)
-            .multipart(form)
-            .send()
-            .await?
-            .json()
-            .await?)
+    /// Send a request built by [`OpenAI::build_request`] with `api_key`, reading its response and
+    /// reporting it to the metrics sink.
+    async fn send_request(&self, request: Request, api_key: String) -> OpenAIResult<ApiResponse> {
+        let mut request = self.capture(request).await?;
+
+        if self.inner.dry_run {
+            let prepared = PreparedRequest::capture(request, &self.inner.auth_scheme).await?;
+            return Err(OpenAIError::DryRun(Box::new(prepared)));
+        }
+        for interceptor in &self.inner.interceptors {
+            interceptor.on_request(&mut request);
+        }
+
+        let mut context = RequestContext {
+            id: self.inner.events.next_request_id(),
+            retries: 0,
+            base_url: None,
+            attempts: Vec::new(),
+            api_key,
+        };
+        let observation = self.observe(&request, &context);
+        let result = self.dispatch(request, &mut context).await;
+
+        if let Some(observation) = observation {
+            let response = result
+                .as_ref()
+                .map(|response| (response.status, Some(response.body.as_slice())));
+            self.finish_observation(observation, &context, response);
+        }
+
+        result
     }
 
-    pub async fn delete<T: DeserializeOwned>(&self, url: &str) -> OpenAIResult<T> {
-        Ok(self
-            .client
-            .delete(format!("{}{url}", self.base_url))
-            .header("Authorization", &self.authorization)
-            .send()
-            .await?
-            .json()
-            .await?)
+    /// Build and send a request, returning the response before its body is read.
+    async fn send_streaming(&self, builder: RequestBuilder) -> OpenAIResult<Response> {
+        let (request, api_key) = self.build_request(builder, None).await?;
+        let mut request = self.capture(request).await?;
+
+        if self.inner.dry_run {
+            let prepared = PreparedRequest::capture(request, &self.inner.auth_scheme).await?;
+            return Err(OpenAIError::DryRun(Box::new(prepared)));
+        }
+        for interceptor in &self.inner.interceptors {
+            interceptor.on_request(&mut request);
+        }
+
+        let mut context = RequestContext {
+            id: self.inner.events.next_request_id(),
+            retries: 0,
+            base_url: None,
+            attempts: Vec::new(),
+            api_key,
+        };
+        let observation = self.observe(&request, &context);
+        let result = self.dispatch_response(request, &mut context).await;
+
+        if let Some(observation) = observation {
+            let response = result.as_ref().map(|response| (response.status(), None));
+            self.finish_observation(observation, &context, response);
+        }
+
+        result
+    }
+
+    /// Build a request, authenticating it with the key of the client's [`ApiKeyProvider`] and
+    /// pointing it at the Azure endpoint in Azure mode (the deployment of `model`, or of the model
+    /// of its JSON body), and return it along with the key.
+    async fn build_request(
+        &self,
+        builder: RequestBuilder,
+        model: Option<&str>,
+    ) -> OpenAIResult<(Request, String)> {
+        let mut request = builder.build()?;
+        let api_key = match self.inner.auth_scheme {
+            AuthScheme::None => String::new(),
+            _ => self.inner.api_key.api_key().await?,
+        };
+
+        // An authentication header of the call replaces the client's.
+        if let Some((name, value)) = self.inner.auth_scheme.header(&api_key) {
+            let name = HeaderName::try_from(name)
+                .map_err(|error| OpenAIError::ConfigError(format!("auth header: {error}")))?;
+            if !request.headers().contains_key(&name) {
+                let value = HeaderValue::try_from(value)
+                    .map_err(|error| OpenAIError::ConfigError(format!("API key: {error}")))?;
+                request.headers_mut().insert(name, value);
+            }
+        }
+
+        let request = match &self.inner.azure {
+            Some(azure) => azure.rewrite(&self.inner.base_url, request, model)?,
+            None => request,
+        };
+
+        Ok((request, api_key))
     }
 
-    pub const fn get_base_url(&self) -> &str {
-        self.base_url
+    /// Capture `request` instead of sending it when a call is being prepared (see `prepare`).
+    async fn capture(&self, request: Request) -> OpenAIResult<Request> {
+        let Ok(capture) = CAPTURE.try_with(Arc::clone) else {
+            return Ok(request);
+        };
+
+        let prepared = PreparedRequest::capture(request, &self.inner.auth_scheme).await?;
+        capture
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| prepared.clone());
+
+        // Stop the call here, the error is discarded by `prepare`.
+        Err(OpenAIError::DryRun(Box::new(prepared)))
+    }
+
+    /// Publish the start of a request, if anyone is observing the client.
+    fn observe(&self, request: &Request, context: &RequestContext) -> Option<Observation> {
+        if self.inner.metrics_sink.is_none() && !self.inner.events.has_subscribers() {
+            return None;
+        }
+
+        let method = request.method().to_string();
+        let path = relative_path(&self.inner.base_url, request.url().as_str());
+        let model = self.inner.metrics_sink.as_ref().and_then(|_| {
+            request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .and_then(request_model)
+        });
+
+        self.inner.events.publish(&ClientEvent::RequestStarted {
+            id: context.id,
+            method: method.clone(),
+            path: path.clone(),
+        });
+
+        Some(Observation {
+            method,
+            path,
+            model,
+            started: Instant::now(),
+        })
+    }
+
+    /// Publish the end of an observed request and report it to the metrics sink.
+    fn finish_observation(
+        &self,
+        observation: Observation,
+        context: &RequestContext,
+        response: Result<(StatusCode, Option<&[u8]>), &OpenAIError>,
+    ) {
+        let duration = observation.started.elapsed();
+        let status = response.as_ref().ok().map(|(status, _)| status.as_u16());
+
+        self.inner.events.publish(&ClientEvent::RequestFinished {
+            id: context.id,
+            status,
+            duration,
+            retries: context.retries,
+            base_url: context.base_url.clone(),
+            error: response.as_ref().err().map(ToString::to_string),
+            attempts: context.attempts.clone(),
+        });
+
+        if let Some(sink) = &self.inner.metrics_sink {
+            sink.record(&RequestMetrics {
+                method: observation.method,
+                endpoint: endpoint_template(&observation.path),
+                model: observation.model,
+                status,
+                duration,
+                retries: context.retries,
+                base_url: context.base_url.clone(),
+                attempts: context.attempts.clone(),
+                usage: response
+                    .ok()
+                    .and_then(|(_, body)| body.and_then(response_usage)),
+            });
+        }
+    }
+
+    /// Send a request through the cassette if one is set, or to the API otherwise.
+    async fn dispatch(
+        &self,
+        request: Request,
+        context: &mut RequestContext,
+    ) -> OpenAIResult<ApiResponse> {
+        let response = ApiResponse::read(self.dispatch_response(request, context).await?).await?;
+
+        #[cfg(feature = "tracing")]
+        if let Some(max_body_len) = self.inner.body_logging {
+            crate::trace::log_response(&response, &context.api_key, max_body_len);
+        }
+
+        Ok(response)
+    }
+
+    async fn dispatch_response(
+        &self,
+        request: Request,
+        context: &mut RequestContext,
+    ) -> OpenAIResult<Response> {
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::RequestSpan::new(&self.inner.base_url, &request);
+        #[cfg(feature = "tracing")]
+        if let Some(max_body_len) = self.inner.body_logging {
+            crate::trace::log_request(&span, &request, &context.api_key, max_body_len);
+        }
+
+        let response = self.intercept(request, context);
+        #[cfg(feature = "tracing")]
+        let response = tracing::Instrument::instrument(response, span.span().clone());
+        let result = response.await;
+
+        #[cfg(feature = "tracing")]
+        span.finish(&result, context.retries, &context.api_key);
+
+        result
+    }
+
+    /// Send a request through the client's interceptors.
+    async fn intercept(
+        &self,
+        request: Request,
+        context: &mut RequestContext,
+    ) -> OpenAIResult<Response> {
+        #[cfg(feature = "test-util")]
+        let response = match &self.inner.cassette {
+            Some(cassette) => cassette.handle(self, request, context).await?,
+            None => self.execute(request, context).await?,
+        };
+        #[cfg(not(feature = "test-util"))]
+        let response = self.execute(request, context).await?;
+
+        for interceptor in &self.inner.interceptors {
+            interceptor.on_response(&response);
+        }
+        meta::record(&response, context.base_url.as_deref());
+
+        Ok(response)
+    }
+
+    /// Send a request, retrying it according to the client's [`RetryPolicy`] and counting the
+    /// retries made in `context`.
+    ///
+    /// Responses with a retryable status and transient errors (connection errors and timeouts)
+    /// are retried, unless the circuit is open or the retry budget is exhausted.
+    ///
+    /// Requests whose body can't be cloned (e.g. multipart forms) are only sent once.
+    pub(crate) async fn execute(
+        &self,
+        request: Request,
+        context: &mut RequestContext,
+    ) -> OpenAIResult<Response> {
+        // Estimated before compression, from the size of the body.
+        let tokens = self.inner.adaptive_rate_limit.then(|| {
+            let body = request.body().and_then(|body| body.as_bytes());
+            let body = String::from_utf8_lossy(body.unwrap_or_default());
+            ApproximateTokenCounter.count_tokens(&body) as u64
+        });
+        let mut request = self.compress(request)?;
+        if self.inner.request_signer.is_some() {
+            request = buffer_body(request).await?;
+        }
+
+        if let Some(breaker) = &self.inner.circuit_breaker {
+            breaker.acquire(&self.inner.events).map_err(|wait| {
+                OpenAIError::CircuitOpen(format!("next probe in {:.1}s", wait.as_secs_f64()))
+            })?;
+        }
+        if let Some(retry_balance) = &self.inner.retry_balance {
+            retry_balance.deposit();
+        }
+        let deadline = self.inner.retry_policy.deadline();
+
+        loop {
+            let retryable = if context.retries < self.inner.retry_policy.max_retries {
+                request.try_clone()
+            } else {
+                None
+            };
+
+            if let Some(tokens) = tokens {
+                self.pace(&context.api_key, tokens).await;
+            }
+
+            let started = Instant::now();
+            let response = match retryable {
+                Some(retry) => {
+                    let result = self.attempt_within(retry, context, deadline).await;
+                    let result = self.record_outcome(result, &context.api_key);
+                    context.record_attempt(started, &result);
+
+                    match result {
+                        Ok(response) => response,
+                        Err(error)
+                            if self.inner.retry_policy.should_retry_error(&error)
+                                && self.may_retry() =>
+                        {
+                            self.back_off(context, None, None, deadline).await?;
+                            continue;
+                        },
+                        Err(error) => return Err(error),
+                    }
+                },
+                None => {
+                    let result = self.attempt_within(request, context, deadline).await;
+                    let result = self.record_outcome(result, &context.api_key);
+                    context.record_attempt(started, &result);
+                    let response = result?;
+                    self.publish_rate_limit(context, response.status(), false);
+
+                    return Ok(response);
+                },
+            };
+
+            if !self.inner.retry_policy.should_retry(response.status()) || !self.may_retry() {
+                self.publish_rate_limit(context, response.status(), false);
+                return Ok(response);
+            }
+
+            self.publish_rate_limit(context, response.status(), true);
+            let wait = retry::retry_after(response.headers());
+            self.back_off(context, Some(response.status().as_u16()), wait, deadline)
+                .await?;
+        }
+    }
+
+    /// Send an attempt of a request, cut short with [`OpenAIError::DeadlineExceeded`] once
+    /// `deadline` passes.
+    async fn attempt_within(
+        &self,
+        request: Request,
+        context: &mut RequestContext,
+        deadline: Option<Deadline>,
+    ) -> OpenAIResult<Response> {
+        let Some(deadline) = deadline else {
+            return self.attempt(request, context).await;
+        };
+
+        let attempt = self.attempt(request, context);
+        tokio::select! {
+            result = attempt => return result,
+            _ = runtime::sleep(deadline.remaining()) => {},
+        }
+
+        Err(deadline.exceeded(context.attempts.len() + 1, last_status(context)))
+    }
+
+    /// Wait until a request of `tokens` tokens authenticated with `api_key` fits in the rate limits
+    /// last reported by the API for that key, counting it against them.
+    async fn pace(&self, api_key: &str, tokens: u64) {
+        let wait = self
+            .inner
+            .rate_limits
+            .lock()
+            .unwrap()
+            .reserve(api_key, tokens);
+
+        if !wait.is_zero() {
+            runtime::sleep(wait).await;
+        }
+    }
+
+    /// Wait before retrying a request whose last attempt failed with `status` (`None` if no
+    /// response was received), for at least the `retry_after` wait the API requested, counting the
+    /// retry in `context`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing nothing on success, or an [`OpenAIError::DeadlineExceeded`] if
+    /// `deadline` would pass before the retry.
+    async fn back_off(
+        &self,
+        context: &mut RequestContext,
+        status: Option<u16>,
+        retry_after: Option<Duration>,
+        deadline: Option<Deadline>,
+    ) -> OpenAIResult<()> {
+        let delay = self
+            .inner
+            .retry_policy
+            .delay_after(context.retries, retry_after);
+        if let Some(deadline) = deadline.filter(|deadline| deadline.remaining() <= delay) {
+            return Err(deadline.exceeded(context.attempts.len(), last_status(context)));
+        }
+        context.retries += 1;
+        if let Some(attempt) = context.attempts.last_mut() {
+            attempt.retry_delay = Some(delay);
+        }
+
+        self.inner.events.publish(&ClientEvent::Retry {
+            id: context.id,
+            attempt: context.retries,
+            status,
+            delay,
+            retry_after,
+        });
+
+        runtime::sleep(delay).await;
+
+        Ok(())
+    }
+
+    /// Report the outcome of an attempt authenticated with `api_key` to the circuit breaker and
+    /// the [`ApiKeyProvider`], and keep the rate limits it reports.
+    fn record_outcome(
+        &self,
+        result: OpenAIResult<Response>,
+        api_key: &str,
+    ) -> OpenAIResult<Response> {
+        let limits = result
+            .as_ref()
+            .ok()
+            .and_then(|response| RateLimits::from_headers(response.headers()));
+        self.inner.api_key.report(
+            api_key,
+            result.as_ref().ok().map(Response::status),
+            limits.as_ref(),
+        );
+
+        if let Some(limits) = limits {
+            let mut rate_limits = self.inner.rate_limits.lock().unwrap();
+            rate_limits.record(api_key, limits);
+
+            // Queued requests can still go out with another key with requests left.
+            let wait = rate_limits.wait();
+            drop(rate_limits);
+            if let Some(scheduler) = self.inner.scheduler.as_ref().filter(|_| !wait.is_zero()) {
+                scheduler.pause_until(Instant::now() + wait);
+            }
+        }
+
+        if let Some(breaker) = &self.inner.circuit_breaker {
+            let success = result
+                .as_ref()
+                .is_ok_and(|response| !response.status().is_server_error());
+            breaker.record(success, &self.inner.events);
+        }
+
+        result
+    }
+
+    /// Whether a failed attempt may be retried: the circuit isn't open and the retry budget isn't
+    /// exhausted.
+    fn may_retry(&self) -> bool {
+        let open = self
+            .inner
+            .circuit_breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.state() == CircuitState::Open);
+
+        !open
+            && self
+                .inner
+                .retry_balance
+                .as_ref()
+                .is_none_or(|retry_balance| retry_balance.withdraw())
     }
 
-    pub fn set_base_url(&mut self, base_url: &'a str) {
-        self.base_url = base_url;
+    /// Gzip the JSON body of `request` if request compression is enabled and the body is large
+    /// enough.
+    fn compress(&self, mut request: Request) -> OpenAIResult<Request> {
+        let Some(min_size) = self.inner.compression_threshold else {
+            return Ok(request);
+        };
+        let is_json = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .is_some_and(|content_type| content_type == "application/json");
+        let Some(body) = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .filter(|body| is_json && body.len() >= min_size)
+        else {
+            return Ok(request);
+        };
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body)?;
+        let compressed = encoder.finish()?;
+
+        request
+            .headers_mut()
+            .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        *request.body_mut() = Some(compressed.into());
+
+        Ok(request)
     }
 
-    pub const fn client(&self) -> ClientApi {
-        ClientApi(self)
+    /// Send a single attempt once the client's [`SchedulingPolicy`] lets it through, its slot
+    /// being held until the body of the response is read or dropped.
+    async fn attempt(
+        &self,
+        request: Request,
+        context: &mut RequestContext,
+    ) -> OpenAIResult<Response> {
+        let Some(scheduler) = &self.inner.scheduler else {
+            return self.fail_over(request, context).await;
+        };
+
+        let permit = scheduler.acquire(self.request_priority(&request)).await;
+        permit.hold(self.fail_over(request, context).await?)
     }
 
-    pub const fn completions(&self) -> CompletionsApi {
-        CompletionsApi(self)
+    /// Send a single attempt to the active base URL, failing over to the next one according to the
+    /// client's [`FailoverPolicy`].
+    ///
+    /// Attempts failing to connect are sent again right away if that made the client switch to
+    /// another base URL.
+    async fn fail_over(
+        &self,
+        mut request: Request,
+        context: &mut RequestContext,
+    ) -> OpenAIResult<Response> {
+        let Some(failover) = &self.inner.failover else {
+            context.base_url = Some(self.inner.base_url.clone());
+            return self.send_attempt(request, context).await;
+        };
+
+        let mut remaining = failover.len();
+
+        loop {
+            remaining -= 1;
+            let endpoint = failover.rebase(&mut request);
+            let fallback = request.try_clone().filter(|_| remaining > 0);
+            context.base_url = Some(failover.url(endpoint).to_string());
+            let result = self.send_attempt(request, context).await;
+
+            let success = result
+                .as_ref()
+                .is_ok_and(|response| !response.status().is_server_error());
+            let switched = failover.record(endpoint, success, &self.inner.events);
+            failover.check_health(
+                &self.inner.client,
+                &self.inner.transport,
+                self.provider_headers(&context.api_key),
+                &self.inner.events,
+            );
+
+            match (result, fallback) {
+                (Err(_), Some(fallback)) if switched => request = fallback,
+                (result, _) => return result,
+            }
+        }
     }
 
-    pub const fn audio(&self) -> AudioApi {
-        AudioApi(self)
+    /// Priority of `request`: the client's, or the default one of its endpoint.
+    fn request_priority(&self, request: &Request) -> Priority {
+        self.inner.priority.unwrap_or_else(|| {
+            Priority::for_path(&relative_path(&self.inner.base_url, request.url().as_str()))
+        })
     }
 
-    pub const fn images(&self) -> ImagesApi {
-        ImagesApi(self)
+    /// Send a single attempt, hedging it if the client's [`HedgePolicy`] applies.
+    async fn send_attempt(
+        &self,
+        mut request: Request,
+        context: &RequestContext,
+    ) -> OpenAIResult<Response> {
+        if let Some(signer) = &self.inner.request_signer {
+            sign(signer, &mut request)?;
+        }
+
+        let hedge = self.inner.hedge_policy.as_ref().filter(|policy| {
+            policy.applies_to(
+                request.method(),
+                &relative_path(&self.inner.base_url, request.url().as_str()),
+            )
+        });
+        let Some((policy, hedged)) = hedge.and_then(|policy| Some((policy, request.try_clone()?)))
+        else {
+            return self.inner.transport.send(request).await;
+        };
+
+        let first = self.inner.transport.send(request);
+        tokio::pin!(first);
+
+        tokio::select! {
+            response = &mut first => return response,
+            _ = runtime::sleep(Duration::from_millis(policy.delay_ms)) => {},
+        }
+
+        self.inner
+            .events
+            .publish(&ClientEvent::Hedged { id: context.id });
+
+        // The slower attempt is cancelled when dropped.
+        tokio::select! {
+            response = first => response,
+            response = self.inner.transport.send(hedged) => response,
+        }
     }
 
-    pub const fn fine_tuning(&self) -> FineTuningApi {
-        FineTuningApi(self)
+    fn publish_rate_limit(&self, context: &RequestContext, status: StatusCode, retrying: bool) {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            self.inner.events.publish(&ClientEvent::RateLimited {
+                id: context.id,
+                retrying,
+            });
+        }
     }
 
-    pub const fn moderation(&self) -> ModerationApi {
-        ModerationApi(self)
+    pub async fn get<T: DeserializeOwned>(&self, url: &str) -> OpenAIResult<T> {
+        let builder = self.json_request(Method::GET, url);
+        let Some(coalescer) = self
+            .inner
+            .coalescer
+            .as_ref()
+            .filter(|_| !self.is_preparing())
+        else {
+            return self.decode_response(url, &self.send(builder).await?);
+        };
+
+        // Coalesced on the built request, so clones with other credentials or headers don't
+        // share responses.
+        let (request, api_key) = self.build_request(builder, None).await?;
+        let response = coalescer
+            .run(&request_key(&request), || {
+                self.send_request(request, api_key)
+            })
+            .await?;
+
+        self.decode_response(url, &response)
     }
 
-    pub const fn embeddings(&self) -> EmbeddingsApi {
-        EmbeddingsApi(self)
+    /// Fetch a list endpoint, decoding the items of its `data` array one by one while the body
+    /// arrives instead of buffering the whole response, e.g. for long file or message listings.
+    ///
+    /// A body ending before the end of the array yields an [`OpenAIError::UnexpectedResponse`]
+    /// after the items received.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a stream of the decoded items on success, or an [`OpenAIError`] if the request failed.
+    pub async fn list_stream<T: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> OpenAIResult<impl Stream<Item = OpenAIResult<T>>> {
+        let response = self.send_streaming(self.request(Method::GET, url)).await?;
+        if !response.status().is_success() {
+            return Err(self.api_error(&ApiResponse::read(response).await?));
+        }
+
+        let state = (
+            Box::pin(response.bytes_stream()),
+            ArrayFieldScanner::new("data"),
+            VecDeque::<Vec<u8>>::new(),
+        );
+
+        Ok(futures::stream::unfold(
+            state,
+            |(mut body, mut scanner, mut pending)| async move {
+                loop {
+                    if let Some(item) = pending.pop_front() {
+                        let item = serde_json::from_slice::<T>(&item).map_err(OpenAIError::from);
+                        return Some((item, (body, scanner, pending)));
+                    }
+                    if scanner.is_done() {
+                        return None;
+                    }
+
+                    match body.next().await {
+                        Some(Ok(bytes)) => scanner.push(&bytes, |item| pending.push_back(item)),
+                        // A truncated body isn't a shorter list.
+                        None => {
+                            scanner.stop();
+                            let error = OpenAIError::UnexpectedResponse(
+                                "list response ended early".to_string(),
+                            );
+                            return Some((Err(error), (body, scanner, pending)));
+                        },
+                        Some(Err(error)) => {
+                            scanner.stop();
+                            return Some((Err(error.into()), (body, scanner, pending)));
+                        },
+                    }
+                }
+            },
+        ))
     }
 
-    pub const fn assistants(&self) -> AssistantsApi {
-        AssistantsApi(self)
+    /// Send a JSON request answered with server-sent events, e.g. a chat completion with `stream`
+    /// set, returning the events as they arrive.
+    ///
+    /// The JSON timeout doesn't apply, as streams last as long as the generation.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a stream of the events on success, or an [`OpenAIError`] if the request failed.
+    pub async fn post_events<B: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> OpenAIResult<impl Stream<Item = OpenAIResult<ServerSentEvent>>> {
+        self.post_events_idempotent(url, body, None).await
     }
 
-    pub const fn threads(&self) -> ThreadsApi {
-        ThreadsApi(self)
+    /// Send a JSON request answered with server-sent events like [`OpenAI::post_events`], with
+    /// the `Idempotency-Key` header set to `idempotency_key` if there is one.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a stream of the events on success, or an [`OpenAIError`] if the request failed.
+    pub async fn post_events_idempotent<B: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        body: &B,
+        idempotency_key: Option<&str>,
+    ) -> OpenAIResult<impl Stream<Item = OpenAIResult<ServerSentEvent>>> {
+        let builder = self
+            .request(Method::POST, url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "text/event-stream")
+            .body(self.encode(url, body)?);
+        let builder = Self::idempotency_key(builder, idempotency_key);
+        let response = self.send_streaming(builder).await?;
+        if !response.status().is_success() {
+            return Err(self.api_error(&ApiResponse::read(response).await?));
+        }
+
+        let state = (
+            Box::pin(response.bytes_stream()),
+            SseParser::default(),
+            VecDeque::<ServerSentEvent>::new(),
+            false,
+        );
+
+        Ok(futures::stream::unfold(
+            state,
+            |(mut body, mut parser, mut pending, mut done)| async move {
+                loop {
+                    if let Some(event) = pending.pop_front() {
+                        return Some((Ok(event), (body, parser, pending, done)));
+                    }
+                    if done {
+                        return None;
+                    }
+
+                    match body.next().await {
+                        Some(Ok(bytes)) => parser.push(&bytes, |event| pending.push_back(event)),
+                        None => {
+                            done = true;
+                            parser.finish(|event| pending.push_back(event));
+                        },
+                        Some(Err(error)) => {
+                            return Some((Err(error.into()), (body, parser, pending, true)));
+                        },
+                    }
+                }
+            },
+        ))
     }
 
-    pub const fn vectors(&self) -> VectorsApi {
-        VectorsApi(self)
+    /// Download the body of a GET request to the file at `path`, streaming it to disk with a
+    /// buffer of [`OpenAI::stream_chunk_size`] bytes, e.g. for file contents.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the number of bytes written on success, or an [`OpenAIError`] if the request or the write failed.
+    #[cfg(feature = "fs")]
+    pub async fn download(&self, url: &str, path: &str) -> OpenAIResult<u64> {
+        let response = self.send_streaming(self.request(Method::GET, url)).await?;
+        if !response.status().is_success() {
+            return Err(self.api_error(&ApiResponse::read(response).await?));
+        }
+
+        crate::util::write_body(response, path, self.inner.stream_chunk_size).await
     }
 
-    pub const fn projects(&self) -> ProjectsApi {
-        ProjectsApi(self)
+    pub async fn post_json<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> OpenAIResult<T> {
+        self.post_json_idempotent(url, body, None).await
+    }
+
+    /// Send a JSON `POST` request with the `Idempotency-Key` header set to `idempotency_key` if
+    /// there is one, so the API recognizes its retries and resubmissions (e.g. of a run creation)
+    /// instead of creating duplicates.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the decoded response on success, or an [`OpenAIError`] on failure.
+    pub async fn post_json_idempotent<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+        idempotency_key: Option<&str>,
+    ) -> OpenAIResult<T> {
+        let builder = self
+            .json_request(Method::POST, url)
+            .header("Content-Type", "application/json")
+            .body(self.encode(url, body)?);
+        let builder = Self::idempotency_key(builder, idempotency_key);
+        let (request, api_key) = self.build_request(builder, None).await?;
+
+        // Keyed on the built request, so clones with other credentials or headers don't share
+        // entries.
+        let cache = self
+            .inner
+            .response_cache
+            .as_ref()
+            .filter(|cache| cache.caches(url) && !self.is_preparing())
+            .map(|cache| (cache, ResponseCache::key(&request)));
+
+        if let Some(cached) = cache.as_ref().and_then(|(cache, key)| cache.get(key)) {
+            return self.decode(url, &cached);
+        }
+
+        let response = self.send_request(request, api_key).await?;
+
+        if let (true, Some((cache, key))) = (response.status.is_success(), &cache) {
+            cache.set(key, response.body.clone());
+        }
+
+        self.decode_response(url, &response)
+    }
+
+    pub async fn post_form<T: DeserializeOwned>(&self, url: &str, form: Form) -> OpenAIResult<T> {
+        self.post_form_idempotent(url, form, None).await
+    }
+
+    /// Send a multipart `POST` request with the `Idempotency-Key` header set to `idempotency_key`
+    /// if there is one, so the API recognizes its retries and resubmissions instead of creating
+    /// duplicates.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the decoded response on success, or an [`OpenAIError`] on failure.
+    pub async fn post_form_idempotent<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: Form,
+        idempotency_key: Option<&str>,
+    ) -> OpenAIResult<T> {
+        self.send_form(url, form, idempotency_key, None).await
+    }
+
+    /// Send a multipart `POST` request for `model`, e.g. a transcription, whose Azure deployment
+    /// can't be read from the streamed form.
+    pub(crate) async fn post_model_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: Form,
+        model: Option<&str>,
+    ) -> OpenAIResult<T> {
+        self.send_form(url, form, None, model).await
+    }
+
+    async fn send_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: Form,
+        idempotency_key: Option<&str>,
+        model: Option<&str>,
+    ) -> OpenAIResult<T> {
+        let mut builder = self.request(Method::POST, url).multipart(form);
+        if let Some(timeout) = self.inner.call_timeout.or(self.inner.upload_timeout) {
+            builder = builder.timeout(timeout);
+        }
+
+        let response = self
+            .send_for_model(Self::idempotency_key(builder, idempotency_key), model)
+            .await?;
+
+        self.decode_response(url, &response)
+    }
+
+    pub async fn delete<T: DeserializeOwned>(&self, url: &str) -> OpenAIResult<T> {
+        let response = self.send(self.json_request(Method::DELETE, url)).await?;
+
+        self.decode_response(url, &response)
+    }
+
+    /// Send a `method` request to `path` (relative to the base URL) with an optional JSON `body`,
+    /// and return its response as is, e.g. for endpoints this crate doesn't wrap yet or to inspect
+    /// an unexpected payload.
+    ///
+    /// The request goes through the same authentication, retries and interceptors as the typed
+    /// APIs, but error statuses are returned rather than turned into an [`OpenAIError::ApiError`].
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the status, headers and body of the response on success, or an [`OpenAIError`] if no response was received.
+    pub async fn request_raw(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> OpenAIResult<RawResponse> {
+        let mut builder = self.json_request(method, &raw::normalize(path));
+        if let Some(body) = body {
+            builder = builder.header(CONTENT_TYPE, "application/json").body(body);
+        }
+
+        let response = self.send(builder).await?;
+
+        Ok(RawResponse {
+            status: response.status,
+            headers: response.headers,
+            body: response.body.into(),
+        })
+    }
+
+    /// Render the request made by `call` as an equivalent `curl` command instead of sending it, e.g.
+    /// `openai.curl(openai.completions().create(request)).await?`.
+    ///
+    /// The API key isn't included: the command reads it from the `OPENAI_API_KEY` environment variable.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the command on success, or an [`OpenAIError`] if the call failed before making a request.
+    pub async fn curl<T>(
+        &self,
+        call: impl Future<Output = OpenAIResult<T>>,
+    ) -> OpenAIResult<String> {
+        Ok(self.prepare(call).await?.to_curl())
+    }
+
+    /// Prepare the request made by `call` without sending it, e.g.
+    /// `openai.dry_run(openai.completions().create(request)).await?`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the method, URL, redacted headers and body of the request on success, or an [`OpenAIError`] if the call failed before making a request.
+    pub async fn dry_run<T>(
+        &self,
+        call: impl Future<Output = OpenAIResult<T>>,
+    ) -> OpenAIResult<PreparedRequest> {
+        self.prepare(call).await
+    }
+
+    /// Run `call` and return its result along with the metadata of its last response (request ID,
+    /// processing time, rate limits and headers), e.g.
+    /// `openai.with_meta(openai.completions().create(request)).await?.meta.request_id`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the result of the call and the metadata on success, or an [`OpenAIError`] if the call failed.
+    pub async fn with_meta<T>(
+        &self,
+        call: impl Future<Output = OpenAIResult<T>>,
+    ) -> OpenAIResult<WithMeta<T>> {
+        let meta = Arc::new(Mutex::new(None));
+        let data = META.scope(Arc::clone(&meta), call).await?;
+        let meta = meta.lock().unwrap().take().unwrap_or_default();
+
+        Ok(WithMeta { data, meta })
+    }
+
+    /// Run `call` up to its first request, capturing that request instead of sending it.
+    async fn prepare<T>(
+        &self,
+        call: impl Future<Output = OpenAIResult<T>>,
+    ) -> OpenAIResult<PreparedRequest> {
+        let capture = Arc::new(Mutex::new(None));
+        let result = CAPTURE.scope(Arc::clone(&capture), call).await;
+        let prepared = capture.lock().unwrap().take();
+
+        match (prepared, result) {
+            (Some(prepared), _) => Ok(prepared),
+            (None, Err(error)) => Err(error),
+            (None, Ok(_)) => Err(OpenAIError::UnexpectedResponse(
+                "the call didn't make any request".to_string(),
+            )),
+        }
+    }
+
+    /// Encode a JSON request body to `url`, without the fields the provider or the model doesn't
+    /// support.
+    fn encode<B: Serialize + ?Sized>(&self, url: &str, body: &B) -> OpenAIResult<Vec<u8>> {
+        let gating = self
+            .inner
+            .parameter_gating
+            .as_ref()
+            .filter(|_| url == "/chat/completions");
+        if self.inner.unsupported_fields.is_empty() && gating.is_none() {
+            return Ok(serde_json::to_vec(body)?);
+        }
+
+        let mut body = serde_json::to_value(body)?;
+        if let Some(object) = body.as_object_mut() {
+            for field in &self.inner.unsupported_fields {
+                object.remove(field);
+            }
+        }
+        if let Some(gating) = gating {
+            gating.apply(&mut body);
+        }
+
+        Ok(serde_json::to_vec(&body)?)
+    }
+
+    /// Decode a JSON response body received from `url`, filling in the missing fields of chat
+    /// completions first in local compatibility mode.
+    fn decode<T: DeserializeOwned>(&self, url: &str, body: &[u8]) -> OpenAIResult<T> {
+        if self.inner.local_compat {
+            if let Some(body) = normalize_local_completion(body) {
+                return self.inner.unknown_fields.decode(url, &body);
+            }
+        }
+
+        self.inner.unknown_fields.decode(url, body)
+    }
+
+    /// Decode the body of a response received from `url`, failing with an
+    /// [`OpenAIError::ApiError`] if its status isn't successful.
+    fn decode_response<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        response: &ApiResponse,
+    ) -> OpenAIResult<T> {
+        if !response.status.is_success() {
+            return Err(self.api_error(response));
+        }
+
+        self.decode(url, &response.body)
+    }
+
+    /// The [`OpenAIError::ApiError`] of an error response, normalized first in local compatibility
+    /// mode.
+    fn api_error(&self, response: &ApiResponse) -> OpenAIError {
+        let normalized = self
+            .inner
+            .local_compat
+            .then(|| normalize_local_error(&response.body))
+            .flatten();
+
+        OpenAIError::api_error(
+            response.status,
+            response.request_id.clone(),
+            normalized.as_deref().unwrap_or(&response.body),
+        )
+    }
+
+    /// Whether requests are currently prepared instead of sent, in which case the response cache is bypassed.
+    fn is_preparing(&self) -> bool {
+        self.inner.dry_run || CAPTURE.try_with(|_| ()).is_ok()
+    }
+
+    pub fn get_base_url(&self) -> &str {
+        &self.inner.base_url
+    }
+
+    pub fn set_base_url(&mut self, base_url: impl Into<String>) {
+        self.inner_mut().base_url = base_url.into();
+
+        let policy = self.failover_policy().cloned();
+        self.set_failover_policy(policy);
+    }
+
+    /// A clone of this client sending the calls made with it to `base_url`, e.g.
+    /// `openai.with_base_url("http://localhost:8080/v1").embeddings()...` to route embeddings to
+    /// a local inference proxy while the other calls go to the API.
+    ///
+    /// The clone keeps the settings of this client and shares its request budget, but its failover
+    /// policy fails over from `base_url` and its circuit breaker and request coalescing only track
+    /// its own requests. The response cache is still shared, but its entries aren't shared across
+    /// base URLs, its keys including the full URL of the requests.
+    pub fn with_base_url(&self, base_url: impl Into<String>) -> Self {
+        let mut openai = self.clone();
+        openai.set_base_url(base_url);
+
+        let circuit_breaker = openai.circuit_breaker().cloned();
+        openai.set_circuit_breaker(circuit_breaker);
+        let request_coalescing = openai.request_coalescing();
+        openai.set_request_coalescing(request_coalescing);
+
+        openai
+    }
+
+    /// The base URL requests are currently sent to, which differs from [`OpenAI::get_base_url`]
+    /// while failed over to a fallback.
+    pub fn active_base_url(&self) -> &str {
+        match &self.inner.failover {
+            Some(failover) => failover.active_url(),
+            None => &self.inner.base_url,
+        }
+    }
+
+    /// Health of the base URL and of the fallback ones, empty without a failover policy.
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.inner
+            .failover
+            .as_ref()
+            .map(|failover| failover.health())
+            .unwrap_or_default()
+    }
+
+    pub fn failover_policy(&self) -> Option<&FailoverPolicy> {
+        self.inner
+            .failover
+            .as_ref()
+            .map(|failover| failover.policy())
+    }
+
+    /// Fail over to other base URLs when the base URL keeps failing, resetting the failover state.
+    pub fn set_failover_policy(&mut self, failover_policy: Option<FailoverPolicy>) {
+        let base_url = self.inner.base_url.clone();
+        self.inner_mut().failover = failover_policy
+            .map(|failover_policy| Arc::new(Failover::new(&base_url, failover_policy)));
+    }
+
+    /// Send the requests of this client with `transport` instead of over the network by its HTTP
+    /// client.
+    pub fn set_transport(&mut self, transport: Arc<dyn HttpTransport>) {
+        self.inner_mut().transport = transport;
+    }
+
+    /// Switch to an OpenAI-compatible provider: its base URL, authentication scheme and extra
+    /// headers replace the current ones.
+    pub fn set_provider(&mut self, provider: &Provider) {
+        self.set_base_url(provider.base_url());
+        self.apply_provider(provider);
+    }
+
+    /// Apply the settings of `provider` other than its base URL.
+    fn apply_provider(&mut self, provider: &Provider) {
+        let inner = self.inner_mut();
+
+        inner.auth_scheme = provider.auth_scheme();
+        inner.headers = provider.headers();
+        inner.local_compat = provider.needs_compat();
+        inner.unsupported_fields = provider
+            .unsupported_fields()
+            .iter()
+            .map(|field| field.to_string())
+            .collect();
+    }
+
+    pub fn azure_config(&self) -> Option<&AzureConfig> {
+        self.inner.azure.as_deref()
+    }
+
+    /// Switch to Azure OpenAI with `azure`, the API key being sent in the `api-key` header, or back
+    /// from it with `None`, the API key being sent as a bearer token again.
+    pub fn set_azure(&mut self, azure: Option<AzureConfig>) {
+        if azure.is_some() {
+            self.set_auth_scheme(AuthScheme::Header("api-key".to_string()));
+        } else if self.inner.azure.is_some() {
+            self.set_auth_scheme(AuthScheme::Bearer);
+        }
+        self.inner_mut().azure = azure.map(Arc::new);
+    }
+
+    pub fn auth_scheme(&self) -> &AuthScheme {
+        &self.inner.auth_scheme
+    }
+
+    /// Send the API key as `auth_scheme` describes.
+    pub fn set_auth_scheme(&mut self, auth_scheme: AuthScheme) {
+        self.inner_mut().auth_scheme = auth_scheme;
+    }
+
+    pub fn api_key_provider(&self) -> &Arc<dyn ApiKeyProvider> {
+        &self.inner.api_key
+    }
+
+    /// Authenticate every request with the key `api_key_provider` returns when it's sent, e.g. a
+    /// key rotated by a secret manager.
+    pub fn set_api_key_provider(&mut self, api_key_provider: Arc<dyn ApiKeyProvider>) {
+        self.inner_mut().api_key = api_key_provider;
+    }
+
+    /// Authenticate every request with `api_key`, replacing the client's [`ApiKeyProvider`].
+    pub fn set_api_key(&mut self, api_key: impl Into<String>) {
+        self.set_api_key_provider(Arc::new(StaticApiKey::new(api_key)));
+    }
+
+    /// The `User-Agent` sent with every request.
+    pub fn user_agent(&self) -> &str {
+        &self.inner.user_agent
+    }
+
+    /// Append an application identifier (e.g. `myapp/1.2`) to the crate's own `User-Agent`, giving
+    /// `rusty-openai/x.y.z myapp/1.2`.
+    pub fn set_app_info(&mut self, app_info: Option<&str>) {
+        self.inner_mut().user_agent = match app_info {
+            Some(app_info) => format!("{USER_AGENT} {app_info}"),
+            None => USER_AGENT.to_string(),
+        };
+    }
+
+    pub fn organization(&self) -> Option<&str> {
+        self.inner.organization.as_deref()
+    }
+
+    pub fn set_organization(&mut self, organization: Option<String>) {
+        self.inner_mut().organization = organization;
+    }
+
+    pub fn project(&self) -> Option<&str> {
+        self.inner.project.as_deref()
+    }
+
+    pub fn set_project(&mut self, project: Option<String>) {
+        self.inner_mut().project = project;
+    }
+
+    pub fn default_headers(&self) -> &[(String, String)] {
+        &self.inner.default_headers
+    }
+
+    /// Send `default_headers` with every request, replacing the current ones, e.g. the token of
+    /// an internal gateway or tracking IDs.
+    ///
+    /// The headers of a call (see [`OpenAI::with_options`]) replace the default headers of the
+    /// same name, and default `OpenAI-Beta` headers are merged with the beta features of the
+    /// client. Invalid headers fail the requests when they're built.
+    pub fn set_default_headers<I, N, V>(&mut self, default_headers: I)
+    where
+        I: IntoIterator<Item = (N, V)>,
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.inner_mut().default_headers = default_headers
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect();
+    }
+
+    /// Send the header `name` with `value` with every request, in addition to the current
+    /// default headers.
+    pub fn add_default_header(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.inner_mut()
+            .default_headers
+            .push((name.into(), value.into()));
+    }
+
+    pub fn beta_features(&self) -> &[String] {
+        &self.inner.beta_features
+    }
+
+    /// Opt in to beta features (e.g. `realtime=v1`) on every request, in the `OpenAI-Beta` header.
+    ///
+    /// `assistants=v2` is sent automatically to the assistants, threads and vector stores
+    /// endpoints unless another `assistants=` version is given here.
+    pub fn set_beta_features<I>(&mut self, beta_features: I)
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.inner_mut().beta_features = beta_features.into_iter().map(Into::into).collect();
+    }
+
+    /// A clone of this client also opting in to `beta_feature`, for the calls made with it, e.g.
+    /// `openai.with_beta_feature("realtime=v1").raw()...`.
+    pub fn with_beta_feature(&self, beta_feature: impl Into<String>) -> Self {
+        let mut openai = self.clone();
+        openai.inner_mut().beta_features.push(beta_feature.into());
+
+        openai
+    }
+
+    /// A clone of this client applying `options` to the calls made with it, in addition to the
+    /// options of this client if it was itself created with this method.
+    pub fn with_options(&self, options: RequestOptions) -> Self {
+        let mut openai = self.clone();
+        openai.inner_mut().call_headers.extend(options.headers);
+        if let Some(timeout) = options.timeout {
+            openai.inner_mut().call_timeout = Some(timeout);
+        }
+
+        openai
+    }
+
+    /// A clone of this client bounding each request of the calls made with it by `timeout`,
+    /// whatever their kind, e.g. `openai.with_timeout(Duration::from_secs(600)).audio()...` for a
+    /// long transcription.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        self.with_options(RequestOptions::new().timeout(timeout))
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.inner.retry_policy
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.inner_mut().retry_policy = retry_policy;
+    }
+
+    /// Rate limits reported by the API in its last response, if any.
+    ///
+    /// With several API keys (e.g. an [`ApiKeyPool`][crate::credentials::ApiKeyPool]), these are
+    /// the limits of whichever key authenticated that response: the adaptive rate limit and the
+    /// scheduler track the limits of each key, and
+    /// [`ApiKeyPool::health`][crate::credentials::ApiKeyPool::health] reports them.
+    pub fn rate_limits(&self) -> Option<RateLimits> {
+        self.inner.rate_limits.lock().unwrap().last()
+    }
+
+    pub fn adaptive_rate_limit(&self) -> bool {
+        self.inner.adaptive_rate_limit
+    }
+
+    /// Delay the requests of this client (and its clones) according to the rate limits reported
+    /// by the API, so they wait for the limits to reset instead of failing with `429`s. Each API
+    /// key (e.g. of an [`ApiKeyPool`][crate::credentials::ApiKeyPool]) is paced on its own limits.
+    pub fn set_adaptive_rate_limit(&mut self, adaptive_rate_limit: bool) {
+        self.inner_mut().adaptive_rate_limit = adaptive_rate_limit;
+    }
+
+    pub fn retry_budget(&self) -> Option<&RetryBudget> {
+        self.inner
+            .retry_balance
+            .as_ref()
+            .map(|retry_balance| retry_balance.budget())
+    }
+
+    /// Cap retries across all requests of this client (and its clones) with `retry_budget`,
+    /// starting with a full budget.
+    pub fn set_retry_budget(&mut self, retry_budget: Option<RetryBudget>) {
+        self.inner_mut().retry_balance =
+            retry_budget.map(|budget| Arc::new(RetryBalance::new(budget)));
+    }
+
+    pub fn scheduling_policy(&self) -> Option<&SchedulingPolicy> {
+        self.inner
+            .scheduler
+            .as_ref()
+            .map(|scheduler| scheduler.policy())
+    }
+
+    /// Queue the requests of this client (and its clones) by priority once they exceed
+    /// `scheduling_policy`.
+    pub fn set_scheduling_policy(&mut self, scheduling_policy: Option<SchedulingPolicy>) {
+        self.inner_mut().scheduler =
+            scheduling_policy.map(|policy| Arc::new(Scheduler::new(policy)));
+    }
+
+    /// Handle on the scheduler of this client, if it has a scheduling policy.
+    pub fn request_budget(&self) -> Option<RequestBudget> {
+        self.inner.scheduler.clone().map(RequestBudget)
+    }
+
+    /// Schedule the requests of this client (and its clones) within `request_budget`, sharing it
+    /// with the other clients using the same handle.
+    pub fn set_request_budget(&mut self, request_budget: Option<RequestBudget>) {
+        self.inner_mut().scheduler = request_budget.map(|budget| budget.0);
+    }
+
+    /// Priority of the requests of this client, `None` for the default one of each endpoint (see
+    /// [`Priority::for_path`]).
+    pub fn priority(&self) -> Option<Priority> {
+        self.inner.priority
+    }
+
+    pub fn set_priority(&mut self, priority: Option<Priority>) {
+        self.inner_mut().priority = priority;
+    }
+
+    /// A clone of this client sending its requests with `priority`, sharing the scheduler.
+    pub fn with_priority(&self, priority: Priority) -> Self {
+        let mut openai = self.clone();
+        openai.set_priority(Some(priority));
+
+        openai
+    }
+
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreakerPolicy> {
+        self.inner
+            .circuit_breaker
+            .as_ref()
+            .map(|breaker| breaker.policy())
+    }
+
+    /// Fail requests fast while the API keeps failing, starting with a closed circuit.
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: Option<CircuitBreakerPolicy>) {
+        self.inner_mut().circuit_breaker =
+            circuit_breaker.map(|policy| Arc::new(CircuitBreaker::new(policy)));
+    }
+
+    /// State of the circuit breaker, `None` without one.
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.inner
+            .circuit_breaker
+            .as_ref()
+            .map(|breaker| breaker.state())
+    }
+
+    pub fn hedge_policy(&self) -> Option<&HedgePolicy> {
+        self.inner.hedge_policy.as_ref()
+    }
+
+    /// Hedge slow idempotent requests with a second concurrent attempt.
+    pub fn set_hedge_policy(&mut self, hedge_policy: Option<HedgePolicy>) {
+        self.inner_mut().hedge_policy = hedge_policy;
+    }
+
+    pub fn request_coalescing(&self) -> bool {
+        self.inner.coalescer.is_some()
+    }
+
+    /// Share a single HTTP call between identical concurrent `GET` requests, e.g. several tasks
+    /// polling the same run.
+    ///
+    /// Requests are identical when their URL and headers are, API key included: clones with other
+    /// credentials or call options never share responses, and neither do requests sent with
+    /// different keys of an [`ApiKeyPool`][crate::credentials::ApiKeyPool].
+    pub fn set_request_coalescing(&mut self, enabled: bool) {
+        self.inner_mut().coalescer = enabled.then(Arc::default);
+    }
+
+    pub fn stream_chunk_size(&self) -> usize {
+        self.inner.stream_chunk_size
+    }
+
+    /// Set the size of the buffer used to stream file uploads and downloads, bounding their memory
+    /// use whatever the file size.
+    pub fn set_stream_chunk_size(&mut self, stream_chunk_size: usize) {
+        self.inner_mut().stream_chunk_size = stream_chunk_size.max(1);
+    }
+
+    pub fn json_timeout(&self) -> Option<Duration> {
+        self.inner.json_timeout
+    }
+
+    /// Set the total timeout of the JSON requests (chat completions, embeddings, listings...),
+    /// overriding the timeout of the HTTP client for them, or `None` to keep the HTTP client's.
+    pub fn set_json_timeout(&mut self, json_timeout: Option<Duration>) {
+        self.inner_mut().json_timeout = json_timeout;
+    }
+
+    pub fn upload_timeout(&self) -> Option<Duration> {
+        self.inner.upload_timeout
+    }
+
+    /// Set the total timeout of the multipart uploads (audio, images, files), overriding the
+    /// timeout of the HTTP client for them, e.g. to give large files minutes where chat calls get
+    /// seconds, or `None` to keep the HTTP client's.
+    pub fn set_upload_timeout(&mut self, upload_timeout: Option<Duration>) {
+        self.inner_mut().upload_timeout = upload_timeout;
+    }
+
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.inner.compression_threshold
+    }
+
+    /// Gzip JSON request bodies of at least `compression_threshold` bytes (e.g. long message
+    /// histories or large tool schemas), for gateways accepting `Content-Encoding: gzip`.
+    pub fn set_compression_threshold(&mut self, compression_threshold: Option<usize>) {
+        self.inner_mut().compression_threshold = compression_threshold;
+    }
+
+    pub fn default_models(&self) -> &DefaultModels {
+        &self.inner.default_models
+    }
+
+    pub fn set_default_models(&mut self, default_models: DefaultModels) {
+        self.inner_mut().default_models = default_models;
+    }
+
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.inner.response_cache.as_ref()
+    }
+
+    pub fn set_response_cache(&mut self, response_cache: Option<ResponseCache>) {
+        self.inner_mut().response_cache = response_cache;
+    }
+
+    pub fn metrics_sink(&self) -> Option<&Arc<dyn MetricsSink>> {
+        self.inner.metrics_sink.as_ref()
+    }
+
+    /// Report the duration, status, retry count and token usage of every request to `metrics_sink`.
+    pub fn set_metrics_sink(&mut self, metrics_sink: Option<Arc<dyn MetricsSink>>) {
+        self.inner_mut().metrics_sink = metrics_sink;
+    }
+
+    pub fn interceptors(&self) -> &[Arc<dyn Interceptor>] {
+        &self.inner.interceptors
+    }
+
+    /// Run `interceptor` on the requests and responses of this client, after the interceptors
+    /// already added.
+    pub fn add_interceptor(&mut self, interceptor: Arc<dyn Interceptor>) {
+        self.inner_mut().interceptors.push(interceptor);
+    }
+
+    /// Replace the interceptors of this client.
+    pub fn set_interceptors<I>(&mut self, interceptors: I)
+    where
+        I: IntoIterator<Item = Arc<dyn Interceptor>>,
+    {
+        self.inner_mut().interceptors = interceptors.into_iter().collect();
+    }
+
+    /// Call `handler` with a [`ClientEvent`] for every request start and finish, retry, rate limit
+    /// and stream chunk of this client.
+    pub fn subscribe(
+        &self,
+        handler: impl Fn(&ClientEvent) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.inner.events.subscribe(Arc::new(handler))
+    }
+
+    /// Remove a handler added with [`OpenAI::subscribe`], returning whether it was registered.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.inner.events.unsubscribe(id)
+    }
+
+    /// Publish a [`ClientEvent::StreamChunk`] event for every chunk of `stream`.
+    pub fn observe_stream<S>(
+        &self,
+        stream: S,
+    ) -> InstrumentedStream<S, impl FnMut(&ChunkMetrics) + Unpin>
+    where
+        S: Stream + Unpin,
+        S::Item: ChunkTokens,
+    {
+        let events = Arc::clone(&self.inner.events);
+
+        InstrumentedStream::new(stream, move |metrics: &ChunkMetrics| {
+            events.publish(&ClientEvent::StreamChunk { metrics: *metrics })
+        })
+    }
+
+    pub fn dry_run_enabled(&self) -> bool {
+        self.inner.dry_run
+    }
+
+    /// Stop sending requests: every call then fails with an [`OpenAIError::DryRun`] holding the
+    /// [`PreparedRequest`] it would have sent first, streamed calls included.
+    ///
+    /// See [`OpenAI::dry_run`] to prepare a single call.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.inner_mut().dry_run = dry_run;
+    }
+
+    pub fn unknown_fields(&self) -> &UnknownFields {
+        &self.inner.unknown_fields
+    }
+
+    pub fn local_compat(&self) -> bool {
+        self.inner.local_compat
+    }
+
+    /// Tolerate the responses of local OpenAI-compatible servers and of Gemini: their error envelopes are
+    /// rewritten into the OpenAI shape (`{"error": {"message": ...}}`) and the fields they omit
+    /// from chat completions (`system_fingerprint`, `usage`...) are filled in before decoding.
+    pub fn set_local_compat(&mut self, local_compat: bool) {
+        self.inner_mut().local_compat = local_compat;
+    }
+
+    pub fn unsupported_fields(&self) -> &[String] {
+        &self.inner.unsupported_fields
+    }
+
+    /// Remove these top-level fields from every JSON request body, for providers rejecting
+    /// parameters of the OpenAI API.
+    pub fn set_unsupported_fields<I>(&mut self, unsupported_fields: I)
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.inner_mut().unsupported_fields =
+            unsupported_fields.into_iter().map(Into::into).collect();
+    }
+
+    pub fn parameter_gating(&self) -> Option<&ParameterGating> {
+        self.inner.parameter_gating.as_deref()
+    }
+
+    /// Omit the chat completion parameters the target model doesn't support instead of sending
+    /// them and failing.
+    pub fn set_parameter_gating(&mut self, parameter_gating: Option<ParameterGating>) {
+        self.inner_mut().parameter_gating = parameter_gating.map(Arc::new);
+    }
+
+    pub fn request_signer(&self) -> Option<&RequestSigner> {
+        self.inner.request_signer.as_ref()
+    }
+
+    /// Sign every request with `request_signer` right before it's sent.
+    pub fn set_request_signer(&mut self, request_signer: Option<RequestSigner>) {
+        self.inner_mut().request_signer = request_signer;
+    }
+
+    /// Choose what happens to response fields the requested type doesn't declare.
+    pub fn set_unknown_fields(&mut self, unknown_fields: UnknownFields) {
+        self.inner_mut().unknown_fields = unknown_fields;
+    }
+
+    /// Record or replay every request of this client through `cassette`.
+    #[cfg(feature = "test-util")]
+    pub fn set_cassette(&mut self, cassette: Option<Arc<crate::test_util::cassette::Cassette>>) {
+        self.inner_mut().cassette = cassette;
+    }
+
+    #[cfg(feature = "tracing")]
+    pub fn body_logging(&self) -> Option<usize> {
+        self.inner.body_logging
+    }
+
+    /// Log every request and response at the debug level, with their headers and their bodies
+    /// truncated to `max_body_len` bytes, e.g. to see which field of a payload the API rejects
+    /// with a vague `400`. The credential headers and the API key are redacted.
+    ///
+    /// The bodies may contain personal data, so this is meant for debugging only.
+    #[cfg(feature = "tracing")]
+    pub fn set_body_logging(&mut self, max_body_len: Option<usize>) {
+        self.inner_mut().body_logging = max_body_len;
+    }
+
+    pub fn client(&self) -> ClientApi {
+        ClientApi(self.clone())
+    }
+
+    pub fn completions(&self) -> CompletionsApi {
+        CompletionsApi(self.clone())
+    }
+
+    pub fn audio(&self) -> crate::openai_api::audio::AudioApi {
+        crate::openai_api::audio::AudioApi(self.clone())
+    }
+
+    pub fn images(&self) -> ImagesApi {
+        ImagesApi(self.clone())
+    }
+
+    pub fn fine_tuning(&self) -> FineTuningApi {
+        FineTuningApi(self.clone())
+    }
+
+    pub fn moderation(&self) -> ModerationApi {
+        ModerationApi(self.clone())
+    }
+
+    pub fn embeddings(&self) -> EmbeddingsApi {
+        EmbeddingsApi(self.clone())
+    }
+
+    pub fn evals(&self) -> EvalsApi {
+        EvalsApi(self.clone())
+    }
+
+    pub fn assistants(&self) -> AssistantsApi {
+        AssistantsApi(self.clone())
+    }
+
+    pub fn threads(&self) -> ThreadsApi {
+        ThreadsApi(self.clone())
+    }
+
+    pub fn vectors(&self) -> VectorsApi {
+        VectorsApi(self.clone())
+    }
+
+    pub fn projects(&self) -> ProjectsApi {
+        ProjectsApi(self.clone())
+    }
+
+    pub fn raw(&self) -> RawApi {
+        RawApi(self.clone())
+    }
+}
+
+/// Status of the last response received for the request of `context`, if any.
+fn last_status(context: &RequestContext) -> Option<u16> {
+    context
+        .attempts
+        .iter()
+        .rev()
+        .find_map(|attempt| attempt.status)
+}
+
+/// Whether `name` is the `OpenAI-Beta` header, whatever its case.
+fn is_beta_header(name: &str) -> bool {
+    name.eq_ignore_ascii_case("OpenAI-Beta")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> OpenAI {
+        OpenAI::new("sk-test", "https://api.openai.com/v1")
+    }
+
+    #[test]
+    fn assistants_endpoints_get_the_assistants_beta() {
+        let openai = client();
+
+        for url in [
+            "/assistants",
+            "/threads/thread_1/runs",
+            "/vector_stores/vs_1/files",
+        ] {
+            assert_eq!(openai.beta_header(url).as_deref(), Some(ASSISTANTS_BETA));
+        }
+        assert_eq!(openai.beta_header("/chat/completions"), None);
+    }
+
+    #[test]
+    fn beta_features_are_merged_with_the_assistants_beta() {
+        let mut openai = client();
+        openai.set_beta_features(["realtime=v1"]);
+        openai.add_default_header("openai-beta", "evals=v1, ");
+
+        assert_eq!(
+            openai.beta_header("/threads").as_deref(),
+            Some("assistants=v2,realtime=v1,evals=v1")
+        );
+        assert_eq!(
+            openai.beta_header("/chat/completions").as_deref(),
+            Some("realtime=v1,evals=v1")
+        );
+    }
+
+    #[test]
+    fn other_assistants_versions_replace_the_default_one() {
+        let mut openai = client();
+        openai.set_beta_features(["assistants=v1"]);
+        assert_eq!(
+            openai.beta_header("/assistants").as_deref(),
+            Some("assistants=v1")
+        );
+
+        let mut openai = client();
+        openai.add_default_header("OpenAI-Beta", "assistants=v3");
+        assert_eq!(
+            openai.beta_header("/vector_stores").as_deref(),
+            Some("assistants=v3")
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_send_a_single_beta_header() {
+        let mut openai = client();
+        openai.add_default_header("OpenAI-Beta", "assistants=v1");
+
+        let prepared = openai
+            .dry_run(openai.raw().get::<serde_json::Value>("/threads/thread_1"))
+            .await
+            .unwrap();
+
+        let beta: Vec<&str> = prepared
+            .headers
+            .iter()
+            .filter(|(name, _)| is_beta_header(name))
+            .map(|(_, value)| value.as_str())
+            .collect();
+        assert_eq!(beta, ["assistants=v1"]);
+    }
+
+    #[cfg(feature = "test-util")]
+    mod list_stream {
+        use super::*;
+        use crate::test_util::mock_transport::{MockResponse, MockTransport};
+
+        async fn ids(body: &str) -> Vec<OpenAIResult<String>> {
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::GET,
+                "/files",
+                MockResponse::raw(body, "application/json"),
+            );
+
+            let items = mock
+                .client()
+                .list_stream::<serde_json::Value>("/files")
+                .await
+                .unwrap();
+            items
+                .map(|item| item.map(|item| item["id"].as_str().unwrap().to_string()))
+                .collect()
+                .await
+        }
+
+        #[tokio::test]
+        async fn items_are_decoded_until_the_end_of_the_list() {
+            let ids =
+                ids(r#"{"object":"list","data":[{"id":"a"},{"id":"b"}],"has_more":false}"#).await;
+
+            assert_eq!(
+                ids.into_iter().collect::<OpenAIResult<Vec<_>>>().unwrap(),
+                ["a", "b"]
+            );
+        }
+
+        #[tokio::test]
+        async fn truncated_lists_end_with_an_error() {
+            let ids = ids(r#"{"object":"list","data":[{"id":"a"},{"id":"b"},{"id":"#).await;
+
+            assert!(matches!(
+                &ids[..],
+                [Ok(a), Ok(b), Err(OpenAIError::UnexpectedResponse(message))]
+                    if a == "a" && b == "b" && message == "list response ended early"
+            ));
+        }
     }
 }
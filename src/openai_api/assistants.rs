@@ -1,15 +1,25 @@
-use crate::{error_handling::OpenAIResult, extend_url_params, openai::OpenAI, setters};
+use crate::{
+    error_handling::OpenAIResult,
+    extend_url_params,
+    model::Model,
+    openai::OpenAI,
+    openai_api::pagination::{paginate, After, AssistantId, Before, Order},
+    request_builder,
+    util::check_range,
+};
+use futures::Stream;
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 /// [`AssistantsApi`] struct to interact with the assistants endpoints of the API.
-pub struct AssistantsApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct AssistantsApi(pub(crate) OpenAI);
 
 /// Struct representing a request for creating or modifying an assistant.
-#[derive(Default, Serialize)]
+#[derive(Serialize)]
 pub struct AssistantCreationRequest {
     /// Model name to be used for the assistant
-    model: String,
+    model: Model,
 
     /// Name for the assistant
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -46,11 +56,19 @@ pub struct AssistantCreationRequest {
     /// Format of responses from the assistant
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<Value>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 /// Struct representing a request for creating or modifying an assistant.
 #[derive(Default, Serialize)]
 pub struct AssistantModificationRequest {
+    /// Model name to be used for the assistant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<Model>,
+
     /// Name for the assistant
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
@@ -86,37 +104,61 @@ pub struct AssistantModificationRequest {
     /// Format of responses from the assistant
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<Value>,
-}
-
-macro_rules! assistant_creation_impl {
-    () => {
-        setters! {
-            /// Set the name for the assistant request.
-            name: String,
-
-            /// Set the description for the assistant request.
-            description: String,
 
-            /// Set the instructions for the assistant request.
-            instructions: String,
-
-            /// Set the tools for the assistant request.
-            tools: Vec<Value>,
-
-            /// Set the tool resources for the assistant request.
-            tool_resources: Value,
-
-            /// Set the metadata for the assistant request.
-            metadata: Value,
-
-            /// Set the temperature for the assistant request.
-            temperature: f64,
-
-            /// Set the top_p parameter for the assistant request.
-            top_p: f64,
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
 
-            /// Set the response format for the assistant request.
-            response_format: Value,
+/// Builder of an assistant request, with the optional fields shared by creations and
+/// modifications.
+macro_rules! assistant_request_builder {
+    (
+        $(#[$builder_attributes:meta])*
+        $request:ident => $builder:ident {
+            required { $($required:tt)* }
+            optional { $($optional:tt)* }
+        }
+    ) => {
+        request_builder! {
+            $(#[$builder_attributes])*
+            $request => $builder {
+                required { $($required)* }
+                optional {
+                    $($optional)*
+
+                    /// Set the name for the assistant request.
+                    name: String,
+
+                    /// Set the description for the assistant request.
+                    description: String,
+
+                    /// Set the instructions for the assistant request.
+                    instructions: String,
+
+                    /// Set the tools for the assistant request.
+                    tools: Vec<Value>,
+
+                    /// Set the tool resources for the assistant request.
+                    tool_resources: Value,
+
+                    /// Set the metadata for the assistant request.
+                    metadata: Value,
+
+                    /// Set the temperature for the assistant request, between 0 and 2.
+                    temperature: f64,
+
+                    /// Set the top_p parameter for the assistant request, between 0 and 1.
+                    top_p: f64,
+
+                    /// Set the response format for the assistant request.
+                    response_format: Value,
+                }
+                validate: |request: &$request| {
+                    check_range("temperature", request.temperature, 0.0..=2.0)?;
+                    check_range("top_p", request.top_p, 0.0..=1.0)
+                },
+            }
         }
     };
 }
@@ -131,21 +173,46 @@ impl AssistantCreationRequest {
     /// # Returns
     ///
     /// A new instance of [`AssistantCreationRequest`].
-    pub fn new(model: String) -> Self {
+    pub fn new(model: impl Into<Model>) -> Self {
         Self {
-            model,
-            ..Default::default()
+            model: model.into(),
+            name: None,
+            description: None,
+            instructions: None,
+            tools: None,
+            tool_resources: None,
+            metadata: None,
+            temperature: None,
+            top_p: None,
+            response_format: None,
+            extra: Map::new(),
         }
     }
+}
 
-    assistant_creation_impl!();
+assistant_request_builder! {
+    /// Builder of an [`AssistantCreationRequest`].
+    AssistantCreationRequest => AssistantCreationRequestBuilder {
+        required {
+            /// Set the model for the assistant request.
+            model: Model,
+        }
+        optional {}
+    }
 }
 
-impl AssistantModificationRequest {
-    assistant_creation_impl!();
+assistant_request_builder! {
+    /// Builder of an [`AssistantModificationRequest`].
+    AssistantModificationRequest => AssistantModificationRequestBuilder {
+        required {}
+        optional {
+            /// Set the model for the assistant request.
+            model: Model,
+        }
+    }
 }
 
-impl<'a> AssistantsApi<'a> {
+impl AssistantsApi {
     /// Create a new assistant using the provided request parameters.
     ///
     /// # Arguments
@@ -166,8 +233,8 @@ impl<'a> AssistantsApi<'a> {
     ///
     /// * `limit` - Maximum number of assistants to retrieve.
     /// * `order` - Order of the retrieved assistants.
-    /// * `after` - Retrieve assistants created after this ID.
-    /// * `before` - Retrieve assistants created before this ID.
+    /// * `after` - Retrieve the assistants following this one.
+    /// * `before` - Retrieve the assistants preceding this one.
     ///
     /// # Returns
     ///
@@ -175,9 +242,9 @@ impl<'a> AssistantsApi<'a> {
     pub async fn list(
         &self,
         limit: Option<u32>,
-        order: Option<&str>,
-        after: Option<&str>,
-        before: Option<&str>,
+        order: Option<Order>,
+        after: Option<After<AssistantId>>,
+        before: Option<Before<AssistantId>>,
     ) -> OpenAIResult<Value> {
         let mut url = String::from("/assistants?");
 
@@ -187,6 +254,30 @@ impl<'a> AssistantsApi<'a> {
         self.0.get(&url).await
     }
 
+    /// Stream every assistant, fetching the pages of [`AssistantsApi::list`] as the stream is
+    /// polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Number of assistants fetched per page.
+    /// * `order` - Order of the assistants.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the assistants as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all(
+        &self,
+        limit: Option<u32>,
+        order: Option<Order>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+
+        paginate(move |after| {
+            let api = AssistantsApi(openai.clone());
+            async move { api.list(limit, order, after.map(After::from), None).await }
+        })
+    }
+
     /// Retrieve details of a specific assistant.
     ///
     /// # Arguments
@@ -1,30 +1,34 @@
-use crate::{error_handling::OpenAIResult, extend_form_text_fields, openai::OpenAI};
+#[cfg(feature = "fs")]
+use crate::util::file_part;
+use crate::{
+    config::required_model, error_handling::OpenAIResult, extend_form_text_fields, model::Model,
+    openai::OpenAI,
+};
 use reqwest::multipart;
 use serde_json::Value;
-use tokio::fs;
 
 /// [`AudioApi`] struct to interact with the audio transcription and translation API.
-pub struct AudioApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct AudioApi(pub(crate) OpenAI);
 
-impl<'a> AudioApi<'a> {
-    /// Transcribe an audio file using the specified model.
+impl AudioApi {
+    /// Transcribe an audio file using the specified model, or the client's default audio model when `None`.
+    #[cfg(feature = "fs")]
     pub async fn transcribe(
         &self,
-        model: &str,                   // The transcription model to use
+        model: Option<Model>,          // The transcription model to use
         file_path: &str,               // Path to the audio file
         prompt: Option<&str>,          // Optional prompt to guide transcription
         response_format: Option<&str>, // Optional response format (e.g., "text", "json")
         temperature: Option<f64>,      // Optional temperature setting for response generation
         language: Option<&str>,        // Optional language hint for the transcription
     ) -> OpenAIResult<Value> {
-        // Open the audio file asynchronously
-        let buffer = fs::read(file_path).await?;
+        let model = required_model(model.as_ref(), &self.0.default_models().audio, "audio")?;
 
-        // Create multipart form with required fields
-        let file_part = multipart::Part::bytes(buffer)
-            .file_name(file_path.to_string())
-            .mime_str("audio/mpeg")?;
+        // Stream the audio file from disk
+        let file_part = file_part(file_path, "audio/mpeg", self.0.stream_chunk_size()).await?;
 
+        // Create multipart form with required fields
         let mut form = multipart::Form::new()
             .text("model", model.to_string())
             .part("file", file_part);
@@ -32,26 +36,27 @@ impl<'a> AudioApi<'a> {
         extend_form_text_fields!(form, prompt, response_format, temperature, language);
 
         // Make HTTP POST request to the transcription API
-        self.0.post_form("/audio/transcriptions", form).await
+        self.0
+            .post_model_form("/audio/transcriptions", form, Some(model))
+            .await
     }
 
-    /// Translate an audio file using the specified model.
+    /// Translate an audio file using the specified model, or the client's default audio model when `None`.
+    #[cfg(feature = "fs")]
     pub async fn translate(
         &self,
-        model: &str,                   // The translation model to use
+        model: Option<Model>,          // The translation model to use
         file_path: &str,               // Path to the audio file
         prompt: Option<&str>,          // Optional prompt to guide translation
         response_format: Option<&str>, // Optional response format (e.g., "text", "json")
         temperature: Option<f64>,      // Optional temperature setting for response generation
     ) -> OpenAIResult<Value> {
-        // Open the audio file asynchronously
-        let buffer = fs::read(file_path).await?;
+        let model = required_model(model.as_ref(), &self.0.default_models().audio, "audio")?;
 
-        // Create multipart form with required fields
-        let file_part = multipart::Part::bytes(buffer)
-            .file_name(file_path.to_string())
-            .mime_str("audio/mpeg")?;
+        // Stream the audio file from disk
+        let file_part = file_part(file_path, "audio/mpeg", self.0.stream_chunk_size()).await?;
 
+        // Create multipart form with required fields
         let mut form = multipart::Form::new()
             .text("model", model.to_string())
             .part("file", file_part);
@@ -59,6 +64,106 @@ impl<'a> AudioApi<'a> {
         extend_form_text_fields!(form, prompt, response_format, temperature);
 
         // Make HTTP POST request to the translation API
-        self.0.post_form("/audio/translations", form).await
+        self.0
+            .post_model_form("/audio/translations", form, Some(model))
+            .await
+    }
+
+    /// Transcribe audio held in memory, e.g. a recording that was never written to disk, using
+    /// the specified model, or the client's default audio model when `None`.
+    pub async fn transcribe_bytes(
+        &self,
+        model: Option<Model>,          // The transcription model to use
+        audio: Vec<u8>,                // Bytes of the audio
+        file_name: &str,               // File name whose extension tells the audio format
+        prompt: Option<&str>,          // Optional prompt to guide transcription
+        response_format: Option<&str>, // Optional response format (e.g., "text", "json")
+        temperature: Option<f64>,      // Optional temperature setting for response generation
+        language: Option<&str>,        // Optional language hint for the transcription
+    ) -> OpenAIResult<Value> {
+        let model = required_model(model.as_ref(), &self.0.default_models().audio, "audio")?;
+
+        let mut form = multipart::Form::new()
+            .text("model", model.to_string())
+            .part("file", audio_part(audio, file_name)?);
+
+        extend_form_text_fields!(form, prompt, response_format, temperature, language);
+
+        self.0
+            .post_model_form("/audio/transcriptions", form, Some(model))
+            .await
+    }
+
+    /// Translate audio held in memory using the specified model, or the client's default audio
+    /// model when `None`.
+    pub async fn translate_bytes(
+        &self,
+        model: Option<Model>,          // The translation model to use
+        audio: Vec<u8>,                // Bytes of the audio
+        file_name: &str,               // File name whose extension tells the audio format
+        prompt: Option<&str>,          // Optional prompt to guide translation
+        response_format: Option<&str>, // Optional response format (e.g., "text", "json")
+        temperature: Option<f64>,      // Optional temperature setting for response generation
+    ) -> OpenAIResult<Value> {
+        let model = required_model(model.as_ref(), &self.0.default_models().audio, "audio")?;
+
+        let mut form = multipart::Form::new()
+            .text("model", model.to_string())
+            .part("file", audio_part(audio, file_name)?);
+
+        extend_form_text_fields!(form, prompt, response_format, temperature);
+
+        self.0
+            .post_model_form("/audio/translations", form, Some(model))
+            .await
+    }
+}
+
+fn audio_part(bytes: Vec<u8>, file_name: &str) -> OpenAIResult<multipart::Part> {
+    Ok(multipart::Part::bytes(bytes)
+        .file_name(file_name.to_string())
+        .mime_str("audio/mpeg")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{openai::OpenAI, prepared::PreparedBody};
+
+    #[tokio::test]
+    async fn audio_bytes_are_sent_as_a_file_field() {
+        let openai = OpenAI::new("sk-test", "https://api.openai.com/v1");
+
+        let prepared = openai
+            .dry_run(openai.audio().transcribe_bytes(
+                Some("whisper-1".into()),
+                b"ID3".to_vec(),
+                "memo.m4a",
+                None,
+                Some("srt"),
+                None,
+                Some("fr"),
+            ))
+            .await
+            .unwrap();
+
+        assert!(prepared.url.ends_with("/audio/transcriptions"));
+        let PreparedBody::Multipart(parts) = prepared.body else {
+            panic!("expected a multipart body");
+        };
+        let fields: Vec<_> = parts
+            .iter()
+            .map(|part| (part.name.as_str(), part.value.as_deref()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("model", Some("whisper-1")),
+                ("file", None),
+                ("response_format", Some("srt")),
+                ("language", Some("fr")),
+            ]
+        );
+        assert_eq!(parts[1].file_name.as_deref(), Some("memo.m4a"));
+        assert_eq!(parts[1].length, 3);
     }
 }
@@ -2,9 +2,10 @@ use crate::{error_handling::OpenAIResult, openai::OpenAI};
 use serde_json::Value;
 
 /// [`ClientApi`] struct to interact with the models endpoint of the API.
-pub struct ClientApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct ClientApi(pub(crate) OpenAI);
 
-impl<'a> ClientApi<'a> {
+impl ClientApi {
     /// Fetch the list of available models from the API.
     ///
     /// # Returns
@@ -1,15 +1,33 @@
-use crate::{error_handling::OpenAIResult, openai::OpenAI, setters};
-use serde::Serialize;
-use serde_json::Value;
+use crate::{
+    capabilities::model_info,
+    chunking::{ApproximateTokenCounter, TokenCounter},
+    config::required_model,
+    deserialize_with_extra,
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::types::Usage,
+    request_builder,
+    stream::{ChunkTokens, ServerSentEvent},
+    structured::{self, StructuredOutput},
+    util::check_range,
+};
+use futures::{Stream, StreamExt};
+#[cfg(feature = "schemars")]
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 
 /// [`CompletionsApi`] struct to interact with the chat completions endpoint of the API.
-pub struct CompletionsApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct CompletionsApi(pub(crate) OpenAI);
 
 /// Struct representing a request for chat completions.
-#[derive(Default, Serialize)]
+#[derive(Clone, Default, Serialize)]
 pub struct ChatCompletionRequest {
     /// Model name to be used for the chat completion
-    model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<Model>,
 
     /// History of messages in the conversation
     messages: Vec<Value>,
@@ -34,6 +52,10 @@ pub struct ChatCompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
 
+    /// Options of a streamed completion, e.g. `{"include_usage": true}` for a final usage chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<Value>,
+
     /// Sequence to stop generation
     #[serde(skip_serializing_if = "Option::is_none")]
     stop: Option<Vec<String>>,
@@ -53,47 +75,796 @@ pub struct ChatCompletionRequest {
     /// User ID
     #[serde(skip_serializing_if = "Option::is_none")]
     user: Option<String>,
+
+    /// Format of the response, e.g. a JSON schema for structured outputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+
+    /// Whether to return the log probabilities of the output tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+
+    /// Number of most likely alternatives returned with the log probability of each output token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u64>,
+
+    /// Tools (functions) the model may call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Value>>,
+
+    /// Controls which tool, if any, the model calls (`none`, `auto`, `required` or a specific tool)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+
+    /// Key sent as the `Idempotency-Key` header, so retries and resubmissions don't create duplicates
+    #[serde(skip)]
+    idempotency_key: Option<String>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// Result of [`CompletionsApi::classify`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Classification {
+    /// The label chosen by the model, one of the given labels
+    pub label: String,
+
+    /// Probability of the model choosing this label, between 0 and 1, `None` if the API returned
+    /// no log probabilities
+    pub confidence: Option<f64>,
+}
+
+/// A chunk of a chat completion streamed by [`CompletionsApi::create_stream`].
+///
+/// Fields the API adds (e.g. `system_fingerprint`) are kept in `extra`.
+#[derive(Clone, Debug, Serialize)]
+#[non_exhaustive]
+pub struct ChatCompletionChunk {
+    /// ID of the completion, the same for every chunk
+    pub id: String,
+
+    /// Model generating the completion
+    pub model: String,
+
+    /// Creation time of the completion, as a Unix timestamp in seconds
+    pub created: u64,
+
+    /// Deltas of the choices, empty in the final usage chunk
+    pub choices: Vec<ChunkChoice>,
+
+    /// Token usage of the whole completion, only in the final chunk when `stream_options`
+    /// includes it
+    pub usage: Option<Usage>,
+
+    /// Fields of the chunk the type doesn't declare
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    ChatCompletionChunk {
+        #[default] id: String,
+        #[default] model: String,
+        #[default] created: u64,
+        #[default] choices: Vec<ChunkChoice>,
+        #[default] usage: Option<Usage>,
+    }
+}
+
+impl ChatCompletionChunk {
+    /// The content delta of the first choice, if any.
+    pub fn content(&self) -> Option<&str> {
+        self.choices.first()?.delta.content.as_deref()
+    }
+}
+
+/// Tokens are counted like the raw JSON chunks, the final usage chunk reporting the total.
+impl ChunkTokens for ChatCompletionChunk {
+    fn tokens(&self) -> usize {
+        self.choices
+            .iter()
+            .filter_map(|choice| choice.delta.content.as_deref())
+            .map(|content| ApproximateTokenCounter.count_tokens(content))
+            .sum()
+    }
+
+    fn reported_tokens(&self) -> Option<usize> {
+        self.usage
+            .as_ref()
+            .map(|usage| usage.completion_tokens as usize)
+    }
+}
+
+/// The delta of a choice in a [`ChatCompletionChunk`].
+#[derive(Clone, Debug, Serialize)]
+#[non_exhaustive]
+pub struct ChunkChoice {
+    /// Index of the choice
+    pub index: u64,
+
+    /// What the chunk adds to the message of the choice
+    pub delta: ChatDelta,
+
+    /// Why the generation stopped (e.g. `stop`, `length` or `tool_calls`), only in the last chunk
+    /// of the choice
+    pub finish_reason: Option<String>,
+
+    /// Fields of the choice the type doesn't declare, e.g. `logprobs`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    ChunkChoice {
+        #[default] index: u64,
+        #[default] delta: ChatDelta,
+        #[default] finish_reason: Option<String>,
+    }
+}
+
+/// Part of an assistant message streamed in a [`ChunkChoice`].
+#[derive(Clone, Debug, Default, Serialize)]
+#[non_exhaustive]
+pub struct ChatDelta {
+    /// Role of the message, only in the first chunk
+    pub role: Option<String>,
+
+    /// Text to append to the content of the message
+    pub content: Option<String>,
+
+    /// Text to append to the refusal of the model
+    pub refusal: Option<String>,
+
+    /// Parts of the tool calls of the message
+    pub tool_calls: Vec<ToolCallDelta>,
+
+    /// Fields of the delta the type doesn't declare
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    ChatDelta {
+        #[default] role: Option<String>,
+        #[default] content: Option<String>,
+        #[default] refusal: Option<String>,
+        #[default] tool_calls: Vec<ToolCallDelta>,
+    }
+}
+
+/// Part of a tool call streamed in a [`ChatDelta`], the `id` and function name coming first and
+/// the arguments in pieces.
+#[derive(Clone, Debug, Serialize)]
+#[non_exhaustive]
+pub struct ToolCallDelta {
+    /// Index of the tool call in the message
+    pub index: u64,
+
+    /// ID of the tool call, only in its first chunk
+    pub id: Option<String>,
+
+    /// Name and arguments of the called function
+    pub function: Option<FunctionCallDelta>,
+
+    /// Fields of the tool call the type doesn't declare, e.g. `type`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    ToolCallDelta {
+        #[default] index: u64,
+        #[default] id: Option<String>,
+        #[default] function: Option<FunctionCallDelta>,
+    }
+}
+
+/// Part of a function call streamed in a [`ToolCallDelta`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct FunctionCallDelta {
+    /// Name of the function, only in the first chunk of the call
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// JSON text to append to the arguments of the call
+    #[serde(default)]
+    pub arguments: Option<String>,
 }
 
 impl ChatCompletionRequest {
     /// Create a new instance of [`ChatCompletionRequest`].
     #[inline(always)]
-    pub fn new(model: String, messages: Vec<Value>) -> Self {
+    pub fn new(model: impl Into<Model>, messages: Vec<Value>) -> Self {
         Self {
-            model,
+            model: Some(model.into()),
             messages,
             ..Default::default()
         }
     }
 
-    // Fluent setter methods to set each option on the request.
+    /// Create a new instance of [`ChatCompletionRequest`] using the client's default chat model.
+    #[inline(always)]
+    pub fn from_messages(messages: Vec<Value>) -> Self {
+        Self {
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// The messages of the conversation.
+    pub fn messages(&self) -> &[Value] {
+        &self.messages
+    }
+
+    /// The maximum number of tokens to generate, if set.
+    pub(crate) fn token_limit(&self) -> Option<u64> {
+        self.max_tokens
+    }
 
-    setters! {
-        max_tokens: u64,
-        temperature: f64,
-        top_p: f64,
-        n: u64,
-        stream: bool,
-        stop: Vec<String>,
-        presence_penalty: f64,
-        frequency_penalty: f64,
-        logit_bias: Value,
-        user: String,
+    /// The model of the request, if set.
+    pub(crate) fn model_name(&self) -> Option<&str> {
+        self.model.as_ref().map(Model::as_str)
     }
 }
 
-impl<'a> CompletionsApi<'a> {
+request_builder! {
+    /// Builder of a [`ChatCompletionRequest`], the client's default chat model being used if no
+    /// model is set.
+    ChatCompletionRequest => ChatCompletionRequestBuilder {
+        required {
+            /// Set the messages of the conversation.
+            messages: Vec<Value>,
+        }
+        optional {
+            /// Set the model of the chat completion.
+            model: Model,
+
+            /// Set the maximum number of tokens to generate.
+            max_tokens: u64,
+
+            /// Set the sampling temperature, between 0 and 2.
+            temperature: f64,
+
+            /// Set the nucleus sampling parameter, between 0 and 1.
+            top_p: f64,
+
+            /// Set the number of completions to generate.
+            n: u64,
+
+            /// Set whether to stream back partial progress.
+            stream: bool,
+
+            /// Set the options of a streamed completion.
+            stream_options: Value,
+
+            /// Set the sequences stopping the generation.
+            stop: Vec<String>,
+
+            /// Set the presence penalty, between -2 and 2.
+            presence_penalty: f64,
+
+            /// Set the frequency penalty, between -2 and 2.
+            frequency_penalty: f64,
+
+            /// Set the bias of the logits.
+            logit_bias: Value,
+
+            /// Set the ID of the end user.
+            user: String,
+
+            /// Set the format of the response, e.g. a JSON schema for structured outputs.
+            response_format: Value,
+
+            /// Set whether to return the log probabilities of the output tokens.
+            logprobs: bool,
+
+            /// Set the number of most likely alternatives returned for each output token.
+            top_logprobs: u64,
+
+            /// Set the tools the model may call.
+            tools: Vec<Value>,
+
+            /// Set which tool, if any, the model calls.
+            tool_choice: Value,
+
+            /// Set the key sent as the `Idempotency-Key` header, so retries and resubmissions of
+            /// the request don't create duplicates.
+            idempotency_key: String,
+        }
+        validate: validate_chat_request,
+    }
+}
+
+/// Check the parameters of a [`ChatCompletionRequest`] built with its builder, against the
+/// [`model_info`] of its model if it's known.
+fn validate_chat_request(request: &ChatCompletionRequest) -> OpenAIResult<()> {
+    if request.messages.is_empty() {
+        return Err(OpenAIError::ConfigError(
+            "a chat completion needs at least one message".to_string(),
+        ));
+    }
+    if request.n == Some(0) {
+        return Err(OpenAIError::ConfigError("n must be at least 1".to_string()));
+    }
+
+    check_range("temperature", request.temperature, 0.0..=2.0)?;
+    check_range("top_p", request.top_p, 0.0..=1.0)?;
+    check_range("presence_penalty", request.presence_penalty, -2.0..=2.0)?;
+    check_range("frequency_penalty", request.frequency_penalty, -2.0..=2.0)?;
+
+    let Some((model, info)) = request
+        .model_name()
+        .and_then(|model| Some((model, model_info(model)?)))
+    else {
+        return Ok(());
+    };
+    if let Some(max_tokens) = request
+        .max_tokens
+        .filter(|&max| max > info.max_output_tokens)
+    {
+        return Err(OpenAIError::ConfigError(format!(
+            "max_tokens of {max_tokens} exceeds the {} output tokens of {model}",
+            info.max_output_tokens
+        )));
+    }
+    if request
+        .tools
+        .as_ref()
+        .is_some_and(|tools| !tools.is_empty())
+        && !info.tools
+    {
+        return Err(OpenAIError::ConfigError(format!(
+            "{model} doesn't support tools"
+        )));
+    }
+
+    Ok(())
+}
+
+impl CompletionsApi {
     /// Create a chat completion using the provided request parameters.
     ///
     /// # Arguments
     ///
     /// * `request` - A [`ChatCompletionRequest`] containing the parameters for the completion.
+    ///   If the request has no model, the client's default chat model is used.
     ///
     /// # Returns
     ///
     /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
-    pub async fn create(&self, request: ChatCompletionRequest) -> OpenAIResult<Value> {
+    pub async fn create(&self, mut request: ChatCompletionRequest) -> OpenAIResult<Value> {
+        let model = required_model(
+            request.model.as_ref(),
+            &self.0.default_models().chat,
+            "chat",
+        )?;
+        request.model = Some(Model::from(model));
+
         // Send a POST request to the chat completions endpoint with the request body.
-        self.0.post_json("/chat/completions", &request).await
+        self.0
+            .post_json_idempotent(
+                "/chat/completions",
+                &request,
+                request.idempotency_key.as_deref(),
+            )
+            .await
+    }
+
+    /// Create a chat completion streamed as server-sent events, yielding its chunks as they are
+    /// generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A [`ChatCompletionRequest`] containing the parameters for the completion,
+    ///   sent with `stream` set. If the request has no model, the client's default chat model is
+    ///   used.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a stream of [`ChatCompletionChunk`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] if the request failed.
+    /// An error reported by the API in the middle of the stream is yielded as [`OpenAIError::UnexpectedResponse`][crate::error_handling::OpenAIError::UnexpectedResponse].
+    pub async fn create_stream(
+        &self,
+        mut request: ChatCompletionRequest,
+    ) -> OpenAIResult<impl Stream<Item = OpenAIResult<ChatCompletionChunk>>> {
+        let model = required_model(
+            request.model.as_ref(),
+            &self.0.default_models().chat,
+            "chat",
+        )?;
+        request.model = Some(Model::from(model));
+        request.stream = Some(true);
+
+        let events = self
+            .0
+            .post_events_idempotent(
+                "/chat/completions",
+                &request,
+                request.idempotency_key.as_deref(),
+            )
+            .await?;
+
+        Ok(events
+            .take_while(|event| {
+                let done = event.as_ref().is_ok_and(|event| event.data == "[DONE]");
+                futures::future::ready(!done)
+            })
+            .map(|event| event.and_then(parse_chunk)))
+    }
+
+    /// Extract a `T` from a conversation with a structured-output chat completion, the response
+    /// being constrained to the JSON schema of `T`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A [`ChatCompletionRequest`] containing the conversation to extract from. Its
+    ///   response format is replaced with the schema of `T`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted `T` on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure,
+    /// [`OpenAIError::Refusal`][crate::error_handling::OpenAIError::Refusal] if the model refused to answer.
+    #[cfg(feature = "schemars")]
+    pub async fn extract<T: JsonSchema + DeserializeOwned>(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> OpenAIResult<T> {
+        self.extract_with_repairs(request, 0).await
+    }
+
+    /// Like [`CompletionsApi::extract`], re-prompting the model with the parse error up to
+    /// `max_repairs` times when its output doesn't deserialize into `T`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted `T` on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure,
+    /// [`OpenAIError::ParseError`][crate::error_handling::OpenAIError::ParseError] with the raw output if every attempt failed to parse.
+    #[cfg(feature = "schemars")]
+    pub async fn extract_with_repairs<T: JsonSchema + DeserializeOwned>(
+        &self,
+        request: ChatCompletionRequest,
+        max_repairs: u32,
+    ) -> OpenAIResult<T> {
+        let schema = serde_json::to_value(schemars::schema_for!(T))?;
+        let request = request.response_format(structured::response_format(schema));
+
+        self.create_parsed(request, max_repairs).await
+    }
+
+    /// Create a chat completion constrained to a [`StructuredOutput`] type and parse its output,
+    /// re-prompting the model with the parse error up to `max_repairs` times when it doesn't
+    /// deserialize.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A [`ChatCompletionRequest`] containing the conversation. Its response format
+    ///   is replaced with the one of `T`.
+    /// * `max_repairs` - Maximum number of repair attempts.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the parsed `T` on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure,
+    /// [`OpenAIError::Refusal`][crate::error_handling::OpenAIError::Refusal] if the model refused to answer.
+    pub async fn create_structured<T: StructuredOutput>(
+        &self,
+        request: ChatCompletionRequest,
+        max_repairs: u32,
+    ) -> OpenAIResult<T> {
+        self.create_parsed(request.response_format(T::response_format()), max_repairs)
+            .await
+    }
+
+    /// Create a chat completion whose output is JSON (structured outputs or JSON mode) and
+    /// deserialize it into `T`, re-prompting the model with the parse error up to `max_repairs`
+    /// times when it doesn't deserialize.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A [`ChatCompletionRequest`] whose response format asks for JSON.
+    /// * `max_repairs` - Maximum number of repair attempts.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the deserialized `T` on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure,
+    /// [`OpenAIError::ParseError`][crate::error_handling::OpenAIError::ParseError] with the raw output if every attempt failed to parse.
+    pub async fn create_parsed<T: DeserializeOwned>(
+        &self,
+        mut request: ChatCompletionRequest,
+        max_repairs: u32,
+    ) -> OpenAIResult<T> {
+        let idempotency_key = request.idempotency_key.clone();
+        let mut repairs = 0;
+
+        loop {
+            let raw = structured::content(self.create(request.clone()).await?)?;
+
+            let message = match serde_json::from_str(&raw) {
+                Ok(parsed) => return Ok(parsed),
+                Err(error) => error.to_string(),
+            };
+            if repairs == max_repairs {
+                return Err(OpenAIError::ParseError { message, raw });
+            }
+
+            request
+                .messages
+                .push(json!({ "role": "assistant", "content": raw }));
+            request.messages.push(json!({
+                "role": "user",
+                "content": format!("Your previous response could not be parsed: {message}. Reply again with only the corrected JSON."),
+            }));
+            repairs += 1;
+            // The repair is a new request, which mustn't be answered with the first response.
+            request.idempotency_key = idempotency_key
+                .as_ref()
+                .map(|key| format!("{key}-repair-{repairs}"));
+        }
+    }
+
+    /// Extract a `T` from `text` with a structured-output chat completion.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to extract from.
+    /// * `model` - The model to use, or `None` for the client's default chat model.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted `T` on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure,
+    /// [`OpenAIError::Refusal`][crate::error_handling::OpenAIError::Refusal] if the model refused to answer.
+    #[cfg(feature = "schemars")]
+    pub async fn extract_text<T: JsonSchema + DeserializeOwned>(
+        &self,
+        text: &str,
+        model: Option<Model>,
+    ) -> OpenAIResult<T> {
+        let mut request = ChatCompletionRequest::from_messages(vec![
+            json!({ "role": "system", "content": "Extract the requested information from the text given by the user." }),
+            json!({ "role": "user", "content": text }),
+        ]);
+        if let Some(model) = model {
+            request = request.model(model);
+        }
+
+        self.extract(request).await
+    }
+
+    /// Classify `text` into exactly one of `labels`, the response being constrained to them with
+    /// structured outputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to classify.
+    /// * `labels` - The labels to choose from.
+    /// * `model` - The model to use, or `None` for the client's default chat model.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`Classification`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn classify(
+        &self,
+        text: &str,
+        labels: &[&str],
+        model: Option<Model>,
+    ) -> OpenAIResult<Classification> {
+        if labels.is_empty() {
+            return Err(OpenAIError::ConfigError(
+                "classification needs at least one label".to_string(),
+            ));
+        }
+
+        let schema = json!({
+            "title": "classification",
+            "type": "object",
+            "properties": { "label": { "type": "string", "enum": labels } },
+        });
+
+        let mut request = ChatCompletionRequest::from_messages(vec![
+            json!({ "role": "system", "content": "Classify the text given by the user with the most appropriate label." }),
+            json!({ "role": "user", "content": text }),
+        ])
+        .response_format(structured::response_format(schema))
+        .logprobs(true);
+        if let Some(model) = model {
+            request = request.model(model);
+        }
+
+        let response = self.create(request).await?;
+        // The schema leaves no choice but the label, so the probability of the whole output is
+        // the probability of the label.
+        let confidence = response["choices"][0]["logprobs"]["content"]
+            .as_array()
+            .map(|tokens| {
+                tokens
+                    .iter()
+                    .filter_map(|token| token["logprob"].as_f64())
+                    .sum::<f64>()
+                    .exp()
+            });
+
+        let content = structured::content(response)?;
+        let label = serde_json::from_str::<Value>(&content)?["label"]
+            .as_str()
+            .filter(|label| labels.contains(label))
+            .map(str::to_string)
+            .ok_or_else(|| {
+                OpenAIError::UnexpectedResponse(format!("unexpected classification: {content}"))
+            })?;
+
+        Ok(Classification { label, confidence })
+    }
+}
+
+/// Parse the data of a streamed chat completion event, turning an error object into an error.
+fn parse_chunk(event: ServerSentEvent) -> OpenAIResult<ChatCompletionChunk> {
+    let value: Value = serde_json::from_str(&event.data)?;
+
+    if let Some(error) = value.get("error") {
+        return Err(OpenAIError::UnexpectedResponse(format!(
+            "error in the stream: {error}"
+        )));
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod builder {
+    use super::*;
+
+    fn messages() -> Vec<Value> {
+        vec![json!({ "role": "user", "content": "Hi" })]
+    }
+
+    fn config_error(result: OpenAIResult<ChatCompletionRequest>) -> String {
+        match result {
+            Err(OpenAIError::ConfigError(message)) => message,
+            Err(error) => panic!("expected a ConfigError, got {error:?}"),
+            Ok(_) => panic!("expected a ConfigError, got a request"),
+        }
+    }
+
+    #[test]
+    fn build_sets_the_fields() {
+        let request = ChatCompletionRequest::builder()
+            .model("gpt-4o")
+            .messages(messages())
+            .temperature(0.2)
+            .n(2u64)
+            .extra("seed", 7)
+            .build()
+            .unwrap();
+
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["model"], "gpt-4o");
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(body["n"], 2);
+        assert_eq!(body["seed"], 7);
+    }
+
+    #[test]
+    fn build_rejects_missing_required_fields() {
+        let message = config_error(ChatCompletionRequest::builder().model("gpt-4o").build());
+        assert_eq!(
+            message,
+            "missing required fields of ChatCompletionRequest: messages"
+        );
+
+        let message = config_error(ChatCompletionRequest::builder().messages(vec![]).build());
+        assert_eq!(message, "a chat completion needs at least one message");
+    }
+
+    #[test]
+    fn build_rejects_out_of_range_values() {
+        let builder = || ChatCompletionRequest::builder().messages(messages());
+
+        assert_eq!(
+            config_error(builder().temperature(2.5).build()),
+            "temperature must be between 0 and 2, got 2.5"
+        );
+        assert_eq!(
+            config_error(builder().top_p(-0.1).build()),
+            "top_p must be between 0 and 1, got -0.1"
+        );
+        assert_eq!(
+            config_error(builder().presence_penalty(3.0).build()),
+            "presence_penalty must be between -2 and 2, got 3"
+        );
+        assert_eq!(
+            config_error(builder().n(0u64).build()),
+            "n must be at least 1"
+        );
+        assert!(
+            config_error(builder().model("gpt-4o").max_tokens(1_000_000u64).build())
+                .starts_with("max_tokens of 1000000 exceeds")
+        );
+
+        assert!(builder().temperature(2.0).top_p(0.0).build().is_ok());
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        fixtures,
+        mock_transport::{MockResponse, MockTransport},
+    };
+    use futures::TryStreamExt;
+    use reqwest::Method;
+    use std::sync::Arc;
+
+    fn request() -> ChatCompletionRequest {
+        ChatCompletionRequest::new("gpt-4o", vec![json!({ "role": "user", "content": "Hi" })])
+    }
+
+    #[tokio::test]
+    async fn create_stream_yields_the_chunks_until_done() {
+        let chunks = fixtures::chat_completion_chunks("gpt-4o", &["Hel", "lo"]);
+        // CRLF line endings, a chunk split over two data lines, and an event after [DONE].
+        let mut body = format!(": connected\r\n\r\ndata: {}\r\n\r\n", chunks[0]);
+        let second = chunks[1].to_string();
+        let (head, tail) = second.split_at(second.find(",\"object\"").unwrap() + 1);
+        body.push_str(&format!("data: {head}\r\ndata: {tail}\r\n\r\n"));
+        for chunk in &chunks[2..] {
+            body.push_str(&format!("data: {chunk}\r\n\r\n"));
+        }
+        body.push_str("data: [DONE]\r\n\r\ndata: not json\r\n\r\n");
+
+        let mock = Arc::new(MockTransport::new());
+        mock.on(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::raw(body, "text/event-stream"),
+        );
+        let openai = mock.client();
+
+        let chunks: Vec<ChatCompletionChunk> = openai
+            .completions()
+            .create_stream(request())
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+
+        let content: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk.choices[0].delta.content.as_deref())
+            .collect();
+        assert_eq!(content, "Hello");
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[3].choices[0].finish_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            mock.last_request().unwrap().json().unwrap()["stream"],
+            json!(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn create_stream_yields_errors_in_the_stream() {
+        let mock = Arc::new(MockTransport::new());
+        mock.on(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::sse(&[json!({ "error": { "message": "overloaded" } })]),
+        );
+        let openai = mock.client();
+
+        let chunks: Vec<_> = openai
+            .completions()
+            .create_stream(request())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 1);
+        assert!(
+            matches!(&chunks[0], Err(OpenAIError::UnexpectedResponse(message)) if message.contains("overloaded"))
+        );
     }
 }
@@ -1,14 +1,37 @@
-use crate::{error_handling::OpenAIResult, openai::OpenAI};
-use serde::Serialize;
-use serde_json::Value;
+use crate::{
+    config::required_model,
+    deserialize_with_extra,
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::types::Usage,
+    util::{base64_sextet, strip_base64_padding},
+};
+use serde::{
+    de::{self, DeserializeOwned, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::{Map, Value};
+use std::fmt;
 
 /// [`EmbeddingsApi`] struct to interact with the embeddings endpoint of the API.
-pub struct EmbeddingsApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct EmbeddingsApi(pub(crate) OpenAI);
+
+/// Maximum number of inputs the API accepts in a single request.
+const MAX_BATCH_SIZE: usize = 2048;
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum EmbeddingInput<'a> {
+    One(&'a str),
+    Many(&'a [&'a str]),
+}
 
 #[derive(Serialize)]
 struct AssistantRequest<'a> {
     /// The input text for which to create embeddings.
-    input: &'a str,
+    input: EmbeddingInput<'a>,
 
     /// Embedding model to use
     model: &'a str,
@@ -26,13 +49,144 @@ struct AssistantRequest<'a> {
     user: Option<&'a str>,
 }
 
-impl<'a> EmbeddingsApi<'a> {
+/// A single embedding vector.
+#[derive(Clone, Debug, Serialize)]
+#[non_exhaustive]
+pub struct Embedding {
+    /// Position of the input the embedding belongs to
+    pub index: usize,
+
+    /// The embedding vector, decoded from a list of floats or from base64
+    pub embedding: Vec<f32>,
+
+    /// Fields of the embedding the type doesn't declare, e.g. `object`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    Embedding {
+        index: usize,
+        embedding: Vec<f32> as Vector,
+    }
+}
+
+/// An embedding vector, as decoded by [`vector`].
+struct Vector(Vec<f32>);
+
+impl<'de> Deserialize<'de> for Vector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        vector(deserializer).map(Self)
+    }
+}
+
+/// Deserialize an embedding vector sent either as a list of floats or, with the `base64` encoding
+/// format, as the base64 of its little-endian `f32` values.
+fn vector<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    struct VectorVisitor;
+
+    impl<'de> Visitor<'de> for VectorVisitor {
+        type Value = Vec<f32>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a list of floats or a base64 string")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut vector = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+            while let Some(value) = seq.next_element()? {
+                vector.push(value);
+            }
+
+            Ok(vector)
+        }
+
+        fn visit_str<E: de::Error>(self, encoded: &str) -> Result<Self::Value, E> {
+            decode_base64_floats(encoded).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_any(VectorVisitor)
+}
+
+/// Decode base64 straight into `f32` values, without an intermediate byte buffer.
+fn decode_base64_floats(encoded: &str) -> Result<Vec<f32>, &'static str> {
+    let encoded = strip_base64_padding(encoded)?;
+    let length = encoded.len() * 3 / 4;
+    if !length.is_multiple_of(4) {
+        return Err("base64 embedding isn't a whole number of floats");
+    }
+
+    let mut vector = Vec::with_capacity(length / 4);
+    let mut float = [0; 4];
+    let mut filled = 0;
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+
+    for &character in encoded {
+        let sextet = base64_sextet(character)?;
+        bits = (bits << 6) | u32::from(sextet);
+        bit_count += 6;
+
+        if bit_count >= 8 {
+            bit_count -= 8;
+            float[filled] = (bits >> bit_count) as u8;
+            bits &= (1 << bit_count) - 1;
+            filled += 1;
+
+            if filled == 4 {
+                vector.push(f32::from_le_bytes(float));
+                filled = 0;
+            }
+        }
+    }
+
+    Ok(vector)
+}
+
+/// Typed response of the embeddings endpoint.
+#[derive(Clone, Debug, Serialize)]
+#[non_exhaustive]
+pub struct EmbeddingsResponse {
+    /// Embeddings, in input order
+    pub data: Vec<Embedding>,
+
+    /// Model that produced the embeddings
+    pub model: String,
+
+    /// Token usage of the request
+    pub usage: Usage,
+
+    /// Fields of the response the type doesn't declare, e.g. `object`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    EmbeddingsResponse {
+        data: Vec<Embedding>,
+        model: String,
+        #[default] usage: Usage,
+    }
+}
+
+impl EmbeddingsResponse {
+    /// Take the embedding vectors out of the response, in input order.
+    pub fn into_vectors(self) -> Vec<Vec<f32>> {
+        self.data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect()
+    }
+}
+
+impl EmbeddingsApi {
     /// Create an embedding using the provided parameters.
     ///
     /// # Arguments
     ///
     /// * `input` - The input text for which to create embeddings.
-    /// * `model` - The name of the model to use for creating embeddings.
+    /// * `model` - The name of the model to use for creating embeddings, or `None` for the client's default embeddings model.
     /// * `encoding_format` - Optional encoding format.
     /// * `dimensions` - Optional number of dimensions for the embeddings.
     /// * `user` - Optional user ID.
@@ -43,11 +197,145 @@ impl<'a> EmbeddingsApi<'a> {
     pub async fn create(
         &self,
         input: &str,
-        model: &str,                   // Embedding model to use
+        model: Option<Model>,          // Embedding model to use
         encoding_format: Option<&str>, // Optional encoding format
         dimensions: Option<u64>,       // Optional number of dimensions
         user: Option<&str>,            // Optional user ID
     ) -> OpenAIResult<Value> {
+        self.send(
+            EmbeddingInput::One(input),
+            model,
+            encoding_format,
+            dimensions,
+            user,
+        )
+        .await
+    }
+
+    /// Create an embedding, decoding the response straight into an [`EmbeddingsResponse`].
+    ///
+    /// The vectors are parsed directly from the response body without building an intermediate
+    /// [`serde_json::Value`], which matters for large batches.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input text for which to create embeddings.
+    /// * `model` - The name of the model to use for creating embeddings, or `None` for the client's default embeddings model.
+    /// * `dimensions` - Optional number of dimensions for the embeddings.
+    /// * `user` - Optional user ID.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`EmbeddingsResponse`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn create_typed(
+        &self,
+        input: &str,
+        model: Option<Model>,    // Embedding model to use
+        dimensions: Option<u64>, // Optional number of dimensions
+        user: Option<&str>,      // Optional user ID
+    ) -> OpenAIResult<EmbeddingsResponse> {
+        self.send(
+            EmbeddingInput::One(input),
+            model,
+            Some("float"),
+            dimensions,
+            user,
+        )
+        .await
+    }
+
+    /// Create an embedding with the `base64` encoding format, decoding the vectors straight from the
+    /// response body into preallocated buffers.
+    ///
+    /// The response is about four times smaller than with floats and skips JSON number parsing
+    /// entirely, the fastest option when embedding large amounts of text.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input text for which to create embeddings.
+    /// * `model` - The name of the model to use for creating embeddings, or `None` for the client's default embeddings model.
+    /// * `dimensions` - Optional number of dimensions for the embeddings.
+    /// * `user` - Optional user ID.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`EmbeddingsResponse`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn create_base64(
+        &self,
+        input: &str,
+        model: Option<Model>,    // Embedding model to use
+        dimensions: Option<u64>, // Optional number of dimensions
+        user: Option<&str>,      // Optional user ID
+    ) -> OpenAIResult<EmbeddingsResponse> {
+        self.send(
+            EmbeddingInput::One(input),
+            model,
+            Some("base64"),
+            dimensions,
+            user,
+        )
+        .await
+    }
+
+    /// Embed many texts, sending them in batches of up to 2048 inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The texts to embed.
+    /// * `model` - The name of the model to use for creating embeddings, or `None` for the client's default embeddings model.
+    /// * `dimensions` - Optional number of dimensions for the embeddings.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing one vector per input, in input order, on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn embed_many(
+        &self,
+        inputs: &[&str],
+        model: Option<Model>,    // Embedding model to use
+        dimensions: Option<u64>, // Optional number of dimensions
+    ) -> OpenAIResult<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(inputs.len());
+
+        for batch in inputs.chunks(MAX_BATCH_SIZE) {
+            let mut response: EmbeddingsResponse = self
+                .send(
+                    EmbeddingInput::Many(batch),
+                    model.clone(),
+                    Some("base64"),
+                    dimensions,
+                    None,
+                )
+                .await?;
+
+            if response.data.len() != batch.len() {
+                return Err(OpenAIError::UnexpectedResponse(format!(
+                    "expected {} embeddings, got {}",
+                    batch.len(),
+                    response.data.len()
+                )));
+            }
+
+            response.data.sort_by_key(|embedding| embedding.index);
+            vectors.extend(response.into_vectors());
+        }
+
+        Ok(vectors)
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        input: EmbeddingInput<'_>,
+        model: Option<Model>,
+        encoding_format: Option<&str>,
+        dimensions: Option<u64>,
+        user: Option<&str>,
+    ) -> OpenAIResult<T> {
+        let model = required_model(
+            model.as_ref(),
+            &self.0.default_models().embeddings,
+            "embeddings",
+        )?;
+
         // Initialize a JSON object to build the request body.
         let body = AssistantRequest {
             input,
@@ -61,3 +349,43 @@ impl<'a> EmbeddingsApi<'a> {
         self.0.post_json("/embeddings", &body).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn base64_embeddings_decode_to_floats() {
+        // 1.0, -2.5 and 0.0 as little-endian f32 values.
+        assert_eq!(
+            decode_base64_floats("AACAPwAAIMAAAAAA").unwrap(),
+            [1.0, -2.5, 0.0]
+        );
+        assert_eq!(decode_base64_floats("AACAPw==").unwrap(), [1.0]);
+        assert_eq!(decode_base64_floats("AACAPw").unwrap(), [1.0]);
+    }
+
+    #[test]
+    fn base64_embeddings_reject_partial_floats_and_invalid_input() {
+        assert!(decode_base64_floats("AACA").is_err());
+        assert!(decode_base64_floats("AACAPw=").is_err());
+        assert!(decode_base64_floats("AACAPw===").is_err());
+        assert!(decode_base64_floats("AACA*w==").is_err());
+    }
+
+    #[test]
+    fn embeddings_decode_from_lists_and_base64() {
+        let embedding: Embedding =
+            serde_json::from_value(json!({ "index": 1, "embedding": "AACAPwAAIMA=" })).unwrap();
+        assert_eq!(embedding.embedding, [1.0, -2.5]);
+
+        let embedding: Embedding =
+            serde_json::from_value(json!({ "index": 0, "embedding": [0.5, 1] })).unwrap();
+        assert_eq!(embedding.embedding, [0.5, 1.0]);
+
+        let result =
+            serde_json::from_value::<Embedding>(json!({ "index": 0, "embedding": "AACAPwAAIM" }));
+        assert!(result.is_err());
+    }
+}
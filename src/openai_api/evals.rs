@@ -0,0 +1,556 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    extend_url_params,
+    openai::OpenAI,
+    openai_api::pagination::{paginate, After, EvalId, EvalRunId, Order, OutputItemId},
+    request_builder, runtime,
+};
+use futures::Stream;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+/// [`EvalsApi`] struct to interact with the hosted evals endpoints of the API.
+#[derive(Clone)]
+pub struct EvalsApi(pub(crate) OpenAI);
+
+/// Struct representing a request for eval creation.
+#[derive(Serialize)]
+pub struct EvalCreationRequest {
+    /// Configuration of the data source the runs of the eval use
+    data_source_config: Value,
+
+    /// Graders applied to every item of the runs
+    testing_criteria: Vec<Value>,
+
+    /// Name for the eval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    /// Metadata for the eval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Value>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// Struct representing a request for eval modification.
+#[derive(Default, Serialize)]
+pub struct EvalModificationRequest {
+    /// Name for the eval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    /// Metadata for the eval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Value>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// Struct representing a request for eval run creation.
+#[derive(Serialize)]
+pub struct EvalRunCreationRequest {
+    /// Data source of the run: the items and how samples are produced for them
+    data_source: Value,
+
+    /// Name for the run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    /// Metadata for the run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Value>,
+
+    /// Key sent as the `Idempotency-Key` header, so retries and resubmissions don't create duplicates
+    #[serde(skip)]
+    idempotency_key: Option<String>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl EvalCreationRequest {
+    /// Create a new instance of [`EvalCreationRequest`].
+    ///
+    /// # Arguments
+    ///
+    /// * `data_source_config` - Configuration of the data source, e.g. `{"type": "custom", "item_schema": {...}}`.
+    /// * `testing_criteria` - Graders applied to every item, e.g. string checks or label models.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of [`EvalCreationRequest`].
+    pub fn new(data_source_config: Value, testing_criteria: Vec<Value>) -> Self {
+        Self {
+            data_source_config,
+            testing_criteria,
+            name: None,
+            metadata: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+request_builder! {
+    /// Builder of an [`EvalCreationRequest`].
+    EvalCreationRequest => EvalCreationRequestBuilder {
+        required {
+            /// Set the configuration of the data source the runs of the eval use.
+            data_source_config: Value,
+
+            /// Set the graders applied to every item of the runs.
+            testing_criteria: Vec<Value>,
+        }
+        optional {
+            /// Set the name for the request.
+            name: String,
+
+            /// Set the metadata for the request.
+            metadata: Value,
+        }
+    }
+}
+
+request_builder! {
+    /// Builder of an [`EvalModificationRequest`].
+    EvalModificationRequest => EvalModificationRequestBuilder {
+        required {}
+        optional {
+            /// Set the name for the request.
+            name: String,
+
+            /// Set the metadata for the request.
+            metadata: Value,
+        }
+    }
+}
+
+impl EvalRunCreationRequest {
+    /// Create a new instance of [`EvalRunCreationRequest`].
+    ///
+    /// # Arguments
+    ///
+    /// * `data_source` - Data source of the run, e.g. `{"type": "completions", "source": {...}, "model": "..."}`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of [`EvalRunCreationRequest`].
+    pub fn new(data_source: Value) -> Self {
+        Self {
+            data_source,
+            name: None,
+            metadata: None,
+            idempotency_key: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+request_builder! {
+    /// Builder of an [`EvalRunCreationRequest`].
+    EvalRunCreationRequest => EvalRunCreationRequestBuilder {
+        required {
+            /// Set the data source of the run.
+            data_source: Value,
+        }
+        optional {
+            /// Set the name for the request.
+            name: String,
+
+            /// Set the metadata for the request.
+            metadata: Value,
+
+            /// Set the key sent as the `Idempotency-Key` header, so retries and resubmissions of
+            /// the request don't create duplicates.
+            idempotency_key: String,
+        }
+    }
+}
+
+impl EvalsApi {
+    /// Create a new eval using the provided request parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - An [`EvalCreationRequest`] containing the parameters for the eval.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn create(&self, request: EvalCreationRequest) -> OpenAIResult<Value> {
+        self.0.post_json("/evals", &request).await
+    }
+
+    /// List evals with optional query parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum number of evals to retrieve.
+    /// * `order` - Order of the retrieved evals.
+    /// * `order_by` - Field the evals are sorted by (`created_at` or `updated_at`).
+    /// * `after` - Retrieve the evals following this one.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn list(
+        &self,
+        limit: Option<u32>,
+        order: Option<Order>,
+        order_by: Option<&str>,
+        after: Option<After<EvalId>>,
+    ) -> OpenAIResult<Value> {
+        let mut url = String::from("/evals?");
+
+        extend_url_params!(url, limit, order, order_by, after);
+        url.pop();
+
+        self.0.get(&url).await
+    }
+
+    /// Stream every eval, fetching the pages of [`EvalsApi::list`] as the stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Number of evals fetched per page.
+    /// * `order` - Order of the evals.
+    /// * `order_by` - Field the evals are sorted by (`created_at` or `updated_at`).
+    ///
+    /// # Returns
+    ///
+    /// A stream of the evals as [`serde_json::Value`], or of an [`OpenAIError`] if a page couldn't be fetched.
+    pub fn list_all(
+        &self,
+        limit: Option<u32>,
+        order: Option<Order>,
+        order_by: Option<&str>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+        let order_by = order_by.map(str::to_string);
+
+        paginate(move |after| {
+            let api = EvalsApi(openai.clone());
+            let order_by = order_by.clone();
+            async move {
+                api.list(limit, order, order_by.as_deref(), after.map(After::from))
+                    .await
+            }
+        })
+    }
+
+    /// Retrieve details of a specific eval.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn retrieve(&self, eval_id: &str) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}");
+
+        self.0.get(&url).await
+    }
+
+    /// Modify an existing eval using the provided request parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval to modify.
+    /// * `request` - An [`EvalModificationRequest`] containing the parameters for the eval modification.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn modify(
+        &self,
+        eval_id: &str,
+        request: EvalModificationRequest,
+    ) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}");
+
+        self.0.post_json(&url, &request).await
+    }
+
+    /// Delete a specific eval.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval to delete.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn delete(&self, eval_id: &str) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}");
+
+        self.0.delete(&url).await
+    }
+
+    /// Start a run of an eval using the provided request parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval to run.
+    /// * `request` - An [`EvalRunCreationRequest`] containing the parameters for the run.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn create_run(
+        &self,
+        eval_id: &str,
+        request: EvalRunCreationRequest,
+    ) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}/runs");
+
+        self.0
+            .post_json_idempotent(&url, &request, request.idempotency_key.as_deref())
+            .await
+    }
+
+    /// List the runs of an eval with optional query parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `limit` - Maximum number of runs to retrieve.
+    /// * `order` - Order of the retrieved runs.
+    /// * `status` - Only retrieve runs with this status, e.g. `completed`.
+    /// * `after` - Retrieve the runs following this one.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn list_runs(
+        &self,
+        eval_id: &str,
+        limit: Option<u32>,
+        order: Option<Order>,
+        status: Option<&str>,
+        after: Option<After<EvalRunId>>,
+    ) -> OpenAIResult<Value> {
+        let mut url = format!("/evals/{eval_id}/runs?");
+
+        extend_url_params!(url, limit, order, status, after);
+        url.pop();
+
+        self.0.get(&url).await
+    }
+
+    /// Stream every run of an eval, fetching the pages of [`EvalsApi::list_runs`] as the stream
+    /// is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `limit` - Number of runs fetched per page.
+    /// * `order` - Order of the runs.
+    /// * `status` - Only retrieve runs with this status, e.g. `completed`.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the runs as [`serde_json::Value`], or of an [`OpenAIError`] if a page couldn't be fetched.
+    pub fn list_all_runs(
+        &self,
+        eval_id: &str,
+        limit: Option<u32>,
+        order: Option<Order>,
+        status: Option<&str>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+        let eval_id = eval_id.to_string();
+        let status = status.map(str::to_string);
+
+        paginate(move |after| {
+            let api = EvalsApi(openai.clone());
+            let (eval_id, status) = (eval_id.clone(), status.clone());
+            async move {
+                let after = after.map(After::from);
+                api.list_runs(&eval_id, limit, order, status.as_deref(), after)
+                    .await
+            }
+        })
+    }
+
+    /// Retrieve details of a specific run.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `run_id` - The ID of the run to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn retrieve_run(&self, eval_id: &str, run_id: &str) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}/runs/{run_id}");
+
+        self.0.get(&url).await
+    }
+
+    /// Cancel an ongoing run.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `run_id` - The ID of the run to cancel.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn cancel_run(&self, eval_id: &str, run_id: &str) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}/runs/{run_id}");
+
+        self.0.post_json(&url, &json!({})).await
+    }
+
+    /// Delete a specific run.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `run_id` - The ID of the run to delete.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn delete_run(&self, eval_id: &str, run_id: &str) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}/runs/{run_id}");
+
+        self.0.delete(&url).await
+    }
+
+    /// Poll a run until it completes, fails or is canceled.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `run_id` - The ID of the run to wait for.
+    /// * `poll_interval` - Time between two retrievals of the run.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON of the finished run on success, or an [`OpenAIError`] on failure.
+    pub async fn wait_for_run(
+        &self,
+        eval_id: &str,
+        run_id: &str,
+        poll_interval: Duration,
+    ) -> OpenAIResult<Value> {
+        loop {
+            let run = self.retrieve_run(eval_id, run_id).await?;
+
+            if let Some(error) = run.get("error").filter(|_| run.get("status").is_none()) {
+                return Err(OpenAIError::UnexpectedResponse(
+                    error["message"].as_str().unwrap_or("API error").to_string(),
+                ));
+            }
+            if matches!(
+                run["status"].as_str(),
+                Some("completed" | "failed" | "canceled")
+            ) {
+                return Ok(run);
+            }
+
+            runtime::sleep(poll_interval).await;
+        }
+    }
+
+    /// List the output items of a run with optional query parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `run_id` - The ID of the run.
+    /// * `limit` - Maximum number of output items to retrieve.
+    /// * `order` - Order of the retrieved output items.
+    /// * `status` - Only retrieve output items with this status (`pass` or `fail`).
+    /// * `after` - Retrieve the output items following this one.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn list_output_items(
+        &self,
+        eval_id: &str,
+        run_id: &str,
+        limit: Option<u32>,
+        order: Option<Order>,
+        status: Option<&str>,
+        after: Option<After<OutputItemId>>,
+    ) -> OpenAIResult<Value> {
+        let mut url = format!("/evals/{eval_id}/runs/{run_id}/output_items?");
+
+        extend_url_params!(url, limit, order, status, after);
+        url.pop();
+
+        self.0.get(&url).await
+    }
+
+    /// Stream every output item of a run, fetching the pages of [`EvalsApi::list_output_items`]
+    /// as the stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `run_id` - The ID of the run.
+    /// * `limit` - Number of output items fetched per page.
+    /// * `order` - Order of the output items.
+    /// * `status` - Only retrieve output items with this status (`pass` or `fail`).
+    ///
+    /// # Returns
+    ///
+    /// A stream of the output items as [`serde_json::Value`], or of an [`OpenAIError`] if a page couldn't be fetched.
+    pub fn list_all_output_items(
+        &self,
+        eval_id: &str,
+        run_id: &str,
+        limit: Option<u32>,
+        order: Option<Order>,
+        status: Option<&str>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+        let (eval_id, run_id) = (eval_id.to_string(), run_id.to_string());
+        let status = status.map(str::to_string);
+
+        paginate(move |after| {
+            let api = EvalsApi(openai.clone());
+            let (eval_id, run_id, status) = (eval_id.clone(), run_id.clone(), status.clone());
+            async move {
+                let after = after.map(After::from);
+                api.list_output_items(&eval_id, &run_id, limit, order, status.as_deref(), after)
+                    .await
+            }
+        })
+    }
+
+    /// Retrieve a specific output item of a run.
+    ///
+    /// # Arguments
+    ///
+    /// * `eval_id` - The ID of the eval.
+    /// * `run_id` - The ID of the run.
+    /// * `output_item_id` - The ID of the output item to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`] on failure.
+    pub async fn retrieve_output_item(
+        &self,
+        eval_id: &str,
+        run_id: &str,
+        output_item_id: &str,
+    ) -> OpenAIResult<Value> {
+        let url = format!("/evals/{eval_id}/runs/{run_id}/output_items/{output_item_id}");
+
+        self.0.get(&url).await
+    }
+}
@@ -1,21 +1,36 @@
-use crate::{error_handling::OpenAIResult, openai::OpenAI};
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::pagination::paginate,
+    request_builder,
+};
+use futures::Stream;
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use {
+    crate::util::decode_base64,
+    std::path::{Path, PathBuf},
+};
 
 /// [`FineTuningApi`] struct to interact with the fine-tuning endpoints of the API.
-pub struct FineTuningApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct FineTuningApi(pub(crate) OpenAI);
 
-#[derive(Serialize)]
-struct FineTuningRequest<'a> {
+/// Struct representing a request for a fine-tuning job.
+#[derive(Clone, Serialize)]
+pub struct FineTuningJobRequest {
     /// Model to be fine-tuned
-    model: &'a str,
+    model: Model,
 
-    /// Path to training data file
-    training_file: &'a str,
+    /// ID of the training data file
+    training_file: String,
 
     /// Optional validation data file
     #[serde(skip_serializing_if = "Option::is_none")]
-    validation_file: Option<&'a str>,
+    validation_file: Option<String>,
 
     /// Optional number of epochs for training
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -43,14 +58,223 @@ struct FineTuningRequest<'a> {
 
     /// Optional positive class for classification
     #[serde(skip_serializing_if = "Option::is_none")]
-    classification_positive_class: Option<&'a str>,
+    classification_positive_class: Option<String>,
 
     /// Optional betas for classification metrics
     #[serde(skip_serializing_if = "Option::is_none")]
     classification_betas: Option<Vec<f64>>,
+
+    /// Key sent as the `Idempotency-Key` header, so retries and resubmissions don't create duplicates
+    #[serde(skip)]
+    idempotency_key: Option<String>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl FineTuningJobRequest {
+    /// Create a new instance of [`FineTuningJobRequest`].
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model to be fine-tuned.
+    /// * `training_file` - The ID of the file containing training data.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of [`FineTuningJobRequest`].
+    pub fn new(model: impl Into<Model>, training_file: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            training_file: training_file.into(),
+            validation_file: None,
+            n_epochs: None,
+            batch_size: None,
+            learning_rate_multiplier: None,
+            prompt_loss_weight: None,
+            compute_classification_metrics: None,
+            classification_n_classes: None,
+            classification_positive_class: None,
+            classification_betas: None,
+            idempotency_key: None,
+            extra: Map::new(),
+        }
+    }
 }
 
-impl<'a> FineTuningApi<'a> {
+request_builder! {
+    /// Builder of a [`FineTuningJobRequest`].
+    FineTuningJobRequest => FineTuningJobRequestBuilder {
+        required {
+            /// Set the model to be fine-tuned.
+            model: Model,
+
+            /// Set the ID of the training data file.
+            training_file: String,
+        }
+        optional {
+            /// Set the ID of the validation data file.
+            validation_file: String,
+
+            /// Set the number of training epochs, at least 1.
+            n_epochs: u32,
+
+            /// Set the batch size for training, at least 1.
+            batch_size: u32,
+
+            /// Set the learning rate multiplier, greater than 0.
+            learning_rate_multiplier: f64,
+
+            /// Set the weight of the prompt loss.
+            prompt_loss_weight: f64,
+
+            /// Set whether to compute classification metrics.
+            compute_classification_metrics: bool,
+
+            /// Set the number of classes for classification.
+            classification_n_classes: u32,
+
+            /// Set the positive class for classification.
+            classification_positive_class: String,
+
+            /// Set the betas for classification metrics.
+            classification_betas: Vec<f64>,
+
+            /// Set the key sent as the `Idempotency-Key` header, so retries and resubmissions of
+            /// the request don't create duplicates.
+            idempotency_key: String,
+        }
+        validate: |request: &FineTuningJobRequest| {
+            if request.training_file.is_empty() {
+                return Err(OpenAIError::ConfigError(
+                    "a fine-tuning job needs a training file".to_string(),
+                ));
+            }
+            if request.n_epochs == Some(0) || request.batch_size == Some(0) {
+                return Err(OpenAIError::ConfigError(
+                    "n_epochs and batch_size must be at least 1".to_string(),
+                ));
+            }
+            if request
+                .learning_rate_multiplier
+                .is_some_and(|multiplier| multiplier <= 0.0)
+            {
+                return Err(OpenAIError::ConfigError(
+                    "learning_rate_multiplier must be greater than 0".to_string(),
+                ));
+            }
+
+            Ok(())
+        },
+    }
+}
+
+/// A row of the metrics file of a fine-tuning job, one per training step.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TrainingMetrics {
+    /// Training step
+    pub step: u64,
+
+    /// Loss on the training batch
+    pub train_loss: Option<f64>,
+
+    /// Token accuracy on the training batch
+    pub train_accuracy: Option<f64>,
+
+    /// Loss on the validation batch, if there is a validation file
+    pub valid_loss: Option<f64>,
+
+    /// Mean token accuracy on the validation batch
+    pub valid_mean_token_accuracy: Option<f64>,
+
+    /// Loss on the whole validation file, reported at the end of each epoch
+    pub full_valid_loss: Option<f64>,
+
+    /// Mean token accuracy on the whole validation file
+    pub full_valid_mean_token_accuracy: Option<f64>,
+
+    /// Other numeric columns, by name
+    pub extra: BTreeMap<String, f64>,
+}
+
+/// A result file of a fine-tuning job, downloaded by [`FineTuningApi::download_results`].
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResultFile {
+    /// ID of the file
+    pub file_id: String,
+
+    /// Path of the downloaded file
+    pub path: PathBuf,
+
+    /// Metrics parsed from the file, in step order
+    pub metrics: Vec<TrainingMetrics>,
+}
+
+/// The CSV of a metrics file, decoded from base64 as the API serves it.
+#[cfg(feature = "fs")]
+fn decode_metrics(contents: &[u8]) -> OpenAIResult<Option<String>> {
+    let text = String::from_utf8_lossy(contents);
+    if text.trim_start().starts_with("step") {
+        return Ok(None);
+    }
+
+    let bytes = decode_base64(text.trim()).map_err(|error| {
+        OpenAIError::UnexpectedResponse(format!("invalid metrics file: {error}"))
+    })?;
+
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Parse the CSV metrics of a fine-tuning job.
+#[cfg(feature = "fs")]
+fn parse_metrics(text: &str) -> OpenAIResult<Vec<TrainingMetrics>> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let columns: Vec<&str> = lines
+        .next()
+        .map(|header| header.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+
+    lines
+        .map(|line| {
+            let mut row = TrainingMetrics::default();
+
+            for (column, value) in columns.iter().zip(line.split(',').map(str::trim)) {
+                if *column == "step" {
+                    row.step = value.parse().map_err(|_| {
+                        OpenAIError::UnexpectedResponse(format!(
+                            "invalid step in metrics: {value:?}"
+                        ))
+                    })?;
+                    continue;
+                }
+                // Empty cells are the metrics that weren't computed at this step.
+                let Ok(value) = value.parse::<f64>() else {
+                    continue;
+                };
+
+                match *column {
+                    "train_loss" => row.train_loss = Some(value),
+                    "train_accuracy" => row.train_accuracy = Some(value),
+                    "valid_loss" => row.valid_loss = Some(value),
+                    "valid_mean_token_accuracy" => row.valid_mean_token_accuracy = Some(value),
+                    "full_valid_loss" => row.full_valid_loss = Some(value),
+                    "full_valid_mean_token_accuracy" => {
+                        row.full_valid_mean_token_accuracy = Some(value)
+                    },
+                    column => {
+                        row.extra.insert(column.to_string(), value);
+                    },
+                }
+            }
+
+            Ok(row)
+        })
+        .collect()
+}
+
+impl FineTuningApi {
     /// Create a new fine-tuning job with the specified parameters.
     ///
     /// # Arguments
@@ -72,35 +296,55 @@ impl<'a> FineTuningApi<'a> {
     /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
     pub async fn create_fine_tuning_job(
         &self,
-        model: &str,                                  // Model to be fine-tuned
-        training_file: &str,                          // Path to training data file
-        validation_file: Option<&str>,                // Optional validation data file
-        n_epochs: Option<u32>,                        // Optional number of epochs for training
-        batch_size: Option<u32>,                      // Optional batch size for training
-        learning_rate_multiplier: Option<f64>,        // Optional learning rate multiplier
-        prompt_loss_weight: Option<f64>,              // Optional prompt loss weight
+        model: impl Into<Model>,               // Model to be fine-tuned
+        training_file: &str,                   // Path to training data file
+        validation_file: Option<&str>,         // Optional validation data file
+        n_epochs: Option<u32>,                 // Optional number of epochs for training
+        batch_size: Option<u32>,               // Optional batch size for training
+        learning_rate_multiplier: Option<f64>, // Optional learning rate multiplier
+        prompt_loss_weight: Option<f64>,       // Optional prompt loss weight
         compute_classification_metrics: Option<bool>, // Optional flag to compute classification metrics
         classification_n_classes: Option<u32>, // Optional number of classes for classification
         classification_positive_class: Option<&str>, // Optional positive class for classification
         classification_betas: Option<Vec<f64>>, // Optional betas for classification metrics
     ) -> OpenAIResult<Value> {
-        // Initialize a JSON map to build the request body.
-        let body = FineTuningRequest {
-            model,
-            training_file,
-            validation_file,
+        let request = FineTuningJobRequest {
+            model: model.into(),
+            training_file: training_file.to_string(),
+            validation_file: validation_file.map(str::to_string),
             n_epochs,
             batch_size,
             learning_rate_multiplier,
             prompt_loss_weight,
             compute_classification_metrics,
             classification_n_classes,
-            classification_positive_class,
+            classification_positive_class: classification_positive_class.map(str::to_string),
             classification_betas,
+            idempotency_key: None,
+            extra: Map::new(),
         };
 
+        self.create_job(request).await
+    }
+
+    /// Create a new fine-tuning job using the provided request parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A [`FineTuningJobRequest`] containing the parameters for the job.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn create_job(&self, request: FineTuningJobRequest) -> OpenAIResult<Value> {
         // Send a POST request to the fine-tuning jobs endpoint with the request body.
-        self.0.post_json("/fine-tuning/jobs", &body).await
+        self.0
+            .post_json_idempotent(
+                "/fine-tuning/jobs",
+                &request,
+                request.idempotency_key.as_deref(),
+            )
+            .await
     }
 
     /// List all fine-tuning jobs.
@@ -108,12 +352,46 @@ impl<'a> FineTuningApi<'a> {
     /// # Returns
     ///
     /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
-
     pub async fn list_fine_tuning_jobs(&self) -> OpenAIResult<Value> {
         // Send a GET request to the fine-tuning jobs endpoint.
         self.0.get("/fine-tuning/jobs").await
     }
 
+    /// Stream every fine-tuning job, following the `after` cursor of the pages of the
+    /// fine-tuning jobs endpoint as the stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Optional number of jobs fetched per page.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the jobs as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all_fine_tuning_jobs(
+        &self,
+        limit: Option<u32>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+
+        paginate(move |after: Option<String>| {
+            let mut query = Vec::new();
+            if let Some(limit) = limit {
+                query.push(format!("limit={limit}"));
+            }
+            if let Some(after) = after {
+                query.push(format!("after={after}"));
+            }
+
+            let mut url = "/fine-tuning/jobs".to_string();
+            if !query.is_empty() {
+                url.push('?');
+                url.push_str(&query.join("&"));
+            }
+            let openai = openai.clone();
+            async move { openai.get(&url).await }
+        })
+    }
+
     /// Retrieve information about a specific fine-tuning job.
     ///
     /// # Arguments
@@ -130,4 +408,72 @@ impl<'a> FineTuningApi<'a> {
         // Send a GET request to the specific fine-tuning job endpoint.
         self.0.get(&url).await
     }
+
+    /// Download the result files of a completed fine-tuning job into `directory`, streaming them
+    /// to disk (decoded from the base64 the API serves them in), and parse the training metrics
+    /// they contain.
+    ///
+    /// # Arguments
+    ///
+    /// * `job_id` - The ID of the fine-tuning job.
+    /// * `directory` - The directory the files are written to, as `<file ID>.csv`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the downloaded files on success, or an [`OpenAIError`] if the job hasn't succeeded or a file couldn't be downloaded or parsed.
+    #[cfg(feature = "fs")]
+    pub async fn download_results(
+        &self,
+        job_id: &str,
+        directory: impl AsRef<Path>,
+    ) -> OpenAIResult<Vec<ResultFile>> {
+        let job = self.retrieve_fine_tuning_job(job_id).await?;
+
+        if let Some(error) = job
+            .get("error")
+            .filter(|error| error["message"].is_string())
+        {
+            return Err(OpenAIError::UnexpectedResponse(
+                error["message"].as_str().unwrap_or_default().to_string(),
+            ));
+        }
+        let status = job["status"].as_str().unwrap_or_default();
+        if status != "succeeded" {
+            return Err(OpenAIError::ConfigError(format!(
+                "fine-tuning job {job_id} hasn't succeeded (status: {status:?})"
+            )));
+        }
+
+        let file_ids: Vec<&str> = job["result_files"]
+            .as_array()
+            .map(|files| files.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let mut results = Vec::with_capacity(file_ids.len());
+
+        for file_id in file_ids {
+            let path = directory.as_ref().join(format!("{file_id}.csv"));
+            self.0
+                .download(
+                    &format!("/files/{file_id}/content"),
+                    &path.to_string_lossy(),
+                )
+                .await?;
+            let contents = tokio::fs::read(&path).await?;
+            let metrics = match decode_metrics(&contents)? {
+                Some(csv) => {
+                    tokio::fs::write(&path, &csv).await?;
+                    parse_metrics(&csv)?
+                },
+                None => parse_metrics(&String::from_utf8_lossy(&contents))?,
+            };
+
+            results.push(ResultFile {
+                file_id: file_id.to_string(),
+                path,
+                metrics,
+            });
+        }
+
+        Ok(results)
+    }
 }
@@ -1,27 +1,38 @@
-use crate::{error_handling::OpenAIResult, extend_form_text_fields, openai::OpenAI};
+#[cfg(feature = "fs")]
+use crate::util::file_part;
+use crate::{
+    config::optional_model,
+    error_handling::{OpenAIError, OpenAIResult},
+    extend_form_text_fields,
+    model::Model,
+    openai::OpenAI,
+    request_builder,
+};
 use reqwest::multipart;
 use serde::Serialize;
-use serde_json::Value;
-use tokio::fs;
+use serde_json::{Map, Value};
 
 /// [`ImagesApi`] struct to interact with the image generation, editing, and variation endpoints of the API.
-pub struct ImagesApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct ImagesApi(pub(crate) OpenAI);
 
-#[derive(Serialize)]
-struct GenerateImageRequest<'a> {
+/// Struct representing a request for image generation.
+#[derive(Clone, Serialize)]
+pub struct ImageGenerationRequest {
     /// The text prompt to generate the image from
-    prompt: &'a str,
+    prompt: String,
 
     /// The model to use for generating the image
-    model: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<Model>,
 
     /// Optional size of the image
     #[serde(skip_serializing_if = "Option::is_none")]
-    size: Option<&'a str>,
+    size: Option<String>,
 
     /// Optional response format
     #[serde(skip_serializing_if = "Option::is_none")]
-    response_format: Option<&'a str>,
+    response_format: Option<String>,
 
     /// Optional number of images to generate
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -29,16 +40,82 @@ struct GenerateImageRequest<'a> {
 
     /// Optional user ID
     #[serde(skip_serializing_if = "Option::is_none")]
-    user: Option<&'a str>,
+    user: Option<String>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl ImageGenerationRequest {
+    /// Create a new instance of [`ImageGenerationRequest`] using the client's default images model.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate the image from.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of [`ImageGenerationRequest`].
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            model: None,
+            size: None,
+            response_format: None,
+            n: None,
+            user: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+request_builder! {
+    /// Builder of an [`ImageGenerationRequest`], the client's default images model being used if
+    /// no model is set.
+    ImageGenerationRequest => ImageGenerationRequestBuilder {
+        required {
+            /// Set the text prompt to generate the image from.
+            prompt: String,
+        }
+        optional {
+            /// Set the model to use for generating the image.
+            model: Model,
+
+            /// Set the size of the image, e.g. `1024x1024`.
+            size: String,
+
+            /// Set the response format (e.g., `url`, `b64_json`).
+            response_format: String,
+
+            /// Set the number of images to generate, at least 1.
+            n: u64,
+
+            /// Set the user ID.
+            user: String,
+        }
+        validate: |request: &ImageGenerationRequest| {
+            if request.prompt.is_empty() {
+                return Err(OpenAIError::ConfigError(
+                    "an image generation needs a prompt".to_string(),
+                ));
+            }
+            if request.n == Some(0) {
+                return Err(OpenAIError::ConfigError("n must be at least 1".to_string()));
+            }
+
+            Ok(())
+        },
+    }
 }
 
-impl<'a> ImagesApi<'a> {
+impl ImagesApi {
     /// Generate an image based on the provided prompt and parameters.
     ///
     /// # Arguments
     ///
     /// * `prompt` - The text prompt to generate the image from.
-    /// * `model` - The name of the model to use for generating the image.
+    /// * `model` - The name of the model to use for generating the image, or `None` for the client's default images model.
     /// * `size` - Optional size of the image.
     /// * `response_format` - Optional response format (e.g., `json`, `url`).
     /// * `n` - Optional number of images to generate.
@@ -50,31 +127,49 @@ impl<'a> ImagesApi<'a> {
     pub async fn generate(
         &self,
         prompt: &str,                  // The text prompt to generate the image from
-        model: &str,                   // The model to use for generating the image
+        model: Option<Model>,          // The model to use for generating the image
         size: Option<&str>,            // Optional size of the image
         response_format: Option<&str>, // Optional response format
         n: Option<u64>,                // Optional number of images to generate
         user: Option<&str>,            // Optional user ID
     ) -> OpenAIResult<Value> {
-        // Initialize a JSON object to build the request body.
-        let body = GenerateImageRequest {
-            prompt,
+        let request = ImageGenerationRequest {
+            prompt: prompt.to_string(),
             model,
-            size,
-            response_format,
+            size: size.map(str::to_string),
+            response_format: response_format.map(str::to_string),
             n,
-            user,
+            user: user.map(str::to_string),
+            extra: Map::new(),
         };
 
+        self.create(request).await
+    }
+
+    /// Generate images using the provided request parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - An [`ImageGenerationRequest`] containing the parameters for the generation.
+    ///   If the request has no model, the client's default images model is used.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn create(&self, mut request: ImageGenerationRequest) -> OpenAIResult<Value> {
+        if request.model.is_none() {
+            request.model = self.0.default_models().images.as_ref().map(Model::from);
+        }
+
         // Send a POST request to the image generation endpoint with the request body.
-        self.0.post_json("/images/generations", &body).await
+        self.0.post_json("/images/generations", &request).await
     }
 
     /// Edit an existing image using the provided parameters and mask.
     ///
     /// # Arguments
     ///
-    /// * `model` - The name of the model to use for editing the image.
+    /// * `model` - The name of the model to use for editing the image, or `None` for the client's default images model.
     /// * `image_path` - The local file path to the image.
     /// * `mask_path` - The local file path to the mask.
     /// * `prompt` - The text prompt to guide the editing.
@@ -86,9 +181,10 @@ impl<'a> ImagesApi<'a> {
     /// # Returns
     ///
     /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    #[cfg(feature = "fs")]
     pub async fn edit(
         &self,
-        model: &str,                   // The model to use for editing the image
+        model: Option<Model>,          // The model to use for editing the image
         image_path: &str,              // Local file path to the image
         mask_path: &str,               // Local file path to the mask
         prompt: &str,                  // Text prompt to guide the editing
@@ -97,36 +193,29 @@ impl<'a> ImagesApi<'a> {
         n: Option<u64>,                // Optional number of edited images to generate
         user: Option<&str>,            // Optional user ID
     ) -> OpenAIResult<Value> {
-        // Open and read the image file asynchronously.
-        let image_buffer = fs::read(image_path).await?;
-        let image_part = multipart::Part::bytes(image_buffer)
-            .file_name(image_path.to_string())
-            .mime_str("image/png")?;
-
-        // Open and read the mask file asynchronously.
-        let mask_buffer = fs::read(mask_path).await?;
-        let mask_part = multipart::Part::bytes(mask_buffer)
-            .file_name(mask_path.to_string())
-            .mime_str("image/png")?;
+        // Stream the image and mask files from disk.
+        let image_part = file_part(image_path, "image/png", self.0.stream_chunk_size()).await?;
+        let mask_part = file_part(mask_path, "image/png", self.0.stream_chunk_size()).await?;
 
         // Initialize a multipart form to build the request body.
+        let model = optional_model(model.as_ref(), &self.0.default_models().images);
+
         let mut form = multipart::Form::new()
-            .text("model", model.to_string())
             .part("image", image_part)
             .part("mask", mask_part)
             .text("prompt", prompt.to_string());
 
-        extend_form_text_fields!(form, size, response_format, n, user);
+        extend_form_text_fields!(form, model, size, response_format, n, user);
 
         // Send a POST request to the image editing endpoint with the multipart form.
-        self.0.post_form("/images/edits", form).await
+        self.0.post_model_form("/images/edits", form, model).await
     }
 
     /// Create variations of an existing image using the provided parameters.
     ///
     /// # Arguments
     ///
-    /// * `model` - The name of the model to use for generating variations.
+    /// * `model` - The name of the model to use for generating variations, or `None` for the client's default images model.
     /// * `image_path` - The local file path to the image.
     /// * `size` - Optional size of the variation images.
     /// * `response_format` - Optional response format (e.g., `json`, `url`).
@@ -136,29 +225,110 @@ impl<'a> ImagesApi<'a> {
     /// # Returns
     ///
     /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    #[cfg(feature = "fs")]
     pub async fn variation(
         &self,
-        model: &str,                   // The model to use for generating variations
-        image_path: &str,              // Local file path to the image
-        size: Option<&str>,            // Optional size of the variation images
+        model: Option<Model>, // The model to use for generating variations
+        image_path: &str,     // Local file path to the image
+        size: Option<&str>,   // Optional size of the variation images
         response_format: Option<&str>, // Optional response format
-        n: Option<u64>,                // Optional number of variation images to generate
-        user: Option<&str>,            // Optional user ID
+        n: Option<u64>,       // Optional number of variation images to generate
+        user: Option<&str>,   // Optional user ID
     ) -> OpenAIResult<Value> {
-        // Open and read the image file asynchronously.
-        let buffer = fs::read(image_path).await?;
-        let image_part = multipart::Part::bytes(buffer)
-            .file_name(image_path.to_string())
-            .mime_str("image/png")?;
+        // Stream the image file from disk.
+        let image_part = file_part(image_path, "image/png", self.0.stream_chunk_size()).await?;
 
         // Initialize a multipart form to build the request body.
-        let mut form = multipart::Form::new()
-            .text("model", model.to_string())
-            .part("image", image_part);
+        let model = optional_model(model.as_ref(), &self.0.default_models().images);
+
+        let mut form = multipart::Form::new().part("image", image_part);
 
-        extend_form_text_fields!(form, size, response_format, n, user);
+        extend_form_text_fields!(form, model, size, response_format, n, user);
 
         // Send a POST request to the image variations endpoint with the multipart form.
-        self.0.post_form("/images/variations", form).await
+        self.0
+            .post_model_form("/images/variations", form, model)
+            .await
+    }
+
+    /// Edit an image held in memory, e.g. the output of a previous generation.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name of the model to use for editing the image, or `None` for the client's default images model.
+    /// * `image` - The PNG bytes of the image.
+    /// * `mask` - Optional PNG bytes of the mask.
+    /// * `prompt` - The text prompt to guide the editing.
+    /// * `size` - Optional size of the edited image.
+    /// * `response_format` - Optional response format (e.g., `b64_json`, `url`).
+    /// * `n` - Optional number of edited images to generate.
+    /// * `user` - Optional user ID.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn edit_bytes(
+        &self,
+        model: Option<Model>,          // The model to use for editing the image
+        image: Vec<u8>,                // PNG bytes of the image
+        mask: Option<Vec<u8>>,         // Optional PNG bytes of the mask
+        prompt: &str,                  // Text prompt to guide the editing
+        size: Option<&str>,            // Optional size of the edited image
+        response_format: Option<&str>, // Optional response format
+        n: Option<u64>,                // Optional number of edited images to generate
+        user: Option<&str>,            // Optional user ID
+    ) -> OpenAIResult<Value> {
+        let model = optional_model(model.as_ref(), &self.0.default_models().images);
+
+        let mut form = multipart::Form::new()
+            .part("image", png_part(image, "image.png")?)
+            .text("prompt", prompt.to_string());
+        if let Some(mask) = mask {
+            form = form.part("mask", png_part(mask, "mask.png")?);
+        }
+
+        extend_form_text_fields!(form, model, size, response_format, n, user);
+
+        self.0.post_model_form("/images/edits", form, model).await
     }
+
+    /// Create variations of an image held in memory, e.g. the output of a previous generation.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name of the model to use for generating variations, or `None` for the client's default images model.
+    /// * `image` - The PNG bytes of the image.
+    /// * `size` - Optional size of the variation images.
+    /// * `response_format` - Optional response format (e.g., `b64_json`, `url`).
+    /// * `n` - Optional number of variation images to generate.
+    /// * `user` - Optional user ID.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn variation_bytes(
+        &self,
+        model: Option<Model>, // The model to use for generating variations
+        image: Vec<u8>,       // PNG bytes of the image
+        size: Option<&str>,   // Optional size of the variation images
+        response_format: Option<&str>, // Optional response format
+        n: Option<u64>,       // Optional number of variation images to generate
+        user: Option<&str>,   // Optional user ID
+    ) -> OpenAIResult<Value> {
+        let model = optional_model(model.as_ref(), &self.0.default_models().images);
+
+        let mut form = multipart::Form::new().part("image", png_part(image, "image.png")?);
+
+        extend_form_text_fields!(form, model, size, response_format, n, user);
+
+        self.0
+            .post_model_form("/images/variations", form, model)
+            .await
+    }
+}
+
+fn png_part(bytes: Vec<u8>, file_name: &str) -> OpenAIResult<multipart::Part> {
+    Ok(multipart::Part::bytes(bytes)
+        .file_name(file_name.to_string())
+        .mime_str("image/png")?)
 }
@@ -3,9 +3,13 @@ pub mod audio;
 pub mod client;
 pub mod completion;
 pub mod embeddings;
+pub mod evals;
 pub mod fine_tuning;
 pub mod images;
 pub mod moderations;
+pub mod pagination;
+pub mod raw;
 pub mod threads;
+pub mod types;
 pub mod vectors;
 pub mod projects;
\ No newline at end of file
@@ -1,9 +1,10 @@
-use crate::{error_handling::OpenAIResult, openai::OpenAI};
+use crate::{config::optional_model, error_handling::OpenAIResult, model::Model, openai::OpenAI};
 use serde::Serialize;
 use serde_json::Value;
 
 /// [`ModerationApi`] struct to interact with the moderation endpoint of the API.
-pub struct ModerationApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct ModerationApi(pub(crate) OpenAI);
 
 #[derive(Serialize)]
 struct ModerationRequest<'a> {
@@ -15,18 +16,20 @@ struct ModerationRequest<'a> {
     model: Option<&'a str>,
 }
 
-impl<'a> ModerationApi<'a> {
+impl ModerationApi {
     /// Submit text input for moderation.
     ///
     /// # Arguments
     ///
     /// * `input` - The text input to be moderated.
-    /// * `model` - Optional name of the moderation model to use.
+    /// * `model` - Optional name of the moderation model to use, falling back to the client's default moderation model.
     ///
     /// # Returns
     ///
     /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
-    pub async fn moderate(&self, input: &str, model: Option<&str>) -> OpenAIResult<Value> {
+    pub async fn moderate(&self, input: &str, model: Option<Model>) -> OpenAIResult<Value> {
+        let model = optional_model(model.as_ref(), &self.0.default_models().moderation);
+
         // Initialize a JSON object to build the request body.
         let body = ModerationRequest { input, model };
 
@@ -0,0 +1,186 @@
+use crate::{error_handling::OpenAIResult, openai_api::types::List};
+use futures::{stream, Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt, future::Future};
+
+/// Sort order of the items of a list endpoint, by creation time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// Oldest items first
+    Asc,
+
+    /// Newest items first
+    Desc,
+}
+
+impl Order {
+    /// Value of the order in a query string.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+/// Cursor listing the items following the one with the given ID, e.g. `After(MessageId)`.
+///
+/// The `last_id` of a page is the cursor of the next one.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct After<I>(pub I);
+
+/// Cursor listing the items preceding the one with the given ID.
+///
+/// The `first_id` of a page is the cursor of the previous one.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Before<I>(pub I);
+
+macro_rules! cursors {
+    ($($cursor:ident),*) => {
+        $(
+            impl<I: fmt::Display> fmt::Display for $cursor<I> {
+                fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    self.0.fmt(formatter)
+                }
+            }
+
+            impl<I: From<String>> From<String> for $cursor<I> {
+                fn from(id: String) -> Self {
+                    Self(I::from(id))
+                }
+            }
+
+            impl<'a, I: From<&'a str>> From<&'a str> for $cursor<I> {
+                fn from(id: &'a str) -> Self {
+                    Self(I::from(id))
+                }
+            }
+        )*
+    };
+}
+
+cursors!(After, Before);
+
+/// Stream of every item of a list endpoint, `fetch` returning the page following the given
+/// `after` cursor, or the first page for `None`.
+///
+/// Pages are only fetched as the stream is polled, until one reports `has_more: false`. The cursor
+/// of the next page is the `last_id` of a page, or the ID of its last item for the lists without
+/// one (e.g. fine-tuning jobs).
+pub(crate) fn paginate<'a, F, Fut>(fetch: F) -> impl Stream<Item = OpenAIResult<Value>> + 'a
+where
+    F: FnMut(Option<String>) -> Fut + 'a,
+    Fut: Future<Output = OpenAIResult<Value>> + 'a,
+{
+    stream::try_unfold((fetch, Some(None)), |(mut fetch, cursor)| async move {
+        let Some(after) = cursor else {
+            return OpenAIResult::Ok(None);
+        };
+
+        let page: List<Value> = serde_json::from_value(fetch(after).await?)?;
+        let last_id = page.last_id.or_else(|| {
+            page.data
+                .last()
+                .and_then(|item| item["id"].as_str())
+                .map(str::to_string)
+        });
+        let next = last_id.filter(|_| page.has_more && !page.data.is_empty());
+
+        Ok(Some((page.data, (fetch, next.map(Some)))))
+    })
+    .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+    .try_flatten()
+}
+
+macro_rules! ids {
+    ($($(#[$attribute:meta])* $id:ident,)*) => {
+        $(
+            $(#[$attribute])*
+            #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+            #[serde(transparent)]
+            pub struct $id(String);
+
+            impl $id {
+                /// Create a new ID from its string form.
+                pub fn new(id: impl Into<String>) -> Self {
+                    Self(id.into())
+                }
+
+                /// The string form of the ID.
+                pub fn as_str(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl fmt::Display for $id {
+                fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    formatter.write_str(&self.0)
+                }
+            }
+
+            impl From<String> for $id {
+                fn from(id: String) -> Self {
+                    Self(id)
+                }
+            }
+
+            impl From<&str> for $id {
+                fn from(id: &str) -> Self {
+                    Self(id.to_string())
+                }
+            }
+
+            impl From<$id> for String {
+                fn from(id: $id) -> Self {
+                    id.0
+                }
+            }
+
+            impl AsRef<str> for $id {
+                fn as_ref(&self) -> &str {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+ids! {
+    /// ID of an assistant, e.g. `asst_abc123`.
+    AssistantId,
+
+    /// ID of a message of a thread, e.g. `msg_abc123`.
+    MessageId,
+
+    /// ID of a run of a thread, e.g. `run_abc123`.
+    RunId,
+
+    /// ID of a step of a run, e.g. `step_abc123`.
+    RunStepId,
+
+    /// ID of a vector store, e.g. `vs_abc123`.
+    VectorStoreId,
+
+    /// ID of a project, e.g. `proj_abc123`.
+    ProjectId,
+
+    /// ID of a user, e.g. `user_abc123`.
+    UserId,
+
+    /// ID of an eval, e.g. `eval_abc123`.
+    EvalId,
+
+    /// ID of a run of an eval, e.g. `evalrun_abc123`.
+    EvalRunId,
+
+    /// ID of an output item of an eval run, e.g. `outputitem_abc123`.
+    OutputItemId,
+}
@@ -1,10 +1,16 @@
-use crate::{error_handling::OpenAIResult, openai::OpenAI};
+use crate::{
+    error_handling::OpenAIResult,
+    openai::OpenAI,
+    openai_api::pagination::{paginate, After, ProjectId, UserId},
+};
+use futures::Stream;
 use serde::Serialize;
 use serde_json::Value;
 
 /// [`ProjectsApi`] struct to interact with the projects endpoints of the API.
 #[allow(dead_code)]
-pub struct ProjectsApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct ProjectsApi(pub(crate) OpenAI);
 
 #[allow(dead_code)]
 #[derive(Serialize)]
@@ -31,13 +37,13 @@ struct CreateProjectUserRequest<'a> {
     role: &'a str,
 }
 
-impl<'a> ProjectsApi<'a> {
+impl ProjectsApi {
     /// List projects within an organization.
     ///
     /// # Arguments
     ///
     /// * `limit` - Optional limit on the number of objects to return (1-100, default 20).
-    /// * `after` - Optional cursor for pagination.
+    /// * `after` - Optional cursor to list the objects following this one.
     /// * `include_archived` - Optional flag to include archived projects.
     ///
     /// # Returns
@@ -46,7 +52,7 @@ impl<'a> ProjectsApi<'a> {
     pub async fn list_projects(
         &self,
         limit: Option<u8>,
-        after: Option<&str>,
+        after: Option<After<ProjectId>>,
         include_archived: Option<bool>,
     ) -> OpenAIResult<Value> {
         let mut url = "/organization/projects".to_string();
@@ -70,6 +76,33 @@ impl<'a> ProjectsApi<'a> {
         self.0.get(&url).await
     }
 
+    /// Stream every project of the organization, fetching the pages of
+    /// [`ProjectsApi::list_projects`] as the stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Optional number of projects fetched per page (1-100, default 20).
+    /// * `include_archived` - Optional flag to include archived projects.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the projects as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all_projects(
+        &self,
+        limit: Option<u8>,
+        include_archived: Option<bool>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+
+        paginate(move |after| {
+            let api = ProjectsApi(openai.clone());
+            async move {
+                api.list_projects(limit, after.map(After::from), include_archived)
+                    .await
+            }
+        })
+    }
+
     /// Create a new project in the organization.
     ///
     /// # Arguments
@@ -159,7 +192,7 @@ impl<'a> ProjectsApi<'a> {
     ///
     /// * `project_id` - The ID of the project.
     /// * `limit` - Optional limit on the number of objects to return (1-100, default 20).
-    /// * `after` - Optional cursor for pagination.
+    /// * `after` - Optional cursor to list the objects following this one.
     ///
     /// # Returns
     ///
@@ -168,7 +201,7 @@ impl<'a> ProjectsApi<'a> {
         &self,
         project_id: &str,
         limit: Option<u8>,
-        after: Option<&str>,
+        after: Option<After<UserId>>,
     ) -> OpenAIResult<Value> {
         let mut url = format!("/organization/projects/{}/users", project_id);
         let mut query_params = Vec::new();
@@ -188,6 +221,35 @@ impl<'a> ProjectsApi<'a> {
         self.0.get(&url).await
     }
 
+    /// Stream every user of a project, fetching the pages of [`ProjectsApi::list_project_users`]
+    /// as the stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id` - The ID of the project.
+    /// * `limit` - Optional number of users fetched per page (1-100, default 20).
+    ///
+    /// # Returns
+    ///
+    /// A stream of the users as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all_project_users(
+        &self,
+        project_id: &str,
+        limit: Option<u8>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+        let project_id = project_id.to_string();
+
+        paginate(move |after| {
+            let api = ProjectsApi(openai.clone());
+            let project_id = project_id.clone();
+            async move {
+                api.list_project_users(&project_id, limit, after.map(After::from))
+                    .await
+            }
+        })
+    }
+
     /// Add a user to a project.
     ///
     /// # Arguments
@@ -269,4 +331,4 @@ impl<'a> ProjectsApi<'a> {
         let url = format!("/organization/projects/{}/users/{}", project_id, user_id);
         self.0.delete(&url).await
     }
-}
\ No newline at end of file
+}
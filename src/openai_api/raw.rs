@@ -0,0 +1,104 @@
+use crate::{error_handling::OpenAIResult, openai::OpenAI};
+use bytes::Bytes;
+use reqwest::{header::HeaderMap, multipart::Form, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+/// A response of [`OpenAI::request_raw`], whatever its status.
+#[derive(Clone, Debug)]
+pub struct RawResponse {
+    /// Status of the response
+    pub status: StatusCode,
+
+    /// Headers of the response
+    pub headers: HeaderMap,
+
+    /// Body of the response, decompressed
+    pub body: Bytes,
+}
+
+impl RawResponse {
+    /// Deserialize the body of the response from JSON.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the deserialized body on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] if it isn't valid JSON for `T`.
+    pub fn json<T: DeserializeOwned>(&self) -> OpenAIResult<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// The body of the response as text, with invalid UTF-8 replaced.
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+}
+
+/// [`RawApi`] struct to call arbitrary endpoints of the API, e.g. endpoints this crate doesn't
+/// wrap yet.
+///
+/// Requests go through the same authentication, retry and caching logic as the typed APIs.
+/// Responses can be deserialized into any type, including [`serde_json::Value`].
+#[derive(Clone)]
+pub struct RawApi(pub(crate) OpenAI);
+
+impl RawApi {
+    /// Send a GET request to `path` (relative to the base URL, query string included).
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the deserialized response on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> OpenAIResult<T> {
+        self.0.get(&normalize(path)).await
+    }
+
+    /// Send a POST request with a JSON body to `path` (relative to the base URL).
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the deserialized response on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn post_json<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> OpenAIResult<T> {
+        self.0.post_json(&normalize(path), body).await
+    }
+
+    /// Send a POST request with a multipart form body to `path` (relative to the base URL).
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the deserialized response on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn post_form<T: DeserializeOwned>(&self, path: &str, form: Form) -> OpenAIResult<T> {
+        self.0.post_form(&normalize(path), form).await
+    }
+
+    /// Download the response of a GET request to `path` (relative to the base URL) into the file
+    /// at `destination`, e.g. `files/file-abc123/content`, without holding it in memory.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the number of bytes written on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    #[cfg(feature = "fs")]
+    pub async fn download(&self, path: &str, destination: &str) -> OpenAIResult<u64> {
+        self.0.download(&normalize(path), destination).await
+    }
+
+    /// Send a DELETE request to `path` (relative to the base URL).
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the deserialized response on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn delete<T: DeserializeOwned>(&self, path: &str) -> OpenAIResult<T> {
+        self.0.delete(&normalize(path)).await
+    }
+}
+
+/// Make sure `path` starts with a slash so it can be appended to the base URL.
+pub(crate) fn normalize(path: &str) -> Cow<'_, str> {
+    if path.starts_with('/') {
+        Cow::Borrowed(path)
+    } else {
+        Cow::Owned(format!("/{path}"))
+    }
+}
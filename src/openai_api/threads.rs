@@ -1,9 +1,19 @@
-use crate::{error_handling::OpenAIResult, extend_url_params, openai::OpenAI, setters};
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    extend_url_params,
+    model::Model,
+    openai::OpenAI,
+    openai_api::pagination::{paginate, After, Before, MessageId, Order, RunId, RunStepId},
+    request_builder,
+    stream::{ChunkTokens, ServerSentEvent},
+};
+use futures::{Stream, StreamExt};
 use serde::Serialize;
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
 
 /// [`ThreadsApi`] struct to interact with thread management endpoints of the API.
-pub struct ThreadsApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct ThreadsApi(pub(crate) OpenAI);
 
 /// Struct representing a request to create a thread.
 #[derive(Default, Serialize)]
@@ -19,6 +29,10 @@ pub struct ThreadCreationRequest {
     /// Optional metadata associated with the thread
     #[serde(skip_serializing_if = "Option::is_none")]
     metadata: Option<Value>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 /// Struct representing a request to modify a thread.
@@ -31,28 +45,40 @@ pub struct ThreadModificationRequest {
     /// Optional metadata associated with the thread
     #[serde(skip_serializing_if = "Option::is_none")]
     metadata: Option<Value>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
-impl ThreadCreationRequest {
-    setters! {
-        /// Set messages for the thread.
-        messages: Vec<Value>,
+request_builder! {
+    /// Builder of a [`ThreadCreationRequest`].
+    ThreadCreationRequest => ThreadCreationRequestBuilder {
+        required {}
+        optional {
+            /// Set messages for the thread.
+            messages: Vec<Value>,
 
-        /// Set tool resources for the thread.
-        tool_resources: Value,
+            /// Set tool resources for the thread.
+            tool_resources: Value,
 
-        /// Set metadata for the thread.
-        metadata: Value,
+            /// Set metadata for the thread.
+            metadata: Value,
+        }
     }
 }
 
-impl ThreadModificationRequest {
-    setters! {
-        /// Set tool resources for the thread.
-        tool_resources: Value,
-
-        /// Set metadata for the thread.
-        metadata: Value,
+request_builder! {
+    /// Builder of a [`ThreadModificationRequest`].
+    ThreadModificationRequest => ThreadModificationRequestBuilder {
+        required {}
+        optional {
+            /// Set tool resources for the thread.
+            tool_resources: Value,
+
+            /// Set metadata for the thread.
+            metadata: Value,
+        }
     }
 }
 
@@ -84,7 +110,7 @@ struct CreateRunRequest<'a> {
     assistant_id: &'a str,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    model: Option<&'a str>,
+    model: Option<Model>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     instructions: Option<&'a str>,
@@ -139,7 +165,182 @@ struct SubmitToolRequest {
     stream: Option<bool>,
 }
 
-impl<'a> ThreadsApi<'a> {
+macro_rules! assistant_stream_events {
+    ($($(#[$attribute:meta])* $name:literal => $variant:ident,)*) => {
+        /// An event of a run streamed by [`ThreadsApi::create_run_stream`], carrying the object
+        /// the event is about: a thread, a run, a run step, a message or a delta of the last two.
+        #[derive(Clone, Debug, PartialEq)]
+        #[non_exhaustive]
+        pub enum AssistantStreamEvent {
+            $($(#[$attribute])* $variant(Value),)*
+
+            /// An event the client doesn't know, with its name and data
+            Unknown {
+                /// Name of the event
+                event: String,
+
+                /// Data of the event
+                data: Value,
+            },
+        }
+
+        impl AssistantStreamEvent {
+            fn new(event: &str, data: Value) -> Self {
+                match event {
+                    $($name => Self::$variant(data),)*
+                    _ => Self::Unknown {
+                        event: event.to_string(),
+                        data,
+                    },
+                }
+            }
+
+            /// Name of the event, e.g. `thread.message.delta`.
+            pub fn name(&self) -> &str {
+                match self {
+                    $(Self::$variant(_) => $name,)*
+                    Self::Unknown { event, .. } => event,
+                }
+            }
+
+            /// The object carried by the event.
+            pub fn data(&self) -> &Value {
+                match self {
+                    $(Self::$variant(data))|* | Self::Unknown { data, .. } => data,
+                }
+            }
+        }
+    };
+}
+
+assistant_stream_events! {
+    /// A thread was created, by a run creating its thread
+    "thread.created" => ThreadCreated,
+
+    /// A run was created
+    "thread.run.created" => RunCreated,
+
+    /// A run was queued
+    "thread.run.queued" => RunQueued,
+
+    /// A run started
+    "thread.run.in_progress" => RunInProgress,
+
+    /// A run waits for the outputs of its tool calls, see [`ThreadsApi::submit_tool_outputs_stream`]
+    "thread.run.requires_action" => RunRequiresAction,
+
+    /// A run completed
+    "thread.run.completed" => RunCompleted,
+
+    /// A run ended without completing, e.g. after reaching its token limit
+    "thread.run.incomplete" => RunIncomplete,
+
+    /// A run failed
+    "thread.run.failed" => RunFailed,
+
+    /// A run is being cancelled
+    "thread.run.cancelling" => RunCancelling,
+
+    /// A run was cancelled
+    "thread.run.cancelled" => RunCancelled,
+
+    /// A run expired
+    "thread.run.expired" => RunExpired,
+
+    /// A step of a run was created
+    "thread.run.step.created" => RunStepCreated,
+
+    /// A step of a run started
+    "thread.run.step.in_progress" => RunStepInProgress,
+
+    /// Part of a step of a run, e.g. of a tool call
+    "thread.run.step.delta" => RunStepDelta,
+
+    /// A step of a run completed
+    "thread.run.step.completed" => RunStepCompleted,
+
+    /// A step of a run failed
+    "thread.run.step.failed" => RunStepFailed,
+
+    /// A step of a run was cancelled
+    "thread.run.step.cancelled" => RunStepCancelled,
+
+    /// A step of a run expired
+    "thread.run.step.expired" => RunStepExpired,
+
+    /// A message was created
+    "thread.message.created" => MessageCreated,
+
+    /// A message started
+    "thread.message.in_progress" => MessageInProgress,
+
+    /// Part of the content of a message
+    "thread.message.delta" => MessageDelta,
+
+    /// A message completed
+    "thread.message.completed" => MessageCompleted,
+
+    /// A message ended without completing
+    "thread.message.incomplete" => MessageIncomplete,
+}
+
+impl AssistantStreamEvent {
+    /// The text added by a [`AssistantStreamEvent::MessageDelta`], if any.
+    pub fn text_delta(&self) -> Option<String> {
+        let Self::MessageDelta(delta) = self else {
+            return None;
+        };
+
+        let text: String = delta["delta"]["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|part| part["text"]["value"].as_str())
+            .collect();
+
+        (!text.is_empty()).then_some(text)
+    }
+}
+
+/// Only message deltas carry tokens.
+impl ChunkTokens for AssistantStreamEvent {
+    fn tokens(&self) -> usize {
+        match self {
+            Self::MessageDelta(delta) => delta.tokens(),
+            _ => 0,
+        }
+    }
+}
+
+/// Turn the server-sent events of a run into [`AssistantStreamEvent`], ending at the `done` event
+/// and turning `error` events into errors.
+fn run_events(
+    events: impl Stream<Item = OpenAIResult<ServerSentEvent>>,
+) -> impl Stream<Item = OpenAIResult<AssistantStreamEvent>> {
+    events
+        .take_while(|event| {
+            let done = event
+                .as_ref()
+                .is_ok_and(|event| event.event.as_deref() == Some("done"));
+            futures::future::ready(!done)
+        })
+        .map(|event| {
+            let event = event?;
+            let name = event.event.unwrap_or_default();
+            let data: Value = serde_json::from_str(&event.data)?;
+
+            if name == "error" {
+                return Err(OpenAIError::UnexpectedResponse(format!(
+                    "error in the stream: {}",
+                    data.get("error").unwrap_or(&data)
+                )));
+            }
+
+            Ok(AssistantStreamEvent::new(&name, data))
+        })
+}
+
+impl ThreadsApi {
     /// Create a new thread with the provided request parameters.
     ///
     /// # Arguments
@@ -242,8 +443,8 @@ impl<'a> ThreadsApi<'a> {
     /// * `thread_id` - The ID of the thread to list messages from.
     /// * `limit` - Optional limit on the number of messages to list.
     /// * `order` - Optional order parameter for the message listing.
-    /// * `after` - Optional parameter to list messages after a specific time.
-    /// * `before` - Optional parameter to list messages before a specific time.
+    /// * `after` - Optional cursor to list the messages following this one.
+    /// * `before` - Optional cursor to list the messages preceding this one.
     ///
     /// # Returns
     ///
@@ -252,9 +453,9 @@ impl<'a> ThreadsApi<'a> {
         &self,
         thread_id: &str,
         limit: Option<u32>,
-        order: Option<&str>,
-        after: Option<&str>,
-        before: Option<&str>,
+        order: Option<Order>,
+        after: Option<After<MessageId>>,
+        before: Option<Before<MessageId>>,
     ) -> OpenAIResult<Value> {
         let mut url = format!("/threads/{thread_id}/messages?");
 
@@ -264,6 +465,37 @@ impl<'a> ThreadsApi<'a> {
         self.0.get(&url).await
     }
 
+    /// Stream every message of a thread, fetching the pages of [`ThreadsApi::list_messages`] as
+    /// the stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_id` - The ID of the thread to list messages from.
+    /// * `limit` - Number of messages fetched per page.
+    /// * `order` - Order of the messages.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the messages as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all_messages(
+        &self,
+        thread_id: &str,
+        limit: Option<u32>,
+        order: Option<Order>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+        let thread_id = thread_id.to_string();
+
+        paginate(move |after| {
+            let api = ThreadsApi(openai.clone());
+            let thread_id = thread_id.clone();
+            async move {
+                api.list_messages(&thread_id, limit, order, after.map(After::from), None)
+                    .await
+            }
+        })
+    }
+
     /// Retrieve a specific message by its ID from a thread.
     ///
     /// # Arguments
@@ -332,7 +564,7 @@ impl<'a> ThreadsApi<'a> {
         &self,
         thread_id: &str,
         assistant_id: &str,
-        model: Option<&str>,
+        model: Option<Model>,
         instructions: Option<&str>,
         additional_instructions: Option<&str>,
         additional_messages: Option<Vec<Value>>,
@@ -371,6 +603,59 @@ impl<'a> ThreadsApi<'a> {
         self.0.post_json(&url, &body).await
     }
 
+    /// Create and initiate a run in a specific thread, streaming its events as server-sent events.
+    ///
+    /// # Arguments
+    ///
+    /// * The parameters of [`ThreadsApi::create_run`], the run being created with `stream` set.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a stream of [`AssistantStreamEvent`] ending with the run on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] if the request failed.
+    /// An `error` event is yielded as [`OpenAIError::UnexpectedResponse`][crate::error_handling::OpenAIError::UnexpectedResponse].
+    pub async fn create_run_stream(
+        &self,
+        thread_id: &str,
+        assistant_id: &str,
+        model: Option<Model>,
+        instructions: Option<&str>,
+        additional_instructions: Option<&str>,
+        additional_messages: Option<Vec<Value>>,
+        tools: Option<Vec<Value>>,
+        metadata: Option<Value>,
+        temperature: Option<f64>,
+        top_p: Option<f64>,
+        max_prompt_tokens: Option<u32>,
+        max_completion_tokens: Option<u32>,
+        truncation_strategy: Option<Value>,
+        tool_choice: Option<Value>,
+        parallel_tool_calls: Option<bool>,
+        response_format: Option<Value>,
+    ) -> OpenAIResult<impl Stream<Item = OpenAIResult<AssistantStreamEvent>>> {
+        let url = format!("/threads/{thread_id}/runs");
+        // Serialized beforehand, so the stream doesn't borrow the arguments.
+        let body = serde_json::to_value(CreateRunRequest {
+            assistant_id,
+            model,
+            instructions,
+            additional_instructions,
+            additional_messages,
+            tools,
+            metadata,
+            temperature,
+            top_p,
+            stream: Some(true),
+            max_prompt_tokens,
+            max_completion_tokens,
+            truncation_strategy,
+            tool_choice,
+            parallel_tool_calls,
+            response_format,
+        })?;
+
+        Ok(run_events(self.0.post_events(&url, &body).await?))
+    }
+
     /// List runs within a specific thread with optional filters.
     ///
     /// # Arguments
@@ -378,8 +663,8 @@ impl<'a> ThreadsApi<'a> {
     /// * `thread_id` - The ID of the thread to list runs from.
     /// * `limit` - Optional limit on the number of runs to list.
     /// * `order` - Optional order parameter for the run listing.
-    /// * `after` - Optional parameter to list runs after a specific time.
-    /// * `before` - Optional parameter to list runs before a specific time.
+    /// * `after` - Optional cursor to list the runs following this one.
+    /// * `before` - Optional cursor to list the runs preceding this one.
     ///
     /// # Returns
     ///
@@ -388,9 +673,9 @@ impl<'a> ThreadsApi<'a> {
         &self,
         thread_id: &str,
         limit: Option<u32>,
-        order: Option<&str>,
-        after: Option<&str>,
-        before: Option<&str>,
+        order: Option<Order>,
+        after: Option<After<RunId>>,
+        before: Option<Before<RunId>>,
     ) -> OpenAIResult<Value> {
         let mut url = format!("/threads/{thread_id}/runs?");
 
@@ -400,6 +685,37 @@ impl<'a> ThreadsApi<'a> {
         self.0.get(&url).await
     }
 
+    /// Stream every run of a thread, fetching the pages of [`ThreadsApi::list_runs`] as the
+    /// stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_id` - The ID of the thread to list runs from.
+    /// * `limit` - Number of runs fetched per page.
+    /// * `order` - Order of the runs.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the runs as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all_runs(
+        &self,
+        thread_id: &str,
+        limit: Option<u32>,
+        order: Option<Order>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+        let thread_id = thread_id.to_string();
+
+        paginate(move |after| {
+            let api = ThreadsApi(openai.clone());
+            let thread_id = thread_id.clone();
+            async move {
+                api.list_runs(&thread_id, limit, order, after.map(After::from), None)
+                    .await
+            }
+        })
+    }
+
     /// Retrieve details of a specific run by its ID.
     ///
     /// # Arguments
@@ -483,6 +799,32 @@ impl<'a> ThreadsApi<'a> {
         self.0.post_json(&url, &body).await
     }
 
+    /// Submit tool outputs for a run waiting for them, streaming the events of the rest of the run.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_id` - The ID of the thread containing the run.
+    /// * `run_id` - The ID of the run to submit outputs to.
+    /// * `tool_outputs` - List of tool outputs to submit.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a stream of [`AssistantStreamEvent`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] if the request failed.
+    pub async fn submit_tool_outputs_stream(
+        &self,
+        thread_id: &str,
+        run_id: &str,
+        tool_outputs: Vec<Value>,
+    ) -> OpenAIResult<impl Stream<Item = OpenAIResult<AssistantStreamEvent>>> {
+        let url = format!("/threads/{thread_id}/runs/{run_id}/submit_tool_outputs");
+        let body = SubmitToolRequest {
+            tool_outputs,
+            stream: Some(true),
+        };
+
+        Ok(run_events(self.0.post_events(&url, &body).await?))
+    }
+
     /// Cancel a specific run by its ID in a thread.
     ///
     /// # Arguments
@@ -508,8 +850,8 @@ impl<'a> ThreadsApi<'a> {
     /// * `run_id` - The ID of the run to list steps from.
     /// * `limit` - Optional limit on the number of steps to list.
     /// * `order` - Optional order parameter for the steps listing.
-    /// * `after` - Optional parameter to list steps after a specific time.
-    /// * `before` - Optional parameter to list steps before a specific time.
+    /// * `after` - Optional cursor to list the steps following this one.
+    /// * `before` - Optional cursor to list the steps preceding this one.
     ///
     /// # Returns
     ///
@@ -519,9 +861,9 @@ impl<'a> ThreadsApi<'a> {
         thread_id: &str,
         run_id: &str,
         limit: Option<u32>,
-        order: Option<&str>,
-        after: Option<&str>,
-        before: Option<&str>,
+        order: Option<Order>,
+        after: Option<After<RunStepId>>,
+        before: Option<Before<RunStepId>>,
     ) -> OpenAIResult<Value> {
         let mut url = format!("/threads/{thread_id}/runs/{run_id}/steps?");
 
@@ -531,6 +873,40 @@ impl<'a> ThreadsApi<'a> {
         self.0.get(&url).await
     }
 
+    /// Stream every step of a run, fetching the pages of [`ThreadsApi::list_run_steps`] as the
+    /// stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_id` - The ID of the thread containing the run.
+    /// * `run_id` - The ID of the run to list steps from.
+    /// * `limit` - Number of steps fetched per page.
+    /// * `order` - Order of the steps.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the steps as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all_run_steps(
+        &self,
+        thread_id: &str,
+        run_id: &str,
+        limit: Option<u32>,
+        order: Option<Order>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+        let (thread_id, run_id) = (thread_id.to_string(), run_id.to_string());
+
+        paginate(move |after| {
+            let api = ThreadsApi(openai.clone());
+            let (thread_id, run_id) = (thread_id.clone(), run_id.clone());
+            async move {
+                let after = after.map(After::from);
+                api.list_run_steps(&thread_id, &run_id, limit, order, after, None)
+                    .await
+            }
+        })
+    }
+
     /// Retrieve a specific step by its ID from a run within a thread.
     ///
     /// # Arguments
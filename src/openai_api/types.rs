@@ -0,0 +1,81 @@
+use crate::deserialize_with_extra;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::ops::AddAssign;
+
+/// Token usage reported in a response.
+///
+/// Fields the API adds (e.g. `prompt_tokens_details`) are kept in `extra`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Usage {
+    /// Tokens in the prompt
+    pub prompt_tokens: u64,
+
+    /// Tokens in the completion, absent for embeddings
+    pub completion_tokens: u64,
+
+    /// Total tokens billed
+    pub total_tokens: u64,
+
+    /// Fields of the response the type doesn't declare
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    Usage {
+        #[default] prompt_tokens: u64,
+        #[default] completion_tokens: u64,
+        #[default] total_tokens: u64,
+    }
+}
+
+/// Only the token counts are added up, `extra` is left as is.
+impl AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, other: &Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        *self += &other;
+    }
+}
+
+/// A page of a list endpoint, decoded into items of type `T`.
+///
+/// Every list endpoint can be fetched as a typed page with
+/// [`OpenAI::get`][crate::openai::OpenAI::get], e.g. `openai.get::<List<MyFile>>("/files")`.
+/// Fields of the page the type doesn't declare are kept in `extra`.
+#[derive(Clone, Debug, Serialize)]
+#[non_exhaustive]
+pub struct List<T> {
+    /// Items of the page
+    pub data: Vec<T>,
+
+    /// ID of the first item, used as the `before` cursor
+    pub first_id: Option<String>,
+
+    /// ID of the last item, used as the `after` cursor
+    pub last_id: Option<String>,
+
+    /// Whether more items follow this page
+    pub has_more: bool,
+
+    /// Fields of the page the type doesn't declare, e.g. `object`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+deserialize_with_extra! {
+    List<T> {
+        data: Vec<T>,
+        #[default] first_id: Option<String>,
+        #[default] last_id: Option<String>,
+        #[default] has_more: bool,
+    }
+}
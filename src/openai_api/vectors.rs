@@ -1,9 +1,17 @@
-use crate::{error_handling::OpenAIResult, extend_url_params, openai::OpenAI, setters};
+use crate::{
+    error_handling::OpenAIResult,
+    extend_url_params,
+    openai::OpenAI,
+    openai_api::pagination::{paginate, After, Before, Order, VectorStoreId},
+    request_builder,
+};
+use futures::Stream;
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{json, Map, Value};
 
 /// [`VectorsApi`] struct to interact with vector stores API endpoints.
-pub struct VectorsApi<'a>(pub(crate) &'a OpenAI<'a>);
+#[derive(Clone)]
+pub struct VectorsApi(pub(crate) OpenAI);
 
 /// Struct representing a request for vector store creation.
 #[derive(Default, Serialize)]
@@ -27,6 +35,10 @@ pub struct VectorStoreCreationRequest {
     /// Metadata for the vector store
     #[serde(skip_serializing_if = "Option::is_none")]
     metadata: Option<Value>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 /// Struct representing a request for vector store modification.
@@ -43,28 +55,53 @@ pub struct VectorStoreModificationRequest {
     /// Metadata for the vector store
     #[serde(skip_serializing_if = "Option::is_none")]
     metadata: Option<Value>,
+
+    /// Extra fields sent as is, e.g. parameters specific to a provider or gateway
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
-impl VectorStoreCreationRequest {
-    setters! {
-        /// Set file IDs for the request.
-        file_ids: Vec<String>,
+request_builder! {
+    /// Builder of a [`VectorStoreCreationRequest`].
+    VectorStoreCreationRequest => VectorStoreCreationRequestBuilder {
+        required {}
+        optional {
+            /// Set file IDs for the request.
+            file_ids: Vec<String>,
+
+            /// Set name for the request.
+            name: String,
+
+            /// Set expiration date for the request.
+            expires_after: Value,
 
-        /// Set name for the request.
-        name: String,
+            /// Set chunking strategy for the request.
+            chunking_strategy: Value,
 
-        /// Set expiration date for the request.
-        expires_after: Value,
+            /// Set metadata for the request.
+            metadata: Value,
+        }
+    }
+}
 
-        /// Set chunking strategy for the request.
-        chunking_strategy: Value,
+request_builder! {
+    /// Builder of a [`VectorStoreModificationRequest`].
+    VectorStoreModificationRequest => VectorStoreModificationRequestBuilder {
+        required {}
+        optional {
+            /// Set name for the request.
+            name: String,
 
-        /// Set metadata for the request.
-        metadata: Value,
+            /// Set expiration date for the request.
+            expires_after: Value,
+
+            /// Set metadata for the request.
+            metadata: Value,
+        }
     }
 }
 
-impl<'a> VectorsApi<'a> {
+impl VectorsApi {
     /// Create a new vector store using the provided request parameters.
     ///
     /// # Arguments
@@ -88,8 +125,8 @@ impl<'a> VectorsApi<'a> {
     ///
     /// * `limit` - Maximum number of vector stores to retrieve.
     /// * `order` - Order of the retrieved vector stores.
-    /// * `after` - Retrieve vector stores created after this ID.
-    /// * `before` - Retrieve vector stores created before this ID.
+    /// * `after` - Retrieve the vector stores following this one.
+    /// * `before` - Retrieve the vector stores preceding this one.
     ///
     /// # Returns
     ///
@@ -97,9 +134,9 @@ impl<'a> VectorsApi<'a> {
     pub async fn list_vector_stores(
         &self,
         limit: Option<u64>,
-        order: Option<String>,
-        after: Option<String>,
-        before: Option<String>,
+        order: Option<Order>,
+        after: Option<After<VectorStoreId>>,
+        before: Option<Before<VectorStoreId>>,
     ) -> OpenAIResult<Value> {
         let mut url = String::from("/vector_stores?");
 
@@ -109,6 +146,33 @@ impl<'a> VectorsApi<'a> {
         self.0.get(&url).await
     }
 
+    /// Stream every vector store, fetching the pages of [`VectorsApi::list_vector_stores`] as the
+    /// stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Number of vector stores fetched per page.
+    /// * `order` - Order of the vector stores.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the vector stores as [`serde_json::Value`], or of an [`OpenAIError`][crate::error_handling::OpenAIError] if a page couldn't be fetched.
+    pub fn list_all_vector_stores(
+        &self,
+        limit: Option<u64>,
+        order: Option<Order>,
+    ) -> impl Stream<Item = OpenAIResult<Value>> + 'static {
+        let openai = self.0.clone();
+
+        paginate(move |after| {
+            let api = VectorsApi(openai.clone());
+            async move {
+                api.list_vector_stores(limit, order, after.map(After::from), None)
+                    .await
+            }
+        })
+    }
+
     /// Retrieve details of a specific vector store.
     ///
     /// # Arguments
@@ -158,4 +222,30 @@ impl<'a> VectorsApi<'a> {
 
         self.0.delete(&url).await
     }
+
+    /// Search a vector store for the chunks most relevant to a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `vector_store_id` - The ID of the vector store to search.
+    /// * `query` - The text to search for.
+    /// * `max_num_results` - Maximum number of results to return.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the JSON response as [`serde_json::Value`] on success, or an [`OpenAIError`][crate::error_handling::OpenAIError] on failure.
+    pub async fn search_vector_store(
+        &self,
+        vector_store_id: &str,
+        query: &str,
+        max_num_results: Option<u64>,
+    ) -> OpenAIResult<Value> {
+        let url = format!("/vector_stores/{vector_store_id}/search");
+        let mut body = json!({ "query": query });
+        if let Some(max_num_results) = max_num_results {
+            body["max_num_results"] = json!(max_num_results);
+        }
+
+        self.0.post_json(&url, &body).await
+    }
 }
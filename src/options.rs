@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+/// Options of individual calls, applied to the calls made through the client returned by
+/// [`OpenAI::with_options`][crate::openai::OpenAI::with_options], e.g.
+/// `openai.with_options(RequestOptions::new().header("X-Experiment", "b")).completions().create(request)`.
+///
+/// The client-wide settings are left unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Extra headers of the calls, e.g. experiment identifiers or gateway routing hints,
+    /// replacing the client's headers of the same name
+    pub headers: Vec<(String, String)>,
+
+    /// Total timeout of each request of the calls, replacing the client's JSON, upload and HTTP
+    /// client timeouts
+    pub timeout: Option<Duration>,
+}
+
+impl RequestOptions {
+    /// Create empty [`RequestOptions`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the header `name` with `value`.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Bound each request of the calls by `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
@@ -0,0 +1,484 @@
+use crate::{
+    error_handling::OpenAIResult, provider::AuthScheme, signing::read_body, util::SECRET_HEADERS,
+};
+use reqwest::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    Request,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// A request as it would be sent to the API, with the API key and other credentials redacted.
+#[derive(Clone, Debug, Serialize)]
+pub struct PreparedRequest {
+    /// HTTP method of the request
+    pub method: String,
+
+    /// Full URL of the request
+    pub url: String,
+
+    /// Headers of the request, the values of the header carrying the API key and of the other
+    /// credential headers being redacted
+    pub headers: Vec<(String, String)>,
+
+    /// Body of the request
+    pub body: PreparedBody,
+
+    /// Name of the header carrying the API key
+    #[serde(skip)]
+    api_key_header: Option<String>,
+}
+
+/// Body of a [`PreparedRequest`].
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", content = "content", rename_all = "snake_case")]
+pub enum PreparedBody {
+    /// No body
+    Empty,
+
+    /// A JSON body
+    Json(Value),
+
+    /// A body that isn't JSON, lossily decoded as UTF-8
+    Text(String),
+
+    /// A multipart form
+    Multipart(Vec<FormPart>),
+}
+
+/// A field of a multipart form.
+#[derive(Clone, Debug, Serialize)]
+pub struct FormPart {
+    /// Name of the field
+    pub name: String,
+
+    /// File name, for file fields
+    pub file_name: Option<String>,
+
+    /// Content type, for file fields
+    pub content_type: Option<String>,
+
+    /// Value, for text fields
+    pub value: Option<String>,
+
+    /// Length of the field content in bytes
+    pub length: usize,
+}
+
+impl PreparedRequest {
+    /// Capture a built request, reading its body, the API key being sent as `auth_scheme`
+    /// describes.
+    pub(crate) async fn capture(
+        mut request: Request,
+        auth_scheme: &AuthScheme,
+    ) -> OpenAIResult<Self> {
+        let body = match request.body_mut().take() {
+            Some(body) => read_body(body).await?.to_vec(),
+            None => Vec::new(),
+        };
+
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let content_type = header(CONTENT_TYPE).unwrap_or_default();
+
+        let body = if body.is_empty() {
+            PreparedBody::Empty
+        } else if let Some(parts) = multipart_parts(content_type, &body) {
+            PreparedBody::Multipart(parts)
+        } else if let Ok(json) = serde_json::from_slice(&body) {
+            PreparedBody::Json(json)
+        } else {
+            PreparedBody::Text(String::from_utf8_lossy(&body).into_owned())
+        };
+
+        let api_key_header = auth_scheme
+            .header(REDACTED)
+            .map(|(name, value)| (name.to_ascii_lowercase(), value));
+        let headers = request
+            .headers()
+            .iter()
+            .filter(|(name, _)| *name != CONTENT_LENGTH)
+            .map(|(name, value)| {
+                let value = match &api_key_header {
+                    Some((api_key_name, redacted)) if name == api_key_name.as_str() => {
+                        redacted.clone()
+                    },
+                    _ if SECRET_HEADERS.contains(&name.as_str()) => REDACTED.to_string(),
+                    _ => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                };
+
+                (name.to_string(), value)
+            })
+            .collect();
+
+        Ok(Self {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers,
+            body,
+            api_key_header: api_key_header.map(|(name, _)| name),
+        })
+    }
+
+    /// Render the request as an equivalent `curl` command.
+    ///
+    /// The API key is read from the `OPENAI_API_KEY` environment variable and the other redacted
+    /// credentials from the variable named after their header (e.g. `PROXY_AUTHORIZATION`), and
+    /// file fields of multipart forms refer to their file name, so the command can be run from a
+    /// shell where the files are available.
+    pub fn to_curl(&self) -> String {
+        let mut command = format!("curl -X {} {}", self.method, shell_quote(&self.url));
+        let multipart = matches!(self.body, PreparedBody::Multipart(_));
+
+        for (name, value) in &self.headers {
+            let api_key = self.api_key_header.as_ref() == Some(name);
+            if api_key || SECRET_HEADERS.contains(&name.as_str()) {
+                let variable = if api_key {
+                    "OPENAI_API_KEY".to_string()
+                } else {
+                    name.to_ascii_uppercase().replace('-', "_")
+                };
+                // Only the variable is expanded, the rest of the header being quoted as is.
+                let header = format!("{name}: {value}")
+                    .split(REDACTED)
+                    .map(|literal| match literal {
+                        "" => String::new(),
+                        literal => shell_quote(literal),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(&format!("\"${variable}\""));
+                command.push_str(&format!(" \\\n  -H {header}"));
+            } else if !(multipart && name == CONTENT_TYPE.as_str()) {
+                // curl generates its own multipart boundary.
+                command.push_str(&format!(
+                    " \\\n  -H {}",
+                    shell_quote(&format!("{name}: {value}"))
+                ));
+            }
+        }
+
+        match &self.body {
+            PreparedBody::Empty => {},
+            PreparedBody::Json(json) => {
+                command.push_str(&format!(" \\\n  -d {}", shell_quote(&json.to_string())));
+            },
+            PreparedBody::Text(text) => {
+                command.push_str(&format!(" \\\n  --data-binary {}", shell_quote(text)));
+            },
+            PreparedBody::Multipart(parts) => {
+                for part in parts {
+                    let field = match (&part.file_name, &part.value) {
+                        (Some(file_name), _) => {
+                            let content_type = part
+                                .content_type
+                                .as_ref()
+                                .map(|content_type| format!(";type={content_type}"))
+                                .unwrap_or_default();
+
+                            format!(
+                                "-F {}",
+                                shell_quote(&format!("{}=@{file_name}{content_type}", part.name))
+                            )
+                        },
+                        (None, value) => format!(
+                            "--form-string {}",
+                            shell_quote(&format!(
+                                "{}={}",
+                                part.name,
+                                value.as_deref().unwrap_or_default()
+                            ))
+                        ),
+                    };
+
+                    command.push_str(&format!(" \\\n  {field}"));
+                }
+            },
+        }
+
+        command
+    }
+}
+
+tokio::task_local! {
+    /// Set while a call is being captured instead of sent (see [`OpenAI::dry_run`][crate::openai::OpenAI::dry_run]).
+    pub(crate) static CAPTURE: Arc<Mutex<Option<PreparedRequest>>>;
+}
+
+/// Quote a string for POSIX shells.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Split a `multipart/form-data` body into its fields.
+fn multipart_parts(content_type: &str, body: &[u8]) -> Option<Vec<FormPart>> {
+    let boundary = content_type
+        .strip_prefix("multipart/form-data")?
+        .split(';')
+        .find_map(|parameter| parameter.trim().strip_prefix("boundary="))?
+        .trim_matches('"');
+    let delimiter = format!("--{boundary}").into_bytes();
+
+    let mut parts = Vec::new();
+    let mut rest = body;
+
+    while let Some(start) = find(rest, &delimiter) {
+        rest = &rest[start + delimiter.len()..];
+
+        if rest.starts_with(b"--") {
+            break;
+        }
+
+        let end = find(rest, &delimiter).unwrap_or(rest.len());
+        let part = rest[..end].strip_prefix(b"\r\n").unwrap_or(&rest[..end]);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        let header_end = find(part, b"\r\n\r\n")?;
+        let headers = String::from_utf8_lossy(&part[..header_end]);
+        let content = &part[header_end + 4..];
+
+        let mut form_part = FormPart {
+            name: String::new(),
+            file_name: None,
+            content_type: None,
+            value: None,
+            length: content.len(),
+        };
+
+        for line in headers.lines() {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            if name.eq_ignore_ascii_case("content-disposition") {
+                for parameter in value.split(';').map(str::trim) {
+                    if let Some(name) = parameter.strip_prefix("name=") {
+                        form_part.name = name.trim_matches('"').to_string();
+                    } else if let Some(file_name) = parameter.strip_prefix("filename=") {
+                        form_part.file_name = Some(file_name.trim_matches('"').to_string());
+                    }
+                }
+            } else if name.eq_ignore_ascii_case("content-type") {
+                form_part.content_type = Some(value.trim().to_string());
+            }
+        }
+
+        if form_part.file_name.is_none() {
+            form_part.value = Some(String::from_utf8_lossy(content).into_owned());
+        }
+
+        parts.push(form_part);
+    }
+
+    Some(parts)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error_handling::OpenAIError, openai::OpenAI, openai_api::completion::ChatCompletionRequest,
+    };
+    use serde_json::json;
+
+    fn client() -> OpenAI {
+        OpenAI::new("sk-test", "https://api.openai.com/v1")
+    }
+
+    fn chat_request() -> ChatCompletionRequest {
+        ChatCompletionRequest::new(
+            "gpt-4o-mini",
+            vec![json!({"role": "user", "content": "Hello"})],
+        )
+    }
+
+    fn prepared(headers: &[(&str, &str)], body: PreparedBody) -> PreparedRequest {
+        PreparedRequest {
+            method: "POST".to_string(),
+            url: "https://api.openai.com/v1/chat/completions".to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body,
+            api_key_header: Some("authorization".to_string()),
+        }
+    }
+
+    #[test]
+    fn curl_commands_quote_everything_but_the_credential_variables() {
+        let request = prepared(
+            &[
+                ("authorization", "Bearer [REDACTED]"),
+                ("proxy-authorization", "Basic [REDACTED]"),
+                ("x-note", "it's $HOME"),
+            ],
+            PreparedBody::Json(json!({ "content": "don't `run` $(this)" })),
+        );
+
+        assert_eq!(
+            request.to_curl(),
+            r#"curl -X POST 'https://api.openai.com/v1/chat/completions' \
+  -H 'authorization: Bearer '"$OPENAI_API_KEY" \
+  -H 'proxy-authorization: Basic '"$PROXY_AUTHORIZATION" \
+  -H 'x-note: it'\''s $HOME' \
+  -d '{"content":"don'\''t `run` $(this)"}'"#
+        );
+    }
+
+    #[test]
+    fn curl_commands_send_multipart_forms_as_fields() {
+        let request = prepared(
+            &[("content-type", "multipart/form-data; boundary=abc")],
+            PreparedBody::Multipart(vec![
+                FormPart {
+                    name: "file".to_string(),
+                    file_name: Some("my image.png".to_string()),
+                    content_type: Some("image/png".to_string()),
+                    value: None,
+                    length: 3,
+                },
+                FormPart {
+                    name: "prompt".to_string(),
+                    file_name: None,
+                    content_type: None,
+                    value: Some("a 'hat'".to_string()),
+                    length: 7,
+                },
+            ]),
+        );
+
+        assert_eq!(
+            request.to_curl(),
+            r#"curl -X POST 'https://api.openai.com/v1/chat/completions' \
+  -F 'file=@my image.png;type=image/png' \
+  --form-string 'prompt=a '\''hat'\'''"#
+        );
+    }
+
+    #[test]
+    fn multipart_bodies_are_split_into_their_fields() {
+        let body = b"--abc\r\n\
+            Content-Disposition: form-data; name=\"prompt\"\r\n\r\n\
+            Add a hat\r\n\
+            --abc\r\n\
+            Content-Disposition: form-data; name=\"image\"; filename=\"image.png\"\r\n\
+            Content-Type: image/png\r\n\r\n\
+            png\r\n\
+            --abc--\r\n";
+
+        let parts = multipart_parts("multipart/form-data; boundary=\"abc\"", body).unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "prompt");
+        assert_eq!(parts[0].value.as_deref(), Some("Add a hat"));
+        assert_eq!(parts[0].file_name, None);
+        assert_eq!(parts[1].name, "image");
+        assert_eq!(parts[1].file_name.as_deref(), Some("image.png"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("image/png"));
+        assert_eq!(parts[1].value, None);
+        assert_eq!(parts[1].length, 3);
+
+        assert!(multipart_parts("application/json", body).is_none());
+    }
+
+    #[tokio::test]
+    async fn dry_run_prepares_a_single_call() {
+        let openai = client();
+
+        let prepared = openai
+            .dry_run(openai.completions().create(chat_request()))
+            .await
+            .unwrap();
+
+        assert_eq!(prepared.method, "POST");
+        assert_eq!(prepared.url, "https://api.openai.com/v1/chat/completions");
+        assert!(prepared
+            .headers
+            .iter()
+            .any(|(name, value)| name == "authorization" && value == "Bearer [REDACTED]"));
+        match prepared.body {
+            PreparedBody::Json(body) => assert_eq!(body["model"], "gpt-4o-mini"),
+            body => panic!("unexpected body {body:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn dry_run_prepares_multipart_calls() {
+        let openai = client();
+
+        let prepared = openai
+            .dry_run(openai.images().edit_bytes(
+                None,
+                b"png".to_vec(),
+                None,
+                "Add a hat",
+                None,
+                None,
+                None,
+                None,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(prepared.url, "https://api.openai.com/v1/images/edits");
+        let PreparedBody::Multipart(parts) = prepared.body else {
+            panic!("unexpected body {:?}", prepared.body);
+        };
+        let image = parts.iter().find(|part| part.name == "image").unwrap();
+        assert!(image.file_name.is_some());
+        assert_eq!(image.length, 3);
+        let prompt = parts.iter().find(|part| part.name == "prompt").unwrap();
+        assert_eq!(prompt.value.as_deref(), Some("Add a hat"));
+    }
+
+    #[tokio::test]
+    async fn client_wide_dry_run_fails_every_call_with_its_request() {
+        let mut openai = client();
+        openai.set_dry_run(true);
+
+        let error = openai.completions().create(chat_request()).await;
+        let Err(OpenAIError::DryRun(prepared)) = error else {
+            panic!("unexpected result {error:?}");
+        };
+        assert_eq!(prepared.url, "https://api.openai.com/v1/chat/completions");
+        assert!(matches!(prepared.body, PreparedBody::Json(_)));
+
+        let error = openai
+            .completions()
+            .create_stream(chat_request())
+            .await
+            .map(|_| ());
+        assert!(matches!(error, Err(OpenAIError::DryRun(_))));
+
+        let error = openai
+            .images()
+            .edit_bytes(
+                None,
+                b"png".to_vec(),
+                None,
+                "Add a hat",
+                None,
+                None,
+                None,
+                None,
+            )
+            .await;
+        let Err(OpenAIError::DryRun(prepared)) = error else {
+            panic!("unexpected result {error:?}");
+        };
+        assert_eq!(prepared.url, "https://api.openai.com/v1/images/edits");
+        assert!(matches!(prepared.body, PreparedBody::Multipart(_)));
+    }
+}
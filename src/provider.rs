@@ -0,0 +1,564 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// How the API key is sent with every request.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <api key>`
+    #[default]
+    Bearer,
+
+    /// The API key as the value of this header, e.g. `api-key` or `x-api-key`
+    Header(String),
+
+    /// No authentication, for servers that don't require any
+    None,
+}
+
+impl AuthScheme {
+    /// The header carrying `api_key`, if any.
+    pub(crate) fn header(&self, api_key: &str) -> Option<(String, String)> {
+        match self {
+            Self::Bearer => Some(("Authorization".to_string(), format!("Bearer {api_key}"))),
+            Self::Header(name) => Some((name.clone(), api_key.to_string())),
+            Self::None => None,
+        }
+    }
+}
+
+/// Preset configuration of an OpenAI-compatible API provider: its base URL, how it authenticates
+/// requests and the extra headers it expects.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Provider {
+    /// The OpenAI API
+    #[default]
+    OpenAI,
+
+    /// OpenRouter, optionally attributing the requests to an application
+    OpenRouter {
+        /// URL of the application, sent in the `HTTP-Referer` header
+        #[serde(default)]
+        referer: Option<String>,
+
+        /// Name of the application, sent in the `X-Title` header
+        #[serde(default)]
+        title: Option<String>,
+    },
+
+    /// Together AI
+    Together,
+
+    /// Groq
+    Groq,
+
+    /// Google Gemini, through its OpenAI compatibility endpoint
+    Gemini,
+
+    /// A local Ollama server
+    Ollama,
+
+    /// A local LM Studio server
+    LmStudio,
+
+    /// A local vLLM server
+    Vllm,
+}
+
+impl Provider {
+    /// Base URL of the API of the provider.
+    pub const fn base_url(&self) -> &'static str {
+        match self {
+            Self::OpenAI => "https://api.openai.com/v1",
+            Self::OpenRouter { .. } => "https://openrouter.ai/api/v1",
+            Self::Together => "https://api.together.xyz/v1",
+            Self::Groq => "https://api.groq.com/openai/v1",
+            Self::Gemini => "https://generativelanguage.googleapis.com/v1beta/openai",
+            Self::Ollama => "http://localhost:11434/v1",
+            Self::LmStudio => "http://localhost:1234/v1",
+            Self::Vllm => "http://localhost:8000/v1",
+        }
+    }
+
+    /// How the provider expects the API key, local servers requiring none.
+    pub fn auth_scheme(&self) -> AuthScheme {
+        if self.is_local() {
+            AuthScheme::None
+        } else {
+            AuthScheme::Bearer
+        }
+    }
+
+    /// Whether the provider is a local server, whose responses need the client's local
+    /// compatibility mode.
+    pub const fn is_local(&self) -> bool {
+        matches!(self, Self::Ollama | Self::LmStudio | Self::Vllm)
+    }
+
+    /// Whether the responses of the provider need the client's compatibility mode: local servers
+    /// and Gemini have their own error envelopes.
+    pub const fn needs_compat(&self) -> bool {
+        self.is_local() || matches!(self, Self::Gemini)
+    }
+
+    /// Request fields of the OpenAI API the provider rejects or doesn't implement, removed from
+    /// the request bodies.
+    pub const fn unsupported_fields(&self) -> &'static [&'static str] {
+        match self {
+            Self::Gemini => &[
+                "logit_bias",
+                "presence_penalty",
+                "frequency_penalty",
+                "logprobs",
+                "top_logprobs",
+                "user",
+            ],
+            // Groq rejects requests with any of these set.
+            Self::Groq => &["logprobs", "top_logprobs", "logit_bias"],
+            Self::Ollama => &["logit_bias", "logprobs", "top_logprobs", "user", "n"],
+            Self::LmStudio => &["logprobs", "top_logprobs", "user"],
+            _ => &[],
+        }
+    }
+
+    /// Extra headers sent with every request to the provider.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        match self {
+            Self::OpenRouter { referer, title } => [("HTTP-Referer", referer), ("X-Title", title)]
+                .into_iter()
+                .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Rewrite an error response of a local OpenAI-compatible server or of Gemini into the shape of
+/// the OpenAI API, or `None` if it already has it.
+///
+/// Errors given as a string (`{"error": "..."}`), as a top-level object (`{"object": "error",
+/// "message": "..."}`), as a FastAPI `detail`, wrapped in an array (`[{"error": {...}}]`) or as
+/// plain text become `{"error": {"message": ...}}`. Numeric codes, which repeat the HTTP status,
+/// are dropped so that the error type is reported as its code instead.
+pub(crate) fn normalize_local_error(body: &[u8]) -> Option<Vec<u8>> {
+    if body.is_empty() {
+        return None;
+    }
+    let code = |code: Option<&Value>| code.filter(|code| code.is_string()).cloned();
+    let Ok(response) = serde_json::from_slice::<Value>(body) else {
+        let message = String::from_utf8_lossy(body).trim().to_string();
+        return Some(
+            json!({ "error": { "message": message } })
+                .to_string()
+                .into_bytes(),
+        );
+    };
+    if let Some(error) = response
+        .as_array()
+        .and_then(|responses| responses.first())
+        .and_then(|first| first.get("error"))
+    {
+        // Google errors: `{"code": 400, "message": "...", "status": "INVALID_ARGUMENT"}`.
+        let error = json!({
+            "message": error["message"].as_str().unwrap_or_default(),
+            "type": error.get("status").cloned().unwrap_or_default(),
+            "code": code(error.get("code")),
+        });
+        return Some(json!({ "error": error }).to_string().into_bytes());
+    }
+    let object = response.as_object()?;
+
+    let error = match (
+        object.get("error"),
+        object.get("object"),
+        object.get("detail"),
+    ) {
+        (Some(Value::String(message)), ..) => Some(json!({ "message": message })),
+        (None, Some(kind), _) if kind == "error" => Some(json!({
+            "message": object.get("message").cloned().unwrap_or_default(),
+            "type": object.get("type").cloned().unwrap_or_default(),
+            "code": code(object.get("code")),
+        })),
+        (None, _, Some(detail)) if !object.contains_key("id") => Some(json!({
+            "message": match detail {
+                Value::String(message) => message.clone(),
+                detail => detail.to_string(),
+            },
+        })),
+        _ => None,
+    };
+
+    error.map(|error| json!({ "error": error }).to_string().into_bytes())
+}
+
+/// Rewrite a successful response of a local OpenAI-compatible server or of Gemini into the shape
+/// of the OpenAI API, or `None` if it already has it: chat completions missing `id`, `created`,
+/// `model`, `system_fingerprint` or `usage` get empty ones.
+pub(crate) fn normalize_local_completion(body: &[u8]) -> Option<Vec<u8>> {
+    let mut response = serde_json::from_slice::<Value>(body).ok()?;
+    let object = response.as_object_mut()?;
+
+    if !object.contains_key("choices") {
+        return None;
+    }
+    let defaults = [
+        ("id", json!("")),
+        ("created", json!(0)),
+        ("model", json!("")),
+        ("system_fingerprint", Value::Null),
+        (
+            "usage",
+            json!({ "prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0 }),
+        ),
+    ];
+    let missing: Map<String, Value> = defaults
+        .into_iter()
+        .filter(|(key, _)| !object.contains_key(*key))
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    object.extend(missing);
+
+    serde_json::to_vec(&response).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_handling::OpenAIError;
+    use reqwest::StatusCode;
+
+    /// Status, message and code of the API error of a response of a local server or of Gemini.
+    fn api_error(status: u16, body: &str) -> (u16, String, Option<String>) {
+        let body = normalize_local_error(body.as_bytes()).unwrap_or_else(|| body.into());
+
+        match OpenAIError::api_error(StatusCode::from_u16(status).unwrap(), None, &body) {
+            OpenAIError::ApiError {
+                status,
+                message,
+                code,
+                ..
+            } => (status, message, code),
+            error => panic!("unexpected error {error:?}"),
+        }
+    }
+
+    #[test]
+    fn gemini_errors_are_unwrapped_from_their_array() {
+        let body = r#"[{"error": {"code": 400, "message": "API key not valid.", "status": "INVALID_ARGUMENT"}}]"#;
+
+        assert_eq!(
+            api_error(400, body),
+            (
+                400,
+                "API key not valid.".to_string(),
+                Some("INVALID_ARGUMENT".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn string_errors_become_messages() {
+        assert_eq!(
+            api_error(404, r#"{"error": "model 'llama3' not found"}"#),
+            (404, "model 'llama3' not found".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn vllm_error_objects_keep_their_type() {
+        let body = r#"{"object": "error", "message": "max_tokens is too large", "type": "BadRequestError", "param": null, "code": 400}"#;
+
+        assert_eq!(
+            api_error(400, body),
+            (
+                400,
+                "max_tokens is too large".to_string(),
+                Some("BadRequestError".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn fastapi_details_become_messages() {
+        assert_eq!(
+            api_error(404, r#"{"detail": "Not Found"}"#),
+            (404, "Not Found".to_string(), None)
+        );
+
+        let (status, message, code) = api_error(
+            422,
+            r#"{"detail": [{"loc": ["body", "messages"], "msg": "field required"}]}"#,
+        );
+        assert_eq!((status, code), (422, None));
+        assert!(message.contains("field required"));
+    }
+
+    #[test]
+    fn plain_text_errors_become_messages() {
+        assert_eq!(
+            api_error(502, "Bad Gateway\n"),
+            (502, "Bad Gateway".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn openai_errors_are_left_as_is() {
+        let body = r#"{"error": {"message": "Rate limited", "type": "requests", "code": "rate_limit_exceeded"}}"#;
+
+        assert_eq!(normalize_local_error(body.as_bytes()), None);
+        assert_eq!(normalize_local_error(b""), None);
+        assert_eq!(
+            api_error(429, body),
+            (
+                429,
+                "Rate limited".to_string(),
+                Some("rate_limit_exceeded".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn completions_get_the_fields_local_servers_omit() {
+        let body = json!({
+            "object": "chat.completion",
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": "Hi" } }],
+        });
+        let normalized: Value = serde_json::from_slice(
+            &normalize_local_completion(body.to_string().as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(normalized["choices"], body["choices"]);
+        assert_eq!(
+            (
+                &normalized["id"],
+                &normalized["created"],
+                &normalized["model"]
+            ),
+            (&json!(""), &json!(0), &json!(""))
+        );
+        assert_eq!(normalized["usage"]["total_tokens"], 0);
+        assert!(normalized["system_fingerprint"].is_null());
+
+        let complete = serde_json::to_vec(&normalized).unwrap();
+        assert_eq!(normalize_local_completion(&complete), None);
+        assert_eq!(normalize_local_completion(br#"{"data": []}"#), None);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn local_compat_clients_normalize_error_responses() {
+        use crate::{
+            openai_api::completion::ChatCompletionRequest,
+            test_util::mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+        use std::sync::Arc;
+
+        let mock = Arc::new(MockTransport::new());
+        mock.on(
+            Method::POST,
+            "/chat/completions",
+            MockResponse::json(&json!({ "error": "model 'llama3' not found" })).status(404),
+        );
+        let mut openai = mock.client();
+        openai.set_local_compat(true);
+
+        let request =
+            ChatCompletionRequest::new("llama3", vec![json!({ "role": "user", "content": "Hi" })]);
+        let error = openai.completions().create(request).await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            OpenAIError::ApiError { status: 404, message, .. } if message == "model 'llama3' not found"
+        ));
+    }
+
+    mod capture {
+        use super::*;
+        use crate::{
+            openai::OpenAI,
+            openai_api::completion::ChatCompletionRequest,
+            prepared::{PreparedBody, PreparedRequest},
+        };
+
+        fn request() -> ChatCompletionRequest {
+            ChatCompletionRequest::new(
+                "some-model",
+                vec![json!({ "role": "user", "content": "Hi" })],
+            )
+        }
+
+        async fn capture(openai: &OpenAI, request: ChatCompletionRequest) -> PreparedRequest {
+            openai
+                .dry_run(openai.completions().create(request))
+                .await
+                .unwrap()
+        }
+
+        fn header<'a>(prepared: &'a PreparedRequest, name: &str) -> Option<&'a str> {
+            prepared
+                .headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        }
+
+        #[tokio::test]
+        async fn provider_clients_send_to_their_base_url_with_their_authentication() {
+            let bearer = Some("Bearer [REDACTED]");
+            let providers = [
+                (Provider::OpenAI, "https://api.openai.com/v1", bearer),
+                (
+                    Provider::OpenRouter {
+                        referer: None,
+                        title: None,
+                    },
+                    "https://openrouter.ai/api/v1",
+                    bearer,
+                ),
+                (Provider::Together, "https://api.together.xyz/v1", bearer),
+                (Provider::Groq, "https://api.groq.com/openai/v1", bearer),
+                (
+                    Provider::Gemini,
+                    "https://generativelanguage.googleapis.com/v1beta/openai",
+                    bearer,
+                ),
+                (Provider::Ollama, "http://localhost:11434/v1", None),
+                (Provider::LmStudio, "http://localhost:1234/v1", None),
+                (Provider::Vllm, "http://localhost:8000/v1", None),
+            ];
+
+            for (provider, base_url, authorization) in providers {
+                let openai = OpenAI::for_provider(&provider, "sk-test");
+                let prepared = capture(&openai, request()).await;
+
+                assert_eq!(
+                    prepared.url,
+                    format!("{base_url}/chat/completions"),
+                    "{provider:?}"
+                );
+                assert_eq!(
+                    header(&prepared, "authorization"),
+                    authorization,
+                    "{provider:?}"
+                );
+                assert_eq!(
+                    openai.local_compat(),
+                    provider.needs_compat(),
+                    "{provider:?}"
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn openrouter_clients_attribute_their_requests() {
+            let provider = Provider::OpenRouter {
+                referer: Some("https://example.com".to_string()),
+                title: Some("Example".to_string()),
+            };
+            let prepared = capture(&OpenAI::for_provider(&provider, "sk-test"), request()).await;
+
+            assert_eq!(
+                header(&prepared, "http-referer"),
+                Some("https://example.com")
+            );
+            assert_eq!(header(&prepared, "x-title"), Some("Example"));
+
+            let provider = Provider::OpenRouter {
+                referer: None,
+                title: Some("Example".to_string()),
+            };
+            let prepared = capture(&OpenAI::for_provider(&provider, "sk-test"), request()).await;
+
+            assert_eq!(header(&prepared, "http-referer"), None);
+            assert_eq!(header(&prepared, "x-title"), Some("Example"));
+        }
+
+        #[tokio::test]
+        async fn switching_provider_replaces_the_authentication_and_headers() {
+            let mut openai = OpenAI::for_provider(
+                &Provider::OpenRouter {
+                    referer: None,
+                    title: Some("Example".to_string()),
+                },
+                "sk-test",
+            );
+            openai.set_auth_scheme(AuthScheme::Header("x-api-key".to_string()));
+            let prepared = capture(&openai, request()).await;
+            assert_eq!(header(&prepared, "x-api-key"), Some("[REDACTED]"));
+            assert_eq!(header(&prepared, "authorization"), None);
+
+            openai.set_provider(&Provider::Ollama);
+            let prepared = capture(&openai, request()).await;
+            assert_eq!(prepared.url, "http://localhost:11434/v1/chat/completions");
+            assert_eq!(header(&prepared, "x-api-key"), None);
+            assert_eq!(header(&prepared, "x-title"), None);
+            assert!(matches!(prepared.body, PreparedBody::Json(_)));
+        }
+
+        #[tokio::test]
+        async fn fields_the_provider_rejects_are_removed_from_the_body() {
+            let request = request()
+                .logit_bias(json!({ "50256": -100 }))
+                .presence_penalty(0.5)
+                .frequency_penalty(0.5)
+                .logprobs(true)
+                .top_logprobs(2u64)
+                .user("user-1")
+                .n(1u64)
+                .temperature(0.5);
+            let every = [
+                "logit_bias",
+                "presence_penalty",
+                "frequency_penalty",
+                "logprobs",
+                "top_logprobs",
+                "user",
+                "n",
+            ];
+            let providers: [(Provider, &[&str]); 6] = [
+                (Provider::OpenAI, &[]),
+                (Provider::Together, &[]),
+                (
+                    Provider::Gemini,
+                    &[
+                        "logit_bias",
+                        "presence_penalty",
+                        "frequency_penalty",
+                        "logprobs",
+                        "top_logprobs",
+                        "user",
+                    ],
+                ),
+                (Provider::Groq, &["logprobs", "top_logprobs", "logit_bias"]),
+                (
+                    Provider::Ollama,
+                    &["logit_bias", "logprobs", "top_logprobs", "user", "n"],
+                ),
+                (Provider::LmStudio, &["logprobs", "top_logprobs", "user"]),
+            ];
+
+            for (provider, removed) in providers {
+                let openai = OpenAI::for_provider(&provider, "sk-test");
+                let PreparedBody::Json(body) = capture(&openai, request.clone()).await.body else {
+                    panic!("{provider:?} sent no JSON body");
+                };
+
+                for field in every {
+                    assert_eq!(
+                        body.get(field).is_none(),
+                        removed.contains(&field),
+                        "{field} sent to {provider:?}"
+                    );
+                }
+                assert_eq!(body["model"], "some-model");
+                assert_eq!(body["temperature"], 0.5);
+            }
+        }
+    }
+}
@@ -0,0 +1,601 @@
+use crate::{
+    chunking::TokenChunker,
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::{completion::ChatCompletionRequest, types::Usage},
+    transport::MaybeSend,
+    vector_index::VectorIndex,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashSet, future::Future};
+
+const DEFAULT_INSTRUCTIONS: &str = "Answer the question using only the numbered sources below. \
+Cite every source you use with its number in square brackets, e.g. [1]. If the sources don't \
+contain the answer, say that you don't know.";
+
+/// A document to index in a [`LocalRetriever`].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Document {
+    /// ID of the document, reported in the [`Passage`]s retrieved from it
+    pub id: String,
+
+    /// Text of the document
+    pub text: String,
+
+    /// Arbitrary metadata (title, URL, ...) copied to the [`Passage`]s retrieved from it
+    pub metadata: Value,
+}
+
+impl Document {
+    /// Create a new [`Document`] without metadata.
+    pub fn new(id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            text: text.into(),
+            metadata: Value::Null,
+        }
+    }
+
+    /// Attach metadata to the document.
+    pub fn metadata(mut self, metadata: Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+/// A piece of a document retrieved for a query, cited by [`RagAnswer`]s.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Passage {
+    /// ID of the document the passage comes from (the file ID for vector stores)
+    pub document_id: String,
+
+    /// Position of the passage in its document (in the results for vector stores)
+    pub chunk_index: usize,
+
+    /// Text of the passage
+    pub text: String,
+
+    /// Relevance of the passage to the query, higher is better
+    pub score: f32,
+
+    /// Metadata of the document (the file name for vector stores)
+    pub metadata: Value,
+}
+
+/// Source of the passages a [`RagPipeline`] answers from.
+pub trait Retriever: Send + Sync {
+    /// Return at most `top_k` passages relevant to `query`, most relevant first.
+    fn retrieve(
+        &self,
+        openai: &OpenAI,
+        query: &str,
+        top_k: usize,
+    ) -> impl Future<Output = OpenAIResult<Vec<Passage>>> + MaybeSend;
+}
+
+/// [`Retriever`] keeping chunked and embedded documents in a [`VectorIndex`], searched by cosine
+/// similarity.
+///
+/// Passages are indexed under `{document_id}#{chunk_index}`, so indexing a document again replaces
+/// its passages.
+pub struct LocalRetriever {
+    chunker: TokenChunker,
+    embedding_model: Option<Model>,
+    dimensions: Option<u64>,
+    index: VectorIndex<Passage>,
+}
+
+impl Default for LocalRetriever {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalRetriever {
+    /// Create an empty retriever splitting documents into chunks of 400 tokens overlapping by 50,
+    /// embedded with the client's default embeddings model.
+    pub fn new() -> Self {
+        Self {
+            chunker: TokenChunker::new(400).overlap(50),
+            embedding_model: None,
+            dimensions: None,
+            index: VectorIndex::new(),
+        }
+    }
+
+    /// Search `index`, e.g. loaded with [`VectorIndex::load`], instead of an empty index.
+    pub fn with_index(mut self, index: VectorIndex<Passage>) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// The index of the retriever, e.g. to save it with [`VectorIndex::save`].
+    pub const fn vector_index(&self) -> &VectorIndex<Passage> {
+        &self.index
+    }
+
+    /// Split documents with `chunker`.
+    pub fn chunker(mut self, chunker: TokenChunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    /// Embed documents and queries with `model`.
+    pub fn embedding_model(mut self, model: impl Into<Model>) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    /// Request embeddings with this number of dimensions.
+    pub fn dimensions(mut self, dimensions: u64) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// Number of indexed passages.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Whether no passage is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Chunk, embed and index `documents`, replacing the passages of documents indexed before.
+    ///
+    /// # Arguments
+    ///
+    /// * `openai` - Client used for the embedding requests.
+    /// * `documents` - The documents to index.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing nothing on success, or an [`OpenAIError`] on failure.
+    pub async fn index(&mut self, openai: &OpenAI, documents: &[Document]) -> OpenAIResult<()> {
+        let passages: Vec<Passage> = documents
+            .iter()
+            .flat_map(|document| {
+                self.chunker
+                    .split(&document.text)
+                    .into_iter()
+                    .map(|chunk| Passage {
+                        document_id: document.id.clone(),
+                        chunk_index: chunk.index,
+                        text: chunk.text,
+                        score: 0.0,
+                        metadata: document.metadata.clone(),
+                    })
+            })
+            .collect();
+
+        let texts: Vec<&str> = passages
+            .iter()
+            .map(|passage| passage.text.as_str())
+            .collect();
+        let embeddings = openai
+            .embeddings()
+            .embed_many(&texts, self.embedding_model.clone(), self.dimensions)
+            .await?;
+
+        let reindexed: HashSet<&str> = documents
+            .iter()
+            .map(|document| document.id.as_str())
+            .collect();
+        let stale: Vec<String> = self
+            .index
+            .entries()
+            .iter()
+            .filter(|entry| reindexed.contains(entry.payload.document_id.as_str()))
+            .map(|entry| entry.id.clone())
+            .collect();
+        for id in stale {
+            self.index.remove(&id);
+        }
+
+        for (passage, embedding) in passages.into_iter().zip(embeddings) {
+            let id = format!("{}#{}", passage.document_id, passage.chunk_index);
+            self.index.add(id, embedding, passage)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Retriever for LocalRetriever {
+    async fn retrieve(
+        &self,
+        openai: &OpenAI,
+        query: &str,
+        top_k: usize,
+    ) -> OpenAIResult<Vec<Passage>> {
+        let query = openai
+            .embeddings()
+            .embed_many(&[query], self.embedding_model.clone(), self.dimensions)
+            .await?
+            .pop()
+            .ok_or_else(|| OpenAIError::UnexpectedResponse("missing embedding".to_string()))?;
+
+        Ok(self
+            .index
+            .search(&query, top_k)?
+            .into_iter()
+            .map(|hit| Passage {
+                score: hit.score,
+                ..hit.payload.clone()
+            })
+            .collect())
+    }
+}
+
+/// [`Retriever`] searching an OpenAI vector store.
+pub struct VectorStoreRetriever {
+    vector_store_id: String,
+}
+
+impl VectorStoreRetriever {
+    /// Create a new [`VectorStoreRetriever`] searching the vector store `vector_store_id`.
+    pub fn new(vector_store_id: impl Into<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+        }
+    }
+}
+
+impl Retriever for VectorStoreRetriever {
+    async fn retrieve(
+        &self,
+        openai: &OpenAI,
+        query: &str,
+        top_k: usize,
+    ) -> OpenAIResult<Vec<Passage>> {
+        let response = openai
+            .vectors()
+            .search_vector_store(&self.vector_store_id, query, Some(top_k as u64))
+            .await?;
+
+        let results = response["data"].as_array().map_or(&[][..], Vec::as_slice);
+
+        Ok(results
+            .iter()
+            .enumerate()
+            .map(|(index, result)| Passage {
+                document_id: result["file_id"].as_str().unwrap_or_default().to_string(),
+                chunk_index: index,
+                text: result["content"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|part| part["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                score: result["score"].as_f64().unwrap_or_default() as f32,
+                metadata: json!({ "filename": result["filename"] }),
+            })
+            .collect())
+    }
+}
+
+/// Answer of a [`RagPipeline`], with the passages it was grounded on.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RagAnswer {
+    /// Text of the answer, citing sources as `[n]`
+    pub answer: String,
+
+    /// Passages given to the model, source `[n]` being `sources[n - 1]`
+    pub sources: Vec<Passage>,
+
+    /// Numbers of the sources cited in the answer, in order of first citation
+    pub citations: Vec<usize>,
+
+    /// Token usage of the completion
+    pub usage: Usage,
+}
+
+impl RagAnswer {
+    /// The passages cited in the answer, in order of first citation.
+    pub fn cited_sources(&self) -> impl Iterator<Item = &Passage> {
+        self.citations.iter().filter_map(|number| {
+            number
+                .checked_sub(1)
+                .and_then(|index| self.sources.get(index))
+        })
+    }
+}
+
+/// Retrieval-augmented generation pipeline: retrieves the passages relevant to a question, adds
+/// them as numbered sources to the prompt and returns the answer with its citations.
+pub struct RagPipeline<R> {
+    openai: OpenAI,
+    retriever: R,
+    model: Option<Model>,
+    top_k: usize,
+    min_score: Option<f32>,
+    instructions: String,
+}
+
+impl<R: Retriever> RagPipeline<R> {
+    /// Create a pipeline answering with the client's default chat model from the 4 most relevant
+    /// passages of `retriever`.
+    pub fn new(openai: OpenAI, retriever: R) -> Self {
+        Self {
+            openai,
+            retriever,
+            model: None,
+            top_k: 4,
+            min_score: None,
+            instructions: DEFAULT_INSTRUCTIONS.to_string(),
+        }
+    }
+
+    /// Use `model` instead of the client's default chat model.
+    pub fn model(mut self, model: impl Into<Model>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Give the model at most `top_k` passages.
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k.max(1);
+        self
+    }
+
+    /// Drop passages scoring below `min_score`.
+    pub fn min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Replace the instructions placed before the sources in the system prompt.
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = instructions.into();
+        self
+    }
+
+    /// The retriever of the pipeline.
+    pub const fn retriever(&self) -> &R {
+        &self.retriever
+    }
+
+    /// The retriever of the pipeline, e.g. to index more documents.
+    pub fn retriever_mut(&mut self) -> &mut R {
+        &mut self.retriever
+    }
+
+    /// Answer `question` from the passages relevant to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `question` - The question to answer.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`RagAnswer`] on success, or an [`OpenAIError`] on failure.
+    pub async fn answer(&self, question: &str) -> OpenAIResult<RagAnswer> {
+        let mut sources = self
+            .retriever
+            .retrieve(&self.openai, question, self.top_k)
+            .await?;
+        if let Some(min_score) = self.min_score {
+            sources.retain(|passage| passage.score >= min_score);
+        }
+        sources.truncate(self.top_k);
+
+        let mut system = format!("{}\n\nSources:", self.instructions);
+        for (number, passage) in sources.iter().enumerate() {
+            system.push_str(&format!("\n\n[{}] {}", number + 1, passage.text));
+        }
+
+        let mut request = ChatCompletionRequest::from_messages(vec![
+            json!({ "role": "system", "content": system }),
+            json!({ "role": "user", "content": question }),
+        ]);
+        if let Some(model) = &self.model {
+            request = request.model(model.clone());
+        }
+
+        let mut response = self.openai.completions().create(request).await?;
+
+        let answer = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| {
+                OpenAIError::UnexpectedResponse("missing message in chat completion".to_string())
+            })?
+            .to_string();
+        let usage = serde_json::from_value(response["usage"].take()).unwrap_or_default();
+        let citations = citations(&answer, sources.len());
+
+        Ok(RagAnswer {
+            answer,
+            sources,
+            citations,
+            usage,
+        })
+    }
+}
+
+/// Numbers between 1 and `count` cited as `[n]` or `[n, m]` in `text`, in order of first citation.
+fn citations(text: &str, count: usize) -> Vec<usize> {
+    let mut citations = Vec::new();
+
+    for (start, _) in text.match_indices('[') {
+        let Some(length) = text[start + 1..].find(']') else {
+            break;
+        };
+
+        let numbers: Option<Vec<usize>> = text[start + 1..start + 1 + length]
+            .split(',')
+            .map(|number| number.trim().parse().ok())
+            .collect();
+
+        for number in numbers.into_iter().flatten() {
+            if (1..=count).contains(&number) && !citations.contains(&number) {
+                citations.push(number);
+            }
+        }
+    }
+
+    citations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn citations_are_numbers_in_brackets_in_order_of_first_citation() {
+        assert_eq!(citations("Both [1, 2] say so.", 2), [1, 2]);
+        assert_eq!(citations("First [3], then [1][3] and [1,3].", 3), [3, 1]);
+        assert_eq!(citations("Sources [0], [4] and [2].", 3), [2]);
+        assert_eq!(citations("Not a citation [a] or [1 2], but [1].", 1), [1]);
+        assert_eq!(citations("Cited [2] then cut off [1", 2), [2]);
+        assert!(citations("No sources.", 2).is_empty());
+    }
+
+    #[test]
+    fn out_of_range_citations_cite_nothing() {
+        let passage = Passage {
+            document_id: "doc".to_string(),
+            chunk_index: 0,
+            text: "text".to_string(),
+            score: 1.0,
+            metadata: Value::Null,
+        };
+        let answer = RagAnswer {
+            answer: String::new(),
+            sources: vec![passage.clone()],
+            citations: vec![0, 1, 2],
+            usage: Usage::default(),
+        };
+
+        assert_eq!(answer.cited_sources().collect::<Vec<_>>(), [&passage]);
+    }
+
+    #[cfg(feature = "test-util")]
+    mod local {
+        use super::*;
+        use crate::test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+        use std::sync::Arc;
+
+        /// An embeddings response with the given vectors.
+        fn embeddings(vectors: &[[f32; 2]]) -> MockResponse {
+            let data: Vec<Value> = vectors
+                .iter()
+                .enumerate()
+                .map(|(index, vector)| {
+                    json!({ "object": "embedding", "index": index, "embedding": vector })
+                })
+                .collect();
+
+            MockResponse::json(&json!({
+                "object": "list",
+                "data": data,
+                "model": "text-embedding-3-small",
+                "usage": { "prompt_tokens": 1, "total_tokens": 1 },
+            }))
+        }
+
+        fn retriever() -> LocalRetriever {
+            LocalRetriever::new()
+                .chunker(TokenChunker::new(2))
+                .embedding_model("text-embedding-3-small")
+        }
+
+        fn ids(retriever: &LocalRetriever) -> Vec<&str> {
+            retriever
+                .vector_index()
+                .entries()
+                .iter()
+                .map(|entry| entry.id.as_str())
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn indexing_a_document_again_replaces_all_its_passages() {
+            let mock = Arc::new(MockTransport::new());
+            mock.once(
+                Method::POST,
+                "/embeddings",
+                embeddings(&[[1.0, 0.0], [0.0, 1.0], [1.0, 1.0], [-1.0, 0.0]]),
+            )
+            .once(Method::POST, "/embeddings", embeddings(&[[0.0, 1.0]]));
+            let openai = mock.client();
+            let mut retriever = retriever();
+
+            let documents = [
+                Document::new("a", "alpha beta gamma"),
+                Document::new("b", "omega"),
+            ];
+            retriever.index(&openai, &documents).await.unwrap();
+            assert_eq!(ids(&retriever), ["a#0", "a#1", "a#2", "b#0"]);
+
+            retriever
+                .index(&openai, &[Document::new("a", "delta")])
+                .await
+                .unwrap();
+            assert_eq!(ids(&retriever), ["b#0", "a#0"]);
+            assert_eq!(
+                retriever.vector_index().get("a#0").unwrap().payload.text,
+                "delta"
+            );
+        }
+
+        #[tokio::test]
+        async fn answers_cite_the_retrieved_passages() {
+            let mock = Arc::new(MockTransport::new());
+            mock.once(
+                Method::POST,
+                "/embeddings",
+                embeddings(&[[1.0, 0.0], [0.0, 1.0], [-1.0, 0.0]]),
+            )
+            .once(Method::POST, "/embeddings", embeddings(&[[0.1, 1.0]]))
+            .on(
+                Method::POST,
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_completion(
+                    "gpt-4o-mini",
+                    "Beta it is [1], not [3].",
+                )),
+            );
+            let openai = mock.client();
+            let mut retriever = retriever();
+            let documents = [
+                Document::new("x", "alpha"),
+                Document::new("y", "beta").metadata(json!({ "title": "Y" })),
+                Document::new("z", "omega"),
+            ];
+            retriever.index(&openai, &documents).await.unwrap();
+
+            let pipeline = RagPipeline::new(openai, retriever)
+                .model("gpt-4o-mini")
+                .top_k(2);
+            let answer = pipeline.answer("Which one?").await.unwrap();
+
+            let sources: Vec<_> = answer
+                .sources
+                .iter()
+                .map(|passage| passage.document_id.as_str())
+                .collect();
+            assert_eq!(sources, ["y", "x"]);
+            assert!(answer.sources[0].score > answer.sources[1].score);
+            assert_eq!(answer.citations, [1]);
+            assert_eq!(
+                answer.cited_sources().next().unwrap().metadata,
+                json!({ "title": "Y" })
+            );
+            assert_eq!(answer.usage.prompt_tokens, 12);
+
+            let request = mock.last_request().unwrap().json().unwrap();
+            let system = request["messages"][0]["content"].as_str().unwrap();
+            assert!(system.ends_with("Sources:\n\n[1] beta\n\n[2] alpha"));
+            assert_eq!(request["messages"][1]["content"], "Which one?");
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{runtime::Instant, util::stable_hash};
+use reqwest::header::HeaderMap;
+use std::{collections::HashMap, time::Duration};
+
+/// Rate limit state reported by the API in the `x-ratelimit-*` headers of its last response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimits {
+    /// Requests allowed per minute
+    pub limit_requests: Option<u64>,
+
+    /// Tokens allowed per minute
+    pub limit_tokens: Option<u64>,
+
+    /// Requests left before the limit is reached
+    pub remaining_requests: Option<u64>,
+
+    /// Tokens left before the limit is reached
+    pub remaining_tokens: Option<u64>,
+
+    /// Time until the request limit resets, from `received_at`
+    pub reset_requests: Option<Duration>,
+
+    /// Time until the token limit resets, from `received_at`
+    pub reset_tokens: Option<Duration>,
+
+    /// When the response carrying these headers was received
+    pub received_at: Instant,
+}
+
+impl RateLimits {
+    /// Read the rate limit headers of a response, `None` if it has none.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let number = |name: &str| header(name).and_then(|value| value.parse().ok());
+        let duration = |name: &str| header(name).and_then(parse_duration);
+
+        let limits = Self {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            limit_tokens: number("x-ratelimit-limit-tokens"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            reset_requests: duration("x-ratelimit-reset-requests"),
+            reset_tokens: duration("x-ratelimit-reset-tokens"),
+            received_at: Instant::now(),
+        };
+
+        (limits.remaining_requests.is_some() || limits.remaining_tokens.is_some()).then_some(limits)
+    }
+
+    /// Time to wait before `tokens` more tokens and one more request fit in the reported limits,
+    /// zero if they already do.
+    pub fn wait_for(&self, tokens: u64) -> Duration {
+        let elapsed = self.received_at.elapsed();
+        let wait = |reset: Option<Duration>| reset.unwrap_or_default().saturating_sub(elapsed);
+
+        let requests = match self.remaining_requests {
+            Some(0) => wait(self.reset_requests),
+            _ => Duration::ZERO,
+        };
+        let tokens = match self.remaining_tokens {
+            Some(remaining) if remaining < tokens => wait(self.reset_tokens),
+            _ => Duration::ZERO,
+        };
+
+        requests.max(tokens)
+    }
+
+    /// Time to wait before sending a request of `tokens` tokens under these limits, counting it
+    /// against them so concurrent requests are spread out too.
+    ///
+    /// Besides waiting for the limits to reset once exhausted, requests are paced once fewer than
+    /// a tenth of the requests per minute are left, spreading the remaining ones until the reset.
+    pub(crate) fn reserve(&mut self, tokens: u64) -> Duration {
+        let pacing = match (self.remaining_requests, self.limit_requests) {
+            (Some(remaining), Some(limit)) if remaining > 0 && remaining < limit / 10 => {
+                let left = self
+                    .reset_requests
+                    .unwrap_or_default()
+                    .saturating_sub(self.received_at.elapsed());
+                left / (remaining as u32 + 1)
+            },
+            _ => Duration::ZERO,
+        };
+        let wait = self.wait_for(tokens).max(pacing);
+
+        self.remaining_requests = self
+            .remaining_requests
+            .map(|remaining| remaining.saturating_sub(1));
+        self.remaining_tokens = self
+            .remaining_tokens
+            .map(|remaining| remaining.saturating_sub(tokens));
+
+        wait
+    }
+}
+
+/// Rate limits reported to a client (and its clones) for each of its API keys, e.g. the keys of an
+/// [`ApiKeyPool`][crate::credentials::ApiKeyPool], each key having its own limits.
+#[derive(Debug, Default)]
+pub(crate) struct ReportedLimits {
+    last: Option<RateLimits>,
+    by_key: HashMap<String, RateLimits>,
+}
+
+impl ReportedLimits {
+    /// The limits reported in the last response, whichever key it was authenticated with.
+    pub(crate) const fn last(&self) -> Option<RateLimits> {
+        self.last
+    }
+
+    /// Keep the limits reported in a response to a request authenticated with `api_key`.
+    pub(crate) fn record(&mut self, api_key: &str, limits: RateLimits) {
+        self.last = Some(limits);
+        self.by_key.insert(key_id(api_key), limits);
+    }
+
+    /// Time to wait before sending a request of `tokens` tokens authenticated with `api_key`,
+    /// counting it against the limits of that key, see [`RateLimits::reserve`].
+    pub(crate) fn reserve(&mut self, api_key: &str, tokens: u64) -> Duration {
+        self.by_key
+            .get_mut(&key_id(api_key))
+            .map_or(Duration::ZERO, |limits| limits.reserve(tokens))
+    }
+
+    /// Time to wait before any key has a request left, zero if one does.
+    pub(crate) fn wait(&self) -> Duration {
+        self.by_key
+            .values()
+            .map(|limits| limits.wait_for(0))
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+/// Identifier of an API key, so the key itself isn't kept.
+fn key_id(api_key: &str) -> String {
+    stable_hash(&[api_key.as_bytes()])
+}
+
+/// Parse a reset duration as sent by the API, e.g. `1s`, `6m0s` or `350ms`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = value.trim();
+
+    while !rest.is_empty() {
+        let split = rest
+            .find(|character: char| !character.is_ascii_digit() && character != '.')
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number: f64 = number.parse().ok()?;
+
+        let unit = tail
+            .find(|character: char| character.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit);
+        let seconds = match unit {
+            "ms" => number / 1_000.0,
+            "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 3_600.0,
+            _ => return None,
+        };
+
+        total += Duration::from_secs_f64(seconds);
+        rest = tail;
+    }
+
+    Some(total)
+}
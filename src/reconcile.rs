@@ -0,0 +1,544 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    openai_api::assistants::{AssistantCreationRequest, AssistantModificationRequest},
+};
+use futures::{future, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Desired state of an assistant, reconciled against the live API by [`AssistantSpec::reconcile`]
+/// so deployments can manage assistants idempotently ("assistant as code").
+///
+/// The assistant is identified by its name, which must be unique among the assistants of the
+/// organization. Fields left to `None` (or empty, for `vector_stores`) aren't managed: they are
+/// set on creation if present and never compared nor changed afterwards.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AssistantSpec {
+    /// Name of the assistant, identifying it
+    pub name: String,
+
+    /// Model used by the assistant
+    pub model: Model,
+
+    /// Description of the assistant
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Instructions of the assistant
+    #[serde(default)]
+    pub instructions: Option<String>,
+
+    /// Tools of the assistant, compared ignoring fields the API fills in with defaults
+    #[serde(default)]
+    pub tools: Vec<Value>,
+
+    /// Vector stores searched by the `file_search` tool, each identified by metadata: every
+    /// vector store whose metadata contains all the key/value pairs of an entry is attached
+    #[serde(default)]
+    pub vector_stores: Vec<Map<String, Value>>,
+
+    /// Metadata of the assistant, compared and updated key by key
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+
+    /// Sampling temperature
+    #[serde(default)]
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling parameter
+    #[serde(default)]
+    pub top_p: Option<f64>,
+
+    /// Format of responses from the assistant
+    #[serde(default)]
+    pub response_format: Option<Value>,
+}
+
+/// What [`AssistantSpec::reconcile`] did, or what [`AssistantSpec::plan`] would do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReconcileAction {
+    /// No assistant had the name of the spec, so one was created
+    Created,
+
+    /// The assistant differed from the spec in these fields, which were updated
+    Updated(Vec<String>),
+
+    /// The assistant already matched the spec
+    Unchanged,
+}
+
+/// Outcome of [`AssistantSpec::reconcile`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reconciliation {
+    /// ID of the assistant
+    pub assistant_id: String,
+
+    /// What was done
+    pub action: ReconcileAction,
+
+    /// The assistant as returned by the API after reconciliation
+    pub assistant: Value,
+}
+
+impl AssistantSpec {
+    /// Create a new spec with only a name and a model.
+    pub fn new(name: impl Into<String>, model: impl Into<Model>) -> Self {
+        Self {
+            name: name.into(),
+            model: model.into(),
+            description: None,
+            instructions: None,
+            tools: Vec::new(),
+            vector_stores: Vec::new(),
+            metadata: Map::new(),
+            temperature: None,
+            top_p: None,
+            response_format: None,
+        }
+    }
+
+    /// Compare the spec against the live assistant without changing anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `openai` - Client used for the requests.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the action [`AssistantSpec::reconcile`] would take on success, or an
+    /// [`OpenAIError`] on failure.
+    pub async fn plan(&self, openai: &OpenAI) -> OpenAIResult<ReconcileAction> {
+        let vector_store_ids = self.vector_store_ids(openai).await?;
+
+        Ok(match self.find(openai).await? {
+            None => ReconcileAction::Created,
+            Some(live) => self.action(&live, &vector_store_ids),
+        })
+    }
+
+    /// Create the assistant if it doesn't exist, update it if it differs from the spec, or leave
+    /// it unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `openai` - Client used for the requests.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`Reconciliation`] on success, or an [`OpenAIError`] on failure,
+    /// e.g. if several assistants have the name of the spec or a vector store entry matches none.
+    pub async fn reconcile(&self, openai: &OpenAI) -> OpenAIResult<Reconciliation> {
+        let vector_store_ids = self.vector_store_ids(openai).await?;
+
+        let (action, assistant) = match self.find(openai).await? {
+            None => {
+                let assistant = openai
+                    .assistants()
+                    .create(self.creation_request(&vector_store_ids))
+                    .await?;
+
+                (ReconcileAction::Created, assistant)
+            },
+            Some(live) => match self.action(&live, &vector_store_ids) {
+                ReconcileAction::Unchanged => (ReconcileAction::Unchanged, live),
+                action => {
+                    let id = live["id"].as_str().unwrap_or_default();
+                    let assistant = openai
+                        .assistants()
+                        .modify(id, self.modification_request(&live, &vector_store_ids))
+                        .await?;
+
+                    (action, assistant)
+                },
+            },
+        };
+
+        Ok(Reconciliation {
+            assistant_id: assistant["id"].as_str().unwrap_or_default().to_string(),
+            action,
+            assistant,
+        })
+    }
+
+    /// The live assistant named like the spec, if any.
+    async fn find(&self, openai: &OpenAI) -> OpenAIResult<Option<Value>> {
+        let mut matching: Vec<Value> = openai
+            .assistants()
+            .list_all(Some(100), None)
+            .try_filter(|assistant| {
+                future::ready(assistant["name"].as_str() == Some(self.name.as_str()))
+            })
+            .try_collect()
+            .await?;
+
+        match matching.len() {
+            0 | 1 => Ok(matching.pop()),
+            count => Err(OpenAIError::ConfigError(format!(
+                "{count} assistants are named {:?}",
+                self.name
+            ))),
+        }
+    }
+
+    /// IDs of the vector stores identified by the metadata entries of the spec.
+    async fn vector_store_ids(&self, openai: &OpenAI) -> OpenAIResult<Vec<String>> {
+        if self.vector_stores.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let stores: Vec<Value> = openai
+            .vectors()
+            .list_all_vector_stores(Some(100), None)
+            .try_collect()
+            .await?;
+
+        let mut ids = Vec::new();
+        for selector in &self.vector_stores {
+            let mut matched = false;
+            for store in &stores {
+                if !selector
+                    .iter()
+                    .all(|(key, value)| &store["metadata"][key] == value)
+                {
+                    continue;
+                }
+
+                matched = true;
+                let id = store["id"].as_str().unwrap_or_default().to_string();
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+
+            if !matched {
+                return Err(OpenAIError::ConfigError(format!(
+                    "no vector store has the metadata {}",
+                    Value::Object(selector.clone())
+                )));
+            }
+        }
+
+        Ok(ids)
+    }
+
+    fn action(&self, live: &Value, vector_store_ids: &[String]) -> ReconcileAction {
+        let changes = self.changes(live, vector_store_ids);
+
+        if changes.is_empty() {
+            ReconcileAction::Unchanged
+        } else {
+            ReconcileAction::Updated(changes)
+        }
+    }
+
+    /// Names of the managed fields whose live value differs from the spec.
+    fn changes(&self, live: &Value, vector_store_ids: &[String]) -> Vec<String> {
+        let text = |field: &str, desired: &Option<String>| {
+            desired
+                .as_deref()
+                .is_some_and(|desired| live[field].as_str() != Some(desired))
+        };
+        let number = |field: &str, desired: Option<f64>| {
+            desired.is_some_and(|desired| {
+                live[field]
+                    .as_f64()
+                    .is_none_or(|live| (live - desired).abs() > 1e-6)
+            })
+        };
+
+        let mut live_ids: Vec<&str> = live["tool_resources"]["file_search"]["vector_store_ids"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        live_ids.sort_unstable();
+        let mut desired_ids: Vec<&str> = vector_store_ids.iter().map(String::as_str).collect();
+        desired_ids.sort_unstable();
+
+        let differs = [
+            ("model", live["model"].as_str() != Some(self.model.as_str())),
+            ("description", text("description", &self.description)),
+            ("instructions", text("instructions", &self.instructions)),
+            (
+                "tools",
+                !contains(&live["tools"], &Value::from(self.tools.clone())),
+            ),
+            (
+                "tool_resources",
+                !self.vector_stores.is_empty() && live_ids != desired_ids,
+            ),
+            (
+                "metadata",
+                !contains(&live["metadata"], &Value::Object(self.metadata.clone())),
+            ),
+            ("temperature", number("temperature", self.temperature)),
+            ("top_p", number("top_p", self.top_p)),
+            (
+                "response_format",
+                self.response_format
+                    .as_ref()
+                    .is_some_and(|desired| !contains(&live["response_format"], desired)),
+            ),
+        ];
+
+        differs
+            .into_iter()
+            .filter(|(_, differs)| *differs)
+            .map(|(field, _)| field.to_string())
+            .collect()
+    }
+
+    fn tool_resources(vector_store_ids: &[String]) -> Option<Value> {
+        (!vector_store_ids.is_empty())
+            .then(|| json!({ "file_search": { "vector_store_ids": vector_store_ids } }))
+    }
+
+    fn creation_request(&self, vector_store_ids: &[String]) -> AssistantCreationRequest {
+        let mut request = AssistantCreationRequest::new(self.model.clone())
+            .name(self.name.clone())
+            .tools(self.tools.clone())
+            .metadata(Value::Object(self.metadata.clone()));
+
+        if let Some(description) = &self.description {
+            request = request.description(description.clone());
+        }
+        if let Some(instructions) = &self.instructions {
+            request = request.instructions(instructions.clone());
+        }
+        if let Some(tool_resources) = Self::tool_resources(vector_store_ids) {
+            request = request.tool_resources(tool_resources);
+        }
+        if let Some(temperature) = self.temperature {
+            request = request.temperature(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            request = request.top_p(top_p);
+        }
+        if let Some(response_format) = &self.response_format {
+            request = request.response_format(response_format.clone());
+        }
+
+        request
+    }
+
+    fn modification_request(
+        &self,
+        live: &Value,
+        vector_store_ids: &[String],
+    ) -> AssistantModificationRequest {
+        // Metadata is replaced as a whole, so keep the keys the spec doesn't manage.
+        let mut metadata = live["metadata"].as_object().cloned().unwrap_or_default();
+        metadata.extend(self.metadata.clone());
+
+        let mut request = AssistantModificationRequest::default()
+            .model(self.model.clone())
+            .tools(self.tools.clone())
+            .metadata(Value::Object(metadata));
+
+        if let Some(description) = &self.description {
+            request = request.description(description.clone());
+        }
+        if let Some(instructions) = &self.instructions {
+            request = request.instructions(instructions.clone());
+        }
+        if let Some(tool_resources) = Self::tool_resources(vector_store_ids) {
+            request = request.tool_resources(tool_resources);
+        }
+        if let Some(temperature) = self.temperature {
+            request = request.temperature(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            request = request.top_p(top_p);
+        }
+        if let Some(response_format) = &self.response_format {
+            request = request.response_format(response_format.clone());
+        }
+
+        request
+    }
+}
+
+/// Whether `live` has every field of `desired` (recursively, arrays having the same length), so
+/// that defaults the API adds don't count as differences.
+fn contains(live: &Value, desired: &Value) -> bool {
+    match (live, desired) {
+        (Value::Object(live), Value::Object(desired)) => desired
+            .iter()
+            .all(|(key, desired)| contains(live.get(key).unwrap_or(&Value::Null), desired)),
+        (Value::Array(live), Value::Array(desired)) => {
+            live.len() == desired.len()
+                && live
+                    .iter()
+                    .zip(desired)
+                    .all(|(live, desired)| contains(live, desired))
+        },
+        // The API returns no metadata and no tools as `null` or `{}`/`[]` depending on the object.
+        (Value::Null, Value::Object(desired)) => desired.is_empty(),
+        (Value::Null, Value::Array(desired)) => desired.is_empty(),
+        (live, desired) => live == desired,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> AssistantSpec {
+        let mut spec = AssistantSpec::new("support", "gpt-4o");
+        spec.instructions = Some("Be helpful.".to_string());
+        spec.tools = vec![json!({ "type": "file_search" })];
+        spec.vector_stores = vec![Map::from_iter([("team".to_string(), json!("support"))])];
+        spec.metadata = Map::from_iter([("env".to_string(), json!("prod"))]);
+        spec.temperature = Some(0.2);
+        spec
+    }
+
+    /// The live assistant matching [`spec`], with the defaults the API fills in.
+    fn live_assistant() -> Value {
+        json!({
+            "id": "asst_1",
+            "name": "support",
+            "model": "gpt-4o",
+            "description": null,
+            "instructions": "Be helpful.",
+            "tools": [{ "type": "file_search", "file_search": { "max_num_results": 20 } }],
+            "tool_resources": { "file_search": { "vector_store_ids": ["vs_2", "vs_1"] } },
+            "metadata": { "env": "prod", "owner": "ops" },
+            "temperature": 0.2000001,
+            "top_p": 1.0,
+            "response_format": "auto",
+        })
+    }
+
+    fn ids() -> Vec<String> {
+        vec!["vs_1".to_string(), "vs_2".to_string()]
+    }
+
+    #[test]
+    fn matching_assistants_have_no_changes() {
+        assert!(spec().changes(&live_assistant(), &ids()).is_empty());
+        assert_eq!(
+            spec().action(&live_assistant(), &ids()),
+            ReconcileAction::Unchanged
+        );
+        // Fields left to `None` aren't compared.
+        let mut unmanaged = AssistantSpec::new("support", "gpt-4o");
+        unmanaged.tools = spec().tools;
+        assert!(unmanaged.changes(&live_assistant(), &[]).is_empty());
+    }
+
+    #[test]
+    fn changed_entries_are_reported() {
+        let mut live = live_assistant();
+        live["model"] = json!("gpt-4o-mini");
+        live["instructions"] = json!("Be terse.");
+        live["metadata"]["env"] = json!("staging");
+        live["temperature"] = json!(0.7);
+
+        assert_eq!(
+            spec().changes(&live, &ids()),
+            ["model", "instructions", "metadata", "temperature"]
+        );
+    }
+
+    #[test]
+    fn added_and_removed_entries_are_reported() {
+        let mut live = live_assistant();
+        live["tools"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "type": "code_interpreter" }));
+        live["metadata"].as_object_mut().unwrap().remove("env");
+        live["temperature"] = Value::Null;
+
+        assert_eq!(
+            spec().changes(&live, &ids()),
+            ["tools", "metadata", "temperature"]
+        );
+
+        let added_store = ["vs_1", "vs_2", "vs_3"].map(String::from);
+        let removed_store = ["vs_1".to_string()];
+        assert_eq!(
+            spec().changes(&live_assistant(), &added_store),
+            ["tool_resources"]
+        );
+        assert_eq!(
+            spec().changes(&live_assistant(), &removed_store),
+            ["tool_resources"]
+        );
+        assert_eq!(
+            spec().action(&live_assistant(), &removed_store),
+            ReconcileAction::Updated(vec!["tool_resources".to_string()])
+        );
+    }
+
+    #[test]
+    fn live_values_contain_the_desired_fields() {
+        let live = json!({ "type": "function", "function": { "name": "f", "strict": false } });
+
+        assert!(contains(&live, &json!({ "function": { "name": "f" } })));
+        assert!(!contains(&live, &json!({ "function": { "name": "g" } })));
+        assert!(!contains(
+            &live,
+            &json!({ "function": { "parameters": { "type": "object" } } })
+        ));
+        assert!(contains(
+            &json!([1, { "a": 1, "b": 2 }]),
+            &json!([1, { "a": 1 }])
+        ));
+        assert!(!contains(&json!([1, 2]), &json!([1])));
+        assert!(contains(
+            &live,
+            &json!({ "function": { "parameters": {} } })
+        ));
+        assert!(contains(&Value::Null, &json!({})));
+        assert!(contains(&Value::Null, &json!([])));
+        assert!(!contains(&Value::Null, &json!({ "a": 1 })));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn assistants_are_found_on_any_page() {
+        use crate::test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        };
+        use reqwest::Method;
+        use std::sync::Arc;
+
+        let mut first_page = fixtures::list(vec![fixtures::assistant(
+            "asst_0",
+            "gpt-4o",
+            Some("other"),
+            None,
+        )]);
+        first_page["has_more"] = json!(true);
+        let mock = Arc::new(MockTransport::new());
+        mock.once(Method::GET, "/assistants", MockResponse::json(&first_page))
+            .once(
+                Method::GET,
+                "/assistants",
+                MockResponse::json(&fixtures::list(vec![fixtures::assistant(
+                    "asst_1",
+                    "gpt-4o",
+                    Some("support"),
+                    Some("Be terse."),
+                )])),
+            );
+
+        let mut spec = AssistantSpec::new("support", "gpt-4o");
+        spec.instructions = Some("Be helpful.".to_string());
+        let action = spec.plan(&mock.client()).await.unwrap();
+
+        assert_eq!(
+            action,
+            ReconcileAction::Updated(vec!["instructions".to_string()])
+        );
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].url.query(), Some("limit=100&after=asst_0"));
+    }
+}
@@ -0,0 +1,448 @@
+use crate::{
+    error_handling::OpenAIError,
+    rate_limit::RateLimits,
+    runtime::{self, Instant},
+    util::random_fraction,
+};
+use reqwest::{header::HeaderMap, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{sync::Mutex, time::Duration};
+
+/// Policy describing how failed requests are retried by the client.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the initial attempt (0 disables retries)
+    pub max_retries: u32,
+
+    /// Delay before the first retry, in milliseconds
+    pub base_delay_ms: u64,
+
+    /// Upper bound for the delay between two attempts, in milliseconds
+    pub max_delay_ms: u64,
+
+    /// Fraction of each delay that is random, between 0 (fixed delays) and 1 (anywhere between
+    /// zero and the full delay), so clients failing together don't retry together
+    pub jitter: f64,
+
+    /// Upper bound for the waits the API requests in its `Retry-After` and rate limit reset
+    /// headers, in milliseconds (0 ignores them)
+    pub max_retry_after_ms: u64,
+
+    /// Total time a request may take across its attempts and the waits between them, in
+    /// milliseconds, after which it fails with [`OpenAIError::DeadlineExceeded`] (no deadline by
+    /// default)
+    pub deadline_ms: Option<u64>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            base_delay_ms: 500,
+            max_delay_ms: 8_000,
+            jitter: 0.2,
+            max_retry_after_ms: 60_000,
+            deadline_ms: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a new [`RetryPolicy`] retrying up to `max_retries` times with the default delays.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a response with the given status code should be retried.
+    pub fn should_retry(&self, status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+    }
+
+    /// Whether a request failing with `error` before any response was received should be retried:
+    /// connection errors and timeouts are transient.
+    pub fn should_retry_error(&self, error: &OpenAIError) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        let transient = |error: &reqwest::Error| error.is_connect() || error.is_timeout();
+        // Failed `fetch` calls don't tell connection errors apart on wasm32.
+        #[cfg(target_arch = "wasm32")]
+        let transient = |error: &reqwest::Error| error.is_request() || error.is_timeout();
+
+        matches!(error, OpenAIError::ReqwestError(error) if transient(error))
+    }
+
+    /// Whether an operation that failed with `error`, response or not, is worth running again,
+    /// unlike e.g. an invalid request.
+    pub(crate) fn should_retry_failure(&self, error: &OpenAIError) -> bool {
+        match error {
+            OpenAIError::ApiError { status, .. } => {
+                StatusCode::from_u16(*status).is_ok_and(|status| self.should_retry(status))
+            },
+            error => self.should_retry_error(error),
+        }
+    }
+
+    /// Compute the exponential backoff delay before the retry following `attempt` (0-based),
+    /// shortened by a random part of up to `jitter` of it.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay_ms
+            .saturating_mul(1u64 << attempt.min(32))
+            .min(self.max_delay_ms);
+        let jitter = self.jitter.clamp(0.0, 1.0) * random_fraction();
+
+        Duration::from_millis(delay).mul_f64(1.0 - jitter)
+    }
+
+    /// Compute the delay before the retry following `attempt` (0-based) of a request whose
+    /// response asked to wait `retry_after` (see [`retry_after`]): the longest of that wait,
+    /// capped at `max_retry_after_ms`, and the backoff delay.
+    pub fn delay_after(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let requested = retry_after
+            .unwrap_or_default()
+            .min(Duration::from_millis(self.max_retry_after_ms));
+
+        self.delay(attempt).max(requested)
+    }
+
+    /// The deadline of a request starting now, if the policy has one.
+    pub(crate) fn deadline(&self) -> Option<Deadline> {
+        self.deadline_ms.map(|deadline_ms| Deadline {
+            started: Instant::now(),
+            budget: Duration::from_millis(deadline_ms),
+        })
+    }
+}
+
+/// Deadline of a request across its attempts and the waits between them.
+#[derive(Clone, Copy)]
+pub(crate) struct Deadline {
+    started: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    /// Time left before the deadline.
+    pub(crate) fn remaining(&self) -> Duration {
+        self.budget.saturating_sub(self.started.elapsed())
+    }
+
+    /// The error of a request missing the deadline after `attempts` attempts, the last response
+    /// received among them having had `last_status`.
+    pub(crate) fn exceeded(&self, attempts: usize, last_status: Option<u16>) -> OpenAIError {
+        OpenAIError::DeadlineExceeded {
+            attempts: attempts as u32,
+            elapsed: self.started.elapsed(),
+            last_status,
+        }
+    }
+}
+
+/// Wait the API requests before retrying a response with `headers`: its `retry-after-ms` or
+/// `Retry-After` header (in seconds or as an HTTP date), or else the time until the exhausted
+/// `x-ratelimit-*` limit resets.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+    };
+    let seconds = |value: &str| {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .map(Duration::from_secs_f64)
+    };
+
+    if let Some(millis) = header("retry-after-ms").and_then(seconds) {
+        return Some(millis / 1_000);
+    }
+    if let Some(value) = header("retry-after") {
+        if let Some(delay) = seconds(value) {
+            return Some(delay);
+        }
+        if let Ok(date) = httpdate::parse_http_date(value) {
+            let now = std::time::UNIX_EPOCH + runtime::unix_time();
+            return Some(date.duration_since(now).unwrap_or_default());
+        }
+    }
+
+    // Every request uses tokens, so an exhausted token limit has to reset too.
+    RateLimits::from_headers(headers)
+        .map(|limits| limits.wait_for(1))
+        .filter(|wait| !wait.is_zero())
+}
+
+/// Outcome of one attempt of a request, reported with the request's events and metrics so calls
+/// that only succeeded after retries can be spotted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attempt {
+    /// HTTP status of the response, or `None` if no response was received
+    pub status: Option<u16>,
+
+    /// Time until the response headers were received or the attempt failed
+    pub duration: Duration,
+
+    /// Base URL the attempt was sent to
+    pub base_url: Option<String>,
+
+    /// Error of the attempt if no response was received
+    pub error: Option<String>,
+
+    /// Delay waited before the next attempt, or `None` if the request wasn't retried after it
+    pub retry_delay: Option<Duration>,
+}
+
+/// Budget capping retries across all the requests of a client, so failures during an incident
+/// don't multiply the load on the API.
+///
+/// Every request adds `ratio` to the budget, up to `reserve`, and every retry takes one from it;
+/// failed requests aren't retried while the budget is below one. Under sustained failures, retries
+/// are therefore limited to `ratio` of the requests.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryBudget {
+    /// Retries earned by each request
+    pub ratio: f64,
+
+    /// Maximum (and initial) number of retries the budget holds, allowing bursts of retries
+    pub reserve: u32,
+}
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        Self {
+            ratio: 0.1,
+            reserve: 10,
+        }
+    }
+}
+
+/// Remaining retries of a [`RetryBudget`], shared by a client and its clones.
+pub(crate) struct RetryBalance {
+    budget: RetryBudget,
+    balance: Mutex<f64>,
+}
+
+impl RetryBalance {
+    pub(crate) fn new(budget: RetryBudget) -> Self {
+        Self {
+            balance: Mutex::new(f64::from(budget.reserve)),
+            budget,
+        }
+    }
+
+    pub(crate) const fn budget(&self) -> &RetryBudget {
+        &self.budget
+    }
+
+    /// Credit the budget for a new request.
+    pub(crate) fn deposit(&self) {
+        let mut balance = self.balance.lock().unwrap();
+        *balance = (*balance + self.budget.ratio).min(f64::from(self.budget.reserve.max(1)));
+    }
+
+    /// Take a retry from the budget, returning whether one was available.
+    pub(crate) fn withdraw(&self) -> bool {
+        let mut balance = self.balance.lock().unwrap();
+        if *balance < 1.0 {
+            return false;
+        }
+
+        *balance -= 1.0;
+        true
+    }
+}
+
+/// Policy sending a second, concurrent attempt of slow idempotent requests to cut tail latency.
+///
+/// If no response arrives within `delay_ms`, the request is sent again and whichever attempt
+/// answers first wins; the other one is cancelled. `GET` requests are always hedged, `POST`
+/// requests only on the listed endpoints.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HedgePolicy {
+    /// Time to wait for the first attempt before sending the second one, in milliseconds
+    pub delay_ms: u64,
+
+    /// `POST` endpoints (relative to the base URL) safe to send twice
+    pub endpoints: Vec<String>,
+}
+
+impl Default for HedgePolicy {
+    fn default() -> Self {
+        Self {
+            delay_ms: 1_000,
+            endpoints: ["/embeddings", "/moderations"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl HedgePolicy {
+    /// Create a new [`HedgePolicy`] hedging after `delay_ms` on the default endpoints.
+    pub fn new(delay_ms: u64) -> Self {
+        Self {
+            delay_ms,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a request with the given method to `path` (relative to the base URL) can be hedged.
+    pub fn applies_to(&self, method: &Method, path: &str) -> bool {
+        let path = path.split('?').next().unwrap_or_default();
+
+        *method == Method::GET
+            || (*method == Method::POST && self.endpoints.iter().any(|endpoint| endpoint == path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|&(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn retry_after_reads_milliseconds_first() {
+        let headers = headers(&[("retry-after-ms", "1500"), ("retry-after", "30")]);
+
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(1_500)));
+    }
+
+    #[test]
+    fn retry_after_reads_seconds() {
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", " 2 ")])),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "0.25")])),
+            Some(Duration::from_millis(250))
+        );
+    }
+
+    #[test]
+    fn retry_after_reads_http_dates() {
+        let now = std::time::SystemTime::now();
+        let later = httpdate::fmt_http_date(now + Duration::from_secs(120));
+        let earlier = httpdate::fmt_http_date(now - Duration::from_secs(120));
+
+        let delay = retry_after(&headers(&[("retry-after", &later)])).unwrap();
+        assert!(delay > Duration::from_secs(118) && delay <= Duration::from_secs(120));
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", &earlier)])),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn retry_after_falls_back_to_the_exhausted_rate_limit() {
+        let requests = headers(&[
+            ("x-ratelimit-remaining-requests", "0"),
+            ("x-ratelimit-reset-requests", "1m30s"),
+            ("x-ratelimit-remaining-tokens", "5000"),
+            ("x-ratelimit-reset-tokens", "2s"),
+        ]);
+        let tokens = headers(&[
+            ("retry-after", "soon"),
+            ("x-ratelimit-remaining-requests", "10"),
+            ("x-ratelimit-reset-requests", "1s"),
+            ("x-ratelimit-remaining-tokens", "0"),
+            ("x-ratelimit-reset-tokens", "750ms"),
+        ]);
+
+        let wait = retry_after(&requests).unwrap();
+        assert!(wait > Duration::from_secs(89) && wait <= Duration::from_secs(90));
+        let wait = retry_after(&tokens).unwrap();
+        assert!(wait > Duration::from_millis(700) && wait <= Duration::from_millis(750));
+    }
+
+    #[test]
+    fn retry_after_is_none_without_a_wait() {
+        let headers = headers(&[
+            ("retry-after", "-1"),
+            ("x-ratelimit-remaining-requests", "10"),
+            ("x-ratelimit-reset-requests", "1s"),
+        ]);
+
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn delay_after_caps_the_requested_wait() {
+        let policy = RetryPolicy {
+            base_delay_ms: 100,
+            jitter: 0.0,
+            max_retry_after_ms: 5_000,
+            ..RetryPolicy::new(3)
+        };
+
+        assert_eq!(
+            policy.delay_after(0, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            policy.delay_after(0, Some(Duration::from_secs(60))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(policy.delay_after(1, None), Duration::from_millis(200));
+        assert_eq!(
+            policy.delay_after(2, Some(Duration::from_millis(10))),
+            Duration::from_millis(400)
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn retries_wait_as_long_as_the_response_asks() {
+        use crate::test_util::{
+            fixtures,
+            mock_transport::{MockResponse, MockTransport},
+        };
+        use std::sync::Arc;
+
+        let mock = Arc::new(MockTransport::new());
+        mock.once(
+            Method::POST,
+            "/embeddings",
+            MockResponse::error(429, "slow down").header("retry-after-ms", "300"),
+        )
+        .on(
+            Method::POST,
+            "/embeddings",
+            MockResponse::json(&fixtures::embeddings("text-embedding-3-small", &["a"], 3)),
+        );
+        let mut openai = mock.client();
+        openai.set_retry_policy(RetryPolicy {
+            base_delay_ms: 1,
+            jitter: 0.0,
+            ..RetryPolicy::new(1)
+        });
+
+        let started = Instant::now();
+        let response = openai
+            .embeddings()
+            .create("a", Some("text-embedding-3-small".into()), None, None, None)
+            .await;
+
+        assert!(response.is_ok());
+        assert_eq!(mock.requests().len(), 2);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+}
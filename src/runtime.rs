@@ -0,0 +1,116 @@
+use crate::transport::MaybeSend;
+use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::sleep;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) use wasm::{sleep, unix_time, Instant};
+
+/// Run `future` in the background, on the tokio runtime or on the JavaScript event loop.
+pub(crate) fn spawn(future: impl Future<Output = ()> + MaybeSend + 'static) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::spawn(future);
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Time elapsed since the Unix epoch, the wall clock of the crate: [`std::time::SystemTime::now`]
+/// panics on `wasm32-unknown-unknown`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn unix_time() -> Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Timers of the browser and of JavaScript workers, which `std::time` and `tokio::time` don't
+/// support on `wasm32-unknown-unknown`.
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use std::{
+        ops::{Add, Sub},
+        time::Duration,
+    };
+    use wasm_bindgen::JsCast;
+
+    /// Point in time measured with `Date.now()`, with the methods of [`std::time::Instant`] the
+    /// crate uses.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(Duration);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self(Duration::from_secs_f64(
+                js_sys::Date::now().max(0.0) / 1000.0,
+            ))
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Self::now().saturating_duration_since(*self)
+        }
+
+        pub fn duration_since(&self, earlier: Self) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+
+        pub fn saturating_duration_since(&self, earlier: Self) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Self;
+
+        fn add(self, duration: Duration) -> Self {
+            Self(self.0 + duration)
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Self;
+
+        fn sub(self, duration: Duration) -> Self {
+            Self(self.0.saturating_sub(duration))
+        }
+    }
+
+    impl Sub for Instant {
+        type Output = Duration;
+
+        fn sub(self, earlier: Self) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+    }
+
+    /// Time elapsed since the Unix epoch, measured with `Date.now()`.
+    pub fn unix_time() -> Duration {
+        Instant::now().0
+    }
+
+    /// Wait for `duration` with `setTimeout`.
+    pub async fn sleep(duration: Duration) {
+        let promise = js_sys::Promise::new(&mut |resolve, _| {
+            let global = js_sys::global();
+            let set_timeout = js_sys::Reflect::get(&global, &"setTimeout".into())
+                .ok()
+                .and_then(|set_timeout| set_timeout.dyn_into::<js_sys::Function>().ok());
+
+            match set_timeout {
+                Some(set_timeout) => {
+                    let delay = duration.as_millis().min(i32::MAX as u128) as f64;
+                    let _ = set_timeout.call2(&global, &resolve, &delay.into());
+                },
+                None => {
+                    let _ = resolve.call0(&global);
+                },
+            }
+        });
+
+        let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+    }
+}
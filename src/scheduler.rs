@@ -0,0 +1,629 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{error_handling::OpenAIError, transport::Body};
+use crate::{
+    error_handling::OpenAIResult,
+    runtime::{self, Instant},
+    transport::Response,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use futures::StreamExt;
+#[cfg(not(target_arch = "wasm32"))]
+use http_body_util::BodyDataStream;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::ResponseBuilderExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Priority of a request when the client's budget is saturated.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// A user is waiting for the response, e.g. a chat completion
+    #[default]
+    Interactive,
+
+    /// Background work that can wait, e.g. embedding jobs
+    Batch,
+}
+
+impl Priority {
+    /// Default priority of a request to `path` (relative to the base URL): embeddings, batches and
+    /// uploads are background work, everything else is interactive.
+    pub fn for_path(path: &str) -> Self {
+        let batch = ["/embeddings", "/batches", "/files", "/uploads"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix));
+
+        if batch {
+            Self::Batch
+        } else {
+            Self::Interactive
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Scheduling of the requests of a client (and its clones) within a concurrency and rate budget.
+///
+/// Requests are sent right away while the budget allows, and queued otherwise: when a slot frees
+/// up, the queued [`Priority::Interactive`] requests go first and the [`Priority::Batch`] ones only
+/// when no interactive request is waiting. Requests also wait while the limits reported by the API
+/// in its `x-ratelimit-*` headers are exhausted for every API key of the client.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SchedulingPolicy {
+    /// Maximum number of requests in flight
+    pub max_concurrency: usize,
+
+    /// Maximum number of requests sent per minute, retries included
+    pub requests_per_minute: Option<u32>,
+}
+
+impl Default for SchedulingPolicy {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 16,
+            requests_per_minute: None,
+        }
+    }
+}
+
+/// Handle on a concurrency and rate budget, to share it between several clients, e.g. clients
+/// with other base URLs or API keys of the same organization, so the organization's limits hold
+/// across all of them.
+///
+/// Clones of the handle share the same budget, see
+/// [`OpenAI::set_request_budget`][crate::openai::OpenAI::set_request_budget].
+#[derive(Clone)]
+pub struct RequestBudget(pub(crate) Arc<Scheduler>);
+
+impl RequestBudget {
+    /// Create a new budget scheduling requests according to `policy`.
+    pub fn new(policy: SchedulingPolicy) -> Self {
+        Self(Arc::new(Scheduler::new(policy)))
+    }
+
+    pub fn policy(&self) -> &SchedulingPolicy {
+        self.0.policy()
+    }
+
+    /// Number of requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.0.state.lock().unwrap().in_flight
+    }
+
+    /// Number of requests waiting for a slot.
+    pub fn queued(&self) -> usize {
+        let state = self.0.state.lock().unwrap();
+
+        state.queues.iter().map(VecDeque::len).sum()
+    }
+}
+
+impl fmt::Debug for RequestBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBudget")
+            .field("policy", self.policy())
+            .field("in_flight", &self.in_flight())
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+struct State {
+    in_flight: usize,
+    sent: VecDeque<Instant>,
+    paused_until: Option<Instant>,
+    queues: [VecDeque<oneshot::Sender<()>>; 2],
+    timer: bool,
+}
+
+/// Shared scheduler of a client and its clones.
+pub(crate) struct Scheduler {
+    policy: SchedulingPolicy,
+    state: Mutex<State>,
+}
+
+/// A slot of the budget, given back when dropped.
+pub(crate) struct Permit {
+    scheduler: Arc<Scheduler>,
+}
+
+impl Permit {
+    /// Keep the slot until the body of `response` is read to the end or dropped, so streamed and
+    /// downloaded bodies count as in flight, and their connections with them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn hold(self, response: Response) -> OpenAIResult<Response> {
+        let url = response.url().clone();
+        let (parts, body) = http::Response::from(response).into_parts();
+        let body = BodyDataStream::new(body).map(move |chunk| {
+            let _permit = &self;
+            chunk
+        });
+
+        let mut builder = http::Response::builder()
+            .status(parts.status)
+            .version(parts.version)
+            .url(url);
+        if let Some(headers) = builder.headers_mut() {
+            headers.extend(parts.headers);
+        }
+        if let Some(extensions) = builder.extensions_mut() {
+            extensions.extend(parts.extensions);
+        }
+
+        builder
+            .body(Body::wrap_stream(body))
+            .map(Response::from)
+            .map_err(|error| OpenAIError::UnexpectedResponse(error.to_string()))
+    }
+
+    /// Give the slot back once the headers of `response` are received: responses of `fetch` can't
+    /// be rebuilt, and browsers cap the connections per host themselves.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn hold(self, response: Response) -> OpenAIResult<Response> {
+        Ok(response)
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+/// A queued request, giving its slot back if it's cancelled after being granted one.
+struct Pending {
+    receiver: oneshot::Receiver<()>,
+    scheduler: Arc<Scheduler>,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if self.receiver.try_recv().is_ok() {
+            self.scheduler.release();
+        }
+    }
+}
+
+impl Scheduler {
+    pub(crate) fn new(policy: SchedulingPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(State {
+                in_flight: 0,
+                sent: VecDeque::new(),
+                paused_until: None,
+                queues: [VecDeque::new(), VecDeque::new()],
+                timer: false,
+            }),
+        }
+    }
+
+    pub(crate) const fn policy(&self) -> &SchedulingPolicy {
+        &self.policy
+    }
+
+    /// Wait for a slot of the budget for a request of `priority`.
+    pub(crate) async fn acquire(self: &Arc<Self>, priority: Priority) -> Permit {
+        let receiver = {
+            let (sender, receiver) = oneshot::channel();
+            let mut state = self.state.lock().unwrap();
+            state.queues[priority.index()].push_back(sender);
+            self.grant(&mut state);
+
+            receiver
+        };
+
+        let mut pending = Pending {
+            receiver,
+            scheduler: Arc::clone(self),
+        };
+        // Queued senders are only ever dropped after sending.
+        let _ = (&mut pending.receiver).await;
+
+        Permit {
+            scheduler: Arc::clone(self),
+        }
+    }
+
+    /// Hold the queued requests until `until`, e.g. when the API reports that no request is left
+    /// before its limit resets.
+    pub(crate) fn pause_until(&self, until: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .paused_until
+            .is_none_or(|paused_until| paused_until < until)
+        {
+            state.paused_until = Some(until);
+        }
+    }
+
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        self.grant(&mut state);
+    }
+
+    /// Hand out the free slots to the queued requests, highest priority first, and wake up again
+    /// later if the rate budget holds them back.
+    fn grant(self: &Arc<Self>, state: &mut State) {
+        loop {
+            if state.in_flight >= self.policy.max_concurrency.max(1) {
+                return;
+            }
+            if state.queues.iter().all(VecDeque::is_empty) {
+                return;
+            }
+
+            let wait = self.wait(state);
+            if !wait.is_zero() {
+                if !state.timer {
+                    state.timer = true;
+                    self.wake_after(wait);
+                }
+                return;
+            }
+
+            let Some(sender) = state.queues.iter_mut().find_map(VecDeque::pop_front) else {
+                return;
+            };
+            // Requests cancelled while queued are skipped.
+            if sender.send(()).is_ok() {
+                state.in_flight += 1;
+                if self.policy.requests_per_minute.is_some() {
+                    state.sent.push_back(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Time until the rate budget allows one more request.
+    fn wait(&self, state: &mut State) -> Duration {
+        let now = Instant::now();
+        let paused = state
+            .paused_until
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or_default();
+
+        while state
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= WINDOW)
+        {
+            state.sent.pop_front();
+        }
+        let window = match (self.policy.requests_per_minute, state.sent.front()) {
+            (Some(limit), Some(oldest)) if state.sent.len() >= limit.max(1) as usize => {
+                WINDOW.saturating_sub(now.duration_since(*oldest))
+            },
+            _ => Duration::ZERO,
+        };
+
+        paused.max(window)
+    }
+
+    fn wake_after(self: &Arc<Self>, wait: Duration) {
+        let scheduler: Weak<Self> = Arc::downgrade(self);
+
+        runtime::spawn(async move {
+            runtime::sleep(wait).await;
+            if let Some(scheduler) = scheduler.upgrade() {
+                let mut state = scheduler.state.lock().unwrap();
+                state.timer = false;
+                scheduler.grant(&mut state);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    const SHORT: Duration = Duration::from_millis(20);
+
+    fn scheduler(max_concurrency: usize, requests_per_minute: Option<u32>) -> Arc<Scheduler> {
+        Arc::new(Scheduler::new(SchedulingPolicy {
+            max_concurrency,
+            requests_per_minute,
+        }))
+    }
+
+    fn in_flight(scheduler: &Scheduler) -> usize {
+        scheduler.state.lock().unwrap().in_flight
+    }
+
+    fn queued(scheduler: &Scheduler) -> usize {
+        let state = scheduler.state.lock().unwrap();
+
+        state.queues.iter().map(VecDeque::len).sum()
+    }
+
+    #[test]
+    fn background_endpoints_are_batch_work() {
+        assert_eq!(Priority::for_path("/embeddings"), Priority::Batch);
+        assert_eq!(Priority::for_path("/files/file-1/content"), Priority::Batch);
+        assert_eq!(
+            Priority::for_path("/chat/completions"),
+            Priority::Interactive
+        );
+    }
+
+    #[tokio::test]
+    async fn interactive_requests_go_before_queued_batch_ones() {
+        let scheduler = scheduler(1, None);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let permit = scheduler.acquire(Priority::Interactive).await;
+
+        for priority in [Priority::Batch, Priority::Batch, Priority::Interactive] {
+            let (scheduler, order) = (Arc::clone(&scheduler), Arc::clone(&order));
+            tokio::spawn(async move {
+                let _permit = scheduler.acquire(priority).await;
+                order.lock().unwrap().push(priority);
+            });
+        }
+        while queued(&scheduler) < 3 {
+            tokio::task::yield_now().await;
+        }
+
+        drop(permit);
+        while order.lock().unwrap().len() < 3 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            [Priority::Interactive, Priority::Batch, Priority::Batch]
+        );
+        assert_eq!(in_flight(&scheduler), 0);
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_a_slot_beyond_the_concurrency_cap() {
+        let scheduler = scheduler(2, None);
+
+        let first = scheduler.acquire(Priority::Interactive).await;
+        let _second = scheduler.acquire(Priority::Interactive).await;
+        assert_eq!(in_flight(&scheduler), 2);
+
+        let mut third = Box::pin(scheduler.acquire(Priority::Interactive));
+        assert!(timeout(SHORT, &mut third).await.is_err());
+        assert_eq!(queued(&scheduler), 1);
+
+        drop(first);
+        let _third = timeout(SHORT, third).await.unwrap();
+        assert_eq!(in_flight(&scheduler), 2);
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_the_rate_window() {
+        let scheduler = scheduler(16, Some(2));
+
+        let _first = scheduler.acquire(Priority::Interactive).await;
+        let _second = scheduler.acquire(Priority::Interactive).await;
+
+        let mut third = Box::pin(scheduler.acquire(Priority::Interactive));
+        assert!(timeout(SHORT, &mut third).await.is_err());
+        {
+            let mut state = scheduler.state.lock().unwrap();
+            assert!(scheduler.wait(&mut state) > Duration::from_secs(59));
+
+            // The first request leaves the window.
+            state.sent[0] = Instant::now().checked_sub(WINDOW).unwrap();
+            assert!(scheduler.wait(&mut state).is_zero());
+            scheduler.grant(&mut state);
+        }
+
+        let _third = timeout(SHORT, third).await.unwrap();
+        assert_eq!(in_flight(&scheduler), 3);
+    }
+
+    #[tokio::test]
+    async fn cancelled_requests_give_their_slot_back() {
+        let scheduler = scheduler(1, None);
+        let permit = scheduler.acquire(Priority::Interactive).await;
+
+        // Cancelled while queued: skipped.
+        let mut queued_request = Box::pin(scheduler.acquire(Priority::Interactive));
+        assert!(timeout(SHORT, &mut queued_request).await.is_err());
+        drop(queued_request);
+
+        // Cancelled after being granted the slot, before using it.
+        let mut granted = Box::pin(scheduler.acquire(Priority::Interactive));
+        assert!(timeout(SHORT, &mut granted).await.is_err());
+        drop(permit);
+        assert_eq!(in_flight(&scheduler), 1);
+        drop(granted);
+        assert_eq!(in_flight(&scheduler), 0);
+
+        let _permit = timeout(SHORT, scheduler.acquire(Priority::Batch))
+            .await
+            .unwrap();
+        assert_eq!(in_flight(&scheduler), 1);
+    }
+
+    #[cfg(feature = "test-util")]
+    mod client {
+        use super::*;
+        use crate::{
+            error_handling::OpenAIResult,
+            openai::OpenAI,
+            test_util::{
+                fixtures,
+                mock_transport::{MockResponse, MockTransport, MOCK_BASE_URL},
+            },
+            transport::{BoxFuture, HttpTransport, Request, Response},
+        };
+        use futures::future::join_all;
+        use reqwest::Method;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Transport answering after a delay, keeping track of the most requests it had in
+        /// flight at once.
+        #[derive(Default)]
+        struct Tracking {
+            mock: Arc<MockTransport>,
+            in_flight: AtomicUsize,
+            max_in_flight: AtomicUsize,
+        }
+
+        impl HttpTransport for Tracking {
+            fn send(&self, request: Request) -> BoxFuture<'_, OpenAIResult<Response>> {
+                Box::pin(async move {
+                    let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+                    crate::runtime::sleep(Duration::from_millis(10)).await;
+                    self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                    self.mock.send(request).await
+                })
+            }
+        }
+
+        fn transport() -> Arc<Tracking> {
+            let transport = Arc::new(Tracking::default());
+            transport.mock.on(
+                Method::POST,
+                "/embeddings",
+                MockResponse::json(&fixtures::embeddings("text-embedding-3-small", &["a"], 3)),
+            );
+
+            transport
+        }
+
+        async fn embed(openai: &OpenAI) -> OpenAIResult<serde_json::Value> {
+            openai
+                .embeddings()
+                .create("a", Some("text-embedding-3-small".into()), None, None, None)
+                .await
+        }
+
+        #[tokio::test]
+        async fn clients_sharing_a_budget_share_its_limits() {
+            let transport = transport();
+            let budget = RequestBudget::new(SchedulingPolicy {
+                max_concurrency: 2,
+                requests_per_minute: None,
+            });
+            let mut first = OpenAI::new("sk-first", MOCK_BASE_URL);
+            let mut second = OpenAI::new("sk-second", "http://gateway.openai.invalid/v1");
+            for openai in [&mut first, &mut second] {
+                openai.set_transport(Arc::clone(&transport) as Arc<dyn HttpTransport>);
+                openai.set_request_budget(Some(budget.clone()));
+            }
+
+            let calls = (0..4).flat_map(|_| [embed(&first), embed(&second)]);
+            let responses = join_all(calls).await;
+
+            assert!(responses.iter().all(Result::is_ok));
+            assert_eq!(transport.mock.requests().len(), 8);
+            assert_eq!(transport.max_in_flight.load(Ordering::SeqCst), 2);
+            assert_eq!(budget.in_flight(), 0);
+            assert_eq!(budget.queued(), 0);
+        }
+
+        #[tokio::test]
+        async fn max_concurrent_requests_caps_fan_outs() {
+            let transport = transport();
+            let mut openai = OpenAI::builder()
+                .api_key("sk-mock")
+                .base_url(MOCK_BASE_URL)
+                .max_concurrent_requests(3)
+                .build()
+                .unwrap();
+            openai.set_transport(Arc::clone(&transport) as Arc<dyn HttpTransport>);
+
+            let responses = join_all((0..10).map(|_| embed(&openai))).await;
+
+            assert!(responses.iter().all(Result::is_ok));
+            assert_eq!(transport.max_in_flight.load(Ordering::SeqCst), 3);
+            assert_eq!(openai.request_budget().unwrap().policy().max_concurrency, 3);
+        }
+
+        /// Transport answering with streamed bodies arriving slowly, keeping track of the most
+        /// bodies it had open at once.
+        #[derive(Default)]
+        struct Streaming {
+            open: Arc<AtomicUsize>,
+            max_open: AtomicUsize,
+        }
+
+        /// An open body, closed when dropped.
+        struct OpenBody(Arc<AtomicUsize>);
+
+        impl Drop for OpenBody {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        impl HttpTransport for Streaming {
+            fn send(&self, _request: Request) -> BoxFuture<'_, OpenAIResult<Response>> {
+                Box::pin(async move {
+                    let open = self.open.fetch_add(1, Ordering::SeqCst) + 1;
+                    self.max_open.fetch_max(open, Ordering::SeqCst);
+
+                    let body = OpenBody(Arc::clone(&self.open));
+                    let events = futures::stream::unfold((body, 0), |(body, sent)| async move {
+                        if sent == 2 {
+                            return None;
+                        }
+                        crate::runtime::sleep(Duration::from_millis(10)).await;
+
+                        let event = Ok::<_, std::io::Error>(&b"data: {}\n\n"[..]);
+                        Some((event, (body, sent + 1)))
+                    });
+
+                    crate::transport::response(
+                        reqwest::StatusCode::OK,
+                        reqwest::header::HeaderMap::new(),
+                        Body::wrap_stream(events),
+                    )
+                })
+            }
+        }
+
+        #[tokio::test]
+        async fn streamed_bodies_hold_their_slot_until_read() {
+            let transport = Arc::new(Streaming::default());
+            let mut openai = OpenAI::builder()
+                .api_key("sk-mock")
+                .base_url(MOCK_BASE_URL)
+                .max_concurrent_requests(2)
+                .build()
+                .unwrap();
+            openai.set_transport(Arc::clone(&transport) as Arc<dyn HttpTransport>);
+
+            let streams = (0..6).map(|_| async {
+                let events = openai
+                    .post_events("/chat/completions", &serde_json::json!({}))
+                    .await?;
+
+                Ok::<_, OpenAIError>(events.collect::<Vec<_>>().await)
+            });
+            let events = join_all(streams).await;
+
+            assert!(events
+                .iter()
+                .all(|events| events.as_ref().unwrap().len() == 2));
+            assert_eq!(transport.max_open.load(Ordering::SeqCst), 2);
+            assert_eq!(transport.open.load(Ordering::SeqCst), 0);
+            assert_eq!(openai.request_budget().unwrap().in_flight(), 0);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(target_arch = "wasm32")]
+use crate::error_handling::OpenAIError;
+use crate::error_handling::OpenAIResult;
+use bytes::Bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use http_body_util::BodyExt;
+use reqwest::{header::HeaderMap, Body, Method, Request, Url};
+use std::sync::Arc;
+
+/// Hook signing every request right before it's sent, e.g. with an HMAC of the canonical request
+/// required by an egress gateway, failing the request if it returns an error.
+pub type RequestSigner = Arc<dyn Fn(SigningRequest<'_>) -> OpenAIResult<()> + Send + Sync>;
+
+/// A request about to be sent, given to a [`RequestSigner`].
+///
+/// The hook runs for every attempt (retries, hedged requests and fallback endpoints included), so
+/// timestamps stay fresh and the URL is the one actually requested.
+pub struct SigningRequest<'a> {
+    /// HTTP method of the request
+    pub method: &'a Method,
+
+    /// Full URL of the request
+    pub url: &'a Url,
+
+    /// Exact bytes of the body as sent, after compression, multipart forms included
+    pub body: &'a [u8],
+
+    /// Headers of the request, to which the signature headers are added
+    pub headers: &'a mut HeaderMap,
+}
+
+/// Read a streamed body (e.g. a multipart form) into memory so it can be signed and cloned.
+pub(crate) async fn buffer_body(mut request: Request) -> OpenAIResult<Request> {
+    let streamed = request.body().is_some_and(|body| body.as_bytes().is_none());
+
+    if streamed {
+        if let Some(body) = request.body_mut().take() {
+            *request.body_mut() = Some(read_body(body).await?.into());
+        }
+    }
+
+    Ok(request)
+}
+
+/// Read `body` into memory.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn read_body(body: Body) -> OpenAIResult<Bytes> {
+    Ok(body.collect().await?.to_bytes())
+}
+
+/// Read `body` into memory: multipart forms are handed to `fetch` as they are on `wasm32`, with no
+/// way to read them back.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn read_body(body: Body) -> OpenAIResult<Bytes> {
+    body.as_bytes().map(Bytes::copy_from_slice).ok_or_else(|| {
+        OpenAIError::ConfigError("multipart bodies can't be read back on wasm32".to_string())
+    })
+}
+
+/// Call `signer` on `request`.
+pub(crate) fn sign(signer: &RequestSigner, request: &mut Request) -> OpenAIResult<()> {
+    let method = request.method().clone();
+    let url = request.url().clone();
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(<[u8]>::to_vec)
+        .unwrap_or_default();
+
+    signer(SigningRequest {
+        method: &method,
+        url: &url,
+        body: &body,
+        headers: request.headers_mut(),
+    })
+}
@@ -0,0 +1,504 @@
+use crate::{
+    chunking::{ApproximateTokenCounter, TokenCounter},
+    runtime::Instant,
+};
+use futures::Stream;
+use serde_json::Value;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Measurements taken when a streamed chunk arrives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkMetrics {
+    /// Position of the chunk in the stream, starting at 0
+    pub index: usize,
+
+    /// Time since the previous chunk, or since the start of the stream for the first chunk (the time
+    /// to first token)
+    pub latency: Duration,
+
+    /// Time since the start of the stream
+    pub elapsed: Duration,
+
+    /// Tokens received so far, this chunk included
+    pub tokens: usize,
+}
+
+/// Token accounting for streamed chunks.
+pub trait ChunkTokens {
+    /// Number of generated tokens carried by the chunk.
+    fn tokens(&self) -> usize;
+
+    /// Total number of generated tokens reported by the API in the chunk, if any (e.g. the final
+    /// `usage` chunk), which replaces the running count.
+    fn reported_tokens(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Raw JSON chunks of chat completions (`choices[].delta.content`) and assistants message deltas
+/// (`delta.content[].text.value`), counted with [`ApproximateTokenCounter`].
+impl ChunkTokens for Value {
+    fn tokens(&self) -> usize {
+        let completion = self["choices"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|choice| choice["delta"]["content"].as_str());
+        let message = self["delta"]["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|part| part["text"]["value"].as_str());
+
+        completion
+            .chain(message)
+            .map(|text| ApproximateTokenCounter.count_tokens(text))
+            .sum()
+    }
+
+    fn reported_tokens(&self) -> Option<usize> {
+        self["usage"]["completion_tokens"]
+            .as_u64()
+            .map(|tokens| tokens as usize)
+    }
+}
+
+/// Errors carry no tokens.
+impl<T: ChunkTokens, E> ChunkTokens for Result<T, E> {
+    fn tokens(&self) -> usize {
+        self.as_ref().map_or(0, ChunkTokens::tokens)
+    }
+
+    fn reported_tokens(&self) -> Option<usize> {
+        self.as_ref().ok().and_then(ChunkTokens::reported_tokens)
+    }
+}
+
+/// Stream wrapper calling a function with the [`ChunkMetrics`] of every chunk, e.g. to monitor
+/// time-to-first-token.
+pub struct InstrumentedStream<S, F> {
+    inner: S,
+    on_chunk: F,
+    started: Instant,
+    previous: Option<Instant>,
+    index: usize,
+    tokens: usize,
+}
+
+impl<S, F> InstrumentedStream<S, F>
+where
+    S: Stream + Unpin,
+    S::Item: ChunkTokens,
+    F: FnMut(&ChunkMetrics) + Unpin,
+{
+    /// Wrap `inner`, measuring from now.
+    pub fn new(inner: S, on_chunk: F) -> Self {
+        Self {
+            inner,
+            on_chunk,
+            started: Instant::now(),
+            previous: None,
+            index: 0,
+            tokens: 0,
+        }
+    }
+
+    /// Measure from `started` instead, e.g. the moment the request was sent.
+    pub fn started_at(mut self, started: Instant) -> Self {
+        self.started = started;
+        self
+    }
+
+    /// The wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, F> Stream for InstrumentedStream<S, F>
+where
+    S: Stream + Unpin,
+    S::Item: ChunkTokens,
+    F: FnMut(&ChunkMetrics) + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_next(cx);
+
+        if let Poll::Ready(Some(chunk)) = &poll {
+            let now = Instant::now();
+
+            this.tokens = chunk
+                .reported_tokens()
+                .unwrap_or(this.tokens + chunk.tokens());
+
+            let metrics = ChunkMetrics {
+                index: this.index,
+                latency: now - this.previous.unwrap_or(this.started),
+                elapsed: now - this.started,
+                tokens: this.tokens,
+            };
+            (this.on_chunk)(&metrics);
+
+            this.previous = Some(now);
+            this.index += 1;
+        }
+
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    Seeking,
+    Items,
+    Done,
+}
+
+/// Incremental scanner extracting the raw items of an array field of a top-level JSON object, so
+/// large list responses can be decoded item by item while the body is still arriving.
+pub(crate) struct ArrayFieldScanner {
+    field: &'static [u8],
+    state: ScanState,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    key: Vec<u8>,
+    item: Vec<u8>,
+}
+
+impl ArrayFieldScanner {
+    pub(crate) fn new(field: &'static str) -> Self {
+        Self {
+            field: field.as_bytes(),
+            state: ScanState::Seeking,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            key: Vec::new(),
+            item: Vec::new(),
+        }
+    }
+
+    /// Whether the end of the array (or of the document, if the field is missing) was reached.
+    pub(crate) fn is_done(&self) -> bool {
+        self.state == ScanState::Done
+    }
+
+    /// Stop scanning, e.g. after a read error.
+    pub(crate) fn stop(&mut self) {
+        self.state = ScanState::Done;
+    }
+
+    /// Scan the next bytes of the body, calling `on_item` with the raw JSON of every complete item.
+    pub(crate) fn push(&mut self, bytes: &[u8], mut on_item: impl FnMut(Vec<u8>)) {
+        for &byte in bytes {
+            match self.state {
+                ScanState::Done => return,
+                ScanState::Seeking => self.seek(byte),
+                ScanState::Items => {
+                    if let Some(item) = self.scan_item(byte) {
+                        on_item(item);
+                    }
+                },
+            }
+        }
+    }
+
+    fn seek(&mut self, byte: u8) {
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+                return;
+            }
+
+            if self.depth == 1 {
+                self.key.push(byte);
+            }
+            return;
+        }
+
+        match byte {
+            b'"' => {
+                self.in_string = true;
+                if self.depth == 1 {
+                    self.key.clear();
+                }
+            },
+            b'[' if self.depth == 1 && self.key == self.field => {
+                self.depth = 2;
+                self.state = ScanState::Items;
+            },
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => {
+                self.depth = self.depth.saturating_sub(1);
+                if self.depth == 0 {
+                    self.state = ScanState::Done;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn scan_item(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if self.in_string {
+            self.item.push(byte);
+
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+            }
+            return None;
+        }
+
+        match byte {
+            b',' if self.depth == 2 => self.take_item(),
+            b']' if self.depth == 2 => {
+                self.state = ScanState::Done;
+                self.take_item()
+            },
+            b'}' | b']' => {
+                self.item.push(byte);
+                self.depth -= 1;
+
+                if self.depth == 2 {
+                    self.take_item()
+                } else {
+                    None
+                }
+            },
+            b'{' | b'[' => {
+                self.item.push(byte);
+                self.depth += 1;
+                None
+            },
+            b'"' => {
+                self.item.push(byte);
+                self.in_string = true;
+                None
+            },
+            // Whitespace around the items, scalars having none inside.
+            byte if byte.is_ascii_whitespace() && self.depth == 2 => None,
+            byte => {
+                self.item.push(byte);
+                None
+            },
+        }
+    }
+
+    fn take_item(&mut self) -> Option<Vec<u8>> {
+        let item = std::mem::take(&mut self.item);
+
+        if item.iter().all(u8::is_ascii_whitespace) {
+            None
+        } else {
+            Some(item)
+        }
+    }
+}
+
+/// A server-sent event of a streamed response.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerSentEvent {
+    /// Type of the event from its `event:` field, e.g. `thread.message.delta`, if any
+    pub event: Option<String>,
+
+    /// Data of the event, its `data:` lines joined with newlines
+    pub data: String,
+}
+
+/// Incremental parser of a `text/event-stream` body, yielding every complete event.
+#[derive(Default)]
+pub(crate) struct SseParser {
+    line: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+}
+
+impl SseParser {
+    /// Parse the next bytes of the body, calling `on_event` with every complete event.
+    pub(crate) fn push(&mut self, bytes: &[u8], mut on_event: impl FnMut(ServerSentEvent)) {
+        for &byte in bytes {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+
+            if self.line.last() == Some(&b'\r') {
+                self.line.pop();
+            }
+            let line = std::mem::take(&mut self.line);
+            self.parse_line(&String::from_utf8_lossy(&line), &mut on_event);
+        }
+    }
+
+    /// End of the body: dispatch the event in progress, if any.
+    pub(crate) fn finish(&mut self, mut on_event: impl FnMut(ServerSentEvent)) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.parse_line(&String::from_utf8_lossy(&line), &mut on_event);
+        }
+        self.parse_line("", &mut on_event);
+    }
+
+    fn parse_line(&mut self, line: &str, on_event: &mut impl FnMut(ServerSentEvent)) {
+        if line.is_empty() {
+            let event = self.event.take();
+            if let Some(data) = self.data.take() {
+                on_event(ServerSentEvent { event, data });
+            }
+            return;
+        }
+        // Lines starting with a colon are comments, e.g. keep-alives.
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                },
+                None => self.data = Some(value.to_string()),
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Items of the `data` field of `body` fed to a scanner in the given chunks.
+    fn scan(chunks: &[&[u8]]) -> (Vec<String>, bool) {
+        let mut scanner = ArrayFieldScanner::new("data");
+        let mut items = Vec::new();
+        for chunk in chunks {
+            scanner.push(chunk, |item| {
+                items.push(String::from_utf8(item).unwrap());
+            });
+        }
+
+        (items, scanner.is_done())
+    }
+
+    #[test]
+    fn scanner_extracts_the_items_of_the_top_level_field() {
+        let body = br#"{"object":"list","meta":{"data":[0]},"first_id":"data","data": [ {"id":"a","n":[1,2]} , "b", 3 ],"has_more":false}"#;
+
+        let (items, done) = scan(&[body]);
+
+        assert_eq!(items, [r#"{"id":"a","n":[1,2]}"#, r#""b""#, "3"]);
+        assert!(done);
+    }
+
+    #[test]
+    fn scanner_handles_every_chunk_split() {
+        let body = br#"{"object":"list","data":[{"text":"a, \"quoted\" ]}"},"\\",{"id":"\\\"data\\\""}],"first_id":"x"}"#;
+        let expected = [
+            r#"{"text":"a, \"quoted\" ]}"}"#,
+            r#""\\""#,
+            r#"{"id":"\\\"data\\\""}"#,
+        ];
+
+        for first in 0..=body.len() {
+            for second in first..=body.len() {
+                let (items, done) = scan(&[&body[..first], &body[first..second], &body[second..]]);
+
+                assert_eq!(items, expected, "split at {first} and {second}");
+                assert!(done);
+            }
+        }
+    }
+
+    #[test]
+    fn scanner_skips_escaped_quotes_in_keys() {
+        let body = br#"{"da\"ta":[1],"data":[2]}"#;
+
+        assert_eq!(scan(&[body]).0, ["2"]);
+    }
+
+    #[test]
+    fn scanner_finishes_without_the_field() {
+        let (items, done) = scan(&[br#"{"object":"list","#, br#""items":[1,2]}"#]);
+
+        assert!(items.is_empty());
+        assert!(done);
+    }
+
+    #[test]
+    fn scanner_ignores_bytes_after_the_array() {
+        let (items, done) = scan(&[br#"{"data":[1]"#, br#","more":[{"data":[2]}]}"#]);
+
+        assert_eq!(items, ["1"]);
+        assert!(done);
+    }
+
+    /// Events of a server-sent events body fed to a parser in the given chunks.
+    fn parse(chunks: &[&[u8]]) -> Vec<ServerSentEvent> {
+        let mut parser = SseParser::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            parser.push(chunk, |event| events.push(event));
+        }
+        parser.finish(|event| events.push(event));
+
+        events
+    }
+
+    fn event(event: Option<&str>, data: &str) -> ServerSentEvent {
+        ServerSentEvent {
+            event: event.map(str::to_string),
+            data: data.to_string(),
+        }
+    }
+
+    #[test]
+    fn sse_parser_handles_crlf_and_multi_line_data_across_chunk_splits() {
+        let body = b": keep-alive\r\n\r\nevent: delta\r\ndata: {\"a\":\r\ndata:1}\r\n\r\ndata: [DONE]\r\n\r\n";
+        let expected = [event(Some("delta"), "{\"a\":\n1}"), event(None, "[DONE]")];
+
+        for split in 0..=body.len() {
+            let events = parse(&[&body[..split], &body[split..]]);
+
+            assert_eq!(events, expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn sse_parser_dispatches_the_last_event_at_the_end_of_the_body() {
+        let events = parse(&[b"data: a\n\ndata: b\nid: 2\nretry: 10\ndata"]);
+
+        assert_eq!(events, [event(None, "a"), event(None, "b\n")]);
+    }
+
+    #[test]
+    fn sse_parser_skips_events_without_data() {
+        let events = parse(&[b"event: ping\n\n: comment\n\nevent: done\ndata: \n\n"]);
+
+        assert_eq!(events, [event(Some("done"), "")]);
+    }
+}
@@ -0,0 +1,381 @@
+use crate::error_handling::{OpenAIError, OpenAIResult};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+#[cfg(feature = "macros")]
+pub use rusty_openai_macros::StructuredOutput;
+
+/// String formats accepted in strict structured-output schemas.
+const SUPPORTED_FORMATS: &[&str] = &[
+    "date-time",
+    "time",
+    "date",
+    "duration",
+    "email",
+    "hostname",
+    "ipv4",
+    "ipv6",
+    "uuid",
+];
+
+/// A type a structured-output chat completion can be constrained to and parsed into.
+///
+/// With the `macros` feature, `#[derive(StructuredOutput)]` implements it for structs and enums of
+/// unit variants whose fields implement it, next to `#[derive(Deserialize)]`.
+pub trait StructuredOutput: DeserializeOwned {
+    /// JSON schema of the type.
+    fn json_schema() -> Value;
+
+    /// The `response_format` of a chat completion constrained to the type with strict structured
+    /// outputs.
+    fn response_format() -> Value {
+        response_format(Self::json_schema())
+    }
+
+    /// Parse the output of a structured-output chat completion.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the parsed value on success, or an [`OpenAIError`] on failure,
+    /// [`OpenAIError::ParseError`] with the raw output if it doesn't deserialize.
+    fn from_response(response: Value) -> OpenAIResult<Self> {
+        let raw = content(response)?;
+
+        serde_json::from_str(&raw).map_err(|error| OpenAIError::ParseError {
+            message: error.to_string(),
+            raw,
+        })
+    }
+}
+
+macro_rules! primitive_outputs {
+    ($($kind:literal: $($ty:ty),*;)*) => {
+        $($(
+            impl StructuredOutput for $ty {
+                fn json_schema() -> Value {
+                    json!({ "type": $kind })
+                }
+            }
+        )*)*
+    };
+}
+
+primitive_outputs! {
+    "string": String, char;
+    "boolean": bool;
+    "integer": i8, i16, i32, i64, isize, u8, u16, u32, u64, usize;
+    "number": f32, f64;
+}
+
+impl<T: StructuredOutput> StructuredOutput for Option<T> {
+    fn json_schema() -> Value {
+        json!({ "anyOf": [T::json_schema(), { "type": "null" }] })
+    }
+}
+
+impl<T: StructuredOutput> StructuredOutput for Box<T> {
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+macro_rules! array_outputs {
+    ($($collection:ident<T $(: $($bound:path),+)?>),*) => {
+        $(
+            impl<T: StructuredOutput $($(+ $bound)+)?> StructuredOutput for $collection<T> {
+                fn json_schema() -> Value {
+                    json!({ "type": "array", "items": T::json_schema() })
+                }
+            }
+        )*
+    };
+}
+
+array_outputs!(Vec<T>, VecDeque<T>, BTreeSet<T: Ord>, HashSet<T: Eq, std::hash::Hash>);
+
+/// Schema builders of the code generated by `#[derive(StructuredOutput)]`.
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json::Value;
+    use serde_json::{json, Map};
+
+    /// Schema of an object with `properties`, given as names, descriptions and schemas.
+    pub fn object(
+        title: &str,
+        description: Option<&str>,
+        properties: Vec<(&str, Option<&str>, Value)>,
+    ) -> Value {
+        let properties: Map<String, Value> = properties
+            .into_iter()
+            .map(|(name, description, mut schema)| {
+                if let (Some(description), Some(schema)) = (description, schema.as_object_mut()) {
+                    schema.insert("description".to_string(), Value::from(description));
+                }
+                (name.to_string(), schema)
+            })
+            .collect();
+
+        let mut schema = json!({ "title": title, "type": "object", "properties": properties });
+        if let Some(description) = description {
+            schema["description"] = Value::from(description);
+        }
+
+        schema
+    }
+
+    /// Schema of a string taking one of `variants`.
+    pub fn string_enum(title: &str, description: Option<&str>, variants: &[&str]) -> Value {
+        let mut schema = json!({ "title": title, "type": "string", "enum": variants });
+        if let Some(description) = description {
+            schema["description"] = Value::from(description);
+        }
+
+        schema
+    }
+}
+
+/// The `response_format` of a strict structured-output request constrained to `schema`.
+pub(crate) fn response_format(mut schema: Value) -> Value {
+    let name: String = schema["title"]
+        .as_str()
+        .unwrap_or("response")
+        .chars()
+        .filter(|character| character.is_ascii_alphanumeric() || matches!(character, '_' | '-'))
+        .take(64)
+        .collect();
+
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("$schema");
+    }
+    strict(&mut schema);
+
+    json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": if name.is_empty() { "response".to_string() } else { name },
+            "schema": schema,
+            "strict": true,
+        },
+    })
+}
+
+/// Make `schema` acceptable in strict mode: every object lists all its properties as required and
+/// forbids additional ones, and unsupported string formats are dropped.
+///
+/// Only the subschemas are visited, not e.g. the `properties` map itself, whose keys are field
+/// names.
+fn strict(schema: &mut Value) {
+    let Value::Object(object) = schema else {
+        return;
+    };
+
+    if let Some(Value::Object(properties)) = object.get("properties") {
+        let required: Vec<Value> = properties.keys().cloned().map(Value::from).collect();
+        object.insert("required".to_string(), Value::from(required));
+        object.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+
+    if object
+        .get("format")
+        .and_then(Value::as_str)
+        .is_some_and(|format| !SUPPORTED_FORMATS.contains(&format))
+    {
+        object.remove("format");
+    }
+
+    for (keyword, subschemas) in object.iter_mut() {
+        match (keyword.as_str(), subschemas) {
+            ("properties" | "$defs" | "definitions", Value::Object(subschemas)) => {
+                subschemas.values_mut().for_each(strict);
+            },
+            ("anyOf" | "allOf" | "oneOf" | "items", Value::Array(subschemas)) => {
+                subschemas.iter_mut().for_each(strict);
+            },
+            ("items", subschema) => strict(subschema),
+            _ => {},
+        }
+    }
+}
+
+/// The text of the first choice of a chat completion, failing on refusals and truncated outputs.
+pub(crate) fn content(response: Value) -> OpenAIResult<String> {
+    let choice = &response["choices"][0];
+    if let Some(refusal) = choice["message"]["refusal"].as_str() {
+        return Err(OpenAIError::Refusal(refusal.to_string()));
+    }
+    if choice["finish_reason"] == "length" {
+        return Err(OpenAIError::UnexpectedResponse(
+            "structured output truncated by the token limit".to_string(),
+        ));
+    }
+
+    choice["message"]["content"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| {
+            OpenAIError::UnexpectedResponse("missing message in chat completion".to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_schemas_require_every_property_of_nested_objects() {
+        let inner = __private::object(
+            "Inner",
+            None,
+            vec![("id", None, json!({ "type": "string", "format": "uri" }))],
+        );
+        let schema = __private::object(
+            "Feature",
+            Some("A feature"),
+            vec![
+                ("properties", None, inner),
+                (
+                    "tags",
+                    None,
+                    json!({ "type": "array", "items": { "$ref": "#/$defs/Tag" } }),
+                ),
+            ],
+        );
+        let mut schema = schema.as_object().unwrap().clone();
+        schema.insert(
+            "$defs".to_string(),
+            json!({ "Tag": { "type": "object", "properties": { "name": { "type": "string" } } } }),
+        );
+
+        let format = response_format(Value::Object(schema));
+        let schema = &format["json_schema"]["schema"];
+
+        assert_eq!(format["json_schema"]["name"], "Feature");
+        assert_eq!(schema["required"], json!(["properties", "tags"]));
+        assert_eq!(schema["additionalProperties"], false);
+        // The field named `properties` is left alone, its schema is made strict.
+        assert_eq!(
+            schema["properties"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            ["properties", "tags"]
+        );
+        assert_eq!(
+            schema["properties"]["properties"],
+            json!({
+                "title": "Inner",
+                "type": "object",
+                "properties": { "id": { "type": "string" } },
+                "required": ["id"],
+                "additionalProperties": false,
+            })
+        );
+        assert_eq!(schema["$defs"]["Tag"]["required"], json!(["name"]));
+        assert_eq!(schema["$defs"]["Tag"]["additionalProperties"], false);
+    }
+
+    #[test]
+    fn strict_schemas_visit_options_and_array_items() {
+        let object = json!({ "type": "object", "properties": { "a": { "type": "integer" } } });
+        let format = response_format(json!({
+            "title": "Wrapper",
+            "type": "object",
+            "properties": {
+                "maybe": { "anyOf": [object, { "type": "null" }] },
+                "list": { "type": "array", "items": object },
+            },
+        }));
+        let properties = &format["json_schema"]["schema"]["properties"];
+
+        assert_eq!(properties["maybe"]["anyOf"][0]["required"], json!(["a"]));
+        assert_eq!(properties["maybe"]["anyOf"][1], json!({ "type": "null" }));
+        assert_eq!(properties["list"]["items"]["required"], json!(["a"]));
+    }
+
+    #[cfg(feature = "macros")]
+    mod derive {
+        use super::*;
+        use serde::Deserialize;
+
+        /// A product listed in
+        /// the catalog.
+        #[derive(Debug, Deserialize, StructuredOutput)]
+        #[serde(rename_all = "camelCase")]
+        struct Product {
+            /// Name shown to customers
+            display_name: String,
+            unit_price: f64,
+            #[serde(rename = "qty")]
+            quantity: Option<u32>,
+            #[serde(skip)]
+            internal_id: u64,
+            category: Category,
+            r#type: Vec<String>,
+        }
+
+        /// Where a product is shelved.
+        #[derive(Debug, PartialEq, Deserialize, StructuredOutput)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        enum Category {
+            HomeGarden,
+            Toys,
+            #[serde(rename = "misc")]
+            Other,
+        }
+
+        #[test]
+        fn structs_get_an_object_schema_of_their_serde_fields() {
+            assert_eq!(
+                Product::json_schema(),
+                json!({
+                    "title": "Product",
+                    "description": "A product listed in\nthe catalog.",
+                    "type": "object",
+                    "properties": {
+                        "displayName": { "type": "string", "description": "Name shown to customers" },
+                        "unitPrice": { "type": "number" },
+                        "qty": { "anyOf": [{ "type": "integer" }, { "type": "null" }] },
+                        "category": Category::json_schema(),
+                        "type": { "type": "array", "items": { "type": "string" } },
+                    },
+                })
+            );
+        }
+
+        #[test]
+        fn enums_get_a_string_schema_of_their_serde_variants() {
+            assert_eq!(
+                Category::json_schema(),
+                json!({
+                    "title": "Category",
+                    "description": "Where a product is shelved.",
+                    "type": "string",
+                    "enum": ["HOME_GARDEN", "TOYS", "misc"],
+                })
+            );
+        }
+
+        #[test]
+        fn derived_outputs_parse_chat_completions() {
+            let format = Product::response_format();
+            assert_eq!(
+                format["json_schema"]["schema"]["required"],
+                json!(["category", "displayName", "qty", "type", "unitPrice"])
+            );
+
+            let content = r#"{"displayName":"Rake","unitPrice":12.5,"qty":null,"category":"HOME_GARDEN","type":["tool"]}"#;
+            let product = Product::from_response(json!({
+                "choices": [{ "message": { "content": content }, "finish_reason": "stop" }],
+            }))
+            .unwrap();
+
+            assert_eq!(product.display_name, "Rake");
+            assert_eq!(product.unit_price, 12.5);
+            assert_eq!((product.quantity, product.internal_id), (None, 0));
+            assert_eq!(product.category, Category::HomeGarden);
+            assert_eq!(product.r#type, ["tool"]);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::test_util::fixtures::{self, CREATED_AT};
+use serde_json::{json, Value};
+
+/// Builder of chat completion responses, with text content and/or tool calls.
+#[derive(Clone, Debug)]
+pub struct ChatCompletionBuilder {
+    model: String,
+    content: Option<String>,
+    tool_calls: Vec<Value>,
+    finish_reason: Option<String>,
+    usage: (u64, u64),
+}
+
+impl ChatCompletionBuilder {
+    /// Start a completion from `model` without content.
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            content: None,
+            tool_calls: Vec::new(),
+            finish_reason: None,
+            usage: (12, 8),
+        }
+    }
+
+    /// Set the text content of the assistant message.
+    pub fn content(mut self, content: &str) -> Self {
+        self.content = Some(content.to_string());
+        self
+    }
+
+    /// Add a function tool call, `arguments` being serialized to a JSON string as the API does.
+    pub fn tool_call(mut self, id: &str, name: &str, arguments: Value) -> Self {
+        self.tool_calls.push(json!({
+            "id": id,
+            "type": "function",
+            "function": { "name": name, "arguments": arguments.to_string() },
+        }));
+        self
+    }
+
+    /// Override the finish reason (`stop`, or `tool_calls` when tool calls were added, by default).
+    pub fn finish_reason(mut self, finish_reason: &str) -> Self {
+        self.finish_reason = Some(finish_reason.to_string());
+        self
+    }
+
+    /// Set the reported token usage.
+    pub fn usage(mut self, prompt_tokens: u64, completion_tokens: u64) -> Self {
+        self.usage = (prompt_tokens, completion_tokens);
+        self
+    }
+
+    /// Build the JSON response.
+    pub fn build(self) -> Value {
+        let finish_reason = self.finish_reason.unwrap_or_else(|| {
+            if self.tool_calls.is_empty() {
+                "stop".to_string()
+            } else {
+                "tool_calls".to_string()
+            }
+        });
+
+        let mut message = json!({ "role": "assistant", "content": self.content, "refusal": null });
+        if !self.tool_calls.is_empty() {
+            message["tool_calls"] = Value::Array(self.tool_calls);
+        }
+
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "created": CREATED_AT,
+            "model": self.model,
+            "system_fingerprint": "fp_mock",
+            "choices": [{
+                "index": 0,
+                "message": message,
+                "logprobs": null,
+                "finish_reason": finish_reason,
+            }],
+            "usage": fixtures::usage(self.usage.0, self.usage.1),
+        })
+    }
+}
+
+/// Lifecycle status of an assistants run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunStatus {
+    /// Waiting to be picked up
+    Queued,
+
+    /// Being executed
+    InProgress,
+
+    /// Waiting for tool outputs to be submitted
+    RequiresAction,
+
+    /// Being cancelled
+    Cancelling,
+
+    /// Cancelled before completion
+    Cancelled,
+
+    /// Ended with an error
+    Failed,
+
+    /// Ended successfully
+    Completed,
+
+    /// Ended early, e.g. after reaching a token limit
+    Incomplete,
+
+    /// Expired before tool outputs were submitted
+    Expired,
+}
+
+impl RunStatus {
+    /// Every status, e.g. to test that each one is handled.
+    pub const ALL: [Self; 9] = [
+        Self::Queued,
+        Self::InProgress,
+        Self::RequiresAction,
+        Self::Cancelling,
+        Self::Cancelled,
+        Self::Failed,
+        Self::Completed,
+        Self::Incomplete,
+        Self::Expired,
+    ];
+
+    /// The status as sent by the API.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::InProgress => "in_progress",
+            Self::RequiresAction => "requires_action",
+            Self::Cancelling => "cancelling",
+            Self::Cancelled => "cancelled",
+            Self::Failed => "failed",
+            Self::Completed => "completed",
+            Self::Incomplete => "incomplete",
+            Self::Expired => "expired",
+        }
+    }
+
+    /// Whether the run won't change status anymore.
+    pub const fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            Self::Cancelled | Self::Failed | Self::Completed | Self::Incomplete | Self::Expired
+        )
+    }
+}
+
+/// Builder of assistants run objects, filling the fields that go with each [`RunStatus`]
+/// (`required_action`, `last_error`, `incomplete_details`, timestamps and usage).
+#[derive(Clone, Debug)]
+pub struct RunBuilder {
+    id: String,
+    thread_id: String,
+    assistant_id: String,
+    status: RunStatus,
+    tool_calls: Vec<Value>,
+    error: (String, String),
+}
+
+impl RunBuilder {
+    /// Start a run in `status`.
+    pub fn new(status: RunStatus) -> Self {
+        Self {
+            id: "run_mock".to_string(),
+            thread_id: "thread_mock".to_string(),
+            assistant_id: "asst_mock".to_string(),
+            status,
+            tool_calls: Vec::new(),
+            error: (
+                "server_error".to_string(),
+                "Something went wrong.".to_string(),
+            ),
+        }
+    }
+
+    /// Set the IDs of the run, its thread and its assistant.
+    pub fn ids(mut self, id: &str, thread_id: &str, assistant_id: &str) -> Self {
+        self.id = id.to_string();
+        self.thread_id = thread_id.to_string();
+        self.assistant_id = assistant_id.to_string();
+        self
+    }
+
+    /// Add a tool call to submit outputs for, used when the run requires action.
+    pub fn tool_call(mut self, id: &str, name: &str, arguments: Value) -> Self {
+        self.tool_calls.push(json!({
+            "id": id,
+            "type": "function",
+            "function": { "name": name, "arguments": arguments.to_string() },
+        }));
+        self
+    }
+
+    /// Set the error reported when the run failed.
+    pub fn error(mut self, code: &str, message: &str) -> Self {
+        self.error = (code.to_string(), message.to_string());
+        self
+    }
+
+    /// Build the JSON run object.
+    pub fn build(self) -> Value {
+        let status = self.status;
+        let mut run = fixtures::run(
+            &self.id,
+            &self.thread_id,
+            &self.assistant_id,
+            status.as_str(),
+        );
+        let ended_at = Some(CREATED_AT + 1);
+
+        match status {
+            RunStatus::Queued => run["started_at"] = Value::Null,
+            RunStatus::RequiresAction => {
+                run["required_action"] = json!({
+                    "type": "submit_tool_outputs",
+                    "submit_tool_outputs": { "tool_calls": self.tool_calls },
+                });
+            },
+            RunStatus::Cancelled => run["cancelled_at"] = json!(ended_at),
+            RunStatus::Failed => {
+                run["failed_at"] = json!(ended_at);
+                run["last_error"] = json!({ "code": self.error.0, "message": self.error.1 });
+            },
+            RunStatus::Incomplete => {
+                run["incomplete_details"] = json!({ "reason": "max_completion_tokens" });
+                run["usage"] = fixtures::usage(20, 10);
+            },
+            RunStatus::Expired => run["expires_at"] = json!(ended_at),
+            RunStatus::InProgress | RunStatus::Cancelling | RunStatus::Completed => {},
+        }
+
+        run
+    }
+}
+
+/// Builder of embeddings responses for a batch of inputs, with deterministic vectors.
+#[derive(Clone, Debug)]
+pub struct EmbeddingBatchBuilder {
+    model: String,
+    dimensions: usize,
+    inputs: Vec<String>,
+}
+
+impl EmbeddingBatchBuilder {
+    /// Start an empty batch from `model`, with vectors of `dimensions` values.
+    pub fn new(model: &str, dimensions: usize) -> Self {
+        Self {
+            model: model.to_string(),
+            dimensions,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Add an input to the batch.
+    pub fn input(mut self, input: &str) -> Self {
+        self.inputs.push(input.to_string());
+        self
+    }
+
+    /// Add several inputs to the batch.
+    pub fn inputs<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, inputs: I) -> Self {
+        self.inputs
+            .extend(inputs.into_iter().map(|input| input.as_ref().to_string()));
+        self
+    }
+
+    /// Build the JSON response, with one vector per input in order.
+    pub fn build(self) -> Value {
+        let inputs = self.inputs.iter().map(String::as_str).collect::<Vec<_>>();
+
+        fixtures::embeddings(&self.model, &inputs, self.dimensions)
+    }
+}
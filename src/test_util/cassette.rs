@@ -0,0 +1,226 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    openai::{OpenAI, RequestContext},
+    util::relative_path,
+};
+use reqwest::{header::CONTENT_TYPE, Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Whether a [`Cassette`] records new interactions or replays recorded ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests to the API and record every interaction
+    Record,
+
+    /// Answer requests from the recorded interactions without touching the network
+    Replay,
+}
+
+/// A recorded request/response pair.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Interaction {
+    /// HTTP method of the request
+    pub method: String,
+
+    /// Path of the request relative to the base URL, query string included
+    pub path: String,
+
+    /// JSON body of the request, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<Value>,
+
+    /// HTTP status of the response
+    pub status: u16,
+
+    /// Content type of the response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+
+    /// Body of the response
+    pub response_body: String,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+/// VCR-style cassette recording real API interactions to a JSON file and replaying them in tests.
+///
+/// Request headers are never recorded, and the client's API key is redacted from recorded
+/// response bodies. In replay mode, requests are matched on method, path and JSON body, each
+/// recorded interaction being used at most once, in order.
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    interactions: Mutex<Vec<(bool, Interaction)>>,
+}
+
+impl Cassette {
+    /// Create a cassette recording to `path` (any existing recording is overwritten).
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: CassetteMode::Record,
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Load a cassette from `path` to replay it.
+    pub fn replay(path: impl Into<PathBuf>) -> OpenAIResult<Self> {
+        let path = path.into();
+        let file: CassetteFile = serde_json::from_slice(&fs::read(&path)?)?;
+
+        Ok(Self {
+            path,
+            mode: CassetteMode::Replay,
+            interactions: Mutex::new(
+                file.interactions
+                    .into_iter()
+                    .map(|interaction| (false, interaction))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Replay the cassette at `path` if it exists, or record a new one otherwise.
+    pub fn auto(path: impl Into<PathBuf>) -> OpenAIResult<Self> {
+        let path = path.into();
+
+        if path.exists() {
+            Self::replay(path)
+        } else {
+            Ok(Self::record(path))
+        }
+    }
+
+    /// The mode of the cassette.
+    pub const fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// The file the cassette is recorded to or replayed from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A copy of the interactions of the cassette.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, interaction)| interaction.clone())
+            .collect()
+    }
+
+    pub(crate) async fn handle(
+        &self,
+        openai: &OpenAI,
+        request: Request,
+        context: &mut RequestContext,
+    ) -> OpenAIResult<Response> {
+        let method = request.method().to_string();
+        let path = relative_path(openai.get_base_url(), request.url().as_str());
+        let request_body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|bytes| serde_json::from_slice(bytes).ok());
+
+        match self.mode {
+            CassetteMode::Replay => self.play(&method, &path, &request_body),
+            CassetteMode::Record => {
+                let response = openai.execute(request, context).await?;
+                let status = response.status().as_u16();
+                let content_type = response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(String::from);
+                let body = response.text().await?;
+                let api_key = context.api_key.as_str();
+                let response_body = if api_key.is_empty() {
+                    body
+                } else {
+                    body.replace(api_key, REDACTED)
+                };
+
+                let interaction = Interaction {
+                    method,
+                    path,
+                    request_body,
+                    status,
+                    content_type,
+                    response_body,
+                };
+
+                let recorded = to_response(&interaction);
+                self.interactions.lock().unwrap().push((true, interaction));
+                self.save()?;
+
+                recorded
+            },
+        }
+    }
+
+    fn play(
+        &self,
+        method: &str,
+        path: &str,
+        request_body: &Option<Value>,
+    ) -> OpenAIResult<Response> {
+        let mut interactions = self.interactions.lock().unwrap();
+        let (used, interaction) = interactions
+            .iter_mut()
+            .find(|(used, interaction)| {
+                !*used
+                    && interaction.method == method
+                    && interaction.path == path
+                    && (interaction.request_body.is_none()
+                        || interaction.request_body == *request_body)
+            })
+            .ok_or_else(|| {
+                OpenAIError::UnexpectedResponse(format!(
+                    "no recorded interaction for {method} {path} in {}",
+                    self.path.display()
+                ))
+            })?;
+
+        *used = true;
+        to_response(interaction)
+    }
+
+    fn save(&self) -> OpenAIResult<()> {
+        let file = CassetteFile {
+            interactions: self.interactions(),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_vec_pretty(&file)?)?;
+
+        Ok(())
+    }
+}
+
+fn to_response(interaction: &Interaction) -> OpenAIResult<Response> {
+    let mut response = http::Response::builder().status(interaction.status);
+
+    if let Some(content_type) = &interaction.content_type {
+        response = response.header(CONTENT_TYPE, content_type);
+    }
+
+    response
+        .body(interaction.response_body.clone())
+        .map(Response::from)
+        .map_err(|error| OpenAIError::UnexpectedResponse(error.to_string()))
+}
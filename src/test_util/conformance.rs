@@ -0,0 +1,202 @@
+use crate::{error_handling::OpenAIResult, test_util::cassette::Interaction};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{fmt, fs, path::Path};
+
+/// A difference between a golden payload and its round-trip through a typed model.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// Field present in the payload but dropped by the model
+    Missing { path: String, expected: Value },
+
+    /// Field whose value changed
+    Changed {
+        path: String,
+        expected: Value,
+        actual: Value,
+    },
+
+    /// Field added by the model (e.g. a default value)
+    Added { path: String, actual: Value },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { path, expected } => write!(f, "{path}: dropped {expected}"),
+            Self::Changed {
+                path,
+                expected,
+                actual,
+            } => write!(f, "{path}: {expected} became {actual}"),
+            Self::Added { path, actual } => write!(f, "{path}: added {actual}"),
+        }
+    }
+}
+
+/// Result of round-tripping a golden payload through a typed model.
+#[derive(Clone, Debug, Default)]
+pub struct ConformanceReport {
+    /// Where the payload came from (file, cassette interaction, ...)
+    pub source: String,
+
+    /// Every difference found, empty if the round-trip was lossless
+    pub differences: Vec<Difference>,
+}
+
+impl ConformanceReport {
+    /// Whether the round-trip preserved every field.
+    ///
+    /// Only missing and changed fields count as a loss; fields added by the model don't.
+    pub fn is_lossless(&self) -> bool {
+        !self
+            .differences
+            .iter()
+            .any(|difference| !matches!(difference, Difference::Added { .. }))
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} difference(s)",
+            self.source,
+            self.differences.len()
+        )?;
+
+        for difference in &self.differences {
+            writeln!(f, "  {difference}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserialize `payload` into `T`, serialize it back and compare the result with the payload.
+///
+/// Fields set to `null` are treated as absent, as models usually skip serializing `None`.
+pub fn round_trip<T: DeserializeOwned + Serialize>(
+    source: &str,
+    payload: &Value,
+) -> OpenAIResult<ConformanceReport> {
+    let model: T = serde_json::from_value(payload.clone())?;
+    let actual = serde_json::to_value(model)?;
+
+    let mut differences = Vec::new();
+    compare("", payload, &actual, &mut differences);
+
+    Ok(ConformanceReport {
+        source: source.to_string(),
+        differences,
+    })
+}
+
+/// Round-trip the JSON payload stored in `path`.
+pub fn check_file<T: DeserializeOwned + Serialize>(
+    path: impl AsRef<Path>,
+) -> OpenAIResult<ConformanceReport> {
+    let path = path.as_ref();
+    let payload: Value = serde_json::from_slice(&fs::read(path)?)?;
+
+    round_trip::<T>(&path.display().to_string(), &payload)
+}
+
+/// Round-trip every `.json` payload in `directory`, in file name order.
+pub fn check_dir<T: DeserializeOwned + Serialize>(
+    directory: impl AsRef<Path>,
+) -> OpenAIResult<Vec<ConformanceReport>> {
+    let mut paths = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "json")
+    });
+    paths.sort();
+
+    paths.iter().map(check_file::<T>).collect()
+}
+
+/// Round-trip the successful JSON responses of recorded `interactions` (e.g. from
+/// [`Cassette::interactions`][crate::test_util::cassette::Cassette::interactions]) to `method`
+/// requests on `path` (relative to the base URL, query string excluded).
+pub fn check_interactions<T: DeserializeOwned + Serialize>(
+    interactions: &[Interaction],
+    method: &str,
+    path: &str,
+) -> OpenAIResult<Vec<ConformanceReport>> {
+    interactions
+        .iter()
+        .enumerate()
+        .filter(|(_, interaction)| {
+            interaction.method == method
+                && interaction.path.split('?').next() == Some(path)
+                && (200..300).contains(&interaction.status)
+        })
+        .map(|(index, interaction)| {
+            let payload: Value = serde_json::from_str(&interaction.response_body)?;
+            let source = format!("interaction #{index} ({method} {})", interaction.path);
+
+            round_trip::<T>(&source, &payload)
+        })
+        .collect()
+}
+
+/// Panic with every report that isn't lossless, for use in tests.
+pub fn assert_lossless(reports: &[ConformanceReport]) {
+    let lossy = reports
+        .iter()
+        .filter(|report| !report.is_lossless())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    assert!(lossy.is_empty(), "lossy round-trips:\n{}", lossy.concat());
+}
+
+fn compare(path: &str, expected: &Value, actual: &Value, differences: &mut Vec<Difference>) {
+    let child = |key: &dyn fmt::Display| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                match actual.get(key) {
+                    Some(actual) => compare(&child(key), expected, actual, differences),
+                    None if expected.is_null() => {},
+                    None => differences.push(Difference::Missing {
+                        path: child(key),
+                        expected: expected.clone(),
+                    }),
+                }
+            }
+
+            for (key, actual) in actual {
+                if !expected.contains_key(key) && !actual.is_null() {
+                    differences.push(Difference::Added {
+                        path: child(key),
+                        actual: actual.clone(),
+                    });
+                }
+            }
+        },
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (index, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                compare(&child(&index), expected, actual, differences);
+            }
+        },
+        (Value::Number(expected), Value::Number(actual))
+            if expected.as_f64() == actual.as_f64() => {},
+        (expected, actual) if expected != actual => differences.push(Difference::Changed {
+            path: path.to_string(),
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }),
+        _ => {},
+    }
+}
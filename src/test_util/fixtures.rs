@@ -0,0 +1,226 @@
+use serde_json::{json, Value};
+
+/// Timestamp used by every fixture, so responses are deterministic.
+pub const CREATED_AT: u64 = 1_700_000_000;
+
+/// A chat completion whose single choice answers with `content`.
+pub fn chat_completion(model: &str, content: &str) -> Value {
+    json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion",
+        "created": CREATED_AT,
+        "model": model,
+        "system_fingerprint": "fp_mock",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content, "refusal": null },
+            "logprobs": null,
+            "finish_reason": "stop",
+        }],
+        "usage": usage(12, token_estimate(content)),
+    })
+}
+
+/// The chunks of a streamed chat completion answering with `deltas` concatenated.
+pub fn chat_completion_chunks(model: &str, deltas: &[&str]) -> Vec<Value> {
+    let chunk = |delta: Value, finish_reason: Value| {
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion.chunk",
+            "created": CREATED_AT,
+            "model": model,
+            "system_fingerprint": "fp_mock",
+            "choices": [{ "index": 0, "delta": delta, "logprobs": null, "finish_reason": finish_reason }],
+        })
+    };
+
+    let mut chunks = vec![chunk(
+        json!({ "role": "assistant", "content": "" }),
+        Value::Null,
+    )];
+    chunks.extend(
+        deltas
+            .iter()
+            .map(|delta| chunk(json!({ "content": delta }), Value::Null)),
+    );
+    chunks.push(chunk(json!({}), json!("stop")));
+
+    chunks
+}
+
+/// Render JSON events as a server-sent events body, terminated by `data: [DONE]`.
+pub fn sse_body(events: &[Value]) -> String {
+    let mut body = events
+        .iter()
+        .map(|event| format!("data: {event}\n\n"))
+        .collect::<String>();
+    body.push_str("data: [DONE]\n\n");
+
+    body
+}
+
+/// An embeddings response with one deterministic vector of `dimensions` values per input.
+pub fn embeddings(model: &str, inputs: &[&str], dimensions: usize) -> Value {
+    let data = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            json!({
+                "object": "embedding",
+                "index": index,
+                "embedding": embedding_vector(input, dimensions),
+            })
+        })
+        .collect::<Vec<_>>();
+    let prompt_tokens = inputs
+        .iter()
+        .map(|input| token_estimate(input))
+        .sum::<u64>();
+
+    json!({
+        "object": "list",
+        "data": data,
+        "model": model,
+        "usage": { "prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens },
+    })
+}
+
+/// A deterministic unit vector derived from `input`, so equal inputs get equal embeddings.
+pub fn embedding_vector(input: &str, dimensions: usize) -> Vec<f32> {
+    let mut state = input.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    let values = (0..dimensions)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 2_000) as f32 / 1_000.0 - 1.0
+        })
+        .collect::<Vec<_>>();
+    let norm = values.iter().map(|value| value * value).sum::<f32>().sqrt();
+
+    values
+        .into_iter()
+        .map(|value| if norm == 0.0 { 0.0 } else { value / norm })
+        .collect()
+}
+
+/// A moderation response flagging nothing.
+pub fn moderation(model: &str) -> Value {
+    json!({
+        "id": "modr-mock",
+        "model": model,
+        "results": [{ "flagged": false, "categories": {}, "category_scores": {} }],
+    })
+}
+
+/// An assistant object.
+pub fn assistant(id: &str, model: &str, name: Option<&str>, instructions: Option<&str>) -> Value {
+    json!({
+        "id": id,
+        "object": "assistant",
+        "created_at": CREATED_AT,
+        "name": name,
+        "description": null,
+        "model": model,
+        "instructions": instructions,
+        "tools": [],
+        "tool_resources": {},
+        "metadata": {},
+        "temperature": 1.0,
+        "top_p": 1.0,
+        "response_format": "auto",
+    })
+}
+
+/// A thread object.
+pub fn thread(id: &str) -> Value {
+    json!({
+        "id": id,
+        "object": "thread",
+        "created_at": CREATED_AT,
+        "tool_resources": {},
+        "metadata": {},
+    })
+}
+
+/// A thread message with text content.
+pub fn message(id: &str, thread_id: &str, role: &str, text: &str) -> Value {
+    json!({
+        "id": id,
+        "object": "thread.message",
+        "created_at": CREATED_AT,
+        "thread_id": thread_id,
+        "status": "completed",
+        "role": role,
+        "content": [{ "type": "text", "text": { "value": text, "annotations": [] } }],
+        "assistant_id": null,
+        "run_id": null,
+        "attachments": [],
+        "metadata": {},
+    })
+}
+
+/// A run object in the given status (`queued`, `in_progress`, `requires_action`, `completed`, ...).
+pub fn run(id: &str, thread_id: &str, assistant_id: &str, status: &str) -> Value {
+    let completed = status == "completed";
+
+    json!({
+        "id": id,
+        "object": "thread.run",
+        "created_at": CREATED_AT,
+        "thread_id": thread_id,
+        "assistant_id": assistant_id,
+        "status": status,
+        "required_action": null,
+        "last_error": null,
+        "started_at": CREATED_AT,
+        "completed_at": if completed { Some(CREATED_AT + 1) } else { None },
+        "model": "gpt-4o-mini",
+        "instructions": "",
+        "tools": [],
+        "metadata": {},
+        "usage": if completed { usage(20, 10) } else { Value::Null },
+    })
+}
+
+/// A paginated list of objects.
+pub fn list(data: Vec<Value>) -> Value {
+    let first_id = data
+        .first()
+        .and_then(|item| item["id"].as_str())
+        .map(String::from);
+    let last_id = data
+        .last()
+        .and_then(|item| item["id"].as_str())
+        .map(String::from);
+
+    json!({
+        "object": "list",
+        "data": data,
+        "first_id": first_id,
+        "last_id": last_id,
+        "has_more": false,
+    })
+}
+
+/// An API error envelope.
+pub fn error(message: &str, error_type: &str, code: Option<&str>) -> Value {
+    json!({
+        "error": { "message": message, "type": error_type, "param": null, "code": code },
+    })
+}
+
+/// A usage object.
+pub fn usage(prompt_tokens: u64, completion_tokens: u64) -> Value {
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    })
+}
+
+fn token_estimate(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4).max(1)
+}
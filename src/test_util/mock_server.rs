@@ -0,0 +1,234 @@
+use crate::test_util::fixtures;
+use serde_json::Value;
+use wiremock::{
+    matchers::{body_partial_json, method, path, path_regex},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+/// A local mock server answering like the OpenAI API, for end-to-end tests.
+///
+/// Point a client at [`MockOpenAI::base_url`] and mount the flows the test needs:
+///
+/// * [`MockOpenAI::mock_chat_completion`] / [`MockOpenAI::mock_chat_completion_stream`]
+/// * [`MockOpenAI::mock_embeddings`]
+/// * [`MockOpenAI::mock_assistants_flow`]
+/// * [`MockOpenAI::mock_error`]
+///
+/// Use [`MockOpenAI::server`] for anything more specific.
+pub struct MockOpenAI {
+    server: MockServer,
+}
+
+impl MockOpenAI {
+    /// Start a new mock server on a random local port.
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+        }
+    }
+
+    /// The base URL to pass to [`OpenAI::new`][crate::openai::OpenAI::new].
+    pub fn base_url(&self) -> String {
+        format!("{}/v1", self.server.uri())
+    }
+
+    /// The underlying [`MockServer`].
+    pub const fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Answer non-streaming chat completions with `content`, echoing the requested model.
+    pub async fn mock_chat_completion(&self, content: &str) -> &Self {
+        let content = content.to_string();
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(move |request: &Request| {
+                ResponseTemplate::new(200).set_body_json(fixtures::chat_completion(
+                    &requested_model(request),
+                    &content,
+                ))
+            })
+            .with_priority(5)
+            .mount(&self.server)
+            .await;
+
+        self
+    }
+
+    /// Answer chat completions with `stream: true` with server-sent events carrying `deltas`.
+    pub async fn mock_chat_completion_stream(&self, deltas: &[&str]) -> &Self {
+        let deltas = deltas
+            .iter()
+            .map(|delta| delta.to_string())
+            .collect::<Vec<_>>();
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(move |request: &Request| {
+                let deltas = deltas.iter().map(String::as_str).collect::<Vec<_>>();
+                let chunks = fixtures::chat_completion_chunks(&requested_model(request), &deltas);
+
+                ResponseTemplate::new(200)
+                    .set_body_raw(fixtures::sse_body(&chunks), "text/event-stream")
+            })
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+
+        self
+    }
+
+    /// Answer embeddings requests with one deterministic vector of `dimensions` values per input.
+    pub async fn mock_embeddings(&self, dimensions: usize) -> &Self {
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .respond_with(move |request: &Request| {
+                let body: Value = request.body_json().unwrap_or_default();
+                let inputs = match &body["input"] {
+                    Value::String(input) => vec![input.as_str()],
+                    Value::Array(inputs) => inputs.iter().filter_map(Value::as_str).collect(),
+                    _ => Vec::new(),
+                };
+
+                ResponseTemplate::new(200).set_body_json(fixtures::embeddings(
+                    &requested_model(request),
+                    &inputs,
+                    dimensions,
+                ))
+            })
+            .mount(&self.server)
+            .await;
+
+        self
+    }
+
+    /// Mount an assistants flow: creating assistants, threads, messages and runs, runs that are
+    /// immediately `completed`, and thread messages listing a single assistant `reply`.
+    pub async fn mock_assistants_flow(&self, reply: &str) -> &Self {
+        Mock::given(method("POST"))
+            .and(path("/v1/assistants"))
+            .respond_with(|request: &Request| {
+                let body: Value = request.body_json().unwrap_or_default();
+
+                ResponseTemplate::new(200).set_body_json(fixtures::assistant(
+                    "asst_mock",
+                    body["model"].as_str().unwrap_or("gpt-4o-mini"),
+                    body["name"].as_str(),
+                    body["instructions"].as_str(),
+                ))
+            })
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/threads"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::thread("thread_mock")))
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path_regex(r"^/v1/threads/[^/]+/messages$"))
+            .respond_with(|request: &Request| {
+                let body: Value = request.body_json().unwrap_or_default();
+
+                ResponseTemplate::new(200).set_body_json(fixtures::message(
+                    "msg_user_mock",
+                    &path_segment(request, 2),
+                    body["role"].as_str().unwrap_or("user"),
+                    body["content"].as_str().unwrap_or_default(),
+                ))
+            })
+            .mount(&self.server)
+            .await;
+
+        let reply = reply.to_string();
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/v1/threads/[^/]+/messages$"))
+            .respond_with(move |request: &Request| {
+                let message =
+                    fixtures::message("msg_mock", &path_segment(request, 2), "assistant", &reply);
+
+                ResponseTemplate::new(200).set_body_json(fixtures::list(vec![message]))
+            })
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path_regex(r"^/v1/threads/[^/]+/runs$"))
+            .respond_with(|request: &Request| {
+                let body: Value = request.body_json().unwrap_or_default();
+
+                ResponseTemplate::new(200).set_body_json(fixtures::run(
+                    "run_mock",
+                    &path_segment(request, 2),
+                    body["assistant_id"].as_str().unwrap_or("asst_mock"),
+                    "queued",
+                ))
+            })
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/v1/threads/[^/]+/runs/[^/]+$"))
+            .respond_with(|request: &Request| {
+                ResponseTemplate::new(200).set_body_json(fixtures::run(
+                    &path_segment(request, 4),
+                    &path_segment(request, 2),
+                    "asst_mock",
+                    "completed",
+                ))
+            })
+            .mount(&self.server)
+            .await;
+
+        self
+    }
+
+    /// Answer `method_name` requests to `endpoint` (relative to the base URL) with an API error.
+    pub async fn mock_error(
+        &self,
+        method_name: &str,
+        endpoint: &str,
+        status: u16,
+        message: &str,
+    ) -> &Self {
+        let error_type = match status {
+            401 => "invalid_authentication",
+            429 => "rate_limit_exceeded",
+            500..=599 => "server_error",
+            _ => "invalid_request_error",
+        };
+
+        Mock::given(method(method_name))
+            .and(path(format!("/v1{endpoint}")))
+            .respond_with(
+                ResponseTemplate::new(status)
+                    .set_body_json(fixtures::error(message, error_type, None)),
+            )
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+
+        self
+    }
+}
+
+fn requested_model(request: &Request) -> String {
+    request
+        .body_json::<Value>()
+        .ok()
+        .and_then(|body| body["model"].as_str().map(String::from))
+        .unwrap_or_else(|| "gpt-4o-mini".to_string())
+}
+
+/// The `index`-th segment of the request path (`/v1` being segment 0).
+fn path_segment(request: &Request, index: usize) -> String {
+    request
+        .url
+        .path_segments()
+        .and_then(|mut segments| segments.nth(index))
+        .unwrap_or_default()
+        .to_string()
+}
@@ -0,0 +1,220 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    openai::OpenAI,
+    signing::buffer_body,
+    test_util::fixtures,
+    transport::{self, BoxFuture, HttpTransport, Request, Response},
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Method, StatusCode, Url,
+};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// Base URL of the clients created by [`MockTransport::client`].
+pub const MOCK_BASE_URL: &str = "http://mock.openai.invalid/v1";
+
+/// A canned response of a [`MockTransport`].
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// A `200` response with `body` as JSON, e.g. a fixture from
+    /// [`fixtures`][crate::test_util::fixtures].
+    pub fn json(body: &Value) -> Self {
+        Self::raw(body.to_string(), "application/json")
+    }
+
+    /// A `200` server-sent events response with one event per JSON value, terminated by
+    /// `data: [DONE]`, e.g. the chunks of [`fixtures::chat_completion_chunks`].
+    pub fn sse(events: &[Value]) -> Self {
+        Self::raw(fixtures::sse_body(events), "text/event-stream")
+    }
+
+    /// An API error response with `status` and `message`.
+    pub fn error(status: u16, message: &str) -> Self {
+        let error_type = match status {
+            401 => "invalid_authentication",
+            429 => "rate_limit_exceeded",
+            500..=599 => "server_error",
+            _ => "invalid_request_error",
+        };
+
+        Self::json(&fixtures::error(message, error_type, None)).status(status)
+    }
+
+    /// A `200` response with `body` of type `content_type`.
+    pub fn raw(body: impl Into<Vec<u8>>, content_type: &str) -> Self {
+        let mut headers = HeaderMap::new();
+        if let Ok(content_type) = HeaderValue::try_from(content_type) {
+            headers.insert(CONTENT_TYPE, content_type);
+        }
+
+        Self {
+            status: StatusCode::OK,
+            headers,
+            body: body.into(),
+        }
+    }
+
+    /// Set the status of the response, ignored if it isn't a valid status code.
+    pub fn status(mut self, status: u16) -> Self {
+        if let Ok(status) = StatusCode::from_u16(status) {
+            self.status = status;
+        }
+        self
+    }
+
+    /// Add the header `name` with `value`, e.g. `x-request-id` or `retry-after`, ignored if it's
+    /// invalid.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            self.headers.append(name, value);
+        }
+        self
+    }
+
+    fn to_response(&self) -> OpenAIResult<Response> {
+        transport::response(self.status, self.headers.clone(), self.body.clone())
+    }
+}
+
+/// A request received by a [`MockTransport`].
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    /// HTTP method of the request
+    pub method: Method,
+
+    /// Full URL of the request
+    pub url: Url,
+
+    /// Headers of the request
+    pub headers: HeaderMap,
+
+    /// Body of the request as sent, multipart forms included
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    /// The body of the request as JSON, if it is.
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_slice(&self.body).ok()
+    }
+}
+
+struct Route {
+    method: Method,
+    path: String,
+    response: MockResponse,
+    once: bool,
+}
+
+/// [`HttpTransport`] answering requests with canned responses instead of sending them, to unit
+/// test code using the typed APIs without a network or a mock server.
+///
+/// Requests are answered by the first route matching their method and the end of their URL path
+/// (e.g. `/chat/completions`), in the order the routes were added, the ones added with
+/// [`MockTransport::once`] being used a single time. Requests matching no route fail with an
+/// [`OpenAIError::UnexpectedResponse`]. Every request is kept for assertions, see
+/// [`MockTransport::requests`].
+#[derive(Default)]
+pub struct MockTransport {
+    routes: Mutex<Vec<Route>>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+impl MockTransport {
+    /// Create a transport without any route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every `method` request to `path` (relative to the base URL) with `response`.
+    pub fn on(&self, method: Method, path: &str, response: MockResponse) -> &Self {
+        self.route(method, path, response, false)
+    }
+
+    /// Answer the next `method` request to `path` (relative to the base URL) with `response`,
+    /// e.g. a `429` response before the [`MockTransport::on`] route answering the retry.
+    pub fn once(&self, method: Method, path: &str, response: MockResponse) -> &Self {
+        self.route(method, path, response, true)
+    }
+
+    fn route(&self, method: Method, path: &str, response: MockResponse, once: bool) -> &Self {
+        self.routes.lock().unwrap().push(Route {
+            method,
+            path: format!("/{}", path.trim_matches('/')),
+            response,
+            once,
+        });
+        self
+    }
+
+    /// A client sending its requests to this transport, with a test API key and
+    /// [`MOCK_BASE_URL`] as base URL.
+    pub fn client(self: &Arc<Self>) -> OpenAI {
+        let mut openai = OpenAI::new("sk-mock", MOCK_BASE_URL);
+        openai.set_transport(Arc::clone(self) as Arc<dyn HttpTransport>);
+
+        openai
+    }
+
+    /// A copy of the requests received so far, in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The last request received, if any.
+    pub fn last_request(&self) -> Option<MockRequest> {
+        self.requests.lock().unwrap().last().cloned()
+    }
+
+    fn answer(&self, request: &MockRequest) -> OpenAIResult<Response> {
+        let mut routes = self.routes.lock().unwrap();
+        let path = request.url.path().trim_end_matches('/');
+        let index = routes
+            .iter()
+            .position(|route| route.method == request.method && path.ends_with(route.path.as_str()))
+            .ok_or_else(|| {
+                OpenAIError::UnexpectedResponse(format!(
+                    "no mock response for {} {path}",
+                    request.method
+                ))
+            })?;
+
+        let response = routes[index].response.to_response();
+        if routes[index].once {
+            routes.remove(index);
+        }
+
+        response
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, OpenAIResult<Response>> {
+        Box::pin(async move {
+            let request = buffer_body(request).await?;
+            let request = MockRequest {
+                method: request.method().clone(),
+                url: request.url().clone(),
+                headers: request.headers().clone(),
+                body: request
+                    .body()
+                    .and_then(|body| body.as_bytes())
+                    .map(<[u8]>::to_vec)
+                    .unwrap_or_default(),
+            };
+
+            let response = self.answer(&request);
+            self.requests.lock().unwrap().push(request);
+
+            response
+        })
+    }
+}
@@ -0,0 +1,6 @@
+pub mod builders;
+pub mod cassette;
+pub mod conformance;
+pub mod fixtures;
+pub mod mock_server;
+pub mod mock_transport;
@@ -0,0 +1,160 @@
+use crate::{
+    error_handling::OpenAIResult,
+    metrics::request_model,
+    openai::ApiResponse,
+    runtime::Instant,
+    util::{relative_path, SECRET_HEADERS},
+};
+use reqwest::{header::HeaderMap, Request, Response};
+use tracing::{field, Span};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// The `openai.request` span of an API call, with its method, path (without the query string)
+/// and model, then the status, `x-request-id`, latency and retries of its response.
+///
+/// For streamed responses, the latency is the time until the response headers arrived. Headers
+/// are never recorded and the API key is redacted from error messages.
+pub(crate) struct RequestSpan {
+    span: Span,
+    started: Instant,
+}
+
+impl RequestSpan {
+    pub(crate) fn new(base_url: &str, request: &Request) -> Self {
+        let path = relative_path(base_url, request.url().as_str());
+        let path = path.split('?').next().unwrap_or_default();
+        let model = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(request_model);
+
+        let span = tracing::info_span!(
+            "openai.request",
+            method = %request.method(),
+            path,
+            model = model.as_deref(),
+            status = field::Empty,
+            request_id = field::Empty,
+            latency_ms = field::Empty,
+            retries = field::Empty,
+        );
+
+        Self {
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) const fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Record the outcome of the call, after `retries` retries.
+    pub(crate) fn finish(self, result: &OpenAIResult<Response>, retries: u32, api_key: &str) {
+        let span = &self.span;
+        span.record("latency_ms", self.started.elapsed().as_millis() as u64);
+        span.record("retries", retries);
+
+        match result {
+            Ok(response) => {
+                span.record("status", response.status().as_u16());
+                if let Some(request_id) = response
+                    .headers()
+                    .get("x-request-id")
+                    .and_then(|request_id| request_id.to_str().ok())
+                {
+                    span.record("request_id", request_id);
+                }
+
+                tracing::debug!(parent: span, "OpenAI API call finished");
+            },
+            Err(error) => {
+                let mut message = error.to_string();
+                if !api_key.is_empty() {
+                    message = message.replace(api_key, "[REDACTED]");
+                }
+
+                tracing::warn!(parent: span, error = %message, "OpenAI API call failed");
+            },
+        }
+    }
+}
+
+/// Log `request` at the debug level, with its headers and its body truncated to `max_body_len`
+/// bytes, the credentials being redacted.
+pub(crate) fn log_request(
+    span: &RequestSpan,
+    request: &Request,
+    api_key: &str,
+    max_body_len: usize,
+) {
+    let body = match request.body() {
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => loggable_body(bytes, api_key, max_body_len),
+            None => "[streamed body]".to_string(),
+        },
+        None => String::new(),
+    };
+
+    tracing::debug!(
+        parent: span.span(),
+        method = %request.method(),
+        url = %request.url(),
+        headers = %loggable_headers(request.headers(), api_key),
+        body,
+        "OpenAI API request"
+    );
+}
+
+/// Log `response` at the debug level, with its headers and its body truncated to `max_body_len`
+/// bytes, the API key being redacted.
+pub(crate) fn log_response(response: &ApiResponse, api_key: &str, max_body_len: usize) {
+    tracing::debug!(
+        status = response.status.as_u16(),
+        request_id = response.request_id.as_deref(),
+        headers = %loggable_headers(&response.headers, api_key),
+        body = loggable_body(&response.body, api_key, max_body_len),
+        "OpenAI API response"
+    );
+}
+
+/// `headers` as `name: value` pairs, the values of the credential headers and of the headers
+/// carrying `api_key` being redacted.
+fn loggable_headers(headers: &HeaderMap, api_key: &str) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            let value = if SECRET_HEADERS.contains(&name.as_str())
+                || (!api_key.is_empty() && value.contains(api_key))
+            {
+                REDACTED.into()
+            } else {
+                value
+            };
+
+            format!("{name}: {value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `body` as text truncated to `max_body_len` bytes, with `api_key` redacted.
+fn loggable_body(body: &[u8], api_key: &str, max_body_len: usize) -> String {
+    let mut text = String::from_utf8_lossy(body).into_owned();
+    if !api_key.is_empty() {
+        text = text.replace(api_key, REDACTED);
+    }
+
+    if text.len() > max_body_len {
+        let mut end = max_body_len;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str(&format!("... ({} bytes)", body.len()));
+    }
+
+    text
+}
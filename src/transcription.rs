@@ -0,0 +1,563 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    model::Model,
+    openai::OpenAI,
+    retry::RetryPolicy,
+    runtime,
+};
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the audio files the transcription endpoint accepts.
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "flac", "m4a", "mp3", "mp4", "mpeg", "mpga", "oga", "ogg", "wav", "webm",
+];
+
+/// Format [`TranscriptionJob`] writes transcripts in.
+///
+/// Subtitle formats and JSON request `verbose_json` transcriptions, which not every model
+/// supports (e.g. `whisper-1` does).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    /// Plain text
+    #[default]
+    Text,
+
+    /// SubRip subtitles
+    Srt,
+
+    /// WebVTT subtitles
+    Vtt,
+
+    /// The verbose JSON transcription, segments and timestamps included
+    Json,
+}
+
+impl TranscriptFormat {
+    /// Extension of the transcript files.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Json => "json",
+        }
+    }
+
+    /// Render a transcription response in this format.
+    fn render(self, transcription: &Value) -> OpenAIResult<String> {
+        let text = || transcription["text"].as_str().unwrap_or_default().trim();
+
+        let segments = || {
+            transcription["segments"].as_array().ok_or_else(|| {
+                OpenAIError::UnexpectedResponse(
+                    "transcription has no segments to build subtitles from".to_string(),
+                )
+            })
+        };
+        let cues = |separator: char| -> OpenAIResult<Vec<String>> {
+            Ok(segments()?
+                .iter()
+                .map(|segment| {
+                    format!(
+                        "{} --> {}\n{}",
+                        timestamp(segment["start"].as_f64().unwrap_or_default(), separator),
+                        timestamp(segment["end"].as_f64().unwrap_or_default(), separator),
+                        segment["text"].as_str().unwrap_or_default().trim()
+                    )
+                })
+                .collect())
+        };
+
+        Ok(match self {
+            Self::Text => format!("{}\n", text()),
+            Self::Srt => cues(',')?
+                .into_iter()
+                .enumerate()
+                .map(|(index, cue)| format!("{}\n{cue}\n\n", index + 1))
+                .collect(),
+            Self::Vtt => cues('.')?
+                .into_iter()
+                .fold("WEBVTT\n\n".to_string(), |vtt, cue| vtt + &cue + "\n\n"),
+            Self::Json => serde_json::to_string_pretty(transcription)?,
+        })
+    }
+}
+
+/// `HH:MM:SS,mmm` (or with a `.` before the milliseconds) timestamp of `seconds`.
+fn timestamp(seconds: f64, separator: char) -> String {
+    let milliseconds = (seconds.max(0.0) * 1_000.0).round() as u64;
+
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        milliseconds / 3_600_000,
+        milliseconds / 60_000 % 60,
+        milliseconds / 1_000 % 60,
+        milliseconds % 1_000
+    )
+}
+
+/// Outcome of the transcription of a file.
+#[derive(Debug)]
+pub struct FileReport {
+    /// The audio file
+    pub input: PathBuf,
+
+    /// The transcript written, or the error of the last attempt
+    pub result: OpenAIResult<PathBuf>,
+}
+
+/// Report of a [`TranscriptionJob`] run, one entry per file in input order.
+#[derive(Debug)]
+pub struct TranscriptionReport {
+    /// Outcome of every file
+    pub files: Vec<FileReport>,
+}
+
+impl TranscriptionReport {
+    /// Files whose transcription failed.
+    pub fn failures(&self) -> impl Iterator<Item = &FileReport> {
+        self.files.iter().filter(|file| file.result.is_err())
+    }
+
+    /// Number of transcripts written (or already existing when skipped).
+    pub fn succeeded(&self) -> usize {
+        self.files.iter().filter(|file| file.result.is_ok()).count()
+    }
+}
+
+/// Transcription of many audio files with bounded concurrency, writing a transcript per file and
+/// reporting per-file failures instead of stopping at the first one.
+///
+/// Requests wait while the rate limits reported by the API are exhausted, and files failing with an
+/// error the client's [`RetryPolicy`] would retry are sent again with its delays, in place of the
+/// client's own retries so that they don't multiply.
+pub struct TranscriptionJob {
+    model: Option<Model>,
+    format: TranscriptFormat,
+    output_dir: Option<PathBuf>,
+    language: Option<String>,
+    prompt: Option<String>,
+    concurrency: usize,
+    max_retries: u32,
+    skip_existing: bool,
+}
+
+impl Default for TranscriptionJob {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TranscriptionJob {
+    /// Create a job writing plain text transcripts next to the audio files, with the client's
+    /// default audio model, 4 files at a time and 2 retries per file.
+    pub fn new() -> Self {
+        Self {
+            model: None,
+            format: TranscriptFormat::Text,
+            output_dir: None,
+            language: None,
+            prompt: None,
+            concurrency: 4,
+            max_retries: 2,
+            skip_existing: false,
+        }
+    }
+
+    /// Transcribe with `model`.
+    pub fn model(mut self, model: impl Into<Model>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Write transcripts in `format`.
+    pub fn format(mut self, format: TranscriptFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Write transcripts to `output_dir` (created if needed) instead of next to the audio files.
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(output_dir.into());
+        self
+    }
+
+    /// Hint the language of the audio, as an ISO-639-1 code.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Guide every transcription with `prompt`, e.g. with the spelling of names.
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Transcribe at most `concurrency` files at a time.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Send a file failing with a retryable error again up to `max_retries` times.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Don't transcribe files whose transcript already exists, e.g. to resume an interrupted run.
+    pub fn skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    /// Path of the transcript of `input`: its file name followed by the extension of the format
+    /// (e.g. `talk.mp3.txt`), in the output directory or next to it.
+    pub fn output_path(&self, input: &Path) -> PathBuf {
+        let mut path = match &self.output_dir {
+            Some(output_dir) => output_dir.join(input.file_name().unwrap_or_default()),
+            None => input.to_path_buf(),
+        }
+        .into_os_string();
+        path.push(".");
+        path.push(self.format.extension());
+
+        path.into()
+    }
+
+    /// Transcribe every file.
+    ///
+    /// # Arguments
+    ///
+    /// * `openai` - Client used for the transcription requests.
+    /// * `files` - The audio files, e.g. found with [`audio_files`].
+    ///
+    /// # Returns
+    ///
+    /// The [`TranscriptionReport`], with the transcript path or the error of each file. A file
+    /// whose transcript path is the one of an earlier file (e.g. files of the same name in
+    /// different directories written to one output directory) fails with an
+    /// [`OpenAIError::ConfigError`].
+    pub async fn run(&self, openai: &OpenAI, files: &[PathBuf]) -> TranscriptionReport {
+        let mut openai = openai.clone();
+        openai.set_retry_policy(RetryPolicy {
+            max_retries: 0,
+            ..openai.retry_policy().clone()
+        });
+        let openai = &openai;
+
+        let mut outputs = HashMap::new();
+        let earlier: Vec<_> = files
+            .iter()
+            .map(|input| match outputs.entry(self.output_path(input)) {
+                Entry::Occupied(entry) => Some(*entry.get()),
+                Entry::Vacant(entry) => {
+                    entry.insert(input);
+                    None
+                },
+            })
+            .collect();
+
+        let files = stream::iter(files.iter().zip(earlier))
+            .map(|(input, earlier)| async move {
+                let result = match earlier {
+                    Some(earlier) => Err(OpenAIError::ConfigError(format!(
+                        "transcript of {} would overwrite the one of {}",
+                        input.display(),
+                        earlier.display()
+                    ))),
+                    None => self.transcribe(openai, input).await,
+                };
+
+                FileReport {
+                    input: input.clone(),
+                    result,
+                }
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        TranscriptionReport { files }
+    }
+
+    async fn transcribe(&self, openai: &OpenAI, input: &Path) -> OpenAIResult<PathBuf> {
+        let output = self.output_path(input);
+        if self.skip_existing && output.exists() {
+            return Ok(output);
+        }
+
+        let path = input.to_str().ok_or_else(|| {
+            OpenAIError::ConfigError(format!("non UTF-8 audio file path: {}", input.display()))
+        })?;
+        let response_format = match self.format {
+            TranscriptFormat::Text => "json",
+            _ => "verbose_json",
+        };
+
+        let mut attempt = 0;
+        let transcription = loop {
+            if let Some(limits) = openai.rate_limits() {
+                runtime::sleep(limits.wait_for(0)).await;
+            }
+
+            let result = openai
+                .audio()
+                .transcribe(
+                    self.model.clone(),
+                    path,
+                    self.prompt.as_deref(),
+                    Some(response_format),
+                    None,
+                    self.language.as_deref(),
+                )
+                .await;
+
+            match result {
+                Err(error)
+                    if attempt < self.max_retries
+                        && openai.retry_policy().should_retry_failure(&error) =>
+                {
+                    runtime::sleep(openai.retry_policy().delay(attempt)).await;
+                    attempt += 1;
+                },
+                result => break result?,
+            }
+        };
+
+        let transcript = self.format.render(&transcription)?;
+        if let Some(parent) = output.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&output, transcript).await?;
+
+        Ok(output)
+    }
+}
+
+/// Audio files under `directory`, recursively, sorted by path.
+///
+/// # Returns
+///
+/// A Result containing the paths of the files with an extension the transcription endpoint accepts
+/// on success, or an [`io::Error`] if the directory couldn't be read.
+pub fn audio_files(directory: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut directories = vec![directory.as_ref().to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                directories.push(path);
+            } else if path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    AUDIO_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+                })
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn transcripts_keep_the_name_and_extension_of_their_audio_file() {
+        let job = TranscriptionJob::new();
+        assert_eq!(
+            job.output_path(Path::new("talks/day1/keynote.mp3")),
+            Path::new("talks/day1/keynote.mp3.txt")
+        );
+        assert_ne!(
+            job.output_path(Path::new("talks/keynote.mp3")),
+            job.output_path(Path::new("talks/keynote.wav"))
+        );
+
+        let job = TranscriptionJob::new()
+            .format(TranscriptFormat::Srt)
+            .output_dir("subtitles");
+        assert_eq!(
+            job.output_path(Path::new("talks/day1/keynote.mp3")),
+            Path::new("subtitles/keynote.mp3.srt")
+        );
+    }
+
+    #[test]
+    fn timestamps_roll_over_into_minutes_and_hours() {
+        assert_eq!(timestamp(0.0, ','), "00:00:00,000");
+        assert_eq!(timestamp(59.9996, ','), "00:01:00,000");
+        assert_eq!(timestamp(3_723.25, ','), "01:02:03,250");
+        assert_eq!(timestamp(3_723.25, '.'), "01:02:03.250");
+        assert_eq!(timestamp(-1.0, '.'), "00:00:00.000");
+    }
+
+    fn transcription() -> Value {
+        json!({
+            "text": " Hello there. General Kenobi. ",
+            "segments": [
+                { "start": 0.0, "end": 1.5, "text": " Hello there." },
+                { "start": 1.5, "end": 3661.0, "text": " General Kenobi." },
+            ],
+        })
+    }
+
+    #[test]
+    fn subtitles_number_their_cues() {
+        assert_eq!(
+            TranscriptFormat::Srt.render(&transcription()).unwrap(),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n\
+             2\n00:00:01,500 --> 01:01:01,000\nGeneral Kenobi.\n\n"
+        );
+        assert_eq!(
+            TranscriptFormat::Vtt.render(&transcription()).unwrap(),
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:01.500\nHello there.\n\n\
+             00:00:01.500 --> 01:01:01.000\nGeneral Kenobi.\n\n"
+        );
+        assert_eq!(
+            TranscriptFormat::Text.render(&transcription()).unwrap(),
+            "Hello there. General Kenobi.\n"
+        );
+    }
+
+    #[test]
+    fn subtitles_need_segments() {
+        let transcription = json!({ "text": "Hello there." });
+
+        for format in [TranscriptFormat::Srt, TranscriptFormat::Vtt] {
+            assert!(matches!(
+                format.render(&transcription),
+                Err(OpenAIError::UnexpectedResponse(_))
+            ));
+        }
+        assert_eq!(
+            TranscriptFormat::Text.render(&transcription).unwrap(),
+            "Hello there.\n"
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    mod job {
+        use super::*;
+        use crate::test_util::mock_transport::{MockResponse, MockTransport};
+        use reqwest::Method;
+        use std::sync::Arc;
+
+        /// A fresh directory with an empty audio file at each of `files`.
+        fn audio(name: &str, files: &[&str]) -> (PathBuf, Vec<PathBuf>) {
+            let directory = std::env::temp_dir().join(format!(
+                "rusty-openai-transcription-{name}-{}",
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&directory);
+
+            let files = files
+                .iter()
+                .map(|file| {
+                    let path = directory.join(file);
+                    fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    fs::write(&path, b"audio").unwrap();
+                    path
+                })
+                .collect();
+
+            (directory, files)
+        }
+
+        fn client(mock: &Arc<MockTransport>) -> OpenAI {
+            let mut openai = mock.client();
+            openai.set_retry_policy(RetryPolicy {
+                max_retries: 3,
+                base_delay_ms: 1,
+                max_delay_ms: 1,
+                ..RetryPolicy::default()
+            });
+            openai
+        }
+
+        #[tokio::test]
+        async fn files_writing_the_same_transcript_fail() {
+            let (directory, files) = audio("collisions", &["a/talk.mp3", "b/talk.mp3", "talk.wav"]);
+            let mock = Arc::new(MockTransport::new());
+            mock.on(
+                Method::POST,
+                "/audio/transcriptions",
+                MockResponse::json(&json!({ "text": "Hello" })),
+            );
+
+            let report = TranscriptionJob::new()
+                .model("whisper-1")
+                .output_dir(directory.join("transcripts"))
+                .run(&client(&mock), &files)
+                .await;
+
+            assert_eq!(report.succeeded(), 2);
+            assert!(matches!(
+                &report.files[1].result,
+                Err(OpenAIError::ConfigError(message)) if message.contains("a/talk.mp3")
+            ));
+            assert_eq!(mock.requests().len(), 2);
+            assert_eq!(
+                fs::read_to_string(directory.join("transcripts/talk.wav.txt")).unwrap(),
+                "Hello\n"
+            );
+
+            fs::remove_dir_all(directory).unwrap();
+        }
+
+        #[tokio::test]
+        async fn only_retryable_errors_are_retried_once_per_attempt() {
+            let (directory, files) = audio("retries", &["bad.mp3", "busy.mp3"]);
+            let mock = Arc::new(MockTransport::new());
+            mock.once(
+                Method::POST,
+                "/audio/transcriptions",
+                MockResponse::error(400, "Invalid file format"),
+            )
+            .on(
+                Method::POST,
+                "/audio/transcriptions",
+                MockResponse::error(503, "Overloaded"),
+            );
+
+            let report = TranscriptionJob::new()
+                .model("whisper-1")
+                .concurrency(1)
+                .max_retries(1)
+                .run(&client(&mock), &files)
+                .await;
+
+            let statuses: Vec<_> = report
+                .files
+                .iter()
+                .map(|file| match &file.result {
+                    Err(OpenAIError::ApiError { status, .. }) => *status,
+                    result => panic!("unexpected result {result:?}"),
+                })
+                .collect();
+            assert_eq!(statuses, [400, 503]);
+            // One request for the invalid file, two for the overloaded one, none by the client.
+            assert_eq!(mock.requests().len(), 3);
+
+            fs::remove_dir_all(directory).unwrap();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error_handling::OpenAIError;
+use crate::error_handling::OpenAIResult;
+use reqwest::Client;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::{header::HeaderMap, StatusCode};
+
+// Types of the transport interface, re-exported for implementations.
+pub use reqwest::{Body, Request, Response};
+
+/// Boxed future returned by the extension traits of the crate ([`HttpTransport`],
+/// [`ApiKeyProvider`][crate::credentials::ApiKeyProvider]), `Send` except on `wasm32`, where the
+/// futures of the JavaScript `fetch` API are not.
+#[cfg(not(target_arch = "wasm32"))]
+pub type BoxFuture<'a, T> = futures::future::BoxFuture<'a, T>;
+/// Boxed future returned by the extension traits of the crate ([`HttpTransport`],
+/// [`ApiKeyProvider`][crate::credentials::ApiKeyProvider]), `Send` except on `wasm32`, where the
+/// futures of the JavaScript `fetch` API are not.
+#[cfg(target_arch = "wasm32")]
+pub type BoxFuture<'a, T> = futures::future::LocalBoxFuture<'a, T>;
+
+/// [`Send`] except on `wasm32`, where it is implemented by every type: the bound of the futures
+/// of the crate's traits, e.g. [`Retriever`][crate::rag::Retriever].
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+/// [`Send`] except on `wasm32`, where it is implemented by every type: the bound of the futures
+/// of the crate's traits, e.g. [`Retriever`][crate::rag::Retriever].
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// Sender of the HTTP requests of an [`OpenAI`][crate::openai::OpenAI] client, replacing the
+/// network calls of its reqwest client, e.g. to route them over Unix domain sockets, another HTTP
+/// stack or an RPC layer.
+///
+/// Requests are fully built (URL, authentication and headers, body) before they reach the
+/// transport, and everything after (retries, rate limits, decoding) applies to the responses it
+/// returns, so the typed API works unchanged.
+pub trait HttpTransport: Send + Sync {
+    /// Send `request`, returning the response once its headers are received, the body being read
+    /// or streamed afterwards.
+    fn send(&self, request: Request) -> BoxFuture<'_, OpenAIResult<Response>>;
+}
+
+/// The default transport, sending requests over the network.
+impl HttpTransport for Client {
+    fn send(&self, request: Request) -> BoxFuture<'_, OpenAIResult<Response>> {
+        Box::pin(async move { Ok(self.execute(request).await?) })
+    }
+}
+
+/// Build the response of a custom [`HttpTransport`] from its parts, the body being bytes or a
+/// stream (see [`Body::wrap_stream`]).
+///
+/// Not available on `wasm32`, where responses can only come from `fetch`.
+///
+/// # Returns
+///
+/// A Result containing the response on success, or an [`OpenAIError::UnexpectedResponse`] if a header is invalid.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn response(
+    status: StatusCode,
+    headers: HeaderMap,
+    body: impl Into<Body>,
+) -> OpenAIResult<Response> {
+    let mut builder = http::Response::builder().status(status);
+    if let Some(response_headers) = builder.headers_mut() {
+        response_headers.extend(headers);
+    }
+
+    builder
+        .body(body.into())
+        .map(Response::from)
+        .map_err(|error| OpenAIError::UnexpectedResponse(error.to_string()))
+}
@@ -1,3 +1,31 @@
+use crate::error_handling::{OpenAIError, OpenAIResult};
+use reqwest::Request;
+#[cfg(feature = "fs")]
+use reqwest::{multipart::Part, Body, Response};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+#[cfg(feature = "fs")]
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+};
+#[cfg(feature = "fs")]
+use tokio_util::io::ReaderStream;
+
+/// Headers carrying credentials, whose values are never logged nor shown.
+pub(crate) const SECRET_HEADERS: [&str; 5] = [
+    "authorization",
+    "proxy-authorization",
+    "api-key",
+    "x-api-key",
+    "ocp-apim-subscription-key",
+];
+
+/// Default size of the buffer used to stream uploads and downloads.
+pub(crate) const DEFAULT_STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
 #[macro_export]
 macro_rules! extend_url_params {
     ($url:ident, $($param:ident),*) => {
@@ -20,6 +48,8 @@ macro_rules! extend_form_text_fields {
     };
 }
 
+/// Generate fluent setters of `Option` fields, e.g. in the `impl` of a request type.
+#[deprecated(note = "request types now come with builders generated by request_builder!")]
 #[macro_export]
 macro_rules! setters {
     ($(
@@ -36,3 +66,412 @@ macro_rules! setters {
         )*
     };
 }
+
+/// Generate the builder of a request type and the fluent setters of its optional fields.
+///
+/// Required fields are plain fields of the request and optional ones `Option`s, and the request
+/// has an `extra` map (see [`extra_fields!`][crate::extra_fields]). Setters take anything converting into the type of
+/// the field, and `build()` fails with an
+/// [`OpenAIError::ConfigError`][crate::error_handling::OpenAIError::ConfigError] listing the missing
+/// required fields, or with the error of the `validate` function if one is given.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! request_builder {
+    (
+        $(#[$builder_attributes:meta])*
+        $request:ident => $builder:ident {
+            required {$(
+                $(#[$required_attributes:meta])*
+                $required:ident: $required_type:ty,
+            )*}
+            optional {$(
+                $(#[$optional_attributes:meta])*
+                $optional:ident: $optional_type:ty,
+            )*}
+            $(validate: $validate:expr,)?
+        }
+    ) => {
+        $(#[$builder_attributes])*
+        #[derive(Clone, Default)]
+        pub struct $builder {
+            $($required: Option<$required_type>,)*
+            $($optional: Option<$optional_type>,)*
+            extra: serde_json::Map<String, serde_json::Value>,
+        }
+
+        impl $request {
+            #[doc = concat!("Start building a [`", stringify!($request), "`].")]
+            #[inline(always)]
+            pub fn builder() -> $builder {
+                $builder::default()
+            }
+
+            $(
+                $(#[$optional_attributes])*
+                #[inline(always)]
+                pub fn $optional(mut self, $optional: impl Into<$optional_type>) -> Self {
+                    self.$optional = Some($optional.into());
+                    self
+                }
+            )*
+
+            $crate::extra_fields!();
+        }
+
+        impl $builder {
+            $(
+                $(#[$required_attributes])*
+                #[inline(always)]
+                pub fn $required(mut self, $required: impl Into<$required_type>) -> Self {
+                    self.$required = Some($required.into());
+                    self
+                }
+            )*
+
+            $(
+                $(#[$optional_attributes])*
+                #[inline(always)]
+                pub fn $optional(mut self, $optional: impl Into<$optional_type>) -> Self {
+                    self.$optional = Some($optional.into());
+                    self
+                }
+            )*
+
+            $crate::extra_fields!();
+
+            #[doc = concat!("Build the [`", stringify!($request), "`], checking that the required fields are set and the values are valid.")]
+            pub fn build(self) -> $crate::error_handling::OpenAIResult<$request> {
+                #[allow(unused_mut)]
+                let mut missing: Vec<&str> = Vec::new();
+                $(
+                    if self.$required.is_none() {
+                        missing.push(stringify!($required));
+                    }
+                )*
+                if !missing.is_empty() {
+                    return Err($crate::error_handling::OpenAIError::ConfigError(format!(
+                        "missing required fields of {}: {}",
+                        stringify!($request),
+                        missing.join(", "),
+                    )));
+                }
+
+                let request = $request {
+                    $($required: self.$required.expect("required field checked above"),)*
+                    $($optional: self.$optional,)*
+                    extra: self.extra,
+                };
+                $(($validate)(&request)?;)?
+
+                Ok(request)
+            }
+        }
+    };
+}
+
+/// Check that the parameter `name` of a request, if set, lies within `range`.
+pub(crate) fn check_range(
+    name: &str,
+    value: Option<f64>,
+    range: std::ops::RangeInclusive<f64>,
+) -> OpenAIResult<()> {
+    match value {
+        Some(value) if !range.contains(&value) => Err(OpenAIError::ConfigError(format!(
+            "{name} must be between {} and {}, got {value}",
+            range.start(),
+            range.end()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Generate the `extra` method of a type with an `extra` map of fields sent as is.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! extra_fields {
+    () => {
+        /// Add a field sent as is with the request, e.g. a parameter specific to a provider or
+        /// gateway that the request doesn't declare.
+        #[inline(always)]
+        pub fn extra(
+            mut self,
+            name: impl Into<String>,
+            value: impl Into<serde_json::Value>,
+        ) -> Self {
+            self.extra.insert(name.into(), value.into());
+            self
+        }
+    };
+}
+
+/// Implement `Deserialize` for a response type, collecting the fields it doesn't declare into its
+/// `extra` map.
+///
+/// Unlike `#[serde(flatten)]`, the declared fields are decoded straight from the input instead of
+/// being buffered first, so large payloads stay cheap and unknown fields of nested typed values are
+/// still seen by [`UnknownFields`][crate::validation::UnknownFields]. Fields marked `#[default]`
+/// may be missing, and `as Wrapper` decodes a field through a newtype wrapping its type.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! deserialize_with_extra {
+    (
+        $response:ident $(<$parameter:ident>)? {
+            $($(#[$default:ident])? $field:ident: $field_type:ty $(as $wrapper:ty)?,)*
+        }
+    ) => {
+        impl<'de, $($parameter: serde::Deserialize<'de>)?> serde::Deserialize<'de>
+            for $response $(<$parameter>)?
+        {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct ResponseVisitor $(<$parameter>)? (std::marker::PhantomData<($($parameter,)?)>);
+
+                impl<'de, $($parameter: serde::Deserialize<'de>)?> serde::de::Visitor<'de>
+                    for ResponseVisitor $(<$parameter>)?
+                {
+                    type Value = $response $(<$parameter>)?;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str(concat!("a ", stringify!($response), " object"))
+                    }
+
+                    fn visit_map<A: serde::de::MapAccess<'de>>(
+                        self,
+                        mut map: A,
+                    ) -> Result<Self::Value, A::Error> {
+                        $(let mut $field: Option<$field_type> = None;)*
+                        let mut extra = serde_json::Map::new();
+
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                $(stringify!($field) => {
+                                    $field = Some($crate::deserialize_with_extra!(
+                                        @read map $($wrapper)?
+                                    ));
+                                },)*
+                                _ => {
+                                    extra.insert(key, map.next_value()?);
+                                },
+                            }
+                        }
+
+                        Ok($response {
+                            $($field: match $field {
+                                Some(value) => value,
+                                None => $crate::deserialize_with_extra!(
+                                    @missing $field $($default)?
+                                ),
+                            },)*
+                            extra,
+                        })
+                    }
+                }
+
+                deserializer.deserialize_map(ResponseVisitor(std::marker::PhantomData))
+            }
+        }
+    };
+    (@read $map:ident) => {
+        $map.next_value()?
+    };
+    (@read $map:ident $wrapper:ty) => {
+        $map.next_value::<$wrapper>()?.0
+    };
+    (@missing $field:ident default) => {
+        Default::default()
+    };
+    (@missing $field:ident) => {
+        return Err(serde::de::Error::missing_field(stringify!($field)))
+    };
+}
+
+/// Decode standard base64, padded or not.
+pub(crate) fn decode_base64(encoded: &str) -> Result<Vec<u8>, &'static str> {
+    let encoded = strip_base64_padding(encoded)?;
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+
+    for &character in encoded {
+        let sextet = base64_sextet(character)?;
+        bits = (bits << 6) | u32::from(sextet);
+        bit_count += 6;
+
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// The base64 characters of `encoded` without its padding, failing if the padding or the length
+/// is invalid.
+pub(crate) fn strip_base64_padding(encoded: &str) -> Result<&[u8], &'static str> {
+    let unpadded = encoded.trim_end_matches('=');
+    let padding = encoded.len() - unpadded.len();
+
+    if padding > 2 || (padding > 0 && !encoded.len().is_multiple_of(4)) || unpadded.len() % 4 == 1 {
+        return Err("invalid base64 length");
+    }
+
+    Ok(unpadded.as_bytes())
+}
+
+/// Value of a character of the standard base64 alphabet.
+pub(crate) fn base64_sextet(character: u8) -> Result<u8, &'static str> {
+    match character {
+        b'A'..=b'Z' => Ok(character - b'A'),
+        b'a'..=b'z' => Ok(character - b'a' + 26),
+        b'0'..=b'9' => Ok(character - b'0' + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err("invalid character in base64"),
+    }
+}
+
+/// Key identifying what a built request asks for: its method, full URL (base URL and Azure
+/// deployment included), headers (credentials, organization, project, beta features and call
+/// headers included) and body, so clients of different tenants or settings never share responses.
+///
+/// The `Idempotency-Key` header, unique to a call, is left out.
+pub(crate) fn request_key(request: &Request) -> String {
+    let mut headers: Vec<(&str, &[u8])> = request
+        .headers()
+        .iter()
+        .filter(|(name, _)| name.as_str() != "idempotency-key")
+        .map(|(name, value)| (name.as_str(), value.as_bytes()))
+        .collect();
+    headers.sort();
+
+    let body = request.body().and_then(|body| body.as_bytes());
+    let mut parts = vec![
+        request.method().as_str().as_bytes(),
+        request.url().as_str().as_bytes(),
+        body.unwrap_or_default(),
+    ];
+    for (name, value) in &headers {
+        parts.push(name.as_bytes());
+        parts.push(value);
+    }
+
+    stable_hash(&parts)
+}
+
+/// Stable 128-bit FNV-1a hash, rendered as hex.
+pub(crate) fn stable_hash(parts: &[&[u8]]) -> String {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    let hash = parts.iter().fold(OFFSET_BASIS, |hash, part| {
+        // Hash the length first so ("ab", "c") and ("a", "bc") differ.
+        (part.len() as u64)
+            .to_le_bytes()
+            .iter()
+            .chain(part.iter())
+            .fold(hash, |hash, byte| {
+                (hash ^ *byte as u128).wrapping_mul(PRIME)
+            })
+    });
+
+    format!("{hash:032x}")
+}
+
+/// A random number between 0 and 1, e.g. to spread retries; not suitable for cryptography.
+pub(crate) fn random_fraction() -> f64 {
+    // Every `RandomState` is seeded with new random keys.
+    let bits = RandomState::new().build_hasher().finish();
+
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Strip the base URL from a request URL.
+pub(crate) fn relative_path(base_url: &str, url: &str) -> String {
+    url.strip_prefix(base_url.trim_end_matches('/'))
+        .unwrap_or(url)
+        .to_string()
+}
+
+/// Multipart file part streaming the file at `path` from disk in chunks of `chunk_size` bytes
+/// instead of reading it into memory.
+#[cfg(feature = "fs")]
+pub(crate) async fn file_part(path: &str, mime: &str, chunk_size: usize) -> OpenAIResult<Part> {
+    let file = File::open(path).await?;
+    let length = file.metadata().await?.len();
+    let body = Body::wrap_stream(ReaderStream::with_capacity(file, chunk_size));
+
+    Ok(Part::stream_with_length(body, length)
+        .file_name(path.to_string())
+        .mime_str(mime)?)
+}
+
+/// Write the body of `response` to the file at `path` as it arrives, buffering at most `chunk_size`
+/// bytes, and return the number of bytes written.
+#[cfg(feature = "fs")]
+pub(crate) async fn write_body(
+    mut response: Response,
+    path: &str,
+    chunk_size: usize,
+) -> OpenAIResult<u64> {
+    let mut file = BufWriter::with_capacity(chunk_size, File::create(path).await?);
+    let mut written = 0;
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await?;
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Options {
+        user: Option<String>,
+    }
+
+    #[allow(deprecated)]
+    impl Options {
+        crate::setters! {
+            user: String,
+        }
+    }
+
+    #[test]
+    fn deprecated_setters_still_set_fields() {
+        let options = Options::default().user("user-1".to_string());
+        assert_eq!(options.user.as_deref(), Some("user-1"));
+    }
+
+    #[test]
+    fn decode_base64_accepts_padded_and_unpadded_input() {
+        assert_eq!(decode_base64("aGk=").unwrap(), b"hi");
+        assert_eq!(decode_base64("aGk").unwrap(), b"hi");
+        assert_eq!(decode_base64("aA==").unwrap(), b"h");
+        assert_eq!(decode_base64("aA").unwrap(), b"h");
+        assert_eq!(decode_base64("aGV5").unwrap(), b"hey");
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("+/8=").unwrap(), [0xfb, 0xff]);
+    }
+
+    #[test]
+    fn decode_base64_rejects_invalid_characters() {
+        assert!(decode_base64("aG k").is_err());
+        assert!(decode_base64("aG-k").is_err());
+        assert!(decode_base64("aA==aA==").is_err());
+    }
+
+    #[test]
+    fn decode_base64_rejects_invalid_padding_and_lengths() {
+        assert!(decode_base64("aGk==").is_err());
+        assert!(decode_base64("aA===").is_err());
+        assert!(decode_base64("aA=").is_err());
+        assert!(decode_base64("aGV5a").is_err());
+        assert!(decode_base64("=").is_err());
+    }
+}
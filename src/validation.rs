@@ -0,0 +1,65 @@
+use crate::error_handling::{OpenAIError, OpenAIResult};
+use serde::de::DeserializeOwned;
+use std::{fmt, sync::Arc};
+
+/// Callback receiving the request URL and the paths of the unknown fields of its response.
+pub type UnknownFieldsReport = Arc<dyn Fn(&str, &[String]) + Send + Sync>;
+
+/// What to do with response fields the requested type doesn't declare.
+///
+/// Responses decoded as [`serde_json::Value`] keep every field, so this only matters for typed
+/// responses, e.g. `openai.get::<MyModel>(...)`. Checking for unknown fields shows when the API
+/// adds or renames fields that would otherwise be dropped silently.
+#[derive(Clone, Default)]
+pub enum UnknownFields {
+    /// Silently drop unknown fields
+    #[default]
+    Ignore,
+
+    /// Fail with an [`OpenAIError::UnexpectedResponse`] listing the unknown fields
+    Deny,
+
+    /// Call the function with the request URL and the path of every unknown field (e.g.
+    /// `choices.0.message.annotations`), then decode the response as usual
+    Report(UnknownFieldsReport),
+}
+
+impl fmt::Debug for UnknownFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ignore => f.write_str("Ignore"),
+            Self::Deny => f.write_str("Deny"),
+            Self::Report(_) => f.write_str("Report(..)"),
+        }
+    }
+}
+
+impl UnknownFields {
+    /// Decode a JSON response body received from `url`, applying the policy.
+    pub(crate) fn decode<T: DeserializeOwned>(&self, url: &str, body: &[u8]) -> OpenAIResult<T> {
+        if let Self::Ignore = self {
+            return Ok(serde_json::from_slice(body)?);
+        }
+
+        let mut unknown = Vec::new();
+        let mut deserializer = serde_json::Deserializer::from_slice(body);
+        let value =
+            serde_ignored::deserialize(&mut deserializer, |path| unknown.push(path.to_string()))?;
+        deserializer.end()?;
+
+        if !unknown.is_empty() {
+            match self {
+                Self::Ignore => {},
+                Self::Deny => {
+                    return Err(OpenAIError::UnexpectedResponse(format!(
+                        "unknown fields in response from {url}: {}",
+                        unknown.join(", ")
+                    )))
+                },
+                Self::Report(report) => report(url, &unknown),
+            }
+        }
+
+        Ok(value)
+    }
+}
@@ -0,0 +1,713 @@
+use crate::{
+    error_handling::{OpenAIError, OpenAIResult},
+    openai_api::embeddings::EmbeddingsResponse,
+};
+#[cfg(feature = "fs")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::{fs, path::Path};
+
+/// A vector stored in a [`VectorIndex`], with the payload returned by searches.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct IndexEntry<T> {
+    /// ID of the entry, unique within the index
+    pub id: String,
+
+    /// The vector, normalized to unit length
+    pub vector: Vec<f32>,
+
+    /// Data attached to the vector (source text, metadata, ...)
+    pub payload: T,
+}
+
+/// A result of a [`VectorIndex`] search.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchHit<'a, T> {
+    /// ID of the matching entry
+    pub id: &'a str,
+
+    /// Cosine similarity between the query and the entry, between -1 and 1
+    pub score: f32,
+
+    /// Payload of the matching entry
+    pub payload: &'a T,
+}
+
+/// In-memory nearest-neighbor index of embeddings, saved to and loaded from JSON files, for
+/// prototypes that don't need an external vector database.
+///
+/// Vectors are compared by cosine similarity. [`VectorIndex::search`] compares the query against
+/// every vector. For larger indexes, [`VectorIndex::build_clusters`] partitions the vectors with
+/// k-means so that [`VectorIndex::search_approximate`] only scans the clusters closest to the
+/// query.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(
+    bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"),
+    from = "StoredIndex<T>"
+)]
+pub struct VectorIndex<T = Value> {
+    dimensions: Option<usize>,
+    entries: Vec<IndexEntry<T>>,
+    #[serde(default)]
+    centroids: Vec<Vec<f32>>,
+    #[serde(default)]
+    clusters: Vec<Vec<usize>>,
+    /// Position of each entry in `entries`, by ID
+    #[serde(skip)]
+    positions: HashMap<String, usize>,
+}
+
+/// A [`VectorIndex`] as saved, its positions being rebuilt on load.
+#[derive(Deserialize)]
+struct StoredIndex<T> {
+    dimensions: Option<usize>,
+    entries: Vec<IndexEntry<T>>,
+    #[serde(default)]
+    centroids: Vec<Vec<f32>>,
+    #[serde(default)]
+    clusters: Vec<Vec<usize>>,
+}
+
+impl<T> From<StoredIndex<T>> for VectorIndex<T> {
+    fn from(stored: StoredIndex<T>) -> Self {
+        let positions = stored
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(position, entry)| (entry.id.clone(), position))
+            .collect();
+
+        Self {
+            dimensions: stored.dimensions,
+            entries: stored.entries,
+            centroids: stored.centroids,
+            clusters: stored.clusters,
+            positions,
+        }
+    }
+}
+
+impl<T> Default for VectorIndex<T> {
+    fn default() -> Self {
+        Self {
+            dimensions: None,
+            entries: Vec::new(),
+            centroids: Vec::new(),
+            clusters: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl<T> VectorIndex<T> {
+    /// Create an empty index, its dimensions being set by the first vector added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the index has no entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of dimensions of the vectors, `None` until a vector is added.
+    pub const fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    /// The entries, in insertion order.
+    pub fn entries(&self) -> &[IndexEntry<T>] {
+        &self.entries
+    }
+
+    /// The entry with ID `id`, if any.
+    pub fn get(&self, id: &str) -> Option<&IndexEntry<T>> {
+        self.positions
+            .get(id)
+            .map(|&position| &self.entries[position])
+    }
+
+    /// Add a vector, replacing the entry with the same ID if any, which keeps its position.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the entry.
+    /// * `vector` - The embedding.
+    /// * `payload` - Data returned with the entry by searches.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing nothing on success, or an [`OpenAIError::ConfigError`] if the vector
+    /// doesn't have the dimensions of the index.
+    pub fn add(&mut self, id: impl Into<String>, vector: Vec<f32>, payload: T) -> OpenAIResult<()> {
+        let id = id.into();
+        let dimensions = self.dimensions.unwrap_or(vector.len());
+        if vector.is_empty() || vector.len() != dimensions {
+            return Err(OpenAIError::ConfigError(format!(
+                "vector of {} dimensions added to an index of {dimensions} dimensions",
+                vector.len()
+            )));
+        }
+
+        self.dimensions = Some(dimensions);
+
+        let vector = normalize(vector);
+        let position = match self.positions.get(&id) {
+            Some(&position) => {
+                for cluster in &mut self.clusters {
+                    cluster.retain(|&clustered| clustered != position);
+                }
+                position
+            },
+            None => self.entries.len(),
+        };
+        if !self.centroids.is_empty() {
+            let cluster = nearest_centroid(&self.centroids, &vector);
+            self.clusters[cluster].push(position);
+        }
+
+        let entry = IndexEntry {
+            id,
+            vector,
+            payload,
+        };
+        if position == self.entries.len() {
+            self.positions.insert(entry.id.clone(), position);
+            self.entries.push(entry);
+        } else {
+            self.entries[position] = entry;
+        }
+
+        Ok(())
+    }
+
+    /// Add the embeddings of an [`EmbeddingsResponse`], the `n`-th one with the `n`-th ID and
+    /// payload of `entries`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing nothing on success, or an [`OpenAIError`] if the number of embeddings
+    /// doesn't match the number of entries or if their dimensions don't match the index.
+    pub fn add_embeddings(
+        &mut self,
+        mut response: EmbeddingsResponse,
+        entries: impl IntoIterator<Item = (String, T)>,
+    ) -> OpenAIResult<()> {
+        let entries: Vec<(String, T)> = entries.into_iter().collect();
+        if entries.len() != response.data.len() {
+            return Err(OpenAIError::UnexpectedResponse(format!(
+                "expected {} embeddings, got {}",
+                entries.len(),
+                response.data.len()
+            )));
+        }
+
+        response.data.sort_by_key(|embedding| embedding.index);
+        for ((id, payload), vector) in entries.into_iter().zip(response.into_vectors()) {
+            self.add(id, vector, payload)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the entry with ID `id`, returning its payload.
+    ///
+    /// Removing an entry discards the clusters, approximate searches being exact until
+    /// [`VectorIndex::build_clusters`] is called again.
+    pub fn remove(&mut self, id: &str) -> Option<T> {
+        let position = self.positions.remove(id)?;
+        self.centroids.clear();
+        self.clusters.clear();
+
+        let entry = self.entries.remove(position);
+        for (position, entry) in self.entries.iter().enumerate().skip(position) {
+            self.positions.insert(entry.id.clone(), position);
+        }
+
+        Some(entry.payload)
+    }
+
+    /// Return the `k` entries most similar to `query`, most similar first, comparing `query`
+    /// against every entry.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the hits on success, or an [`OpenAIError::ConfigError`] if the query
+    /// doesn't have the dimensions of the index.
+    pub fn search(&self, query: &[f32], k: usize) -> OpenAIResult<Vec<SearchHit<'_, T>>> {
+        self.check_query(query)?;
+        let query = normalize(query.to_vec());
+
+        Ok(top_k(&query, 0..self.entries.len(), &self.entries, k))
+    }
+
+    /// Return (approximately) the `k` entries most similar to `query`, most similar first,
+    /// scanning only the `probes` clusters closest to `query`.
+    ///
+    /// Without clusters, the search is exact.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the hits on success, or an [`OpenAIError::ConfigError`] if the query
+    /// doesn't have the dimensions of the index.
+    pub fn search_approximate(
+        &self,
+        query: &[f32],
+        k: usize,
+        probes: usize,
+    ) -> OpenAIResult<Vec<SearchHit<'_, T>>> {
+        if self.centroids.is_empty() {
+            return self.search(query, k);
+        }
+
+        self.check_query(query)?;
+        let query = normalize(query.to_vec());
+        let mut centroids: Vec<(f32, usize)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(cluster, centroid)| (dot(&query, centroid), cluster))
+            .collect();
+        centroids.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let candidates = centroids
+            .into_iter()
+            .take(probes.max(1))
+            .flat_map(|(_, cluster)| self.clusters[cluster].iter().copied());
+
+        Ok(top_k(&query, candidates, &self.entries, k))
+    }
+
+    /// Fail if `query` doesn't have the dimensions of the vectors, which would otherwise be
+    /// compared on their common prefix only.
+    fn check_query(&self, query: &[f32]) -> OpenAIResult<()> {
+        match self.dimensions {
+            Some(dimensions) if query.len() != dimensions => {
+                Err(OpenAIError::ConfigError(format!(
+                    "query of {} dimensions searched in an index of {dimensions} dimensions",
+                    query.len()
+                )))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the consistency of an index read from a file: the dimensions of its vectors and
+    /// centroids, and clusters holding every entry exactly once.
+    #[cfg(feature = "fs")]
+    fn validate(&self) -> OpenAIResult<()> {
+        let invalid = |message: String| Err(OpenAIError::ConfigError(message));
+
+        let dimensions = match self.dimensions {
+            Some(dimensions) => dimensions,
+            None if self.entries.is_empty()
+                && self.centroids.is_empty()
+                && self.clusters.is_empty() =>
+            {
+                return Ok(())
+            },
+            None => return invalid("index without dimensions has vectors".to_string()),
+        };
+        if self.positions.len() != self.entries.len() {
+            return invalid("several entries have the same ID".to_string());
+        }
+        if let Some(entry) = self
+            .entries
+            .iter()
+            .find(|entry| entry.vector.len() != dimensions)
+        {
+            return invalid(format!(
+                "entry {:?} has {} dimensions in an index of {dimensions} dimensions",
+                entry.id,
+                entry.vector.len()
+            ));
+        }
+
+        if self.centroids.len() != self.clusters.len() {
+            return invalid(format!(
+                "{} centroids for {} clusters",
+                self.centroids.len(),
+                self.clusters.len()
+            ));
+        }
+        if self
+            .centroids
+            .iter()
+            .any(|centroid| centroid.len() != dimensions)
+        {
+            return invalid(format!(
+                "centroid dimensions don't match the {dimensions} dimensions of the index"
+            ));
+        }
+
+        if !self.clusters.is_empty() {
+            let mut clustered = vec![false; self.entries.len()];
+            for &position in self.clusters.iter().flatten() {
+                match clustered.get_mut(position) {
+                    Some(seen) if !*seen => *seen = true,
+                    Some(_) => return invalid(format!("entry {position} is in several clusters")),
+                    None => {
+                        return invalid(format!(
+                            "cluster entry {position} out of the {} entries",
+                            self.entries.len()
+                        ))
+                    },
+                }
+            }
+            if let Some(position) = clustered.iter().position(|seen| !seen) {
+                return invalid(format!("entry {position} is in no cluster"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Partition the entries into `count` clusters with `iterations` rounds of k-means, for
+    /// [`VectorIndex::search_approximate`].
+    ///
+    /// Entries added afterwards join their closest cluster.
+    pub fn build_clusters(&mut self, count: usize, iterations: usize) {
+        let count = count.min(self.entries.len());
+        if count == 0 {
+            self.centroids.clear();
+            self.clusters.clear();
+            return;
+        }
+
+        // Deterministic seeding with entries spread over the index.
+        let mut centroids: Vec<Vec<f32>> = (0..count)
+            .map(|cluster| {
+                self.entries[cluster * self.entries.len() / count]
+                    .vector
+                    .clone()
+            })
+            .collect();
+        let mut clusters = Vec::new();
+
+        for iteration in 0..=iterations {
+            clusters = vec![Vec::new(); count];
+            for (position, entry) in self.entries.iter().enumerate() {
+                clusters[nearest_centroid(&centroids, &entry.vector)].push(position);
+            }
+
+            if iteration == iterations {
+                break;
+            }
+
+            for (centroid, members) in centroids.iter_mut().zip(&clusters) {
+                // Empty clusters keep their centroid.
+                if members.is_empty() {
+                    continue;
+                }
+
+                let mut sum = vec![0.0; centroid.len()];
+                for &position in members {
+                    for (total, value) in sum.iter_mut().zip(&self.entries[position].vector) {
+                        *total += value;
+                    }
+                }
+                *centroid = normalize(sum);
+            }
+        }
+
+        self.centroids = centroids;
+        self.clusters = clusters;
+    }
+}
+
+#[cfg(feature = "fs")]
+impl<T: Serialize> VectorIndex<T> {
+    /// Write the index, clusters included, to a JSON file at `path`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing nothing on success, or an [`OpenAIError`] on failure.
+    pub fn save(&self, path: impl AsRef<Path>) -> OpenAIResult<()> {
+        // Write to a temporary file first so a crash can't leave a truncated index.
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(temporary, path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl<T: DeserializeOwned> VectorIndex<T> {
+    /// Read an index written by [`VectorIndex::save`].
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the index on success, or an [`OpenAIError`] on failure, an
+    /// [`OpenAIError::ConfigError`] if the file holds an inconsistent index (vectors of different
+    /// dimensions, clusters referring to missing entries).
+    pub fn load(path: impl AsRef<Path>) -> OpenAIResult<Self> {
+        let index: Self = serde_json::from_slice(&fs::read(path)?)?;
+        index.validate()?;
+
+        Ok(index)
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Scale `vector` to unit length, zero vectors being left as is.
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+
+    vector
+}
+
+fn nearest_centroid(centroids: &[Vec<f32>], vector: &[f32]) -> usize {
+    centroids
+        .iter()
+        .map(|centroid| dot(vector, centroid))
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(cluster, _)| cluster)
+}
+
+fn top_k<'a, T>(
+    query: &[f32],
+    candidates: impl Iterator<Item = usize>,
+    entries: &'a [IndexEntry<T>],
+    k: usize,
+) -> Vec<SearchHit<'a, T>> {
+    let mut hits: Vec<SearchHit<'a, T>> = candidates
+        .map(|position| {
+            let entry = &entries[position];
+            SearchHit {
+                id: &entry.id,
+                score: dot(query, &entry.vector),
+                payload: &entry.payload,
+            }
+        })
+        .collect();
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(k);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two well-separated groups of vectors, around the `x` and `y` axes.
+    fn index() -> VectorIndex<u32> {
+        let mut index = VectorIndex::new();
+        let vectors = [
+            ("x1", [1.0, 0.1, 0.0]),
+            ("y1", [0.1, 1.0, 0.0]),
+            ("x2", [1.0, 0.2, 0.0]),
+            ("y2", [0.0, 1.0, 0.2]),
+            ("x3", [0.9, 0.0, 0.1]),
+            ("y3", [0.2, 0.9, 0.0]),
+        ];
+        for (position, (id, vector)) in vectors.into_iter().enumerate() {
+            index.add(id, vector.to_vec(), position as u32).unwrap();
+        }
+
+        index
+    }
+
+    fn ids<'a>(hits: &[SearchHit<'a, u32>]) -> Vec<&'a str> {
+        hits.iter().map(|hit| hit.id).collect()
+    }
+
+    fn cluster_of(index: &VectorIndex<u32>, id: &str) -> usize {
+        let position = index
+            .entries
+            .iter()
+            .position(|entry| entry.id == id)
+            .unwrap();
+
+        index
+            .clusters
+            .iter()
+            .position(|cluster| cluster.contains(&position))
+            .unwrap()
+    }
+
+    #[test]
+    fn searches_rank_entries_by_cosine_similarity() {
+        let index = index();
+
+        let hits = index.search(&[2.0, 0.0, 0.0], 3).unwrap();
+
+        assert_eq!(ids(&hits), ["x1", "x3", "x2"]);
+        assert_eq!(*hits[1].payload, 4);
+        assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!((hits[0].score - 0.995).abs() < 1e-3);
+        assert!(matches!(
+            index.search(&[1.0, 0.0], 3),
+            Err(OpenAIError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn approximate_searches_scan_the_closest_clusters() {
+        let mut index = index();
+        index.build_clusters(2, 5);
+
+        assert_eq!(cluster_of(&index, "x1"), cluster_of(&index, "x3"));
+        assert_ne!(cluster_of(&index, "x1"), cluster_of(&index, "y1"));
+
+        let query = [1.0, 0.0, 0.0];
+        let exact = index.search(&query, 4).unwrap();
+        let approximate = index.search_approximate(&query, 4, 1).unwrap();
+        assert_eq!(ids(&approximate), ids(&exact)[..3]);
+        assert_eq!(index.search_approximate(&query, 4, 2).unwrap(), exact);
+    }
+
+    #[test]
+    fn entries_added_after_clustering_join_the_nearest_cluster() {
+        let mut index = index();
+        index.build_clusters(2, 5);
+
+        index.add("y4", vec![0.0, 1.0, 0.05], 6).unwrap();
+
+        assert_eq!(cluster_of(&index, "y4"), cluster_of(&index, "y1"));
+        let hits = index.search_approximate(&[0.0, 1.0, 0.0], 1, 1).unwrap();
+        assert_eq!(ids(&hits), ["y4"]);
+    }
+
+    #[test]
+    fn removing_an_entry_discards_the_clusters() {
+        let mut index = index();
+        index.build_clusters(2, 5);
+
+        assert_eq!(index.remove("x2"), Some(2));
+        assert_eq!(index.remove("x2"), None);
+
+        assert!(index.centroids.is_empty() && index.clusters.is_empty());
+        let query = [1.0, 0.0, 0.0];
+        assert_eq!(
+            index.search_approximate(&query, 5, 1).unwrap(),
+            index.search(&query, 5).unwrap()
+        );
+    }
+
+    #[test]
+    fn adding_an_existing_id_replaces_the_entry_in_place() {
+        let mut index = index();
+        index.build_clusters(2, 5);
+
+        index.add("x2", vec![0.0, 1.0, 0.0], 7).unwrap();
+
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.entries()[2].id, "x2");
+        assert_eq!(index.get("x2").unwrap().payload, 7);
+        assert_eq!(cluster_of(&index, "x2"), cluster_of(&index, "y1"));
+        assert_eq!(index.clusters.iter().flatten().count(), 6);
+    }
+
+    #[test]
+    fn entries_are_found_by_id_after_removals() {
+        let mut index = index();
+
+        index.remove("x1");
+        index.remove("y3");
+        index.add("x1", vec![1.0, 0.0, 0.0], 6).unwrap();
+
+        for (position, entry) in index.entries().iter().enumerate() {
+            assert_eq!(index.get(&entry.id), Some(&index.entries()[position]));
+        }
+        assert_eq!(index.get("x1").unwrap().payload, 6);
+        assert_eq!(index.get("y3"), None);
+    }
+
+    #[test]
+    fn clustering_an_empty_index_is_a_no_op() {
+        let mut index = VectorIndex::<u32>::new();
+        index.build_clusters(4, 5);
+
+        assert!(index.clusters.is_empty());
+        assert!(index.search_approximate(&[1.0], 1, 1).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    mod files {
+        use super::*;
+        use serde_json::json;
+        use std::path::PathBuf;
+
+        fn path(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!(
+                "rusty-openai-index-{name}-{}.json",
+                std::process::id()
+            ))
+        }
+
+        /// Load the clustered index after `tamper` changed its saved JSON.
+        fn load_tampered(
+            name: &str,
+            tamper: impl FnOnce(&mut Value),
+        ) -> OpenAIResult<VectorIndex<u32>> {
+            let path = path(name);
+            let mut index = index();
+            index.build_clusters(2, 5);
+            index.save(&path).unwrap();
+
+            let mut json: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+            tamper(&mut json);
+            fs::write(&path, json.to_string()).unwrap();
+
+            let loaded = VectorIndex::load(&path);
+            fs::remove_file(&path).unwrap();
+            loaded
+        }
+
+        #[test]
+        fn saved_indexes_load_with_their_clusters() {
+            let loaded = load_tampered("round-trip", |_| {}).unwrap();
+
+            assert_eq!(loaded.len(), 6);
+            assert_eq!(loaded.get("y2").unwrap().payload, 3);
+            assert_eq!(loaded.dimensions(), Some(3));
+            assert_eq!(loaded.clusters.len(), 2);
+            assert_eq!(
+                ids(&loaded.search_approximate(&[1.0, 0.0, 0.0], 2, 1).unwrap()),
+                ["x1", "x3"]
+            );
+        }
+
+        #[test]
+        fn inconsistent_indexes_are_rejected() {
+            let tampered = [
+                ("/entries/1/vector", json!([1.0, 0.0])),
+                ("/entries/1/id", json!("x1")),
+                ("/centroids/0", json!([1.0])),
+                ("/clusters", json!([[0, 1, 2, 99], [3, 4, 5]])),
+                ("/clusters", json!([[0, 1, 2], [2, 3, 4, 5]])),
+                ("/clusters", json!([[0, 1, 2], [3, 4]])),
+                ("/clusters", json!([[0, 1, 2, 3, 4, 5]])),
+            ];
+
+            for (position, (pointer, value)) in tampered.into_iter().enumerate() {
+                let loaded = load_tampered(&position.to_string(), |json| {
+                    *json.pointer_mut(pointer).unwrap() = value.clone();
+                });
+
+                assert!(
+                    matches!(loaded, Err(OpenAIError::ConfigError(_))),
+                    "{pointer} = {value}"
+                );
+            }
+        }
+    }
+}